@@ -0,0 +1,309 @@
+/// Kernel counters for a socket capture, from `PACKET_STATISTICS`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Stats {
+    /// Packets that passed the in-kernel filter.
+    pub received: u32,
+    /// Packets the kernel dropped because the socket buffer was full.
+    pub dropped: u32,
+}
+
+#[cfg(target_os = "linux")]
+mod linux {
+    use std::env;
+    use std::ffi::CString;
+    use std::fs;
+    use std::io::{self, Write};
+    use std::mem;
+    use std::process::{Command, Stdio};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{mpsc, Arc};
+    use std::thread::{self, JoinHandle};
+    use std::time::{SystemTime, UNIX_EPOCH};
+
+    use libc;
+
+    use super::Stats;
+    use crate::pcap;
+
+    const ETH_P_ALL: u16 = 0x0003;
+    const ARPHRD_ETHER: u32 = 1;
+    const ARPHRD_LOOPBACK: u32 = 772;
+    /// Ancillary load of a random number (`SKF_AD_OFF + SKF_AD_RANDOM`).
+    const BPF_LOAD_RANDOM: u32 = 0xffff_f000 + 56;
+    const BPF_LD_W_ABS: u16 = 0x20;
+    const BPF_ALU_MOD_K: u16 = 0x94;
+    const BPF_JMP_JEQ_K: u16 = 0x15;
+    const BPF_RET_K: u16 = 0x06;
+    /// Snap length returned by an accepting filter.
+    const SNAPLEN: u32 = 0x40000;
+    const CAP_NET_RAW: u32 = 13;
+    /// `SIOCGSTAMP`: the kernel's receive time of the last packet read from a socket.
+    const SIOCGSTAMP: libc::c_ulong = 0x8906;
+
+    /// A capture reading from an `AF_PACKET` socket on a background thread.
+    pub struct Capture {
+        fd: i32,
+        filtered: bool,
+        stop: Arc<AtomicBool>,
+        reader: Option<JoinHandle<()>>,
+    }
+
+    impl Capture {
+        /// Whether the capture filter was compiled into the kernel; without tcpdump to compile
+        /// it every packet is delivered.
+        pub fn filtered(&self) -> bool {
+            self.filtered
+        }
+
+        /// Reads the kernel counters, then stops the reader and closes the socket.
+        pub fn stop(&mut self) -> Stats {
+            let reader = match self.reader.take() {
+                Some(reader) => reader,
+                None => return Stats::default(),
+            };
+            let stats = statistics(self.fd);
+            self.stop.store(true, Ordering::Relaxed);
+            let _ = reader.join();
+            unsafe { libc::close(self.fd) };
+            stats
+        }
+    }
+
+    impl Drop for Capture {
+        fn drop(&mut self) {
+            self.stop();
+        }
+    }
+
+    /// Checks that a packet socket can be opened, explaining how to grant access if not.
+    pub fn check() -> Result<(), String> {
+        let fd = unsafe { libc::socket(libc::AF_PACKET, libc::SOCK_RAW, 0) };
+        if fd >= 0 {
+            unsafe { libc::close(fd) };
+            return Ok(());
+        }
+        let error = io::Error::last_os_error();
+        match error.raw_os_error() {
+            Some(libc::EPERM) | Some(libc::EACCES) if !has_net_raw() => Err(format!(
+                "packet sockets need CAP_NET_RAW; run with sudo or grant it once with `sudo setcap cap_net_raw+ep {}`",
+                env::current_exe().map(|p| p.display().to_string()).unwrap_or_else(|_| "netdiag".to_string())
+            )),
+            Some(libc::EAFNOSUPPORT) => Err("this kernel was built without AF_PACKET support".to_string()),
+            _ => Err(format!("cannot open a packet socket: {}", error)),
+        }
+    }
+
+    /// Whether the effective capability set (or root) includes `CAP_NET_RAW`.
+    fn has_net_raw() -> bool {
+        if unsafe { libc::geteuid() } == 0 {
+            return true;
+        }
+        fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|status| {
+                let caps = status.lines().find_map(|l| l.strip_prefix("CapEff:"))?;
+                u64::from_str_radix(caps.trim(), 16).ok()
+            })
+            .is_some_and(|caps| caps & (1 << CAP_NET_RAW) != 0)
+    }
+
+    /// Opens a packet socket on `interface` with `filter` and 1-in-`sample` sampling applied in
+    /// the kernel, and returns the capture with a channel of `(linktype, record)` pairs.
+    pub fn start(interface: &str, filter: &str, sample: u32) -> io::Result<(Capture, mpsc::Receiver<(u32, pcap::Record)>)> {
+        let name = CString::new(interface).map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "bad interface name"))?;
+        let ifindex = unsafe { libc::if_nametoindex(name.as_ptr()) };
+        if ifindex == 0 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("no interface named {}", interface)));
+        }
+        // Interfaces without an Ethernet header (tun, PPP, WireGuard) are read at the IP layer.
+        let hardware = fs::read_to_string(format!("/sys/class/net/{}/type", interface))
+            .ok()
+            .and_then(|t| t.trim().parse().ok())
+            .unwrap_or(ARPHRD_ETHER);
+        let (kind, linktype) = if hardware == ARPHRD_ETHER || hardware == ARPHRD_LOOPBACK {
+            (libc::SOCK_RAW, pcap::LINKTYPE_ETHERNET)
+        } else {
+            (libc::SOCK_DGRAM, pcap::LINKTYPE_RAW)
+        };
+
+        // Protocol 0 receives nothing until bind, so no unfiltered packets slip in first.
+        let fd = unsafe { libc::socket(libc::AF_PACKET, kind, 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let compiled = compile(filter, linktype);
+        let filtered = compiled.is_some();
+        let program = program(compiled, sample);
+        let fprog = libc::sock_fprog { len: program.len() as u16, filter: program.as_ptr() as *mut libc::sock_filter };
+        let timeout = libc::timeval { tv_sec: 0, tv_usec: 200_000 };
+        let mut address: libc::sockaddr_ll = unsafe { mem::zeroed() };
+        address.sll_family = libc::AF_PACKET as u16;
+        address.sll_protocol = ETH_P_ALL.to_be();
+        address.sll_ifindex = ifindex as i32;
+        let ok = unsafe {
+            setsockopt(fd, libc::SOL_SOCKET, libc::SO_ATTACH_FILTER, &fprog)
+                && setsockopt(fd, libc::SOL_SOCKET, libc::SO_RCVTIMEO, &timeout)
+                && libc::bind(fd, &address as *const _ as *const libc::sockaddr, mem::size_of::<libc::sockaddr_ll>() as u32) == 0
+        };
+        if !ok {
+            let error = io::Error::last_os_error();
+            unsafe { libc::close(fd) };
+            return Err(error);
+        }
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let (tx, rx) = mpsc::channel();
+        let reader = {
+            let stop = stop.clone();
+            thread::spawn(move || {
+                let mut buffer = vec![0u8; 65536];
+                let mut from: libc::sockaddr_ll = unsafe { mem::zeroed() };
+                while !stop.load(Ordering::Relaxed) {
+                    let mut from_len = mem::size_of::<libc::sockaddr_ll>() as u32;
+                    let len = unsafe {
+                        libc::recvfrom(
+                            fd,
+                            buffer.as_mut_ptr() as *mut libc::c_void,
+                            buffer.len(),
+                            0,
+                            &mut from as *mut _ as *mut libc::sockaddr,
+                            &mut from_len,
+                        )
+                    };
+                    if len < 0 {
+                        // The receive timeout lets the stop flag be checked on quiet links.
+                        match io::Error::last_os_error().kind() {
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut | io::ErrorKind::Interrupted => continue,
+                            _ => break,
+                        }
+                    }
+                    // Loopback delivers every packet twice, once as outgoing; keep the copy
+                    // tcpdump would show.
+                    if hardware == ARPHRD_LOOPBACK && from.sll_pkttype == libc::PACKET_OUTGOING {
+                        continue;
+                    }
+                    let (ts_sec, ts_usec) = receive_time(fd);
+                    let record = pcap::Record { ts_sec, ts_usec, orig_len: len as u32, data: buffer[..len as usize].to_vec() };
+                    if tx.send((linktype, record)).is_err() {
+                        break;
+                    }
+                }
+            })
+        };
+        Ok((Capture { fd, filtered, stop, reader: Some(reader) }, rx))
+    }
+
+    /// When the kernel received the packet just read from `fd`, so queueing in the socket buffer
+    /// does not skew timings. Falls back to the current time if the kernel cannot say.
+    fn receive_time(fd: i32) -> (u32, u32) {
+        let mut stamp = libc::timeval { tv_sec: 0, tv_usec: 0 };
+        if unsafe { libc::ioctl(fd, SIOCGSTAMP as _, &mut stamp as *mut libc::timeval) } == 0 {
+            return (stamp.tv_sec as u32, stamp.tv_usec as u32);
+        }
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        (now.as_secs() as u32, now.subsec_micros())
+    }
+
+    unsafe fn setsockopt<T>(fd: i32, level: i32, name: i32, value: &T) -> bool {
+        libc::setsockopt(fd, level, name, value as *const T as *const libc::c_void, mem::size_of::<T>() as u32) == 0
+    }
+
+    /// Reads (and resets) the kernel's received/dropped counters.
+    fn statistics(fd: i32) -> Stats {
+        let mut stats: libc::tpacket_stats = unsafe { mem::zeroed() };
+        let mut len = mem::size_of::<libc::tpacket_stats>() as u32;
+        let ok = unsafe {
+            libc::getsockopt(fd, libc::SOL_PACKET, libc::PACKET_STATISTICS, &mut stats as *mut _ as *mut libc::c_void, &mut len) == 0
+        };
+        if ok {
+            Stats { received: stats.tp_packets, dropped: stats.tp_drops }
+        } else {
+            Stats::default()
+        }
+    }
+
+    /// Compiles a pcap filter expression to classic BPF with `tcpdump -ddd`, reading the link
+    /// type from an empty savefile on stdin so no capture privileges (or temporary files) are needed.
+    fn compile(filter: &str, linktype: u32) -> Option<Vec<libc::sock_filter>> {
+        let mut header = Vec::new();
+        for field in [0xa1b2_c3d4u32.to_le_bytes().to_vec(), vec![2, 0, 4, 0], vec![0; 8], 65535u32.to_le_bytes().to_vec(), linktype.to_le_bytes().to_vec()] {
+            header.extend(field);
+        }
+        let mut child = Command::new("tcpdump")
+            .args(["-ddd", "-r", "-", filter])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .ok()?;
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = stdin.write_all(&header);
+        }
+        let output = child.wait_with_output().ok().filter(|o| o.status.success())?;
+        let text = String::from_utf8_lossy(&output.stdout);
+        let mut lines = text.lines();
+        let count: usize = lines.next()?.trim().parse().ok()?;
+        let program: Vec<libc::sock_filter> = lines
+            .filter_map(|line| {
+                let fields: Vec<u32> = line.split_whitespace().filter_map(|f| f.parse().ok()).collect();
+                match fields[..] {
+                    [code, jt, jf, k] => Some(libc::sock_filter { code: code as u16, jt: jt as u8, jf: jf as u8, k }),
+                    _ => None,
+                }
+            })
+            .collect();
+        Some(program).filter(|p| p.len() == count)
+    }
+
+    /// Prefixes the filter with a random 1-in-`sample` check. Without a compiled filter every
+    /// packet is accepted and filtering is left to the decoder's callers.
+    fn program(filter: Option<Vec<libc::sock_filter>>, sample: u32) -> Vec<libc::sock_filter> {
+        let op = |code, jt, jf, k| libc::sock_filter { code, jt, jf, k };
+        let filter = filter.unwrap_or_else(|| vec![op(BPF_RET_K, 0, 0, SNAPLEN)]);
+        // Jump offsets are 8 bits, so very long filters are left unsampled.
+        if sample <= 1 || filter.len() > 255 {
+            return filter;
+        }
+        let mut program = vec![
+            op(BPF_LD_W_ABS, 0, 0, BPF_LOAD_RANDOM),
+            op(BPF_ALU_MOD_K, 0, 0, sample),
+            op(BPF_JMP_JEQ_K, 0, filter.len() as u8, 0),
+        ];
+        program.extend(filter);
+        program.push(op(BPF_RET_K, 0, 0, 0));
+        program
+    }
+}
+
+#[cfg(target_os = "linux")]
+pub use self::linux::{check, start, Capture};
+
+/// Placeholder capture on platforms without `AF_PACKET`.
+#[cfg(not(target_os = "linux"))]
+pub struct Capture;
+
+#[cfg(not(target_os = "linux"))]
+impl Capture {
+    pub fn filtered(&self) -> bool {
+        false
+    }
+
+    pub fn stop(&mut self) -> Stats {
+        Stats::default()
+    }
+}
+
+/// Packet sockets only exist on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn check() -> Result<(), String> {
+    Err("the socket backend needs Linux AF_PACKET sockets".to_string())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn start(
+    _interface: &str,
+    _filter: &str,
+    _sample: u32,
+) -> std::io::Result<(Capture, std::sync::mpsc::Receiver<(u32, crate::pcap::Record)>)> {
+    Err(std::io::Error::new(std::io::ErrorKind::Unsupported, "AF_PACKET capture is Linux-only"))
+}
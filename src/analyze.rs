@@ -0,0 +1,319 @@
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::IpAddr;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::packet::{self, Packet};
+use crate::pcap;
+use crate::throughput::ThroughputWatch;
+
+/// Share of traffic (in percentage points) a protocol must gain or lose to be called out.
+const MIX_SHIFT: f64 = 10.0;
+
+/// A TCP or UDP conversation, with the endpoints in a fixed order so both directions match.
+type FlowKey = (String, (IpAddr, u16), (IpAddr, u16));
+
+/// Per-flow TCP state used to count handshakes and retransmissions.
+#[derive(Debug, Default)]
+struct Flow {
+    syn: bool,
+    syn_ack: bool,
+    reset: bool,
+    /// Sequence number after the last byte sent so far, per direction (true: first endpoint sends).
+    next_seq: HashMap<bool, u32>,
+}
+
+/// What a capture contains, reduced to the figures worth comparing.
+#[derive(Debug, Default)]
+struct Profile {
+    packets: usize,
+    bytes: u64,
+    first: Option<f64>,
+    last: Option<f64>,
+    /// Packets per service label, e.g. `TCP/443`, `UDP/53`, `ARP`.
+    mix: BTreeMap<String, usize>,
+    flows: HashMap<FlowKey, Flow>,
+    data_segments: usize,
+    retransmissions: usize,
+    icmp_errors: BTreeMap<String, usize>,
+    throughput: ThroughputWatch,
+}
+
+impl Profile {
+    /// Reads a pcap file and tallies every frame.
+    fn load(path: &str) -> io::Result<Profile> {
+        let mut reader = pcap::Reader::new(BufReader::new(File::open(path)?))?;
+        let mut profile = Profile::default();
+        while let Some(record) = reader.next_record()? {
+            let time = f64::from(record.ts_sec) + f64::from(record.ts_usec) / 1e6;
+            profile.first = Some(profile.first.map_or(time, |f: f64| f.min(time)));
+            profile.last = Some(profile.last.map_or(time, |l: f64| l.max(time)));
+            profile.packets += 1;
+            profile.bytes += u64::from(record.orig_len.max(record.data.len() as u32));
+            let packet = packet::decode(reader.linktype, &record.data);
+            profile.throughput.observe(time, &packet);
+            profile.observe(&packet);
+        }
+        Ok(profile)
+    }
+
+    fn observe(&mut self, packet: &Packet) {
+        *self.mix.entry(service(packet)).or_insert(0) += 1;
+        if let Some(error) = packet.icmp.as_ref().and_then(|i| i.error()) {
+            *self.icmp_errors.entry(error.name()).or_insert(0) += 1;
+        }
+        let (src, dst, src_port, dst_port) = match (packet.src, packet.dst, packet.src_port, packet.dst_port) {
+            (Some(s), Some(d), Some(sp), Some(dp)) => (s, d, sp, dp),
+            _ => return,
+        };
+        let forward = (src, src_port) <= (dst, dst_port);
+        let key = if forward {
+            (packet.protocol.clone(), (src, src_port), (dst, dst_port))
+        } else {
+            (packet.protocol.clone(), (dst, dst_port), (src, src_port))
+        };
+        let flow = self.flows.entry(key).or_default();
+        let tcp = match packet.tcp {
+            Some(tcp) => tcp,
+            None => return,
+        };
+        match (tcp.flags & 0x02 != 0, tcp.flags & 0x10 != 0) {
+            (true, false) => flow.syn = true,
+            (true, true) => flow.syn_ack = true,
+            _ => {}
+        }
+        if tcp.flags & 0x04 != 0 {
+            flow.reset = true;
+        }
+        if tcp.payload_len == 0 {
+            return;
+        }
+        self.data_segments += 1;
+        let end = tcp.seq.wrapping_add(tcp.payload_len);
+        let next = flow.next_seq.entry(forward).or_insert(tcp.seq);
+        // A segment starting below data already sent is a retransmission; sequence numbers wrap.
+        if (tcp.seq.wrapping_sub(*next) as i32) < 0 {
+            self.retransmissions += 1;
+        }
+        if (end.wrapping_sub(*next) as i32) > 0 {
+            *next = end;
+        }
+    }
+
+    fn duration(&self) -> f64 {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last - first,
+            _ => 0.0,
+        }
+    }
+
+    /// Connections that sent a SYN, and how many of those got a SYN-ACK.
+    fn handshakes(&self) -> (usize, usize) {
+        let attempted = self.flows.values().filter(|f| f.syn).count();
+        let completed = self.flows.values().filter(|f| f.syn && f.syn_ack).count();
+        (attempted, completed)
+    }
+
+    fn handshake_rate(&self) -> Option<f64> {
+        match self.handshakes() {
+            (0, _) => None,
+            (attempted, completed) => Some(completed as f64 * 100.0 / attempted as f64),
+        }
+    }
+
+    fn retransmission_rate(&self) -> Option<f64> {
+        if self.data_segments == 0 {
+            return None;
+        }
+        Some(self.retransmissions as f64 * 100.0 / self.data_segments as f64)
+    }
+
+    fn resets(&self) -> usize {
+        self.flows.values().filter(|f| f.reset).count()
+    }
+
+    /// Server endpoints, as `TCP 93.184.216.34:443`: the side with the lower port of each flow.
+    fn servers(&self) -> HashSet<String> {
+        self.flows
+            .keys()
+            .map(|(protocol, a, b)| {
+                let server = if a.1 <= b.1 { a } else { b };
+                format!("{} {}", protocol, endpoint(server.0, server.1))
+            })
+            .collect()
+    }
+
+    fn share(&self, label: &str) -> f64 {
+        if self.packets == 0 {
+            return 0.0;
+        }
+        self.mix.get(label).cloned().unwrap_or(0) as f64 * 100.0 / self.packets as f64
+    }
+}
+
+/// Labels a packet by protocol and, for TCP and UDP, the well-known (lower) port.
+pub fn service(packet: &Packet) -> String {
+    match (packet.src_port, packet.dst_port) {
+        (Some(a), Some(b)) => format!("{}/{}", packet.protocol, a.min(b)),
+        _ => packet.protocol.clone(),
+    }
+}
+
+fn endpoint(addr: IpAddr, port: u16) -> String {
+    match addr {
+        IpAddr::V6(a) => format!("[{}]:{}", a, port),
+        IpAddr::V4(a) => format!("{}:{}", a, port),
+    }
+}
+
+fn percent(value: Option<f64>) -> String {
+    value.map(|v| format!("{:.1}%", v)).unwrap_or_else(|| "-".to_string())
+}
+
+/// Returns the `analyze` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("analyze")
+        .about("Summarizes a pcap file, or compares a working and a broken capture with --diff")
+        .arg(Arg::with_name("capture").required_unless("diff").help("Capture to summarize"))
+        .arg(Arg::with_name("diff").long("diff").takes_value(true).number_of_values(2).value_names(&["good.pcap", "bad.pcap"])
+            .conflicts_with("capture")
+            .help("Compare protocol mix, flows, handshakes and retransmissions between two captures"))
+}
+
+/// Runs the `analyze` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let load = |path: &str| match Profile::load(path) {
+        Ok(profile) => Some(profile),
+        Err(e) => {
+            println!("❌ {} Could not read {}: {}", colorize("[ERROR]", "red"), path, e);
+            None
+        }
+    };
+    if let Some(mut paths) = matches.values_of("diff") {
+        let (good_path, bad_path) = (paths.next().unwrap_or_default(), paths.next().unwrap_or_default());
+        if let (Some(good), Some(bad)) = (load(good_path), load(bad_path)) {
+            print_diff(good_path, &good, bad_path, &bad);
+        }
+    } else if let Some(path) = matches.value_of("capture") {
+        if let Some(profile) = load(path) {
+            print_profile(path, &profile);
+        }
+    }
+}
+
+/// Prints the figures of a single capture.
+fn print_profile(path: &str, profile: &Profile) {
+    println!("\n🔹 {}", colorize(&format!("Capture {}", path), "blue"));
+    println!("   Packets:          {} ({} bytes over {:.1} s)", profile.packets, profile.bytes, profile.duration());
+    println!("   Flows:            {}", profile.flows.len());
+    let (attempted, completed) = profile.handshakes();
+    println!("   TCP handshakes:   {} of {} completed ({})", completed, attempted, percent(profile.handshake_rate()));
+    println!("   Resets:           {} flow(s)", profile.resets());
+    println!("   Retransmissions:  {} of {} data segments ({})", profile.retransmissions, profile.data_segments, percent(profile.retransmission_rate()));
+    println!("\n🔹 {}", colorize("Protocol mix", "blue"));
+    let mut mix: Vec<(&String, &usize)> = profile.mix.iter().collect();
+    mix.sort_by(|a, b| b.1.cmp(a.1));
+    for (label, count) in mix.iter().take(15) {
+        println!("   {:<16} {:>7} {:>6.1}%", label, count, profile.share(label));
+    }
+    for (error, count) in &profile.icmp_errors {
+        println!("⚠️  {} {} × {}", colorize("[ICMP]", "yellow"), count, error);
+    }
+    profile.throughput.print_summary();
+    println!();
+}
+
+/// Prints both captures side by side and lists what changed between them.
+fn print_diff(good_path: &str, good: &Profile, bad_path: &str, bad: &Profile) {
+    let mut differences = Vec::new();
+    println!("\n🔍 {} Comparing {} (good) with {} (bad)\n", colorize("[INFO]", "blue"), good_path, bad_path);
+
+    println!("🔹 {}", colorize("Overview", "blue"));
+    println!("   {:<22} {:>14} {:>14}", "", "good", "bad");
+    let rate = |p: &Profile| if p.duration() > 0.0 { p.packets as f64 / p.duration() } else { 0.0 };
+    println!("   {:<22} {:>14} {:>14}", "Packets", good.packets, bad.packets);
+    println!("   {:<22} {:>14.1} {:>14.1}", "Packets/s", rate(good), rate(bad));
+    println!("   {:<22} {:>14} {:>14}", "Flows", good.flows.len(), bad.flows.len());
+    let handshakes = |p: &Profile| {
+        let (attempted, completed) = p.handshakes();
+        format!("{}/{} {}", completed, attempted, percent(p.handshake_rate()))
+    };
+    println!("   {:<22} {:>14} {:>14}", "Handshakes completed", handshakes(good), handshakes(bad));
+    println!("   {:<22} {:>14} {:>14}", "Flows reset", good.resets(), bad.resets());
+    println!("   {:<22} {:>14} {:>14}", "Retransmissions", percent(good.retransmission_rate()), percent(bad.retransmission_rate()));
+    println!();
+
+    if let (Some(g), Some(b)) = (good.handshake_rate(), bad.handshake_rate()) {
+        if g - b >= 10.0 {
+            differences.push(format!("TCP handshake success fell from {:.0}% to {:.0}%: SYNs go unanswered (firewall, down service or routing)", g, b));
+        }
+    }
+    let (g, b) = (good.retransmission_rate().unwrap_or(0.0), bad.retransmission_rate().unwrap_or(0.0));
+    if b >= 1.0 && b > g * 2.0 {
+        differences.push(format!("Retransmissions rose from {:.1}% to {:.1}%: packet loss or congestion on the path", g, b));
+    }
+    let (g, b) = (good.resets() as f64 / good.flows.len().max(1) as f64, bad.resets() as f64 / bad.flows.len().max(1) as f64);
+    if b - g >= 0.1 {
+        differences.push(format!("{} of {} flows were reset in the bad capture, against {} of {}", bad.resets(), bad.flows.len(), good.resets(), good.flows.len()));
+    }
+
+    println!("🔹 {}", colorize("Protocol mix (share of packets)", "blue"));
+    let mut labels: Vec<&String> = good.mix.keys().chain(bad.mix.keys()).collect::<HashSet<_>>().into_iter().collect();
+    labels.sort_by(|a, b| (good.share(b) + bad.share(b)).partial_cmp(&(good.share(a) + bad.share(a))).unwrap_or(Ordering::Equal));
+    for label in labels.iter().take(15) {
+        let (g, b) = (good.share(label), bad.share(label));
+        let shifted = (g - b).abs() >= MIX_SHIFT || (g == 0.0) != (b == 0.0);
+        let line = format!("   {:<16} {:>13.1}% {:>13.1}%", label, g, b);
+        println!("{}", if shifted { colorize(&line, "yellow") } else { line });
+        if g == 0.0 {
+            differences.push(format!("{} traffic appears only in the bad capture", label));
+        } else if b == 0.0 {
+            differences.push(format!("{} traffic is missing from the bad capture", label));
+        } else if (g - b).abs() >= MIX_SHIFT {
+            differences.push(format!("{} went from {:.0}% to {:.0}% of packets", label, g, b));
+        }
+    }
+    println!();
+
+    println!("🔹 {}", colorize("Servers contacted", "blue"));
+    let (good_servers, bad_servers) = (good.servers(), bad.servers());
+    let mut only_good: Vec<&String> = good_servers.difference(&bad_servers).collect();
+    let mut only_bad: Vec<&String> = bad_servers.difference(&good_servers).collect();
+    only_good.sort();
+    only_bad.sort();
+    println!("   {} in both, {} only in good, {} only in bad", good_servers.intersection(&bad_servers).count(), only_good.len(), only_bad.len());
+    for server in only_good.iter().take(10) {
+        println!("   {} {}", colorize("- only in good:", "yellow"), server);
+    }
+    for server in only_bad.iter().take(10) {
+        println!("   {} {}", colorize("+ only in bad: ", "yellow"), server);
+    }
+    println!();
+
+    let errors: BTreeSet<&String> = good.icmp_errors.keys().chain(bad.icmp_errors.keys()).collect();
+    if !errors.is_empty() {
+        println!("🔹 {}", colorize("ICMP errors", "blue"));
+        for error in errors {
+            let (g, b) = (good.icmp_errors.get(error).cloned().unwrap_or(0), bad.icmp_errors.get(error).cloned().unwrap_or(0));
+            println!("   {:<36} {:>6} {:>6}", error, g, b);
+            if b > g {
+                differences.push(format!("{} more ICMP \"{}\" error(s) in the bad capture", b - g, error));
+            }
+        }
+        println!();
+    }
+
+    if differences.is_empty() {
+        println!("📊 {} No significant differences; the problem may be above the transport layer (TLS, HTTP, application)\n", colorize("[SUMMARY]", "blue"));
+        return;
+    }
+    println!("📊 {} {} difference(s) between the working and broken session:", colorize("[SUMMARY]", "blue"), differences.len());
+    for difference in &differences {
+        println!("   • {}", difference);
+    }
+    println!();
+}
@@ -0,0 +1,460 @@
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::packet::{MacAddr, MAX_LAYERS};
+use crate::pcap;
+
+/// Longest text form of an address: an IPv6 address with an embedded IPv4 one.
+const MAX_ADDRESS_LEN: usize = 45;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+
+/// Consistent replacements for addresses and names, so the same host keeps the same stand-in
+/// across every packet and file it is applied to.
+///
+/// IPv4 addresses keep their last octet and have their /24 remapped, so hosts on one subnet
+/// stay on one subnet: private networks move into 10.0.0.0/8, public ones into the 198.18.0.0/15
+/// benchmarking range. Networks past what those hold (65536 and 512 /24s) continue in the
+/// reserved 240.0.0.0/4, so two real networks never share a stand-in. IPv6 addresses become documentation (2001:db8::/32), ULA or link-local
+/// addresses. MACs keep their vendor OUI. Loopback, multicast, broadcast and unspecified
+/// addresses and netmasks are left alone because they reveal nothing and matter for diagnosis.
+#[derive(Debug, Default)]
+pub struct Anonymizer {
+    private_nets: HashMap<[u8; 3], [u8; 3]>,
+    public_nets: HashMap<[u8; 3], [u8; 3]>,
+    /// /24s handed out in 240.0.0.0/4 once the private or public range is full.
+    overflow_nets: u32,
+    v4: HashSet<Ipv4Addr>,
+    v6: HashMap<Ipv6Addr, Ipv6Addr>,
+    macs: HashMap<MacAddr, MacAddr>,
+    names: Vec<(String, String)>,
+}
+
+/// What `anonymize_file` changed.
+#[derive(Debug, Default)]
+pub struct Stats {
+    pub packets: usize,
+    pub payload_bytes: usize,
+}
+
+impl Anonymizer {
+    pub fn new() -> Anonymizer {
+        Anonymizer::default()
+    }
+
+    /// Addresses replaced so far.
+    pub fn addresses(&self) -> usize {
+        self.v4.len() + self.v6.len()
+    }
+
+    /// MACs replaced so far.
+    pub fn mac_count(&self) -> usize {
+        self.macs.len()
+    }
+
+    /// Replaces `name` (e.g. the hostname) wherever it appears in text.
+    pub fn redact_name(&mut self, name: &str) {
+        let name = name.trim();
+        if !name.is_empty() && !self.names.iter().any(|(n, _)| n == name) {
+            let replacement = format!("host-{}", self.names.len() + 1);
+            self.names.push((name.to_string(), replacement));
+        }
+    }
+
+    /// The stand-in for `addr`.
+    pub fn ip(&mut self, addr: IpAddr) -> IpAddr {
+        match addr {
+            IpAddr::V4(v4) => IpAddr::V4(self.ipv4(v4)),
+            IpAddr::V6(v6) => IpAddr::V6(self.ipv6(v6)),
+        }
+    }
+
+    fn ipv4(&mut self, addr: Ipv4Addr) -> Ipv4Addr {
+        if addr.is_loopback() || addr.is_multicast() || addr.is_broadcast() || addr.is_unspecified() || addr.is_link_local() {
+            return addr;
+        }
+        // Netmasks such as 255.255.255.0 show up in the same text and are not hosts.
+        let bits = u32::from(addr);
+        if bits.leading_ones() >= 8 && bits.leading_ones() == bits.count_ones() {
+            return addr;
+        }
+        self.v4.insert(addr);
+        let o = addr.octets();
+        // 100.64.0.0/10 (carrier-grade NAT) is internal addressing too.
+        let private = addr.is_private() || (o[0] == 100 && (o[1] & 0xc0) == 64);
+        let (nets, capacity) = if private { (&mut self.private_nets, 1 << 16) } else { (&mut self.public_nets, 512) };
+        let next = nets.len() as u32;
+        let overflow = &mut self.overflow_nets;
+        let net = *nets.entry([o[0], o[1], o[2]]).or_insert_with(|| match next {
+            n if n < capacity && private => [10, (n >> 8) as u8, n as u8],
+            n if n < capacity => [198, 18 + (n >> 8) as u8, n as u8],
+            _ => {
+                // 240.0.0.0/4 holds 2^20 /24s; a capture with more distinct networks is not a concern.
+                let n = *overflow;
+                *overflow += 1;
+                [240 + ((n >> 16) & 0x0f) as u8, (n >> 8) as u8, n as u8]
+            }
+        });
+        Ipv4Addr::new(net[0], net[1], net[2], o[3])
+    }
+
+    fn ipv6(&mut self, addr: Ipv6Addr) -> Ipv6Addr {
+        if addr.is_loopback() || addr.is_multicast() || addr.is_unspecified() {
+            return addr;
+        }
+        if let Some(v4) = addr.to_ipv4_mapped() {
+            return self.ipv4(v4).to_ipv6_mapped();
+        }
+        // Spread over the last 48 bits, so the numbering never wraps onto an earlier stand-in.
+        let next = self.v6.len() as u64 + 1;
+        let prefix = match addr.segments()[0] {
+            s if s & 0xffc0 == 0xfe80 => [0xfe80, 0],
+            s if s & 0xfe00 == 0xfc00 => [0xfd00, 0],
+            _ => [0x2001, 0x0db8],
+        };
+        *self
+            .v6
+            .entry(addr)
+            .or_insert_with(|| Ipv6Addr::new(prefix[0], prefix[1], 0, 0, 0, (next >> 32) as u16, (next >> 16) as u16, next as u16))
+    }
+
+    /// The stand-in for `mac`: the vendor OUI is kept, the device part is renumbered.
+    pub fn mac(&mut self, mac: MacAddr) -> MacAddr {
+        // Broadcast and multicast have the group bit set.
+        if mac.0[0] & 0x01 != 0 || mac.0 == [0; 6] {
+            return mac;
+        }
+        let next = self.macs.len() as u32 + 1;
+        *self.macs.entry(mac).or_insert_with(|| {
+            // Randomised (locally administered) MACs have no meaningful OUI to keep.
+            let oui = if mac.0[0] & 0x02 != 0 { [0x02, 0, 0] } else { [mac.0[0], mac.0[1], mac.0[2]] };
+            MacAddr([oui[0], oui[1], oui[2], (next >> 16) as u8, (next >> 8) as u8, next as u8])
+        })
+    }
+
+    /// Replaces every IP address, MAC address and redacted name in free text.
+    pub fn text(&mut self, text: &str) -> String {
+        let mut text = text.to_string();
+        for (name, replacement) in &self.names {
+            text = replace_word(&text, name, replacement);
+        }
+        let mut out = String::with_capacity(text.len());
+        let mut token = String::new();
+        for c in text.chars() {
+            if c.is_ascii_hexdigit() || c == '.' || c == ':' || c == '-' {
+                token.push(c);
+                continue;
+            }
+            out.push_str(&self.token(&token));
+            token.clear();
+            out.push(c);
+        }
+        out.push_str(&self.token(&token));
+        out
+    }
+
+    /// Rewrites the addresses in one run of hex digits, dots, colons and dashes. The run is
+    /// usually a single address, but labels that end in hex digits are part of it
+    /// (`addr:192.168.1.5`, `Bcast:10.0.0.255`), as are ports (`10.0.0.5:443`, `10.0.0.5.443`)
+    /// and ranges (`10.0.0.1-10.0.0.9`), so addresses are also looked for inside it.
+    fn token(&mut self, token: &str) -> String {
+        // Sentence punctuation and separators are not part of the address.
+        let core = token.trim_end_matches(['.', ':', '-']);
+        if let Some(mapped) = self.address(core) {
+            return format!("{}{}", mapped, &token[core.len()..]);
+        }
+        if !token.contains(['.', ':', '-']) {
+            return token.to_string();
+        }
+        // Leftmost-longest matches; the run is all ASCII, so byte offsets are characters.
+        let mut out = String::with_capacity(token.len());
+        let mut start = 0;
+        'scan: while start < token.len() {
+            for end in (start + 2..=token.len().min(start + MAX_ADDRESS_LEN)).rev() {
+                if let Some(mapped) = self.address(&token[start..end]) {
+                    out.push_str(&mapped);
+                    start = end;
+                    continue 'scan;
+                }
+            }
+            out.push_str(&token[start..start + 1]);
+            start += 1;
+        }
+        out
+    }
+
+    /// The stand-in for `text` if all of it is one IP or MAC address, in the same style.
+    fn address(&mut self, text: &str) -> Option<String> {
+        if let Ok(addr) = text.parse::<IpAddr>() {
+            return Some(self.ip(addr).to_string());
+        }
+        let mac = MacAddr::parse(text)?;
+        let separator = if text.contains('-') { "-" } else { ":" };
+        let mapped = self.mac(mac).to_string().replace(':', separator);
+        Some(if text.chars().any(|c| c.is_ascii_uppercase()) { mapped.to_uppercase() } else { mapped })
+    }
+
+    /// Returns `data` with addresses replaced and everything after the transport headers
+    /// removed. Checksums covering the removed payload are zeroed.
+    pub fn frame(&mut self, linktype: u32, data: &[u8]) -> Vec<u8> {
+        let mut out = data.to_vec();
+        let keep = match linktype {
+            pcap::LINKTYPE_ETHERNET => self.ethernet(&mut out, 0, 0),
+            pcap::LINKTYPE_LINUX_SLL if out.len() >= 16 => {
+                // Cooked header: only the sender's link-layer address, when it is a MAC.
+                if u16::from_be_bytes([out[4], out[5]]) == 6 {
+                    self.rewrite_mac(&mut out, 6);
+                }
+                let ethertype = u16::from_be_bytes([out[14], out[15]]);
+                self.ethertype(&mut out, 16, ethertype, 0)
+            }
+            pcap::LINKTYPE_NULL if out.len() >= 4 => self.ip_packet(&mut out, 4, 0),
+            pcap::LINKTYPE_RAW => self.ip_packet(&mut out, 0, 0),
+            _ => 0,
+        };
+        out.truncate(keep);
+        out
+    }
+
+    fn rewrite_mac(&mut self, buf: &mut [u8], at: usize) {
+        let mac = self.mac(MacAddr::from_slice(&buf[at..at + 6]));
+        buf[at..at + 6].copy_from_slice(&mac.0);
+    }
+
+    fn rewrite_v4(&mut self, buf: &mut [u8], at: usize) {
+        let addr = self.ipv4(Ipv4Addr::new(buf[at], buf[at + 1], buf[at + 2], buf[at + 3]));
+        buf[at..at + 4].copy_from_slice(&addr.octets());
+    }
+
+    fn rewrite_v6(&mut self, buf: &mut [u8], at: usize) {
+        let mut octets = [0u8; 16];
+        octets.copy_from_slice(&buf[at..at + 16]);
+        let addr = self.ipv6(Ipv6Addr::from(octets));
+        buf[at..at + 16].copy_from_slice(&addr.octets());
+    }
+
+    /// Each of these returns how many bytes of `buf` to keep: the end of the headers it rewrote.
+    /// `depth` counts nested VLAN tags, tunnels and quoted packets; past `MAX_LAYERS` the rest
+    /// of the frame is dropped.
+    fn ethernet(&mut self, buf: &mut [u8], at: usize, depth: usize) -> usize {
+        if buf.len() < at + 14 {
+            return at;
+        }
+        self.rewrite_mac(buf, at);
+        self.rewrite_mac(buf, at + 6);
+        let ethertype = u16::from_be_bytes([buf[at + 12], buf[at + 13]]);
+        self.ethertype(buf, at + 14, ethertype, depth)
+    }
+
+    fn ethertype(&mut self, buf: &mut [u8], at: usize, ethertype: u16, depth: usize) -> usize {
+        if depth > MAX_LAYERS {
+            return at;
+        }
+        match ethertype {
+            ETHERTYPE_VLAN | ETHERTYPE_QINQ if buf.len() >= at + 4 => {
+                let inner = u16::from_be_bytes([buf[at + 2], buf[at + 3]]);
+                self.ethertype(buf, at + 4, inner, depth + 1)
+            }
+            ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => self.ip_packet(buf, at, depth),
+            // Ethernet/IPv4 ARP only: hardware length 6, protocol length 4.
+            ETHERTYPE_ARP if buf.len() >= at + 28 && buf[at + 4] == 6 && buf[at + 5] == 4 => {
+                self.rewrite_mac(buf, at + 8);
+                self.rewrite_v4(buf, at + 14);
+                self.rewrite_mac(buf, at + 18);
+                self.rewrite_v4(buf, at + 24);
+                at + 28
+            }
+            _ => at,
+        }
+    }
+
+    fn ip_packet(&mut self, buf: &mut [u8], at: usize, depth: usize) -> usize {
+        if depth > MAX_LAYERS {
+            return at;
+        }
+        match buf.get(at).map(|b| b >> 4) {
+            Some(4) if buf.len() >= at + 20 => {
+                let header_len = (((buf[at] & 0x0f) as usize) * 4).clamp(20, buf.len() - at);
+                self.rewrite_v4(buf, at + 12);
+                self.rewrite_v4(buf, at + 16);
+                buf[at + 10] = 0;
+                buf[at + 11] = 0;
+                let sum = checksum(&buf[at..at + header_len]);
+                buf[at + 10..at + 12].copy_from_slice(&sum.to_be_bytes());
+                // Only the first fragment carries the transport header.
+                if u16::from_be_bytes([buf[at + 6], buf[at + 7]]) & 0x1fff != 0 {
+                    return at + header_len;
+                }
+                let protocol = buf[at + 9];
+                self.transport(buf, at + header_len, protocol, depth)
+            }
+            Some(6) if buf.len() >= at + 40 => {
+                self.rewrite_v6(buf, at + 8);
+                self.rewrite_v6(buf, at + 24);
+                let next_header = buf[at + 6];
+                self.transport(buf, at + 40, next_header, depth)
+            }
+            _ => at,
+        }
+    }
+
+    fn transport(&mut self, buf: &mut [u8], at: usize, protocol: u8, depth: usize) -> usize {
+        let available = buf.len().saturating_sub(at);
+        match protocol {
+            // TCP: the header with its options; ICMP errors may quote only the first 8 bytes.
+            6 if available >= 20 => {
+                buf[at + 16] = 0;
+                buf[at + 17] = 0;
+                (at + ((buf[at + 12] >> 4) as usize) * 4).min(buf.len())
+            }
+            6 => at + available.min(8),
+            17 if available >= 8 => {
+                buf[at + 6] = 0;
+                buf[at + 7] = 0;
+                at + 8
+            }
+            1 | 58 if available >= 8 => {
+                buf[at + 2] = 0;
+                buf[at + 3] = 0;
+                let (kind, v6) = (buf[at], protocol == 58);
+                match (v6, kind) {
+                    // Errors quote the offending packet, whose addresses need the same treatment.
+                    (false, 3) | (false, 4) | (false, 11) | (false, 12) | (true, 1..=4) => self.ip_packet(buf, at + 8, depth + 1),
+                    // Redirects also name the better gateway.
+                    (false, 5) => {
+                        self.rewrite_v4(buf, at + 4);
+                        self.ip_packet(buf, at + 8, depth + 1)
+                    }
+                    // Neighbor solicitations and advertisements carry a target address.
+                    (true, 135) | (true, 136) if available >= 24 => {
+                        self.rewrite_v6(buf, at + 8);
+                        at + 24
+                    }
+                    _ => at + 8,
+                }
+            }
+            4 | 41 => self.ip_packet(buf, at, depth + 1),
+            _ => at,
+        }
+    }
+}
+
+/// Replaces `word` in `text` only where it is not part of a longer name.
+fn replace_word(text: &str, word: &str, replacement: &str) -> String {
+    let part_of_name = |c: Option<char>| c.is_some_and(|c| c.is_alphanumeric() || c == '-' || c == '_');
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+    while let Some(at) = rest.find(word) {
+        let before = if at > 0 { rest[..at].chars().last() } else { out.chars().last() };
+        let after = rest[at + word.len()..].chars().next();
+        out.push_str(&rest[..at]);
+        out.push_str(if part_of_name(before) || part_of_name(after) { word } else { replacement });
+        rest = &rest[at + word.len()..];
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Internet checksum (RFC 1071) of `data`.
+fn checksum(data: &[u8]) -> u16 {
+    let mut sum: u32 = data.chunks(2).map(|c| u32::from(u16::from_be_bytes([c[0], *c.get(1).unwrap_or(&0)]))).sum();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Copies the capture at `input` to `output` with every frame anonymized.
+pub fn anonymize_file(input: &str, output: &str, anonymizer: &mut Anonymizer) -> io::Result<Stats> {
+    let mut reader = pcap::Reader::new(BufReader::new(File::open(input)?))?;
+    let mut writer = pcap::Writer::new(BufWriter::new(File::create(output)?), reader.linktype)?;
+    let mut stats = Stats::default();
+    while let Some(mut record) = reader.next_record()? {
+        let frame = anonymizer.frame(reader.linktype, &record.data);
+        stats.packets += 1;
+        stats.payload_bytes += record.data.len() - frame.len();
+        record.data = frame;
+        writer.write_record(&record)?;
+    }
+    writer.flush()?;
+    Ok(stats)
+}
+
+/// Returns the `anonymize` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("anonymize")
+        .about("Rewrites a pcap with consistently remapped IP/MAC addresses and payloads stripped, for sharing with vendors")
+        .arg(Arg::with_name("input").required(true).help("Capture to read"))
+        .arg(Arg::with_name("output").required(true).help("Anonymized capture to write"))
+}
+
+/// Runs the `anonymize` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let input = matches.value_of("input").unwrap_or_default();
+    let output = matches.value_of("output").unwrap_or_default();
+    if input == output {
+        println!("❌ {} Refusing to overwrite the input; choose a different output file", colorize("[ERROR]", "red"));
+        return;
+    }
+    let mut anonymizer = Anonymizer::new();
+    match anonymize_file(input, output, &mut anonymizer) {
+        Ok(stats) => {
+            println!("\n✅ {} Wrote {} ({} packets)", colorize("[SUCCESS]", "green"), output, stats.packets);
+            println!("   Remapped {} IP address(es) and {} MAC address(es)", anonymizer.addresses(), anonymizer.mac_count());
+            println!("   Stripped {} bytes of payload; headers, ports, flags, timing and original lengths are kept\n", stats.payload_bytes);
+        }
+        Err(e) => println!("❌ {} Could not anonymize {}: {}", colorize("[ERROR]", "red"), input, e),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ipv6_stand_ins_stay_distinct_past_65536_addresses() {
+        let mut anonymizer = Anonymizer::new();
+        let mut seen = HashSet::new();
+        for i in 0..70_000u32 {
+            let addr = Ipv6Addr::new(0x2a00, 0x1450, 0, 0, 0, 0, (i >> 16) as u16, i as u16);
+            let mapped = anonymizer.ipv6(addr);
+            assert_ne!(mapped, Ipv6Addr::new(0x2001, 0x0db8, 0, 0, 0, 0, 0, 0));
+            assert!(seen.insert(mapped), "{} mapped onto an earlier stand-in {}", addr, mapped);
+        }
+    }
+
+    #[test]
+    fn ipv6_mapping_is_consistent() {
+        let mut anonymizer = Anonymizer::new();
+        let addr: Ipv6Addr = "2a00:1450::1".parse().unwrap();
+        assert_eq!(anonymizer.ipv6(addr), anonymizer.ipv6(addr));
+        assert_eq!(anonymizer.addresses(), 1);
+    }
+
+    #[test]
+    fn public_ipv4_networks_do_not_wrap() {
+        let mut anonymizer = Anonymizer::new();
+        let mut seen = HashSet::new();
+        for i in 0..2_000u32 {
+            let mapped = anonymizer.ipv4(Ipv4Addr::new(8, (i >> 8) as u8, i as u8, 1));
+            assert!(seen.insert(mapped), "{} reused", mapped);
+        }
+    }
+
+    #[test]
+    fn finds_addresses_inside_labels() {
+        let mut anonymizer = Anonymizer::new();
+        let text = anonymizer.text("inet addr:192.168.1.5  Bcast:192.168.1.255  Mask:255.255.255.0");
+        assert!(!text.contains("192.168.1"), "{}", text);
+        assert!(text.contains("Mask:255.255.255.0"), "{}", text);
+    }
+}
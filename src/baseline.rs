@@ -0,0 +1,218 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+
+use clap::{App, AppSettings, ArgMatches, SubCommand};
+use serde_json;
+
+use crate::{colorize, data_dir};
+use crate::clock;
+use crate::netinfo;
+use crate::telemetry;
+use crate::traceroute;
+
+/// Hosts whose latency and path are recorded in every snapshot.
+const KEY_HOSTS: &[&str] = &["8.8.8.8", "1.1.1.1", "google.com"];
+
+/// A known-good picture of the network that later runs are compared against.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub taken_at: u64,
+    pub gateway: Option<String>,
+    pub dns_servers: Vec<String>,
+    pub public_ip: Option<String>,
+    pub latencies: BTreeMap<String, f64>,
+    pub paths: BTreeMap<String, Vec<String>>,
+}
+
+impl Snapshot {
+    /// Collects a fresh snapshot, probing key hosts in parallel.
+    pub fn take() -> Snapshot {
+        let probes: Vec<_> = KEY_HOSTS
+            .iter()
+            .map(|host| {
+                let host = host.to_string();
+                thread::spawn(move || {
+                    let latency = netinfo::ping(&host, 4).filter(|s| s.received > 0).map(|s| s.avg_ms);
+                    let path = traceroute::path(&host);
+                    (host, latency, path)
+                })
+            })
+            .collect();
+
+        let mut snapshot = Snapshot {
+            taken_at: clock::unix_now(),
+            gateway: netinfo::default_gateway(),
+            dns_servers: netinfo::dns_servers(),
+            public_ip: netinfo::public_ip(),
+            latencies: BTreeMap::new(),
+            paths: BTreeMap::new(),
+        };
+
+        for probe in probes {
+            if let Ok((host, latency, path)) = probe.join() {
+                if let Some(ms) = latency {
+                    snapshot.latencies.insert(host.clone(), ms);
+                }
+                if !path.is_empty() {
+                    snapshot.paths.insert(host, path);
+                }
+            }
+        }
+        snapshot
+    }
+}
+
+/// Returns the `baseline` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("baseline")
+        .about("Records a known-good network snapshot and compares against it")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("save").about("Saves the current network state as the baseline"))
+        .subcommand(SubCommand::with_name("diff").about("Compares the current network state with the baseline"))
+}
+
+/// Runs the `baseline` subcommand.
+pub fn run(matches: &ArgMatches) {
+    match matches.subcommand_name() {
+        Some("save") => save(),
+        Some("diff") => {
+            if load().is_none() {
+                println!("❌ {} No baseline saved yet. Run `netdiag baseline save` first.", colorize("[ERROR]", "red"));
+                return;
+            }
+            report_deviations();
+        }
+        _ => {}
+    }
+}
+
+fn baseline_path() -> PathBuf {
+    data_dir().join("baseline.json")
+}
+
+/// Takes a snapshot and writes it to the baseline file.
+fn save() {
+    println!("\n📸 {} Recording baseline (this runs ping and traceroute to key hosts)...\n", colorize("[INFO]", "blue"));
+    let snapshot = Snapshot::take();
+    let path = baseline_path();
+
+    let result = fs::create_dir_all(data_dir())
+        .and_then(|_| fs::write(&path, serde_json::to_string_pretty(&snapshot).unwrap_or_default()));
+    match result {
+        Ok(()) => {
+            println!("✅ {} Baseline saved to {}", colorize("[SUCCESS]", "green"), path.display());
+            println!("   Gateway:     {}", snapshot.gateway.as_deref().unwrap_or("unknown"));
+            println!("   DNS servers: {}", snapshot.dns_servers.join(", "));
+            println!("   Public IP:   {}", snapshot.public_ip.as_deref().unwrap_or("unknown"));
+            for (host, ms) in &snapshot.latencies {
+                println!("   {:<12} {:.1} ms over {} hops", host, ms, snapshot.paths.get(host).map_or(0, |p| p.len()));
+            }
+        }
+        Err(e) => println!("❌ {} Could not write {}: {}", colorize("[ERROR]", "red"), path.display(), e),
+    }
+}
+
+/// Loads the saved baseline, if there is one.
+pub fn load() -> Option<Snapshot> {
+    let text = fs::read_to_string(baseline_path()).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// Lists human-readable differences between a baseline and the current state.
+pub fn deviations(baseline: &Snapshot, current: &Snapshot) -> Vec<String> {
+    let mut found = Vec::new();
+
+    if baseline.gateway != current.gateway {
+        found.push(format!(
+            "Default gateway changed: {} → {}",
+            baseline.gateway.as_deref().unwrap_or("none"),
+            current.gateway.as_deref().unwrap_or("none")
+        ));
+    }
+    if baseline.dns_servers != current.dns_servers {
+        found.push(format!(
+            "DNS servers changed: [{}] → [{}]",
+            baseline.dns_servers.join(", "),
+            current.dns_servers.join(", ")
+        ));
+    }
+    if baseline.public_ip != current.public_ip {
+        found.push(format!(
+            "Public IP changed: {} → {}",
+            baseline.public_ip.as_deref().unwrap_or("unknown"),
+            current.public_ip.as_deref().unwrap_or("unknown")
+        ));
+    }
+
+    for (host, &before) in &baseline.latencies {
+        match current.latencies.get(host) {
+            None => found.push(format!("{} no longer answers ping (was {:.1} ms)", host, before)),
+            // Small absolute swings are normal; only flag a clear slowdown.
+            Some(&now) if now > before * 1.5 && now - before > 10.0 => {
+                found.push(format!("Latency to {} rose from {:.1} ms to {:.1} ms", host, before, now))
+            }
+            _ => {}
+        }
+    }
+
+    for (host, before) in &baseline.paths {
+        if let Some(now) = current.paths.get(host) {
+            if let Some(hop) = first_path_difference(before, now) {
+                found.push(format!(
+                    "Path to {} differs from hop {} ({} → {})",
+                    host,
+                    hop + 1,
+                    before.get(hop).map_or("-", |h| h.as_str()),
+                    now.get(hop).map_or("-", |h| h.as_str())
+                ));
+            }
+        }
+    }
+
+    found
+}
+
+/// Returns the index of the first hop that differs, ignoring hops that did not answer.
+fn first_path_difference(before: &[String], now: &[String]) -> Option<usize> {
+    for i in 0..before.len().max(now.len()) {
+        match (before.get(i), now.get(i)) {
+            (Some(a), Some(b)) if a == "*" || b == "*" || a == b => continue,
+            _ => return Some(i),
+        }
+    }
+    None
+}
+
+/// Compares the live network against the saved baseline and prints a deviation block.
+/// Does nothing when no baseline has been saved.
+pub fn report_deviations() {
+    let baseline = match load() {
+        Some(baseline) => baseline,
+        None => return,
+    };
+
+    println!("🔹 {}", colorize("Comparing against saved baseline", "blue"));
+    let current = Snapshot::take();
+    let found = deviations(&baseline, &current);
+    let age_hours = current.taken_at.saturating_sub(baseline.taken_at) / 3600;
+
+    if found.is_empty() {
+        println!("✅ {} Network matches the baseline saved {} hours ago.\n", colorize("[BASELINE]", "green"), age_hours);
+        return;
+    }
+
+    println!("{}", colorize(&"=".repeat(90), "yellow"));
+    println!(
+        "⚠️  {} {} deviation(s) from the baseline saved {} hours ago:",
+        colorize("[BASELINE]", "yellow"),
+        found.len(),
+        age_hours
+    );
+    for deviation in &found {
+        println!("   • {}", colorize(deviation, "yellow"));
+    }
+    telemetry::fail(&format!("{} deviation(s) from the baseline", found.len()));
+    println!("{}\n", colorize(&"=".repeat(90), "yellow"));
+}
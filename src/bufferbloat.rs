@@ -0,0 +1,165 @@
+use std::io::Write;
+use std::process::{Child, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::chart;
+use crate::colorize;
+use crate::http;
+use crate::netinfo;
+use crate::repeat;
+use crate::telemetry;
+
+const DOWNLOAD_URL: &str = "https://speed.cloudflare.com/__down?bytes=1000000000";
+const UPLOAD_URL: &str = "https://speed.cloudflare.com/__up";
+
+#[derive(Clone, Copy)]
+enum Direction {
+    Download,
+    Upload,
+}
+
+/// Returns the `bufferbloat` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bufferbloat")
+        .about("Measures how much latency grows while the link is saturated")
+        .arg(Arg::with_name("host").long("host").takes_value(true).default_value("1.1.1.1")
+            .help("Host to ping while loading the link"))
+        .arg(Arg::with_name("streams").long("streams").takes_value(true).default_value("4")
+            .help("Parallel transfers per direction"))
+        .arg(Arg::with_name("duration").long("duration").takes_value(true).default_value("10")
+            .help("Seconds to ping under load in each direction"))
+}
+
+/// Runs the `bufferbloat` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let host = matches.value_of("host").unwrap_or("1.1.1.1");
+    let streams = value_t!(matches, "streams", usize).unwrap_or(4);
+    let duration = value_t!(matches, "duration", u32).unwrap_or(10);
+    bufferbloat_test(host, streams, duration);
+}
+
+/// Grades the latency increase under load using the DSLReports scale.
+pub fn grade(increase_ms: f64) -> &'static str {
+    match increase_ms {
+        x if x < 5.0 => "A+",
+        x if x < 30.0 => "A",
+        x if x < 60.0 => "B",
+        x if x < 200.0 => "C",
+        x if x < 400.0 => "D",
+        _ => "F",
+    }
+}
+
+fn grade_color(grade: &str) -> &'static str {
+    match grade {
+        "A+" | "A" => "green",
+        "B" | "C" => "yellow",
+        _ => "red",
+    }
+}
+
+/// Measures idle latency, then latency while downloading and while uploading.
+pub fn bufferbloat_test(host: &str, streams: usize, duration: u32) {
+    println!("\n🚦 {} Running Bufferbloat Test against {}\n", colorize("[INFO]", "blue"), colorize(host, "cyan"));
+
+    println!("🔹 {}", colorize("Measuring idle latency", "blue"));
+    let idle = match netinfo::ping(host, 10).filter(|s| s.received > 0) {
+        Some(stats) => stats,
+        None => {
+            println!("❌ {} {} did not answer ping; cannot measure bufferbloat.", colorize("[ERROR]", "red"), host);
+            telemetry::fail(&format!("{} did not answer ping", host));
+            return;
+        }
+    };
+    chart::print_distribution("Idle", &idle.samples);
+    repeat::record("idle latency (ms)", idle.avg_ms);
+    println!();
+
+    let mut worst: f64 = 0.0;
+    for &(direction, label) in &[(Direction::Download, "download"), (Direction::Upload, "upload")] {
+        println!("🔹 {}", colorize(&format!("Measuring latency during {} ({} streams)", label, streams), "blue"));
+        let loaded = ping_under_load(host, direction, streams, duration);
+
+        match loaded {
+            Some(stats) if stats.received > 0 => {
+                let increase = (stats.avg_ms - idle.avg_ms).max(0.0);
+                worst = worst.max(increase);
+                repeat::record(&format!("{} latency increase (ms)", label), increase);
+                let g = grade(increase);
+                chart::print_distribution("Loaded", &stats.samples);
+                println!(
+                    "   +{:.1} ms over idle, {}/{} replies → grade {}\n",
+                    increase, stats.received, stats.transmitted, colorize(g, grade_color(g))
+                );
+            }
+            _ => {
+                worst = f64::INFINITY;
+                println!("   ❌ {} No ping replies while the link was loaded.\n", colorize("[ERROR]", "red"));
+            }
+        }
+    }
+
+    let overall = grade(worst);
+    println!("📊 {} Bufferbloat grade: {}", colorize("[SUMMARY]", "blue"), colorize(overall, grade_color(overall)));
+    if overall != "A+" && overall != "A" {
+        telemetry::fail(&format!("bufferbloat grade {}", overall));
+        println!(
+            "   {}",
+            colorize("Latency rises sharply under load; enabling SQM (fq_codel/cake) on the router usually fixes this.", "yellow")
+        );
+    }
+    println!();
+}
+
+/// Starts `streams` transfers, pings `host` for `duration` seconds, then stops the transfers.
+fn ping_under_load(host: &str, direction: Direction, streams: usize, duration: u32) -> Option<netinfo::PingStats> {
+    let mut transfers: Vec<Child> = (0..streams).filter_map(|_| start_transfer(direction, duration + 5)).collect();
+    if transfers.is_empty() {
+        println!("   ❌ {} Could not start curl transfers.", colorize("[ERROR]", "red"));
+        return None;
+    }
+
+    // Give TCP a moment to ramp up and fill the queues before sampling latency.
+    thread::sleep(Duration::from_secs(2));
+    let stats = netinfo::ping(host, duration);
+
+    for transfer in &mut transfers {
+        let _ = transfer.kill();
+        let _ = transfer.wait();
+    }
+    stats
+}
+
+/// Spawns one curl transfer that runs for at most `max_secs` seconds.
+fn start_transfer(direction: Direction, max_secs: u32) -> Option<Child> {
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let max_time = max_secs.to_string();
+
+    match direction {
+        Direction::Download => http::curl(DOWNLOAD_URL)
+            .args(["-s", "-o", null, "--max-time", &max_time])
+            .stderr(Stdio::null())
+            .spawn()
+            .ok(),
+        Direction::Upload => {
+            let mut child = http::curl(UPLOAD_URL)
+                .args(["-s", "-o", null, "--max-time", &max_time, "-X", "POST", "-T", "-"])
+                .stdin(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .ok()?;
+
+            // Feed zeros until curl exits and the pipe breaks.
+            if let Some(mut stdin) = child.stdin.take() {
+                thread::spawn(move || {
+                    let chunk = vec![0u8; 64 * 1024];
+                    while stdin.write_all(&chunk).is_ok() {}
+                });
+            }
+            Some(child)
+        }
+    }
+}
@@ -0,0 +1,344 @@
+use std::env;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json;
+
+use crate::anonymize::{self, Anonymizer};
+use crate::clock;
+use crate::interrupt;
+use crate::netinfo::{self, Interface};
+use crate::privileges;
+use crate::report::Report;
+use crate::scheduler;
+use crate::{colorize, random_u64};
+
+/// What the bundle was collected on.
+#[derive(Serialize)]
+struct Environment {
+    os: String,
+    arch: String,
+    kernel: Option<String>,
+    distribution: Option<String>,
+    hostname: Option<String>,
+    privileged: bool,
+    interfaces: Vec<Interface>,
+}
+
+/// One file in the bundle, as listed in `index.json`.
+#[derive(Serialize)]
+struct IndexEntry {
+    path: String,
+    description: String,
+    bytes: u64,
+}
+
+/// Table of contents written next to the collected files.
+#[derive(Serialize)]
+struct Index {
+    created_at: String,
+    netdiag_version: String,
+    files: Vec<IndexEntry>,
+    /// Parts that could not be collected, and why.
+    skipped: Vec<String>,
+}
+
+/// Returns the `bundle` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("bundle")
+        .about("Packages a report, raw command outputs, a packet capture and environment info into one archive for support")
+        .arg(Arg::with_name("output").long("output").short("o").takes_value(true)
+            .help("Archive to write (default: netdiag-bundle-<time>.tar.gz in the current directory)"))
+        .arg(Arg::with_name("interface").long("interface").short("i").takes_value(true)
+            .help("Interface to capture on (default: the one routing to the internet)"))
+        .arg(Arg::with_name("capture-secs").long("capture-secs").takes_value(true).default_value("15")
+            .help("How long to capture packets"))
+        .arg(Arg::with_name("no-capture").long("no-capture").help("Leave the packet capture out of the bundle"))
+        .arg(Arg::with_name("anonymize").long("anonymize")
+            .help("Consistently remap IP/MAC addresses and the hostname, and strip captured payloads, before packaging"))
+}
+
+/// Runs the `bundle` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let stamp = clock::file_stamp(clock::unix_now());
+    let name = format!("netdiag-bundle-{}", stamp);
+    let output = PathBuf::from(matches.value_of("output").map(|o| o.to_string()).unwrap_or_else(|| format!("{}.tar.gz", name)));
+    let private = match private_dir() {
+        Ok(private) => private,
+        Err(e) => {
+            println!("❌ {} Could not create a staging directory in {}: {}", colorize("[ERROR]", "red"), env::temp_dir().display(), e);
+            return;
+        }
+    };
+    // The archive's top-level directory keeps the readable name.
+    let staging = private.join(&name);
+    // Whatever was collected is archived and the staging directory removed even past the
+    // interrupt grace period; later steps are skipped after Ctrl-C, and a second one quits at once.
+    let _cleanup = interrupt::cleanup();
+    if let Err(e) = fs::create_dir_all(staging.join("commands")) {
+        println!("❌ {} Could not create {}: {}", colorize("[ERROR]", "red"), staging.display(), e);
+        let _ = fs::remove_dir_all(&private);
+        return;
+    }
+
+    println!("\n📦 {} Collecting a diagnostic bundle (this takes a minute)\n", colorize("[INFO]", "blue"));
+    let mut index = Index {
+        created_at: clock::format_timestamp(clock::unix_now()),
+        netdiag_version: env!("CARGO_PKG_VERSION").to_string(),
+        files: Vec::new(),
+        skipped: Vec::new(),
+    };
+
+    println!("🔹 {}", colorize("Environment", "blue"));
+    let text = serde_json::to_string_pretty(&environment()).unwrap_or_default();
+    write_file(&staging, "environment.json", "OS, kernel, hostname and interface list", text.as_bytes(), &mut index);
+
+    println!("🔹 {}", colorize("Raw command outputs", "blue"));
+    for (file, description, command, args) in commands() {
+        if interrupt::interrupted() {
+            break;
+        }
+        collect_command(&staging, file, description, command, &args, &mut index);
+    }
+
+    if !interrupt::interrupted() {
+        println!("🔹 {}", colorize("Probing key hosts for the report", "blue"));
+        let text = serde_json::to_string_pretty(&Report::collect()).unwrap_or_default();
+        write_file(&staging, "report.json", "Gateway, DNS, public IP, latency and paths to key hosts, routes", text.as_bytes(), &mut index);
+    }
+
+    if matches.is_present("no-capture") {
+        index.skipped.push("capture.pcap: disabled with --no-capture".to_string());
+    } else if !interrupt::interrupted() {
+        let interface = matches
+            .value_of("interface")
+            .map(|i| i.to_string())
+            .or_else(|| netinfo::route_interface("8.8.8.8"))
+            .unwrap_or_else(|| "any".to_string());
+        let secs = value_t!(matches, "capture-secs", u64).unwrap_or(15);
+        capture(&staging, &interface, secs, &mut index);
+    }
+
+    if interrupt::interrupted() {
+        index.skipped.push("collection was interrupted; later parts are missing".to_string());
+    }
+    let anonymized = matches.is_present("anonymize");
+    if anonymized {
+        anonymize_staged(&staging, &mut index);
+    }
+    let text = serde_json::to_string_pretty(&index).unwrap_or_default();
+    let _ = fs::write(staging.join("index.json"), text);
+
+    archive(&staging, &name, &output, anonymized);
+    let _ = fs::remove_dir_all(&private);
+}
+
+/// Creates an owner-only directory with an unguessable name in the temp dir for staging. It
+/// fails rather than reuse an existing directory, which another local user could own or have
+/// planted symlinks in.
+fn private_dir() -> io::Result<PathBuf> {
+    let dir = env::temp_dir().join(format!("netdiag-bundle-{:016x}", random_u64()));
+    let mut builder = fs::DirBuilder::new();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::DirBuilderExt;
+        builder.mode(0o700);
+    }
+    builder.create(&dir)?;
+    Ok(dir)
+}
+
+/// Describes the OS and network interfaces.
+fn environment() -> Environment {
+    let release = fs::read_to_string("/etc/os-release").ok();
+    let distribution = release.as_deref().and_then(|text| {
+        text.lines().find_map(|l| l.strip_prefix("PRETTY_NAME=")).map(|n| n.trim_matches('"').to_string())
+    });
+    let kernel = if cfg!(windows) { netinfo::command_stdout("cmd", &["/C", "ver"]) } else { netinfo::command_stdout("uname", &["-a"]) };
+    Environment {
+        os: env::consts::OS.to_string(),
+        arch: env::consts::ARCH.to_string(),
+        kernel: kernel.map(|k| k.trim().to_string()),
+        distribution,
+        hostname: netinfo::command_stdout("hostname", &[]).map(|h| h.trim().to_string()),
+        privileged: privileges::is_root(),
+        interfaces: netinfo::interfaces(),
+    }
+}
+
+/// Commands whose raw output support staff usually ask for, per platform.
+fn commands() -> Vec<(&'static str, &'static str, &'static str, Vec<&'static str>)> {
+    if cfg!(windows) {
+        return vec![
+            ("ipconfig.txt", "Interfaces, addresses and DNS servers", "ipconfig", vec!["/all"]),
+            ("routes.txt", "Routing table", "route", vec!["print"]),
+            ("arp.txt", "Neighbour (ARP) cache", "arp", vec!["-a"]),
+            ("connections.txt", "Open connections and listening ports", "netstat", vec!["-ano"]),
+            ("ping.txt", "Ping to 8.8.8.8", "ping", vec!["-n", "4", "8.8.8.8"]),
+            ("traceroute.txt", "Path to google.com", "tracert", vec!["-d", "google.com"]),
+        ];
+    }
+    let linux = cfg!(target_os = "linux");
+    vec![
+        ("interfaces.txt", "Interfaces and addresses", if linux { "ip" } else { "ifconfig" }, if linux { vec!["addr", "show"] } else { vec!["-a"] }),
+        ("routes.txt", "Routing table", if linux { "ip" } else { "netstat" }, if linux { vec!["route", "show", "table", "all"] } else { vec!["-rn"] }),
+        ("neighbors.txt", "Neighbour (ARP/NDP) cache", if linux { "ip" } else { "arp" }, if linux { vec!["neigh", "show"] } else { vec!["-an"] }),
+        ("connections.txt", "Open connections and listening ports", if linux { "ss" } else { "netstat" }, if linux { vec!["-tunap"] } else { vec!["-an"] }),
+        ("resolv.conf.txt", "Resolver configuration", "cat", vec!["/etc/resolv.conf"]),
+        ("ping.txt", "Ping to 8.8.8.8", "ping", vec!["-c", "4", "8.8.8.8"]),
+        ("traceroute.txt", "Path to google.com", "traceroute", vec!["-n", "-q", "1", "google.com"]),
+    ]
+}
+
+/// Runs one command and saves its stdout and stderr under `commands/`.
+fn collect_command(staging: &Path, file: &str, description: &str, command: &str, args: &[&str], index: &mut Index) {
+    let path = format!("commands/{}", file);
+    let mut run = Command::new(command);
+    run.args(args);
+    // ping and traceroute probe their last argument, so they are scheduled like other probes.
+    let result = match (command, args.last()) {
+        ("ping", Some(target)) => interrupt::output(&mut scheduler::command("icmp-echo(4)", target, None, run)),
+        ("traceroute" | "tracert", Some(target)) => interrupt::output(&mut scheduler::command("traceroute", target, None, run)),
+        _ => interrupt::output(&mut run),
+    };
+    let text = match result {
+        Ok(output) => format!(
+            "$ {} {}\n{}{}",
+            command,
+            args.join(" "),
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => {
+            println!("   ⚠️  {} {}: {}", colorize("[WARN]", "yellow"), command, e);
+            index.skipped.push(format!("{}: could not run {}: {}", path, command, e));
+            return;
+        }
+    };
+    write_file(staging, &path, description, text.as_bytes(), index);
+}
+
+/// Captures full packets on `interface` for `secs` seconds into `capture.pcap`.
+fn capture(staging: &Path, interface: &str, secs: u64, index: &mut Index) {
+    if let Err(reason) = privileges::can_capture() {
+        println!("{}", privileges::hint("Packet capture", &reason));
+        index.skipped.push(format!("capture.pcap: {}", reason.lines().next().unwrap_or_default()));
+        return;
+    }
+    println!("🔹 {}", colorize(&format!("Capturing on {} for {} seconds", interface, secs), "blue"));
+    let path = staging.join("capture.pcap");
+    // tcpdump writes to stdout, a file opened here: it may drop root before opening an output
+    // file itself, and could then not write into the private staging directory.
+    let file = match File::create(&path) {
+        Ok(file) => file,
+        Err(e) => {
+            index.skipped.push(format!("capture.pcap: could not create: {}", e));
+            return;
+        }
+    };
+    // -U writes each packet as it arrives, so stopping tcpdump loses nothing.
+    let mut child = match Command::new("tcpdump")
+        .args(["-i", interface, "-U", "-w", "-"])
+        .stdout(Stdio::from(file))
+        .stderr(Stdio::null())
+        .spawn() {
+        Ok(child) => child,
+        Err(e) => {
+            let _ = fs::remove_file(&path);
+            index.skipped.push(format!("capture.pcap: could not start tcpdump: {}", e));
+            return;
+        }
+    };
+    interrupt::track(&child);
+    interrupt::sleep(Duration::from_secs(secs));
+    let _ = child.kill();
+    let _ = child.wait();
+    interrupt::untrack(&child);
+    let bytes = match fs::metadata(&path) {
+        Ok(metadata) if metadata.len() > 0 => metadata.len(),
+        _ => {
+            let _ = fs::remove_file(&path);
+            index.skipped.push(format!("capture.pcap: tcpdump wrote nothing on {}", interface));
+            return;
+        }
+    };
+    index.files.push(IndexEntry {
+        path: "capture.pcap".to_string(),
+        description: format!("{} seconds of traffic on {} (open with Wireshark)", secs, interface),
+        bytes,
+    });
+    println!("   ✅ capture.pcap ({} bytes)", bytes);
+}
+
+/// Writes one file into the staging directory and lists it in the index.
+fn write_file(staging: &Path, path: &str, description: &str, contents: &[u8], index: &mut Index) {
+    match fs::write(staging.join(path), contents) {
+        Ok(()) => {
+            println!("   ✅ {}", path);
+            index.files.push(IndexEntry { path: path.to_string(), description: description.to_string(), bytes: contents.len() as u64 });
+        }
+        Err(e) => index.skipped.push(format!("{}: could not write: {}", path, e)),
+    }
+}
+
+/// Rewrites every collected file with one `Anonymizer`, so a host has the same stand-in address
+/// in the report, the command outputs and the capture.
+fn anonymize_staged(staging: &Path, index: &mut Index) {
+    println!("🔹 {}", colorize("Anonymizing", "blue"));
+    let mut anonymizer = Anonymizer::new();
+    if let Some(hostname) = netinfo::command_stdout("hostname", &[]) {
+        anonymizer.redact_name(&hostname);
+        anonymizer.redact_name(hostname.trim().split('.').next().unwrap_or_default());
+    }
+    for entry in &mut index.files {
+        let path = staging.join(&entry.path);
+        let result = if entry.path.ends_with(".pcap") {
+            let anonymized = staging.join("anonymized.pcap");
+            anonymize::anonymize_file(&path.to_string_lossy(), &anonymized.to_string_lossy(), &mut anonymizer)
+                .and_then(|_| fs::rename(&anonymized, &path))
+        } else {
+            fs::read_to_string(&path).and_then(|text| fs::write(&path, anonymizer.text(&text)))
+        };
+        match result {
+            Ok(()) => entry.bytes = fs::metadata(&path).map(|m| m.len()).unwrap_or(entry.bytes),
+            Err(e) => {
+                // Better to leave a file out than to ship it with real addresses.
+                let _ = fs::remove_file(&path);
+                index.skipped.push(format!("{}: removed because it could not be anonymized: {}", entry.path, e));
+            }
+        }
+    }
+    index.files.retain(|entry| staging.join(&entry.path).exists());
+    println!("   ✅ Remapped {} IP and {} MAC address(es)", anonymizer.addresses(), anonymizer.mac_count());
+}
+
+/// Compresses the staging directory into `output` with `tar`.
+fn archive(staging: &Path, name: &str, output: &Path, anonymized: bool) {
+    let parent = staging.parent().unwrap_or(Path::new("."));
+    let mut tar = Command::new("tar");
+    tar.arg("-czf").arg(output).arg("-C").arg(parent).arg(name);
+    // Its own process group, so a Ctrl-C at the terminal does not cut the archive short.
+    #[cfg(unix)]
+    {
+        use std::os::unix::process::CommandExt;
+        tar.process_group(0);
+    }
+    let result = tar.output();
+    match result {
+        Ok(out) if out.status.success() => {
+            let bytes = fs::metadata(output).map(|m| m.len()).unwrap_or(0);
+            println!("\n📦 {} Bundle written to {} ({} KiB)", colorize("[SUCCESS]", "green"), colorize(&output.display().to_string(), "cyan"), bytes / 1024);
+            if anonymized {
+                println!("   Addresses and the hostname are remapped and payloads stripped; skim it for names and URLs before sharing.\n");
+            } else {
+                println!("   It contains addresses, hostnames and captured traffic from this network; share it only with people you trust.\n");
+            }
+        }
+        Ok(out) => println!("❌ {} tar failed: {}", colorize("[ERROR]", "red"), String::from_utf8_lossy(&out.stderr).trim()),
+        Err(e) => println!("❌ {} Could not run tar: {}", colorize("[ERROR]", "red"), e),
+    }
+}
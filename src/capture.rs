@@ -0,0 +1,887 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::io::{self, Read};
+use std::net::{IpAddr, Ipv4Addr};
+use std::process::{Child, Command, Stdio};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+#[cfg(unix)]
+use libc;
+use serde_json;
+
+use crate::afpacket;
+use crate::colorize;
+use crate::config::{Config, SiteConfig};
+use crate::interrupt;
+use crate::netinfo;
+use crate::packet::{self, Arp, MacAddr, Packet, TcpHandshake, Tunnel};
+use crate::peer::{self, PeerOptions};
+use crate::pcap;
+use crate::privileges;
+use crate::routes;
+use crate::sockets::{self, Socket};
+use crate::telemetry;
+use crate::throughput::ThroughputWatch;
+use crate::traffic::TrafficStats;
+use crate::vendor;
+use crate::vpn;
+use crate::websites;
+
+/// What to capture and for how long.
+pub struct CaptureOptions {
+    pub interface: String,
+    /// Port to capture; empty captures all TCP and UDP.
+    pub port: String,
+    pub max_packets: usize,
+    pub timeout_secs: u64,
+    /// Visit a list of websites during the capture to generate traffic.
+    pub visit_sites: bool,
+    /// Sites to visit; empty uses the built-in list.
+    pub sites: Vec<SiteConfig>,
+    /// Write one JSON object per packet to stdout instead of the table and summaries.
+    pub ndjson: bool,
+    /// Only show packets of sockets owned by this process (PID or name).
+    pub process: Option<String>,
+    pub backend: Backend,
+    /// Keep one packet in this many (1 keeps all); applied in the kernel by the socket backend and
+    /// as packets are read with tcpdump.
+    pub sample: u32,
+    /// Count traffic per second, protocol and host instead of listing packets.
+    pub stats: bool,
+}
+
+/// Where captured packets come from.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    /// `tcpdump` writing pcap to a pipe; works everywhere tcpdump does.
+    Tcpdump,
+    /// An in-process Linux `AF_PACKET` socket with the filter and sampling run in the kernel as
+    /// classic BPF (not eBPF), and packets timestamped by the kernel on receipt.
+    Socket,
+}
+
+impl Default for CaptureOptions {
+    fn default() -> CaptureOptions {
+        CaptureOptions {
+            interface: "en0".to_string(),
+            port: "53".to_string(),
+            max_packets: 10,
+            timeout_secs: 1,
+            visit_sites: true,
+            sites: Vec::new(),
+            ndjson: false,
+            process: None,
+            backend: Backend::Tcpdump,
+            sample: 1,
+            stats: false,
+        }
+    }
+}
+
+/// Returns the `capture` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("capture")
+        .about("Captures packets with tcpdump, decoding them and watching ARP for spoofing")
+        .arg(Arg::with_name("interface").long("interface").short("i").takes_value(true)
+            .help("Interface to capture on (default: the default-route interface)"))
+        .arg(Arg::with_name("port").long("port").short("p").takes_value(true).default_value("53")
+            .help("Port to capture; ARP is always captured as well (default with --process: all TCP/UDP)"))
+        .arg(Arg::with_name("count").long("count").short("c").takes_value(true).default_value("50")
+            .help("Stop after this many packets matching --port (or --process)"))
+        .arg(Arg::with_name("timeout").long("timeout").takes_value(true).default_value("30")
+            .help("Stop after this many seconds"))
+        .arg(Arg::with_name("passive").long("passive")
+            .help("Only listen; do not visit websites to generate traffic"))
+        .arg(Arg::with_name("format").long("format").takes_value(true).default_value("text")
+            .possible_values(&["text", "ndjson"])
+            .help("Output format; ndjson streams one JSON object per packet for jq or a SIEM"))
+        .arg(Arg::with_name("process").long("process").takes_value(true).value_name("name|pid")
+            .help("Only show packets to or from sockets owned by this process"))
+        .arg(Arg::with_name("backend").long("backend").takes_value(true)
+            .possible_values(&["tcpdump", "socket"])
+            .help("Capture backend; socket uses a Linux packet socket with an in-kernel classic BPF filter and falls back to tcpdump (default: tcpdump, or socket with --peer)"))
+        .arg(Arg::with_name("sample").long("sample").takes_value(true).default_value("1").value_name("N|1/N")
+            .help("Keep one packet in N, e.g. 100 or 1/100; sampled in the kernel with the socket backend"))
+        .arg(Arg::with_name("peer").long("peer").takes_value(true).value_name("host[:port]")
+            .help("Capture a test flow at both ends together with netdiag on another machine (run there with --peer pointing back here) to locate one-way loss"))
+        .arg(Arg::with_name("peer-port").long("peer-port").takes_value(true).value_name("port")
+            .help("TCP control port this side listens on with --peer; the test flow uses the same UDP port (default 7475)"))
+        .arg(Arg::with_name("flow-rate").long("flow-rate").takes_value(true).default_value("50")
+            .help("Test probes per second in each direction with --peer"))
+        .arg(Arg::with_name("flow-seconds").long("flow-seconds").takes_value(true).default_value("10")
+            .help("How long the test flow runs with --peer"))
+        .arg(Arg::with_name("stats").long("stats")
+            .help("Only count packets and bytes per second, per protocol and per host, for busy links; runs until --timeout unless --count is given"))
+}
+
+/// Runs the `capture` subcommand.
+pub fn run(matches: &ArgMatches, config: &Config) {
+    if let Some(peer) = matches.value_of("peer") {
+        peer::run(&PeerOptions {
+            peer: peer.to_string(),
+            listen_port: value_t!(matches, "peer-port", u16).unwrap_or(peer::DEFAULT_PORT),
+            rate: value_t!(matches, "flow-rate", u32).unwrap_or(50),
+            seconds: value_t!(matches, "flow-seconds", u64).unwrap_or(10),
+            wait_secs: value_t!(matches, "timeout", u64).unwrap_or(30),
+            interface: matches.value_of("interface").map(|i| i.to_string()),
+            // Packet sockets need no tcpdump, and fall back to it when unavailable.
+            backend: if matches.value_of("backend") == Some("tcpdump") { Backend::Tcpdump } else { Backend::Socket },
+        });
+        return;
+    }
+    let interface = matches
+        .value_of("interface")
+        .map(|i| i.to_string())
+        .or_else(|| routes::table().into_iter().find(|r| r.is_default()).map(|r| r.interface))
+        .unwrap_or_else(|| "en0".to_string());
+    let process = matches.value_of("process").map(|p| p.to_string());
+    let stats = matches.is_present("stats");
+    let sample = match parse_sample(matches.value_of("sample").unwrap_or("1")) {
+        Some(sample) => sample,
+        None => {
+            println!("❌ {} --sample takes N or 1/N with N ≥ 1, e.g. 100 or 1/100", colorize("[ERROR]", "red"));
+            return;
+        }
+    };
+    // A process filter or traffic statistics are usually about all traffic, not just DNS.
+    let port = if (process.is_some() || stats) && matches.occurrences_of("port") == 0 {
+        String::new()
+    } else {
+        matches.value_of("port").unwrap_or("53").to_string()
+    };
+    let options = CaptureOptions {
+        interface,
+        port,
+        max_packets: match value_t!(matches, "count", usize) {
+            Ok(_) if stats && matches.occurrences_of("count") == 0 => usize::MAX,
+            count => count.unwrap_or(50),
+        },
+        timeout_secs: value_t!(matches, "timeout", u64).unwrap_or(30),
+        visit_sites: !matches.is_present("passive"),
+        sites: config.sites.clone(),
+        ndjson: matches.value_of("format") == Some("ndjson") && !stats,
+        process,
+        backend: match matches.value_of("backend") {
+            Some("socket") => Backend::Socket,
+            _ => Backend::Tcpdump,
+        },
+        sample,
+        stats,
+    };
+    capture_traffic(&options);
+}
+
+/// Reads a sampling rate given as `N` or `1/N`.
+fn parse_sample(text: &str) -> Option<u32> {
+    let n = match text.split_once('/') {
+        Some(("1", n)) => n,
+        Some(_) => return None,
+        None => text,
+    };
+    n.trim().parse().ok().filter(|n| *n >= 1)
+}
+
+/// Tracks IP→MAC bindings seen in ARP traffic and reports anything that looks like spoofing.
+struct ArpWatch {
+    bindings: HashMap<Ipv4Addr, MacAddr>,
+    gateway: Option<Ipv4Addr>,
+    reported: HashSet<(Ipv4Addr, MacAddr)>,
+    /// Every IP each MAC has claimed during the capture.
+    claims: HashMap<MacAddr, HashSet<Ipv4Addr>>,
+    arp_packets: usize,
+    warnings: Vec<String>,
+}
+
+impl ArpWatch {
+    /// Seeds the bindings from the system neighbor cache so a changed gateway MAC is caught on first sight.
+    fn new() -> ArpWatch {
+        let bindings = netinfo::neighbors()
+            .into_iter()
+            .filter_map(|(ip, mac)| Some((ip.parse().ok()?, MacAddr::parse(&mac)?)))
+            .collect();
+        let gateway = netinfo::default_gateway().and_then(|g| g.parse().ok());
+        ArpWatch { bindings, gateway, reported: HashSet::new(), claims: HashMap::new(), arp_packets: 0, warnings: Vec::new() }
+    }
+
+    /// Records one ARP message and returns any new warnings it raises.
+    fn observe(&mut self, arp: &Arp) -> Vec<String> {
+        self.arp_packets += 1;
+        let ip = arp.sender_ip;
+        let mac = arp.sender_mac;
+        // ARP probes (RFC 5227) use 0.0.0.0 as the sender and claim nothing.
+        if ip.is_unspecified() || !self.reported.insert((ip, mac)) {
+            return Vec::new();
+        }
+        let is_gateway = self.gateway == Some(ip);
+        let mut found = Vec::new();
+
+        if arp.is_gratuitous() {
+            found.push(format!(
+                "Gratuitous ARP: {} is-at {}{}",
+                ip,
+                mac,
+                if is_gateway { " (the gateway; expected only during router failover)" } else { "" }
+            ));
+        }
+        match self.bindings.get(&ip) {
+            Some(known) if *known != mac && is_gateway => found.push(format!(
+                "Gateway {} changed MAC {} → {}: possible ARP spoofing / man-in-the-middle",
+                ip, with_vendor(known), with_vendor(&mac)
+            )),
+            Some(known) if *known != mac => {
+                found.push(format!("IP conflict: {} is claimed by both {} and {}", ip, with_vendor(known), with_vendor(&mac)))
+            }
+            Some(_) => {}
+            None => {
+                self.bindings.insert(ip, mac);
+            }
+        }
+        let claimed = self.claims.entry(mac).or_default();
+        claimed.insert(ip);
+        if let Some(gateway) = self.gateway.filter(|g| claimed.contains(g)) {
+            for other in claimed.iter().filter(|c| **c != gateway && (ip == gateway || **c == ip)) {
+                found.push(format!("{} answers for both the gateway {} and {}: typical of an ARP spoofer", with_vendor(&mac), gateway, other));
+            }
+        }
+
+        self.warnings.extend(found.iter().cloned());
+        found
+    }
+
+    fn print_summary(&self) {
+        if self.warnings.is_empty() {
+            println!("✅ {} No ARP anomalies in {} ARP packet(s).", colorize("[SUCCESS]", "green"), self.arp_packets);
+            return;
+        }
+        println!("\n🚨 {} {} suspicious ARP event(s):", colorize("[ARP]", "red"), self.warnings.len());
+        for warning in &self.warnings {
+            println!("   • {}", colorize(warning, "red"));
+        }
+        println!("   Compare the gateway MAC with the router's label or admin page, and consider a static ARP entry");
+        println!("   or switch features such as Dynamic ARP Inspection if spoofing is confirmed.");
+    }
+}
+
+/// A MAC address followed by its vendor.
+fn with_vendor(mac: &MacAddr) -> String {
+    format!("{} ({})", mac, vendor::label(mac))
+}
+
+/// Counts frames per sending MAC, with the IP addresses each one used, to show which devices
+/// were talking on the link.
+#[derive(Default)]
+struct DeviceWatch {
+    devices: HashMap<MacAddr, (usize, BTreeSet<IpAddr>)>,
+}
+
+impl DeviceWatch {
+    fn observe(&mut self, packet: &Packet) {
+        // Frames unwrapped from a tunnel carry the remote side's MACs, not this link's.
+        if let Some(mac) = packet.src_mac.filter(|_| packet.tunnels.is_empty()) {
+            let (frames, addrs) = self.devices.entry(mac).or_default();
+            *frames += 1;
+            addrs.extend(packet.src);
+        }
+    }
+
+    fn print_summary(&self) {
+        if self.devices.is_empty() {
+            return;
+        }
+        let mut devices: Vec<(&MacAddr, &(usize, BTreeSet<IpAddr>))> = self.devices.iter().collect();
+        devices.sort_by_key(|(_, (frames, _))| Reverse(*frames));
+        println!("\n🔹 {}", colorize("Devices seen on the link", "blue"));
+        println!("   {:<19} {:<30} {:>7}  Addresses", "MAC", "Vendor", "Frames");
+        for (mac, (frames, addrs)) in devices.iter().take(15) {
+            let mut shown: Vec<String> = addrs.iter().take(3).map(|a| a.to_string()).collect();
+            if addrs.len() > 3 {
+                shown.push(format!("+{} more", addrs.len() - 3));
+            }
+            println!("   {:<19} {:<30} {:>7}  {}", mac.to_string(), vendor::label(mac), frames, shown.join(", "));
+        }
+        if devices.len() > 15 {
+            println!("   … and {} more", devices.len() - 15);
+        }
+    }
+}
+
+/// Pairs SYNs with their SYN-ACKs and checks the negotiated MSS, window scaling and SACK.
+struct HandshakeWatch {
+    interface: String,
+    mtu: Option<u32>,
+    /// SYN options by (client, server) endpoint, waiting for the SYN-ACK.
+    pending: HashMap<(String, String), TcpHandshake>,
+    /// (client, server, SYN, SYN-ACK, tunnel overhead in bytes, IPv6).
+    pairs: Vec<(String, String, TcpHandshake, TcpHandshake, u32, bool)>,
+}
+
+impl HandshakeWatch {
+    /// Looks up the capture interface's MTU to compare advertised MSS values against.
+    fn new(interface: &str) -> HandshakeWatch {
+        HandshakeWatch { interface: interface.to_string(), mtu: netinfo::mtu(interface), pending: HashMap::new(), pairs: Vec::new() }
+    }
+
+    /// Records the handshake options of a SYN or SYN-ACK.
+    fn observe(&mut self, packet: &Packet) {
+        let handshake = match &packet.handshake {
+            Some(handshake) => handshake.clone(),
+            None => return,
+        };
+        let (src, dst) = (packet.source(), packet.destination());
+        if !handshake.ack {
+            self.pending.insert((src, dst), handshake);
+        } else if let Some(syn) = self.pending.remove(&(dst.clone(), src.clone())) {
+            let overhead = packet.tunnels.iter().map(|t| tunnel_overhead(&t.kind)).sum();
+            self.pairs.push((dst, src, syn, handshake, overhead, matches!(packet.src, Some(IpAddr::V6(_)))));
+        }
+    }
+
+    /// Largest MSS that fits the capture interface's MTU after IP/TCP and tunnel headers.
+    fn mss_limit(&self, overhead: u32, v6: bool) -> Option<u32> {
+        let mtu = self.mtu?;
+        // Segments crossing a tunnel captured on the outer interface still have to fit 1500 bytes.
+        let mtu = if overhead > 0 { mtu.min(1500) } else { mtu };
+        Some(mtu.saturating_sub(overhead + if v6 { 60 } else { 40 }))
+    }
+
+    fn print_summary(&self) {
+        if self.pairs.is_empty() {
+            return;
+        }
+        println!("\n🔹 {}", colorize("TCP handshake options", "blue"));
+        println!("   {:<44} {:<30} SYN-ACK", "Connection", "SYN");
+        for (client, server, syn, syn_ack, _, _) in self.pairs.iter().take(10) {
+            println!("   {:<44} {:<30} {}", format!("{} → {}", client, server), syn.describe(), syn_ack.describe());
+        }
+        if self.pairs.len() > 10 {
+            println!("   … and {} more", self.pairs.len() - 10);
+        }
+
+        let tunnel_interface = vpn::is_vpn_interface(&self.interface);
+        let mut warnings = BTreeMap::new();
+        for (client, server, syn, syn_ack, overhead, v6) in &self.pairs {
+            let (client, server) = (host(client), host(server));
+            let limit = self.mss_limit(*overhead, *v6);
+            if tunnel_interface || *overhead > 0 {
+                for (side, handshake) in [("SYN", syn), ("SYN-ACK", syn_ack)] {
+                    if let (Some(mss), Some(limit)) = (handshake.mss, limit) {
+                        if u32::from(mss) > limit {
+                            warnings.insert(
+                                format!("{} from {} advertises MSS {} but the tunnel fits only {}", side, if side == "SYN" { client } else { server }, mss, limit),
+                                "MSS is not clamped on this tunnel: full-size segments need fragmentation or PMTUD, so small pages load while large ones hang. Clamp MSS on the tunnel (e.g. iptables TCPMSS --clamp-mss-to-pmtu) or lower the MTU.",
+                            );
+                        }
+                    }
+                }
+            }
+            if syn.window_scale.is_some() && syn_ack.window_scale.is_none() {
+                warnings.insert(
+                    format!("{} did not agree to window scaling", server),
+                    "Without window scaling the receive window is capped at 64 KB, limiting throughput to 64 KB per round trip; a middlebox may be stripping the option.",
+                );
+            } else if syn.window_scale.is_none() {
+                warnings.insert(
+                    format!("{} sent SYNs without window scaling", client),
+                    "Enable TCP window scaling (net.ipv4.tcp_window_scaling) or check for a middlebox stripping options.",
+                );
+            }
+            if syn.sack_permitted && !syn_ack.sack_permitted {
+                warnings.insert(
+                    format!("{} did not agree to SACK", server),
+                    "Without selective acknowledgements every loss forces retransmitting everything after it.",
+                );
+            }
+        }
+
+        if warnings.is_empty() {
+            println!("✅ {} {} handshake(s) negotiated sensible MSS, window scaling and SACK.", colorize("[SUCCESS]", "green"), self.pairs.len());
+            return;
+        }
+        let mut advice: Vec<&str> = Vec::new();
+        for (warning, hint) in &warnings {
+            println!("⚠️  {} {}", colorize("[TCP]", "yellow"), warning);
+            if !advice.contains(hint) {
+                advice.push(hint);
+            }
+        }
+        for hint in advice {
+            println!("   {}", hint);
+        }
+    }
+}
+
+/// Whether `packet` matches the user's `--port` selection, rather than being a SYN or ICMP error
+/// the capture filter admits for its own checks. ICMP errors count when they quote that port.
+fn in_selection(packet: &Packet, port: &str) -> bool {
+    if packet.arp.is_some() {
+        return true;
+    }
+    if port.is_empty() {
+        return packet.icmp.is_none();
+    }
+    // A filter expression rather than a port number cannot be checked here, so all of it counts.
+    let port: u16 = match port.parse() {
+        Ok(port) => port,
+        Err(_) => return true,
+    };
+    match packet.icmp.as_ref().and_then(|icmp| icmp.quoted()) {
+        Some(quoted) => quoted.dst_port == Some(port),
+        None => packet.src_port == Some(port) || packet.dst_port == Some(port),
+    }
+}
+
+/// Strips the port from an endpoint rendered by `Packet::source`.
+fn host(endpoint: &str) -> &str {
+    endpoint.rsplit_once(':').map_or(endpoint, |(host, _)| host).trim_start_matches('[').trim_end_matches(']')
+}
+
+/// Bytes a tunnel adds in front of the inner IP packet.
+fn tunnel_overhead(kind: &str) -> u32 {
+    if kind.starts_with("VXLAN") {
+        50
+    } else if kind.starts_with("GRE key") {
+        28
+    } else if kind.starts_with("GRE") {
+        24
+    } else {
+        20
+    }
+}
+
+/// Maps packets to the processes that own their local sockets.
+struct SocketOwners {
+    sockets: Vec<Socket>,
+    refreshed: Instant,
+    cgroups: HashMap<u32, Option<String>>,
+}
+
+impl SocketOwners {
+    /// Loads the current socket table.
+    fn new() -> SocketOwners {
+        SocketOwners { sockets: sockets::table(), refreshed: Instant::now(), cgroups: HashMap::new() }
+    }
+
+    /// Sockets at either end of the packet. On a miss the socket table is re-read, at most once
+    /// a second, so connections opened since are picked up.
+    fn owners(&mut self, packet: &Packet) -> Vec<Socket> {
+        let find = |sockets: &[Socket]| -> Vec<Socket> {
+            sockets
+                .iter()
+                .filter(|s| s.owns(&packet.protocol, packet.src, packet.src_port) || s.owns(&packet.protocol, packet.dst, packet.dst_port))
+                .cloned()
+                .collect()
+        };
+        let found = find(&self.sockets);
+        if !found.is_empty() || self.refreshed.elapsed() < Duration::from_secs(1) {
+            return found;
+        }
+        self.sockets = sockets::table();
+        self.refreshed = Instant::now();
+        find(&self.sockets)
+    }
+
+    /// The cgroup of `pid`, cached because processes rarely move.
+    fn cgroup(&mut self, pid: u32) -> Option<String> {
+        self.cgroups.entry(pid).or_insert_with(|| sockets::cgroup(pid)).clone()
+    }
+}
+
+/// A running capture from either backend.
+pub enum Source {
+    Tcpdump(Child),
+    Socket(afpacket::Capture),
+}
+
+impl Source {
+    /// Stops capturing and returns the kernel's counters when the backend has them.
+    pub fn stop(self) -> Option<afpacket::Stats> {
+        match self {
+            Source::Tcpdump(mut child) => {
+                // SIGINT rather than a kill, so tcpdump prints its counters on the way out.
+                #[cfg(unix)]
+                unsafe {
+                    libc::kill(child.id() as libc::pid_t, libc::SIGINT);
+                }
+                let deadline = Instant::now() + Duration::from_secs(2);
+                while matches!(child.try_wait(), Ok(None)) && Instant::now() < deadline {
+                    thread::sleep(Duration::from_millis(20));
+                }
+                let _ = child.kill();
+                let _ = child.wait();
+                interrupt::untrack(&child);
+                let mut messages = String::new();
+                if let Some(mut stderr) = child.stderr.take() {
+                    let _ = stderr.read_to_string(&mut messages);
+                }
+                tcpdump_stats(&messages)
+            }
+            Source::Socket(mut capture) => Some(capture.stop()),
+        }
+    }
+}
+
+/// Starts the requested backend, falling back to tcpdump when packet sockets are unavailable.
+/// Fails with remediation advice when tcpdump cannot capture either.
+pub fn open_source(options: &CaptureOptions, filter: &str) -> Result<(Source, mpsc::Receiver<(u32, pcap::Record)>), String> {
+    if options.backend == Backend::Socket {
+        let started = afpacket::check().map_err(io::Error::other).and_then(|_| afpacket::start(&options.interface, filter, options.sample));
+        match started {
+            Ok((capture, rx)) => {
+                if !capture.filtered() {
+                    status(options.ndjson, &format!(
+                        "⚠️  {} tcpdump is needed to compile the capture filter; every packet is captured",
+                        colorize("[WARN]", "yellow")
+                    ));
+                }
+                return Ok((Source::Socket(capture), rx));
+            }
+            Err(e) => status(options.ndjson, &format!("⚠️  {} socket backend unavailable: {}; falling back to tcpdump", colorize("[WARN]", "yellow"), e)),
+        }
+    }
+    privileges::can_capture()?;
+    let (child, rx) = start_tcpdump(&options.interface, filter, Stdio::piped()).map_err(|e| format!("cannot start tcpdump: {}", e))?;
+    Ok((Source::Tcpdump(child), rx))
+}
+
+/// Reads the counters tcpdump prints when it exits, e.g. `1234 packets received by filter` and
+/// `56 packets dropped by kernel`.
+fn tcpdump_stats(messages: &str) -> Option<afpacket::Stats> {
+    let count = |suffix: &str| {
+        messages
+            .lines()
+            .find_map(|line| line.trim().strip_suffix(suffix))
+            .and_then(|n| n.trim().parse().ok())
+    };
+    Some(afpacket::Stats { received: count("packets received by filter")?, dropped: count("packets dropped by kernel")? })
+}
+
+/// Prints a progress or warning line, on stderr in NDJSON mode so stdout carries only packets.
+fn status(ndjson: bool, message: &str) {
+    if ndjson {
+        eprintln!("{}", message);
+    } else {
+        println!("{}", message);
+    }
+}
+
+/// One decoded packet as written by `--format ndjson`.
+#[derive(Serialize)]
+struct PacketEvent<'a> {
+    /// Seconds since the Unix epoch.
+    timestamp: f64,
+    length: usize,
+    protocol: &'a str,
+    src: Option<IpAddr>,
+    dst: Option<IpAddr>,
+    src_port: Option<u16>,
+    dst_port: Option<u16>,
+    tos: Option<u8>,
+    info: &'a str,
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    vlans: &'a [u16],
+    #[serde(skip_serializing_if = "<[_]>::is_empty")]
+    tunnels: &'a [Tunnel],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tcp_options: Option<&'a TcpHandshake>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    icmp_error: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    alerts: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    process: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pid: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cgroup: Option<String>,
+}
+
+/// Captures packets using `tcpdump` or a packet socket while visiting websites, decoding them as they arrive.
+pub fn capture_traffic(options: &CaptureOptions) {
+    // In NDJSON mode stdout carries only packets; progress goes to stderr.
+    let ndjson = options.ndjson;
+    let scope = match &options.process {
+        Some(process) => format!("process {}", colorize(process, "cyan")),
+        None if options.port.is_empty() => "all TCP/UDP + ARP".to_string(),
+        None => format!("port {} + ARP", colorize(&options.port, "cyan")),
+    };
+    let sampling = if options.sample > 1 { format!(", sampling 1 in {}", options.sample) } else { String::new() };
+    if options.stats {
+        status(ndjson, &format!("\n📡 {} Counting traffic on {} for {}s ({}{})\n",
+            colorize("[INFO]", "blue"), colorize(&options.interface, "cyan"), options.timeout_secs, scope, sampling));
+    } else {
+        status(ndjson, &format!("\n📡 {} Capturing {} packets on {} ({}{})\n",
+            colorize("[INFO]", "blue"), options.max_packets, colorize(&options.interface, "cyan"), scope, sampling));
+    }
+
+    // tcpdump writes pcap to stdout (-w -), flushing after every packet (-U). Port filters only
+    // see the outermost header, so tunnels and VLAN-tagged frames are matched separately; `vlan`
+    // shifts offsets for everything after it, so it must come last. SYNs and ICMP errors are
+    // always captured so handshake options and path errors can be checked; those outside the
+    // selection are analysed but not listed or counted towards --count.
+    let selected = if options.port.is_empty() { "tcp or udp".to_string() } else { format!("port {}", options.port) };
+    let filter = format!(
+        "{selected} or arp or ip proto 4 or ip proto 41 or ip proto 47 or udp port 4789 \
+         or icmp[icmptype] == icmp-unreach or icmp[icmptype] == icmp-timxceed \
+         or (icmp6 and ip6[40] >= 1 and ip6[40] <= 3) or tcp[tcpflags] & tcp-syn != 0 \
+         or (ip6 and ip6[6] == 6 and ip6[53] & 2 != 0) or (vlan and ({selected} or arp))"
+    );
+    let (source, rx) = match open_source(options, &filter) {
+        Ok(started) => started,
+        Err(reason) => {
+            status(ndjson, &privileges::hint("Packet capture", &reason));
+            telemetry::fail(&reason);
+            if options.visit_sites && !ndjson {
+                println!("\n🌍 {} Visiting websites without capturing\n", colorize("[INFO]", "blue"));
+                let sites = options.sites.clone();
+                websites::visit(&sites, true);
+            }
+            return;
+        }
+    };
+    let attribute = matches!(source, Source::Socket(_));
+    // Packet sockets sample in the kernel; tcpdump's packets are sampled here as they are read.
+    let keep_every = if attribute { 1 } else { options.sample as usize };
+
+    let site_thread = if options.visit_sites {
+        if !ndjson {
+            println!("\n🌍 {} Visiting Websites While Capturing Traffic...\n", colorize("[INFO]", "blue"));
+        }
+        let sites = options.sites.clone();
+        Some(thread::spawn(move || websites::visit(&sites, !ndjson)))
+    } else {
+        None
+    };
+
+    if options.stats {
+        println!("   {:<10} {:>10} {:>12}", "Time", "pkt/s", "Mbit/s");
+    } else if !ndjson {
+        println!(
+            "{} {} {} {}",
+            colorize(&format!("{:<16}", "Timestamp"), "yellow"),
+            colorize(&format!("{:<24}", "Source"), "cyan"),
+            colorize(&format!("{:<7}", "Protocol"), "blue"),
+            colorize("Info", "green")
+        );
+        println!("{}", "-".repeat(90));
+    }
+
+    let mut arp_watch = ArpWatch::new();
+    let mut devices = DeviceWatch::default();
+    let mut handshakes = HandshakeWatch::new(&options.interface);
+    let mut throughput = ThroughputWatch::new();
+    let mut traffic = if options.stats { Some(TrafficStats::new(options.sample)) } else { None };
+    let mut owners = if attribute || options.process.is_some() { Some(SocketOwners::new()) } else { None };
+    if let (Some(selector), Some(owners)) = (&options.process, &owners) {
+        if !owners.sockets.iter().any(|s| s.matches(selector)) {
+            status(ndjson, &format!(
+                "⚠️  {} No sockets owned by {} yet; waiting for it to open some (other users' processes need root)",
+                colorize("[WARN]", "yellow"),
+                selector
+            ));
+        }
+    }
+    let mut icmp_errors: BTreeMap<String, usize> = BTreeMap::new();
+    let deadline = Instant::now() + Duration::from_secs(options.timeout_secs);
+    let mut packet_count = 0;
+    let mut read = 0;
+    while packet_count < options.max_packets {
+        if interrupt::interrupted() {
+            status(ndjson, &format!("\n🛑 {} Capture interrupted after {} packets.", colorize("[INTERRUPTED]", "yellow"), packet_count));
+            break;
+        }
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        // Wake up regularly so an interrupt is noticed even when no packets arrive.
+        let (linktype, record) = match rx.recv_timeout(remaining.min(Duration::from_millis(200))) {
+            Ok(received) => received,
+            Err(mpsc::RecvTimeoutError::Timeout) if !remaining.is_zero() => continue,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                status(ndjson, &format!("\n⏳ {} Stopping capture after {} packets or {} seconds.",
+                                        colorize("[TIMEOUT]", "yellow"), packet_count, options.timeout_secs));
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        read += 1;
+        if read % keep_every != 0 {
+            continue;
+        }
+        let decoded = packet::decode(linktype, &record.data);
+        let alerts = match &decoded.arp {
+            Some(arp) => arp_watch.observe(arp),
+            None => Vec::new(),
+        };
+        handshakes.observe(&decoded);
+        let diagnosis = match (&decoded.icmp, decoded.src) {
+            (Some(icmp), Some(reporter)) => icmp.diagnosis(reporter),
+            _ => None,
+        };
+        let first_diagnosis = diagnosis.as_ref().is_some_and(|d| !icmp_errors.contains_key(d));
+        if let Some(diagnosis) = &diagnosis {
+            *icmp_errors.entry(diagnosis.clone()).or_insert(0) += 1;
+        }
+
+        let owned_by = owners.as_mut().map(|o| o.owners(&decoded)).unwrap_or_default();
+        if let Some(selector) = &options.process {
+            if !owned_by.iter().any(|s| s.matches(selector)) {
+                continue;
+            }
+        }
+        if !in_selection(&decoded, &options.port) {
+            if let Some(diagnosis) = diagnosis.filter(|_| first_diagnosis && !ndjson) {
+                println!("⚠️  {} {}", colorize("[ICMP]", "yellow"), colorize(&diagnosis, "yellow"));
+            }
+            continue;
+        }
+        packet_count += 1;
+        devices.observe(&decoded);
+        throughput.observe(record.ts_sec as f64 + record.ts_usec as f64 / 1e6, &decoded);
+        if let Some(traffic) = traffic.as_mut() {
+            if let Some((second, packets, bytes)) = traffic.observe(u64::from(record.ts_sec), record.orig_len, &decoded) {
+                println!("   {:<10} {:>10} {:>12.2}", &time_of_day(second as u32, 0)[..8], packets, bytes as f64 * 8.0 / 1e6);
+            }
+            continue;
+        }
+        let owner = owned_by.into_iter().next().filter(|_| attribute);
+        let cgroup = match (&owner, owners.as_mut()) {
+            (Some(owner), Some(owners)) => owners.cgroup(owner.pid),
+            _ => None,
+        };
+
+        if ndjson {
+            let event = PacketEvent {
+                timestamp: record.ts_sec as f64 + record.ts_usec as f64 / 1e6,
+                length: record.data.len(),
+                protocol: &decoded.protocol,
+                src: decoded.src,
+                dst: decoded.dst,
+                src_port: decoded.src_port,
+                dst_port: decoded.dst_port,
+                tos: decoded.tos,
+                info: &decoded.info,
+                vlans: &decoded.vlans,
+                tunnels: &decoded.tunnels,
+                tcp_options: decoded.handshake.as_ref(),
+                icmp_error: diagnosis,
+                alerts,
+                process: owner.as_ref().map(|o| o.process.clone()),
+                pid: owner.as_ref().map(|o| o.pid),
+                cgroup,
+            };
+            if let Ok(line) = serde_json::to_string(&event) {
+                println!("{}", line);
+            }
+            continue;
+        }
+
+        let mut info = match decoded.arp {
+            Some(_) => decoded.info.clone(),
+            None => format!("→ {} {}", decoded.destination(), decoded.info),
+        };
+        let encapsulation = decoded.encapsulation();
+        if !encapsulation.is_empty() {
+            info = format!("{} {}", encapsulation, info);
+        }
+        if let Some(owner) = &owner {
+            let cgroup = cgroup.map(|c| format!(" {}", c)).unwrap_or_default();
+            info = format!("{} [{}/{}{}]", info, owner.process, owner.pid, cgroup);
+        }
+        println!(
+            "{} {} {} {}",
+            colorize(&format!("{:<16}", time_of_day(record.ts_sec, record.ts_usec)), "yellow"),
+            colorize(&format!("{:<24}", decoded.source()), "cyan"),
+            colorize(&format!("{:<7}", decoded.protocol), "blue"),
+            colorize(&info, "green")
+        );
+        for warning in &alerts {
+            println!("🚨 {} {}", colorize("[ARP]", "red"), colorize(warning, "red"));
+        }
+        if let Some(diagnosis) = diagnosis.filter(|_| first_diagnosis) {
+            println!("⚠️  {} {}", colorize("[ICMP]", "yellow"), colorize(&diagnosis, "yellow"));
+        }
+    }
+
+    // Ensure tcpdump exits cleanly; socket captures report the kernel's counters.
+    let kernel = source.stop();
+    if let Some(site_thread) = site_thread {
+        let _ = site_thread.join();
+    }
+
+    let dropped = kernel.as_ref().map_or(0, |k| k.dropped);
+    let kernel = kernel
+        .map(|k| format!(" Kernel: {} packets passed the filter, {} dropped.", k.received, k.dropped))
+        .unwrap_or_default();
+    if ndjson {
+        eprintln!("📊 {} Captured {} packets.{}", colorize("[SUMMARY]", "blue"), packet_count, kernel);
+        return;
+    }
+    println!("\n📊 {} Summary: Captured {} packets.{}", colorize("[SUMMARY]", "blue"), packet_count, kernel);
+    if dropped > 0 {
+        println!(
+            "⚠️  {} Packets were lost because the capture fell behind; counts and analyses below are incomplete. Use {}--sample 1/N or a narrower --port.",
+            colorize("[WARN]", "yellow"),
+            if options.stats { "" } else { "--stats, " }
+        );
+    }
+    if let Some(traffic) = &traffic {
+        traffic.print_summary();
+    }
+    arp_watch.print_summary();
+    devices.print_summary();
+    print_icmp_summary(&icmp_errors);
+    handshakes.print_summary();
+    // Sampling leaves gaps in every TCP stream, which would read as loss.
+    if options.sample == 1 {
+        throughput.print_summary();
+    }
+    println!();
+}
+
+/// Lists each distinct ICMP error diagnosis with how often it was seen.
+fn print_icmp_summary(errors: &BTreeMap<String, usize>) {
+    if errors.is_empty() {
+        return;
+    }
+    println!("\n⚠️  {} {} kind(s) of ICMP error seen:", colorize("[ICMP]", "yellow"), errors.len());
+    for (diagnosis, count) in errors {
+        println!("   • {} ×{}", diagnosis, count);
+    }
+}
+
+/// Starts `tcpdump` on `interface` writing pcap to a pipe, and returns the child together with a
+/// channel of `(linktype, record)` pairs fed by a reader thread.
+pub fn spawn_tcpdump(interface: &str, filter: &str) -> io::Result<(Child, mpsc::Receiver<(u32, pcap::Record)>)> {
+    start_tcpdump(interface, filter, Stdio::inherit())
+}
+
+fn start_tcpdump(interface: &str, filter: &str, stderr: Stdio) -> io::Result<(Child, mpsc::Receiver<(u32, pcap::Record)>)> {
+    let mut child = Command::new("tcpdump")
+        .args(["-i", interface, "-U", "-w", "-", filter])
+        .stdout(Stdio::piped())
+        .stderr(stderr)
+        .spawn()?;
+    interrupt::track(&child);
+    let stdout = child.stdout.take().ok_or_else(|| io::Error::other("tcpdump stdout unavailable"))?;
+
+    let (tx, rx) = mpsc::channel();
+    thread::spawn(move || {
+        let mut reader = match pcap::Reader::new(stdout) {
+            Ok(reader) => reader,
+            Err(_) => return,
+        };
+        while let Ok(Some(record)) = reader.next_record() {
+            if tx.send((reader.linktype, record)).is_err() {
+                break;
+            }
+        }
+    });
+    Ok((child, rx))
+}
+
+/// Formats a capture timestamp as UTC `HH:MM:SS.micros`.
+fn time_of_day(secs: u32, micros: u32) -> String {
+    let secs = secs % 86_400;
+    format!("{:02}:{:02}:{:02}.{:06}", secs / 3600, secs / 60 % 60, secs % 60, micros)
+}
@@ -0,0 +1,202 @@
+use std::io::Write;
+use std::net::IpAddr;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::clock;
+use crate::colorize;
+use crate::config::{self, CertsConfig, Config};
+use crate::interrupt;
+use crate::scheduler;
+use crate::service;
+
+/// Health of a certificate relative to the configured thresholds.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum CertStatus {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// Expiry information for one host.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CertReport {
+    pub host: String,
+    pub not_after: Option<String>,
+    pub days_left: Option<i64>,
+    pub status: CertStatus,
+    pub error: Option<String>,
+}
+
+/// Returns the `certs` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("certs")
+        .about("Reports days until TLS certificate expiry for configured hosts")
+        .arg(Arg::with_name("hosts").multiple(true)
+            .help("Hosts to check (host or host:port); defaults to certs.hosts in the config"))
+        .arg(Arg::with_name("warn-days").long("warn-days").takes_value(true)
+            .help("Warn when fewer days remain (default from config, 30)"))
+        .arg(Arg::with_name("fail-days").long("fail-days").takes_value(true)
+            .help("Fail when fewer days remain (default from config, 7)"))
+}
+
+/// Runs the `certs` subcommand.
+pub fn run(matches: &ArgMatches, config: &Config) {
+    let mut settings = config.certs.clone();
+    if let Some(hosts) = matches.values_of("hosts") {
+        settings.hosts = hosts.map(|h| h.to_string()).collect();
+    }
+    if let Ok(days) = value_t!(matches, "warn-days", i64) {
+        settings.warn_days = days;
+    }
+    if let Ok(days) = value_t!(matches, "fail-days", i64) {
+        settings.fail_days = days;
+    }
+
+    if settings.hosts.is_empty() {
+        println!(
+            "❌ {} No hosts to check. Pass hostnames or add them under `certs: hosts:` in {}",
+            colorize("[ERROR]", "red"),
+            config::default_path().display()
+        );
+        return;
+    }
+    println!();
+    check_certs(&settings);
+    println!();
+}
+
+/// Checks every configured host and prints a table. Returns the reports so callers such as
+/// monitor mode can alert on them.
+pub fn check_certs(settings: &CertsConfig) -> Vec<CertReport> {
+    println!("🔹 {}", colorize("Checking TLS certificate expiry", "blue"));
+    println!("   {:<32} {:<26} {:>9}  Status", "Host", "Expires", "Days left");
+
+    // Each host may take the full handshake timeout; ping the watchdog between them so a long
+    // host list under monitor mode is not mistaken for a hang.
+    let reports: Vec<CertReport> = settings
+        .hosts
+        .iter()
+        .map(|h| {
+            service::watchdog();
+            inspect(h, settings)
+        })
+        .collect();
+    for report in &reports {
+        let (label, color) = match (report.status, &report.error) {
+            (_, Some(_)) => ("ERROR", "red"),
+            (CertStatus::Ok, None) => ("OK", "green"),
+            (CertStatus::Warn, None) => ("WARN", "yellow"),
+            (CertStatus::Fail, None) => ("FAIL", "red"),
+        };
+        println!(
+            "   {:<32} {:<26} {:>9}  {}",
+            report.host,
+            report.not_after.as_deref().unwrap_or("-"),
+            report.days_left.map_or("-".to_string(), |d| d.to_string()),
+            colorize(report.error.as_deref().unwrap_or(label), color)
+        );
+    }
+    reports
+}
+
+/// Fetches the certificate for `host` and grades its remaining lifetime.
+fn inspect(host: &str, settings: &CertsConfig) -> CertReport {
+    let mut report = CertReport { host: host.to_string(), not_after: None, days_left: None, status: CertStatus::Fail, error: None };
+
+    match fetch_not_after(host) {
+        Ok(not_after) => {
+            report.days_left = parse_openssl_date(&not_after).map(|expiry| (expiry - clock::unix_now() as i64).div_euclid(86_400));
+            report.status = match report.days_left {
+                Some(days) if days < settings.fail_days => CertStatus::Fail,
+                Some(days) if days < settings.warn_days => CertStatus::Warn,
+                Some(_) => CertStatus::Ok,
+                None => CertStatus::Fail,
+            };
+            report.not_after = Some(not_after);
+        }
+        Err(e) => report.error = Some(e),
+    }
+    report
+}
+
+/// How long one TLS handshake may take before the host is reported as not answering.
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Splits `host`, `host:port`, a bare IPv6 literal or `[v6]:port`, defaulting to port 443.
+fn split_target(target: &str) -> Result<(String, u16), String> {
+    if let Some(rest) = target.strip_prefix('[') {
+        let (host, after) = rest.split_once(']').ok_or_else(|| format!("bad address {}", target))?;
+        return match after.strip_prefix(':') {
+            Some(port) => Ok((host.to_string(), port.parse().map_err(|_| format!("bad port in {}", target))?)),
+            None => Ok((host.to_string(), 443)),
+        };
+    }
+    if target.parse::<IpAddr>().is_ok() {
+        return Ok((target.to_string(), 443));
+    }
+    match target.rsplit_once(':') {
+        Some((host, port)) => Ok((host.to_string(), port.parse().map_err(|_| format!("bad port in {}", target))?)),
+        None => Ok((target.to_string(), 443)),
+    }
+}
+
+/// Retrieves the leaf certificate with `openssl s_client` and returns its notAfter date.
+fn fetch_not_after(host: &str) -> Result<String, String> {
+    let (name, port) = split_target(host)?;
+    let literal = name.parse::<IpAddr>();
+    let connect = match literal {
+        Ok(IpAddr::V6(_)) => format!("[{}]:{}", name, port),
+        _ => format!("{}:{}", name, port),
+    };
+
+    let mut command = Command::new("openssl");
+    command.args(["s_client", "-connect", &connect]).stdin(Stdio::null());
+    // SNI carries names only; an address target sends none.
+    match literal {
+        Ok(_) => command.arg("-noservername"),
+        Err(_) => command.args(["-servername", &name]),
+    };
+    let handshake = interrupt::output_timeout(&mut scheduler::command("tls-handshake", &name, Some(port), command), HANDSHAKE_TIMEOUT)
+        .map_err(|e| format!("openssl: {}", e))?;
+    if !String::from_utf8_lossy(&handshake.stdout).contains("BEGIN CERTIFICATE") {
+        return Err("TLS handshake failed".to_string());
+    }
+
+    let mut x509 = Command::new("openssl")
+        .args(["x509", "-noout", "-enddate"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+        .map_err(|e| format!("openssl: {}", e))?;
+    if let Some(mut stdin) = x509.stdin.take() {
+        let _ = stdin.write_all(&handshake.stdout);
+    }
+    let output = x509.wait_with_output().map_err(|e| e.to_string())?;
+
+    String::from_utf8_lossy(&output.stdout)
+        .trim()
+        .strip_prefix("notAfter=")
+        .map(|d| d.to_string())
+        .ok_or_else(|| "could not read certificate".to_string())
+}
+
+/// Parses OpenSSL's `Mar  9 12:00:00 2026 GMT` into seconds since the epoch.
+fn parse_openssl_date(date: &str) -> Option<i64> {
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+    let parts: Vec<&str> = date.split_whitespace().collect();
+    if parts.len() < 4 {
+        return None;
+    }
+    let month = MONTHS.iter().position(|m| *m == parts[0])? as i64 + 1;
+    let day: i64 = parts[1].parse().ok()?;
+    let year: i64 = parts[3].parse().ok()?;
+    let time: Vec<i64> = parts[2].split(':').filter_map(|t| t.parse().ok()).collect();
+    if time.len() != 3 {
+        return None;
+    }
+    Some(clock::days_from_civil(year, month, day) * 86_400 + time[0] * 3600 + time[1] * 60 + time[2])
+}
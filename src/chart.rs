@@ -0,0 +1,93 @@
+use crate::colorize;
+
+const BARS: [char; 8] = ['▁', '▂', '▃', '▄', '▅', '▆', '▇', '█'];
+
+/// Width of the longest histogram bar, in characters.
+const HISTOGRAM_WIDTH: usize = 40;
+
+/// Renders samples as a one-line Unicode sparkline scaled between their min and max.
+pub fn sparkline(samples: &[f64]) -> String {
+    let (min, max) = bounds(samples);
+    let range = max - min;
+    samples
+        .iter()
+        .map(|&v| {
+            let level = if range > 0.0 { ((v - min) / range * 7.0).round() as usize } else { 0 };
+            BARS[level.min(7)]
+        })
+        .collect()
+}
+
+/// Groups samples into `buckets` equal-width ranges and renders one bar per range.
+pub fn histogram(samples: &[f64], buckets: usize) -> Vec<String> {
+    if samples.is_empty() || buckets == 0 {
+        return Vec::new();
+    }
+    let (min, max) = bounds(samples);
+    let width = ((max - min) / buckets as f64).max(f64::EPSILON);
+
+    let mut counts = vec![0usize; buckets];
+    for &v in samples {
+        let index = (((v - min) / width) as usize).min(buckets - 1);
+        counts[index] += 1;
+    }
+    let peak = counts.iter().cloned().max().unwrap_or(1).max(1);
+
+    counts
+        .iter()
+        .enumerate()
+        .map(|(i, &count)| {
+            let low = min + width * i as f64;
+            let bar = "█".repeat((count * HISTOGRAM_WIDTH).div_ceil(peak));
+            let range = format!("{:.1}–{:.1} ms", low, low + width);
+            format!("{:>18} │{} {}", range, bar, count)
+        })
+        .collect()
+}
+
+/// Minimum, mean, maximum and standard deviation of the samples.
+pub fn summarize(samples: &[f64]) -> (f64, f64, f64, f64) {
+    if samples.is_empty() {
+        return (0.0, 0.0, 0.0, 0.0);
+    }
+    let (min, max) = bounds(samples);
+    let mean = samples.iter().sum::<f64>() / samples.len() as f64;
+    let variance = samples.iter().map(|v| (v - mean).powi(2)).sum::<f64>() / samples.len() as f64;
+    (min, mean, max, variance.sqrt())
+}
+
+/// The `p`th percentile (0–100) of the samples, interpolating between neighbouring ranks.
+pub fn percentile(samples: &[f64], p: f64) -> f64 {
+    if samples.is_empty() {
+        return 0.0;
+    }
+    let mut sorted = samples.to_vec();
+    sorted.sort_by(|a, b| a.total_cmp(b));
+    let rank = (p / 100.0).clamp(0.0, 1.0) * (sorted.len() - 1) as f64;
+    let (low, high) = (rank.floor() as usize, rank.ceil() as usize);
+    sorted[low] + (sorted[high] - sorted[low]) * (rank - low as f64)
+}
+
+fn bounds(samples: &[f64]) -> (f64, f64) {
+    samples.iter().fold((f64::INFINITY, f64::NEG_INFINITY), |(lo, hi), &v| (lo.min(v), hi.max(v)))
+}
+
+/// Prints min/avg/max/jitter followed by a sparkline and a histogram of the samples.
+pub fn print_distribution(label: &str, samples: &[f64]) {
+    if samples.is_empty() {
+        println!("   {}: no samples", label);
+        return;
+    }
+    let (min, mean, max, stddev) = summarize(samples);
+    println!(
+        "   {}: min {:.1} / avg {:.1} / max {:.1} ms, jitter (σ) {:.1} ms",
+        label, min, mean, max, stddev
+    );
+    println!("   {}", colorize(&sparkline(samples), "cyan"));
+    // A handful of samples does not make a meaningful histogram.
+    if samples.len() >= 5 {
+        for line in histogram(samples, 6) {
+            println!("   {}", line);
+        }
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde_yaml;
+
+use crate::{colorize, data_dir};
+
+/// Settings read from `~/.netdiag/config.yaml` (or `--config`). Every section is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub certs: CertsConfig,
+    pub monitor: MonitorConfig,
+    pub probes: ProbesConfig,
+    /// Named sets of checks for `--profile`; one named like a built-in profile replaces it.
+    pub profiles: HashMap<String, ProfileConfig>,
+    /// Assertions `netdiag verify` checks the live system against.
+    pub policy: PolicyConfig,
+    /// Websites visited during captures; empty uses the built-in list.
+    pub sites: Vec<SiteConfig>,
+    /// Where runs are exported as OpenTelemetry traces.
+    pub telemetry: TelemetryConfig,
+}
+
+/// Hosts whose TLS certificates are watched, and when to start complaining.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct CertsConfig {
+    pub hosts: Vec<String>,
+    pub warn_days: i64,
+    pub fail_days: i64,
+}
+
+impl Default for CertsConfig {
+    fn default() -> CertsConfig {
+        CertsConfig { hosts: Vec::new(), warn_days: 30, fail_days: 7 }
+    }
+}
+
+/// What monitor mode watches and how often.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct MonitorConfig {
+    pub targets: Vec<String>,
+    pub interval_secs: u64,
+    /// Hours between checks of the certificates under `certs:`; 0 turns them off.
+    pub cert_interval_hours: u64,
+}
+
+impl Default for MonitorConfig {
+    fn default() -> MonitorConfig {
+        MonitorConfig { targets: vec!["8.8.8.8".to_string(), "1.1.1.1".to_string()], interval_secs: 300, cert_interval_hours: 6 }
+    }
+}
+
+/// Limits on probes sent to other hosts, so scans stay polite on shared networks.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct ProbesConfig {
+    /// Probes per second across all targets; 0 removes the limit.
+    pub max_rate: f64,
+    /// Probes in flight at once to any one address.
+    pub per_target: usize,
+    /// Record every probe (time, type, target) in `~/.netdiag/probes.log`.
+    pub audit_log: bool,
+}
+
+impl Default for ProbesConfig {
+    fn default() -> ProbesConfig {
+        ProbesConfig { max_rate: 100.0, per_target: 16, audit_log: true }
+    }
+}
+
+/// OTLP/HTTP collector runs are traced to. The `OTEL_EXPORTER_OTLP_*` variables override it.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct TelemetryConfig {
+    /// Collector base URL, e.g. `http://otel-collector:4318`; unset disables tracing.
+    pub endpoint: Option<String>,
+    /// Extra request headers, typically an API key for a hosted backend.
+    pub headers: HashMap<String, String>,
+    pub service_name: String,
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> TelemetryConfig {
+        TelemetryConfig { endpoint: None, headers: HashMap::new(), service_name: "netdiag".to_string() }
+    }
+}
+
+/// A user-defined diagnostics profile.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct ProfileConfig {
+    pub description: Option<String>,
+    /// Check names, run in order; empty keeps the checks of the built-in profile of that name.
+    pub checks: Vec<String>,
+    /// Host the latency and bufferbloat checks ping.
+    pub target: Option<String>,
+    /// Services file for the reachability matrix check.
+    pub services: Option<String>,
+}
+
+/// What a compliant host's network looks like. Every assertion is optional.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PolicyConfig {
+    /// The only resolvers the system may use.
+    pub dns_servers: Vec<String>,
+    pub routes: Vec<RoutePolicy>,
+    pub public_ip: Option<PublicIpPolicy>,
+    pub resolves: Vec<ResolvePolicy>,
+}
+
+/// How traffic to a destination must leave the host.
+#[derive(Debug, Deserialize)]
+pub struct RoutePolicy {
+    /// An address or host name, or `default` for the path to the internet.
+    pub destination: String,
+    #[serde(default)]
+    pub interface: Option<String>,
+    #[serde(default)]
+    pub gateway: Option<String>,
+}
+
+/// Where the host must appear to come from on the internet.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+pub struct PublicIpPolicy {
+    /// Origin AS the public address must belong to.
+    pub asn: Option<u32>,
+    /// Prefixes (CIDR) the public address must fall within; any one suffices.
+    pub prefixes: Vec<String>,
+}
+
+/// Addresses a name must resolve to, e.g. an internal name only split DNS answers.
+#[derive(Debug, Deserialize)]
+pub struct ResolvePolicy {
+    pub name: String,
+    pub addresses: Vec<String>,
+}
+
+/// A website to check, and what a healthy response looks like.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SiteConfig {
+    pub url: String,
+    #[serde(default)]
+    pub name: Option<String>,
+    /// Acceptable final status codes after redirects; empty accepts any 2xx.
+    #[serde(default)]
+    pub expect_status: Vec<u16>,
+    /// Text the final response body must contain.
+    #[serde(default)]
+    pub expect_content: Option<String>,
+}
+
+/// Location used when `--config` is not given.
+pub fn default_path() -> PathBuf {
+    data_dir().join("config.yaml")
+}
+
+/// Loads the config from `path` or the default location. A missing file yields the defaults;
+/// an unreadable one is reported and ignored.
+pub fn load(path: Option<&str>) -> Config {
+    let path = path.map(PathBuf::from).unwrap_or_else(default_path);
+    if !path.exists() {
+        return Config::default();
+    }
+    match load_strict(&path) {
+        Ok(config) => config,
+        Err(e) => {
+            eprintln!("⚠️  {} Ignoring {}", colorize("[WARN]", "yellow"), e);
+            Config::default()
+        }
+    }
+}
+
+/// Loads the config at `path`, failing if it is missing, unreadable or invalid. For commands
+/// whose result means nothing without it, like `verify`.
+pub fn load_strict(path: &Path) -> Result<Config, String> {
+    let text = fs::read_to_string(path).map_err(|e| format!("config {}: {}", path.display(), e))?;
+    let config: Config = serde_yaml::from_str(&text).map_err(|e| format!("invalid config {}: {}", path.display(), e))?;
+    let rate = config.probes.max_rate;
+    if !rate.is_finite() || rate < 0.0 {
+        return Err(format!("invalid config {}: probes.max_rate must be a number of probes per second, or 0 for no limit", path.display()));
+    }
+    Ok(config)
+}
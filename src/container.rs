@@ -0,0 +1,221 @@
+use std::env;
+use std::fs;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use clap::{App, ArgMatches, SubCommand};
+
+#[cfg(target_os = "linux")]
+use libc;
+
+use crate::colorize;
+use crate::dns;
+use crate::netinfo;
+use crate::tcping::{self, Attempt};
+
+/// Docker's embedded resolver, used on user-defined networks and by Compose.
+const EMBEDDED_DNS: Ipv4Addr = Ipv4Addr::new(127, 0, 0, 11);
+
+/// Names runtimes publish for the host machine, when the container was started with them.
+const HOST_ALIASES: &[&str] = &["host.docker.internal", "host.containers.internal"];
+
+/// How a container is attached to the network.
+#[derive(Debug, PartialEq)]
+pub enum NetworkMode {
+    /// Shares the host's stack (`--network host`): host interfaces are visible.
+    Host,
+    /// Default bridge: a veth behind the host's NAT, resolv.conf copied from the host.
+    Bridge,
+    /// User-defined bridge or Compose network, served by the embedded DNS at 127.0.0.11.
+    UserDefined,
+    /// Rootless networking through slirp4netns' user-mode stack.
+    Slirp,
+    /// `--network none`: loopback only.
+    Isolated,
+}
+
+impl NetworkMode {
+    fn describe(&self) -> &'static str {
+        match self {
+            NetworkMode::Host => "host (sharing the host's network stack)",
+            NetworkMode::Bridge => "bridge (NAT behind the host)",
+            NetworkMode::UserDefined => "user-defined network (embedded DNS at 127.0.0.11)",
+            NetworkMode::Slirp => "slirp4netns (rootless user-mode networking)",
+            NetworkMode::Isolated => "none (loopback only)",
+        }
+    }
+}
+
+/// Returns the container runtime this process runs under, if any.
+pub fn detect() -> Option<&'static str> {
+    if fs::metadata("/run/.containerenv").is_ok() {
+        return Some("Podman");
+    }
+    if fs::metadata("/.dockerenv").is_ok() {
+        return Some("Docker");
+    }
+    if env::var_os("KUBERNETES_SERVICE_HOST").is_some() {
+        return Some("Kubernetes");
+    }
+    match env::var("container").as_deref() {
+        Ok("podman") => return Some("Podman"),
+        Ok("docker") => return Some("Docker"),
+        Ok("lxc") => return Some("LXC"),
+        Ok("systemd-nspawn") => return Some("systemd-nspawn"),
+        _ => {}
+    }
+    // cgroup v1 paths name the runtime; under cgroup v2 namespaces they are just "0::/".
+    let cgroup = fs::read_to_string("/proc/1/cgroup").unwrap_or_default();
+    [("kubepods", "Kubernetes"), ("libpod", "Podman"), ("docker", "Docker"), ("containerd", "containerd"), ("lxc", "LXC")]
+        .iter()
+        .find(|(marker, _)| cgroup.contains(marker))
+        .map(|(_, runtime)| *runtime)
+}
+
+/// Infers the container's network mode from its interfaces and resolver.
+pub fn network_mode() -> NetworkMode {
+    let interfaces = netinfo::interfaces();
+    let names: Vec<&str> = interfaces.iter().map(|i| i.name.as_str()).filter(|n| *n != "lo").collect();
+    if names.is_empty() {
+        return NetworkMode::Isolated;
+    }
+    // Bridges and physical NICs only show up when the host's namespace is shared.
+    if names.iter().any(|n| ["docker0", "cni0", "podman0", "virbr0"].contains(n) || n.starts_with("wl") || n.starts_with("en")) {
+        return NetworkMode::Host;
+    }
+    if netinfo::dns_servers().iter().any(|s| s.parse::<IpAddr>() == Ok(IpAddr::V4(EMBEDDED_DNS))) {
+        return NetworkMode::UserDefined;
+    }
+    if interfaces.iter().any(|i| i.addrs.iter().any(|a| a == "10.0.2.100")) {
+        return NetworkMode::Slirp;
+    }
+    NetworkMode::Bridge
+}
+
+/// Returns the `container` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("container")
+        .about("Checks container networking: network mode, host gateway, embedded DNS and outbound NAT")
+}
+
+/// Runs the `container` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    if detect().is_none() {
+        println!("\nℹ️  {} Not running inside a container; checking this host's namespace anyway", colorize("[INFO]", "blue"));
+    }
+    report();
+}
+
+/// Prints the runtime and network mode, then tests the gateway, DNS and outbound NAT.
+pub fn report() {
+    println!("🔹 {}", colorize("Container networking", "blue"));
+    let mode = network_mode();
+    println!("   Runtime:      {}", detect().unwrap_or("none detected"));
+    println!("   Network mode: {}", mode.describe());
+    if mode == NetworkMode::Isolated {
+        println!("⚠️  {} The container has no network interfaces besides loopback; every external check will fail.\n", colorize("[WARN]", "yellow"));
+        return;
+    }
+    check_gateway();
+    check_dns(&mode);
+    check_nat();
+    println!();
+}
+
+/// Pings the default gateway (the host side of the bridge) and resolves the host aliases.
+fn check_gateway() {
+    match netinfo::default_gateway() {
+        Some(gateway) => match netinfo::ping(&gateway, 3) {
+            Some(stats) if stats.received > 0 => {
+                println!("✅ {} Host gateway {} answers ({:.1} ms)", colorize("[SUCCESS]", "green"), gateway, stats.avg_ms)
+            }
+            _ => println!(
+                "❌ {} Host gateway {} does not answer: the bridge or veth is down, or the host firewall drops it",
+                colorize("[ERROR]", "red"),
+                gateway
+            ),
+        },
+        None => println!("❌ {} No default route: the container cannot reach anything off its subnet", colorize("[ERROR]", "red")),
+    }
+    for alias in HOST_ALIASES {
+        if let Some(addr) = (*alias, 0).to_socket_addrs().ok().and_then(|mut a| a.next()) {
+            println!("   {} resolves to {}", alias, addr.ip());
+        }
+    }
+}
+
+/// Queries the embedded DNS server, or explains what the container inherited instead.
+fn check_dns(mode: &NetworkMode) {
+    let servers = netinfo::dns_servers();
+    if *mode != NetworkMode::UserDefined {
+        println!("   Resolvers: {}", if servers.is_empty() { "none configured".to_string() } else { servers.join(", ") });
+        if servers.iter().any(|s| s.starts_with("127.") && s != "127.0.0.11") && *mode != NetworkMode::Host {
+            println!(
+                "⚠️  {} resolv.conf points at a loopback resolver copied from the host, which does not exist inside the container's namespace",
+                colorize("[WARN]", "yellow")
+            );
+        }
+        return;
+    }
+    match dns::query(IpAddr::V4(EMBEDDED_DNS), "example.com", dns::TYPE_A, Duration::from_secs(3)) {
+        Ok(response) if !response.values(dns::TYPE_A).is_empty() => println!(
+            "✅ {} Embedded DNS {} resolves external names ({} ms)",
+            colorize("[SUCCESS]", "green"),
+            EMBEDDED_DNS,
+            response.elapsed.as_millis()
+        ),
+        Ok(response) => println!(
+            "⚠️  {} Embedded DNS answered {} for example.com: it works, but the host's upstream resolvers do not",
+            colorize("[WARN]", "yellow"),
+            dns::rcode_name(response.rcode)
+        ),
+        Err(e) => println!("❌ {} Embedded DNS {} did not answer: {}", colorize("[ERROR]", "red"), EMBEDDED_DNS, e),
+    }
+}
+
+/// Verifies that traffic leaves through the host's NAT by comparing the public address with
+/// the container's own.
+fn check_nat() {
+    let local: Vec<String> = netinfo::interfaces().into_iter().flat_map(|i| i.addrs).collect();
+    match netinfo::public_ip() {
+        Some(public) if local.contains(&public) => {
+            println!("ℹ️  {} The container owns its public address {}; no NAT in the path", colorize("[INFO]", "blue"), public)
+        }
+        Some(public) => println!("✅ {} Outbound NAT works: traffic leaves as {}", colorize("[SUCCESS]", "green"), public),
+        None => match tcping::connect(SocketAddr::from(([1, 1, 1, 1], 443)), Duration::from_secs(3)) {
+            Attempt::Connected(_) | Attempt::Refused(_) => println!(
+                "⚠️  {} TCP gets out, but the public IP lookup over HTTPS failed: check proxy settings or DNS",
+                colorize("[WARN]", "yellow")
+            ),
+            _ => println!(
+                "❌ {} No outbound connectivity: on the host, check `sysctl net.ipv4.ip_forward` and the MASQUERADE rule for the bridge subnet",
+                colorize("[ERROR]", "red")
+            ),
+        },
+    }
+}
+
+/// Moves this process into another network namespace before any checks run. `name` is an
+/// `ip netns` name or a path such as `/proc/<pid>/ns/net`.
+#[cfg(target_os = "linux")]
+pub fn enter_netns(name: &str) -> Result<(), String> {
+    use std::os::unix::io::AsRawFd;
+
+    let path = if name.contains('/') { name.to_string() } else { format!("/run/netns/{}", name) };
+    let file = fs::File::open(&path).map_err(|e| format!("cannot open {}: {}", path, e))?;
+    // Threads and child processes started afterwards inherit the namespace.
+    if unsafe { libc::setns(file.as_raw_fd(), libc::CLONE_NEWNET) } != 0 {
+        let e = std::io::Error::last_os_error();
+        if e.raw_os_error() == Some(libc::EPERM) {
+            return Err(format!("entering {} needs root (CAP_SYS_ADMIN)", path));
+        }
+        return Err(format!("setns {}: {}", path, e));
+    }
+    Ok(())
+}
+
+/// Network namespaces only exist on Linux.
+#[cfg(not(target_os = "linux"))]
+pub fn enter_netns(_name: &str) -> Result<(), String> {
+    Err("--netns is only supported on Linux".to_string())
+}
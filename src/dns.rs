@@ -0,0 +1,276 @@
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use crate::random_u64;
+use crate::scheduler;
+
+pub const TYPE_A: u16 = 1;
+pub const TYPE_NS: u16 = 2;
+pub const TYPE_CNAME: u16 = 5;
+pub const TYPE_MX: u16 = 15;
+pub const TYPE_TXT: u16 = 16;
+pub const TYPE_AAAA: u16 = 28;
+
+pub const RCODE_NOERROR: u8 = 0;
+pub const RCODE_NXDOMAIN: u8 = 3;
+
+/// Well-known public resolvers used as an unfiltered reference.
+pub const PUBLIC_RESOLVERS: &[(&str, &str)] = &[
+    ("1.1.1.1", "Cloudflare"),
+    ("8.8.8.8", "Google"),
+    ("9.9.9.9", "Quad9"),
+];
+
+/// A single resource record from the answer section.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Record {
+    pub name: String,
+    pub rtype: u16,
+    pub ttl: u32,
+    pub data: String,
+}
+
+/// A parsed DNS response.
+#[derive(Debug, Clone)]
+pub struct Response {
+    pub rcode: u8,
+    pub answers: Vec<Record>,
+    pub elapsed: Duration,
+}
+
+impl Response {
+    /// Returns the data of answers of the given type, e.g. the addresses of an A query.
+    pub fn values(&self, rtype: u16) -> Vec<String> {
+        self.answers.iter().filter(|r| r.rtype == rtype).map(|r| r.data.clone()).collect()
+    }
+}
+
+/// Parses a record type name such as `A` or `aaaa`.
+pub fn type_from_name(name: &str) -> Option<u16> {
+    match name.to_ascii_uppercase().as_str() {
+        "A" => Some(TYPE_A),
+        "NS" => Some(TYPE_NS),
+        "CNAME" => Some(TYPE_CNAME),
+        "MX" => Some(TYPE_MX),
+        "TXT" => Some(TYPE_TXT),
+        "AAAA" => Some(TYPE_AAAA),
+        _ => None,
+    }
+}
+
+/// Human-readable name for a response code.
+pub fn rcode_name(rcode: u8) -> &'static str {
+    match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        _ => "UNKNOWN",
+    }
+}
+
+/// Sends a single recursive query for `name` to `server` over UDP port 53.
+pub fn query(server: IpAddr, name: &str, rtype: u16, timeout: Duration) -> io::Result<Response> {
+    let bind: SocketAddr = match server {
+        IpAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+        IpAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+    };
+    let socket = UdpSocket::bind(bind)?;
+    socket.set_read_timeout(Some(timeout))?;
+    socket.connect((server, 53))?;
+
+    let _permit = scheduler::acquire("dns", server, Some(53));
+    let id = random_u64() as u16;
+    let start = Instant::now();
+    socket.send(&build_query(id, name, rtype))?;
+
+    let mut buf = [0u8; 4096];
+    loop {
+        let len = socket.recv(&mut buf)?;
+        // Ignore stray datagrams that do not answer our query.
+        if len >= 12 && u16::from_be_bytes([buf[0], buf[1]]) == id {
+            let mut response = parse_response(&buf[..len])
+                .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "malformed DNS response"))?;
+            response.elapsed = start.elapsed();
+            return Ok(response);
+        }
+        if start.elapsed() >= timeout {
+            return Err(io::Error::new(io::ErrorKind::TimedOut, "no matching DNS response"));
+        }
+    }
+}
+
+fn build_query(id: u16, name: &str, rtype: u16) -> Vec<u8> {
+    let mut packet = Vec::with_capacity(32 + name.len());
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[0x01, 0x00]); // recursion desired
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 0]); // one question
+    for label in name.trim_end_matches('.').split('.').filter(|l| !l.is_empty()) {
+        packet.push(label.len().min(63) as u8);
+        packet.extend_from_slice(&label.as_bytes()[..label.len().min(63)]);
+    }
+    packet.push(0);
+    packet.extend_from_slice(&rtype.to_be_bytes());
+    packet.extend_from_slice(&[0, 1]); // class IN
+    packet
+}
+
+fn parse_response(buf: &[u8]) -> Option<Response> {
+    let rcode = buf.get(3)? & 0x0f;
+    let questions = u16::from_be_bytes([*buf.get(4)?, *buf.get(5)?]);
+    let answers = u16::from_be_bytes([*buf.get(6)?, *buf.get(7)?]);
+
+    let mut pos = 12;
+    for _ in 0..questions {
+        pos = read_name(buf, pos)?.1 + 4;
+    }
+
+    let mut records = Vec::new();
+    for _ in 0..answers {
+        let (name, next) = read_name(buf, pos)?;
+        let header = buf.get(next..next + 10)?;
+        let rtype = u16::from_be_bytes([header[0], header[1]]);
+        let ttl = u32::from_be_bytes([header[4], header[5], header[6], header[7]]);
+        let rdlen = u16::from_be_bytes([header[8], header[9]]) as usize;
+        let rdata_start = next + 10;
+        let rdata = buf.get(rdata_start..rdata_start + rdlen)?;
+
+        let data = match rtype {
+            TYPE_A if rdlen == 4 => Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]).to_string(),
+            TYPE_AAAA if rdlen == 16 => {
+                let mut octets = [0u8; 16];
+                octets.copy_from_slice(rdata);
+                Ipv6Addr::from(octets).to_string()
+            }
+            TYPE_NS | TYPE_CNAME => read_name(buf, rdata_start)?.0,
+            TYPE_MX if rdlen > 2 => {
+                format!("{} {}", u16::from_be_bytes([rdata[0], rdata[1]]), read_name(buf, rdata_start + 2)?.0)
+            }
+            TYPE_TXT => {
+                let mut text = String::new();
+                let mut i = 0;
+                while i < rdata.len() {
+                    let len = rdata[i] as usize;
+                    text.push_str(&String::from_utf8_lossy(rdata.get(i + 1..i + 1 + len)?));
+                    i += 1 + len;
+                }
+                text
+            }
+            _ => rdata.iter().map(|b| format!("{:02x}", b)).collect(),
+        };
+
+        records.push(Record { name, rtype, ttl, data });
+        pos = rdata_start + rdlen;
+    }
+
+    Some(Response { rcode, answers: records, elapsed: Duration::from_secs(0) })
+}
+
+/// Reads a possibly compressed domain name; returns it and the offset just past it.
+fn read_name(buf: &[u8], start: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut pos = start;
+    let mut end = None;
+    let mut jumps = 0;
+
+    loop {
+        let len = *buf.get(pos)? as usize;
+        if len == 0 {
+            pos += 1;
+            break;
+        }
+        if len & 0xc0 == 0xc0 {
+            let target = ((len & 0x3f) << 8) | *buf.get(pos + 1)? as usize;
+            if end.is_none() {
+                end = Some(pos + 2);
+            }
+            jumps += 1;
+            if jumps > 16 {
+                return None;
+            }
+            pos = target;
+            continue;
+        }
+        labels.push(String::from_utf8_lossy(buf.get(pos + 1..pos + 1 + len)?).into_owned());
+        pos += 1 + len;
+    }
+
+    Some((labels.join("."), end.unwrap_or(pos)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A response to `build_query` with the header rewritten and `answers` appended.
+    fn response(name: &str, rcode: u8, count: u16, answers: &[u8]) -> Vec<u8> {
+        let mut buf = build_query(0x1234, name, TYPE_A);
+        buf[2] = 0x81;
+        buf[3] = 0x80 | rcode;
+        buf[6..8].copy_from_slice(&count.to_be_bytes());
+        buf.extend_from_slice(answers);
+        buf
+    }
+
+    /// An answer whose name points back at the question name at offset 12.
+    fn answer(rtype: u16, rdata: &[u8]) -> Vec<u8> {
+        let mut out = vec![0xc0, 12];
+        out.extend_from_slice(&rtype.to_be_bytes());
+        out.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+        out.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+        out.extend_from_slice(rdata);
+        out
+    }
+
+    #[test]
+    fn parses_compressed_answers() {
+        let mut answers = answer(TYPE_A, &[192, 0, 2, 7]);
+        answers.extend(answer(TYPE_CNAME, &[3, b'w', b'w', b'w', 0xc0, 12]));
+        answers.extend(answer(TYPE_MX, &[0, 10, 0xc0, 12]));
+        answers.extend(answer(TYPE_TXT, &[2, b'h', b'i', 1, b'!']));
+        let parsed = parse_response(&response("example.com", RCODE_NOERROR, 4, &answers)).unwrap();
+        assert_eq!(parsed.rcode, RCODE_NOERROR);
+        assert_eq!(parsed.answers[0].name, "example.com");
+        assert_eq!(parsed.answers[0].ttl, 3600);
+        assert_eq!(parsed.values(TYPE_A), vec!["192.0.2.7"]);
+        assert_eq!(parsed.values(TYPE_CNAME), vec!["www.example.com"]);
+        assert_eq!(parsed.values(TYPE_MX), vec!["10 example.com"]);
+        assert_eq!(parsed.values(TYPE_TXT), vec!["hi!"]);
+    }
+
+    #[test]
+    fn keeps_the_rcode_of_empty_answers() {
+        let parsed = parse_response(&response("nx.example", RCODE_NXDOMAIN, 0, &[])).unwrap();
+        assert_eq!(parsed.rcode, RCODE_NXDOMAIN);
+        assert!(parsed.answers.is_empty());
+    }
+
+    #[test]
+    fn rejects_truncated_responses() {
+        let full = response("example.com", RCODE_NOERROR, 1, &answer(TYPE_A, &[192, 0, 2, 7]));
+        for len in 0..full.len() {
+            assert!(parse_response(&full[..len]).is_none(), "accepted a response cut at {} bytes", len);
+        }
+        // An answer count larger than the answers present.
+        assert!(parse_response(&response("example.com", RCODE_NOERROR, 2, &answer(TYPE_A, &[192, 0, 2, 7]))).is_none());
+    }
+
+    #[test]
+    fn rejects_compression_pointer_loops() {
+        let question_end = build_query(0, "example.com", TYPE_A).len();
+        // A name that points at itself, and two that point at each other.
+        let mut self_loop = response("example.com", RCODE_NOERROR, 1, &[]);
+        let at = self_loop.len() as u16;
+        self_loop.extend_from_slice(&(0xc000 | at).to_be_bytes());
+        assert!(parse_response(&self_loop).is_none());
+        let mut pair = response("example.com", RCODE_NOERROR, 1, &[]);
+        pair.extend_from_slice(&(0xc000 | (question_end as u16 + 2)).to_be_bytes());
+        pair.extend_from_slice(&(0xc000 | question_end as u16).to_be_bytes());
+        assert!(parse_response(&pair).is_none());
+        // A pointer past the end of the message.
+        assert!(read_name(&[0xc0, 0xff], 0).is_none());
+    }
+}
@@ -0,0 +1,406 @@
+use std::fs;
+use std::net::{IpAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::dns;
+use crate::netinfo::command_stdout;
+
+/// systemd-resolved's local stub; the real upstreams are listed elsewhere.
+const RESOLVED_STUB: &str = "127.0.0.53";
+
+/// One line of the hosts file.
+#[derive(Debug)]
+struct HostsEntry {
+    line: usize,
+    addr: IpAddr,
+    names: Vec<String>,
+}
+
+/// A set of nameservers and the names they are used for.
+#[derive(Debug, Default)]
+struct Resolver {
+    /// Where it applies: `global`, an interface, or a resolver block.
+    scope: String,
+    nameservers: Vec<String>,
+    /// Domains routed to this resolver (split DNS); empty means "everything else".
+    domains: Vec<String>,
+    search: Vec<String>,
+}
+
+/// Settings from resolv.conf.
+#[derive(Debug, Default)]
+struct ResolvConf {
+    nameservers: Vec<String>,
+    search: Vec<String>,
+    options: Vec<String>,
+}
+
+impl ResolvConf {
+    /// Names with fewer dots than this are tried with search domains first.
+    fn ndots(&self) -> usize {
+        self.options.iter().find_map(|o| o.strip_prefix("ndots:")).and_then(|n| n.parse().ok()).unwrap_or(1)
+    }
+}
+
+/// Returns the `dns-config` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dns-config")
+        .about("Audits the hosts file and resolver configuration, and explains how a hostname gets resolved")
+        .arg(Arg::with_name("hostname").help("Hostname whose resolution path to explain"))
+}
+
+/// Runs the `dns-config` subcommand.
+pub fn run(matches: &ArgMatches) {
+    println!("\n🧭 {} Resolver configuration audit\n", colorize("[INFO]", "blue"));
+    let hosts = hosts_file();
+    let resolv = resolv_conf();
+    let resolvers = resolvers(&resolv);
+    let order = lookup_order();
+
+    print_hosts(&hosts);
+    print_resolvers(&resolv, &resolvers, &order);
+    if let Some(hostname) = matches.value_of("hostname") {
+        explain(hostname, &hosts, &resolv, &resolvers, &order);
+    }
+}
+
+fn hosts_path() -> &'static str {
+    if cfg!(windows) { r"C:\Windows\System32\drivers\etc\hosts" } else { "/etc/hosts" }
+}
+
+/// Parses the hosts file, skipping comments and malformed lines.
+fn hosts_file() -> Vec<HostsEntry> {
+    fs::read_to_string(hosts_path())
+        .unwrap_or_default()
+        .lines()
+        .enumerate()
+        .filter_map(|(i, line)| {
+            let line = line.split('#').next().unwrap_or("");
+            let mut words = line.split_whitespace();
+            let addr = words.next()?.parse().ok()?;
+            let names: Vec<String> = words.map(|w| w.to_lowercase()).collect();
+            if names.is_empty() { None } else { Some(HostsEntry { line: i + 1, addr, names }) }
+        })
+        .collect()
+}
+
+/// Whether a hosts entry only carries the usual loopback and localhost names.
+fn is_standard(entry: &HostsEntry) -> bool {
+    entry.addr.is_loopback()
+        && entry.names.iter().all(|n| n.starts_with("localhost") || n.starts_with("ip6-") || !n.contains('.'))
+}
+
+/// Parses resolv.conf; on Windows it does not exist and this is empty.
+fn resolv_conf() -> ResolvConf {
+    let mut conf = ResolvConf::default();
+    for line in fs::read_to_string("/etc/resolv.conf").unwrap_or_default().lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("nameserver") => conf.nameservers.extend(words.next().map(|w| w.to_string())),
+            // The last search/domain line wins, as in the libc resolver.
+            Some("search") | Some("domain") => conf.search = words.map(|w| w.to_string()).collect(),
+            Some("options") => conf.options.extend(words.map(|w| w.to_string())),
+            _ => {}
+        }
+    }
+    conf
+}
+
+/// Lists every resolver the system may use, with per-interface and split-DNS scopes.
+fn resolvers(resolv: &ResolvConf) -> Vec<Resolver> {
+    if cfg!(target_os = "macos") {
+        return command_stdout("scutil", &["--dns"]).map(|out| parse_scutil(&out)).unwrap_or_default();
+    }
+    if cfg!(windows) {
+        let script = "Get-DnsClientServerAddress -AddressFamily IPv4,IPv6 | ForEach-Object { $_.InterfaceAlias + '|' + ($_.ServerAddresses -join ',') }";
+        return command_stdout("powershell", &["-NoProfile", "-Command", script]).map(|out| parse_windows(&out)).unwrap_or_default();
+    }
+    if resolv.nameservers.iter().any(|s| s == RESOLVED_STUB) {
+        if let Some(out) = command_stdout("resolvectl", &["status"]) {
+            return parse_resolvectl(&out);
+        }
+    }
+    vec![Resolver {
+        scope: "global (/etc/resolv.conf)".to_string(),
+        nameservers: resolv.nameservers.clone(),
+        domains: Vec::new(),
+        search: resolv.search.clone(),
+    }]
+}
+
+/// Every nameserver the system may query, looking through the systemd-resolved stub to its
+/// upstreams and including per-interface and split-DNS resolvers.
+pub fn nameservers() -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+    for server in resolvers(&resolv_conf()).into_iter().flat_map(|r| r.nameservers) {
+        if !servers.contains(&server) {
+            servers.push(server);
+        }
+    }
+    servers
+}
+
+/// Parses `resolvectl status`: a Global section and one `Link N (iface)` section per interface.
+fn parse_resolvectl(out: &str) -> Vec<Resolver> {
+    let mut found: Vec<Resolver> = Vec::new();
+    let mut key = String::new();
+    for line in out.lines() {
+        if !line.starts_with(' ') && !line.trim().is_empty() {
+            let scope = line.trim();
+            let scope = match scope.split_once('(') {
+                Some((_, name)) => format!("interface {}", name.trim_end_matches(')')),
+                None => scope.to_lowercase(),
+            };
+            found.push(Resolver { scope, ..Resolver::default() });
+            continue;
+        }
+        // Wrapped server lists continue on lines without a label.
+        let value = match field(line) {
+            Some((label, value)) => {
+                key = label.to_string();
+                value
+            }
+            None => line,
+        };
+        let resolver = match found.last_mut() {
+            Some(resolver) => resolver,
+            None => continue,
+        };
+        match key.as_str() {
+            "DNS Servers" | "Fallback DNS Servers" => resolver.nameservers.extend(value.split_whitespace().map(|s| s.to_string())),
+            "DNS Domain" => {
+                for domain in value.split_whitespace() {
+                    // `~corp` is routing-only; a plain domain is also a search domain.
+                    match domain.strip_prefix('~') {
+                        Some(route) => resolver.domains.push(route.to_string()),
+                        None => {
+                            resolver.domains.push(domain.to_string());
+                            resolver.search.push(domain.to_string());
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    found.retain(|r| !r.nameservers.is_empty());
+    found
+}
+
+/// Splits `Label: value` where the label is words only, so IPv6 addresses are not mistaken for labels.
+fn field(line: &str) -> Option<(&str, &str)> {
+    let (label, value) = line.split_once(':')?;
+    let label = label.trim();
+    let is_label = !label.is_empty() && label.chars().all(|c| c.is_ascii_alphabetic() || c == ' ' || c == '.');
+    if is_label && (value.is_empty() || value.starts_with(' ')) { Some((label, value)) } else { None }
+}
+
+/// Parses `scutil --dns` resolver blocks, which carry scoped and supplemental (split DNS) resolvers.
+fn parse_scutil(out: &str) -> Vec<Resolver> {
+    let mut found: Vec<Resolver> = Vec::new();
+    let mut section = "";
+    for line in out.lines() {
+        let line = line.trim();
+        if line.starts_with("DNS configuration") {
+            section = if line.contains("scoped") { "scoped" } else { "" };
+            continue;
+        }
+        if let Some(number) = line.strip_prefix("resolver #") {
+            let scope = if section.is_empty() { format!("resolver #{}", number) } else { format!("{} resolver #{}", section, number) };
+            found.push(Resolver { scope, ..Resolver::default() });
+            continue;
+        }
+        let (label, value) = match line.split_once(':') {
+            Some((label, value)) => (label.trim(), value.trim().to_string()),
+            None => continue,
+        };
+        let resolver = match found.last_mut() {
+            Some(resolver) => resolver,
+            None => continue,
+        };
+        if label.starts_with("nameserver[") {
+            resolver.nameservers.push(value);
+        } else if label.starts_with("search domain[") {
+            resolver.search.push(value);
+        } else if label == "domain" {
+            resolver.domains.push(value);
+        } else if label == "if_index" {
+            resolver.scope = format!("{} ({})", resolver.scope, value.trim_matches(|c| c == '(' || c == ')'));
+        }
+    }
+    found.retain(|r| !r.nameservers.is_empty());
+    found
+}
+
+/// Parses `InterfaceAlias|server,server` lines from the PowerShell query.
+fn parse_windows(out: &str) -> Vec<Resolver> {
+    out.lines()
+        .filter_map(|line| {
+            let (alias, servers) = line.trim().split_once('|')?;
+            let nameservers: Vec<String> = servers.split(',').filter(|s| !s.is_empty()).map(|s| s.to_string()).collect();
+            if nameservers.is_empty() {
+                return None;
+            }
+            Some(Resolver { scope: format!("interface {}", alias), nameservers, ..Resolver::default() })
+        })
+        .collect()
+}
+
+/// The `hosts:` source order from nsswitch.conf, e.g. `files mdns4_minimal [NOTFOUND=return] dns`.
+fn lookup_order() -> Vec<String> {
+    fs::read_to_string("/etc/nsswitch.conf")
+        .unwrap_or_default()
+        .lines()
+        .find_map(|l| l.trim().strip_prefix("hosts:"))
+        .map(|order| order.split_whitespace().map(|w| w.to_string()).collect())
+        .unwrap_or_else(|| vec!["files".to_string(), "dns".to_string()])
+}
+
+/// Prints non-standard hosts entries, blocklist mappings and conflicting duplicates.
+fn print_hosts(hosts: &[HostsEntry]) {
+    println!("🔹 {}", colorize(&format!("Hosts file ({})", hosts_path()), "blue"));
+    let blocked = hosts.iter().filter(|e| e.addr.is_unspecified()).count();
+    let overrides: Vec<&HostsEntry> = hosts.iter().filter(|e| !is_standard(e) && !e.addr.is_unspecified()).collect();
+    println!("   {} entries, {} pointing at 0.0.0.0/:: (blocklist), {} overrides", hosts.len(), blocked, overrides.len());
+    for entry in &overrides {
+        println!("   ⚠️  {} line {}: {} → {}", colorize("[OVERRIDE]", "yellow"), entry.line, entry.names.join(" "), entry.addr);
+    }
+    let mut seen: Vec<(&str, IpAddr, usize)> = Vec::new();
+    for entry in hosts {
+        for name in &entry.names {
+            match seen.iter().find(|(n, _, _)| n == name) {
+                Some((_, addr, line)) if *addr != entry.addr && addr.is_ipv4() == entry.addr.is_ipv4() => println!(
+                    "   ⚠️  {} {} is mapped on line {} ({}) and again on line {} ({}); only the first applies",
+                    colorize("[CONFLICT]", "yellow"),
+                    name,
+                    line,
+                    addr,
+                    entry.line,
+                    entry.addr
+                ),
+                Some(_) => {}
+                None => seen.push((name, entry.addr, entry.line)),
+            }
+        }
+    }
+    if blocked > 0 {
+        println!("   ℹ️  Blocklisted names fail to connect instantly rather than failing to resolve.");
+    }
+    println!();
+}
+
+/// Prints lookup order, search domains, options and each resolver scope.
+fn print_resolvers(resolv: &ResolvConf, resolvers: &[Resolver], order: &[String]) {
+    println!("🔹 {}", colorize("Resolvers", "blue"));
+    if !cfg!(windows) {
+        println!("   Lookup order: {}", order.join(" "));
+        println!("   Search:       {}", if resolv.search.is_empty() { "none".to_string() } else { resolv.search.join(" ") });
+        println!("   Options:      {}", if resolv.options.is_empty() { "none".to_string() } else { resolv.options.join(" ") });
+    }
+    if resolv.nameservers.iter().any(|s| s == RESOLVED_STUB) {
+        println!("   resolv.conf points at the systemd-resolved stub {}; the upstreams are below", RESOLVED_STUB);
+    }
+    for resolver in resolvers {
+        println!("   • {:<28} {}", resolver.scope, resolver.nameservers.join(", "));
+        if !resolver.domains.is_empty() {
+            println!("     {:<28} {}", "used for", resolver.domains.join(" "));
+        }
+        if !resolver.search.is_empty() && resolver.search != resolv.search {
+            println!("     {:<28} {}", "search", resolver.search.join(" "));
+        }
+    }
+    if resolv.options.iter().any(|o| o == "rotate") {
+        println!("   ℹ️  options rotate spreads queries over all nameservers, so one bad server causes intermittent failures.");
+    }
+    println!();
+}
+
+/// Walks through how `hostname` would be resolved and flags what could make it resolve unexpectedly.
+fn explain(hostname: &str, hosts: &[HostsEntry], resolv: &ResolvConf, resolvers: &[Resolver], order: &[String]) {
+    let name = hostname.trim_end_matches('.').to_lowercase();
+    println!("🔹 {}", colorize(&format!("How {} resolves", hostname), "blue"));
+    let mut step = 1;
+
+    let overridden = hosts.iter().find(|e| e.names.contains(&name));
+    if let Some(entry) = overridden {
+        let files_first = order.first().is_some_and(|s| s == "files");
+        println!(
+            "   {}. ⚠️  {} {} line {} maps it to {}{}",
+            step,
+            colorize("[HOSTS]", "yellow"),
+            hosts_path(),
+            entry.line,
+            entry.addr,
+            if files_first { "; the lookup stops there and DNS is never asked" } else { "" }
+        );
+        step += 1;
+    }
+    if name.ends_with(".local") && order.iter().any(|s| s.starts_with("mdns")) {
+        println!("   {}. .local names go to multicast DNS (mDNS) on the LAN, not to a DNS server", step);
+        step += 1;
+    }
+
+    let dots = name.matches('.').count();
+    if !hostname.ends_with('.') && dots < resolv.ndots() && !resolv.search.is_empty() {
+        let tried: Vec<String> = resolv.search.iter().map(|d| format!("{}.{}", name, d)).collect();
+        println!("   {}. With fewer than {} dot(s) it is first tried as {}", step, resolv.ndots(), tried.join(", "));
+        println!("      A wildcard record in one of those domains would answer instead of the real name.");
+        step += 1;
+    }
+
+    let split = resolvers
+        .iter()
+        .filter_map(|r| r.domains.iter().find(|d| name == **d || name.ends_with(&format!(".{}", d))).map(|d| (r, d)))
+        .max_by_key(|(_, d)| d.len());
+    match split {
+        Some((resolver, domain)) => println!(
+            "   {}. Split DNS: names under {} go to {} ({}){}",
+            step,
+            domain,
+            resolver.nameservers.join(", "),
+            resolver.scope,
+            if overridden.is_some() { ", once the hosts entry is removed" } else { "" }
+        ),
+        None => println!(
+            "   {}. {} to the default resolver(s): {}",
+            step,
+            if overridden.is_some() { "Without the hosts entry it would go" } else { "Sent" },
+            resolvers.iter().find(|r| r.domains.is_empty()).map(|r| r.nameservers.join(", ")).unwrap_or_else(|| resolv.nameservers.join(", "))
+        ),
+    }
+
+    // What the system library actually returns, against what a direct DNS query says.
+    let system: Vec<String> = (name.as_str(), 0)
+        .to_socket_addrs()
+        .map(|addrs| addrs.map(|a| a.ip().to_string()).collect())
+        .unwrap_or_default();
+    println!("\n   System lookup: {}", if system.is_empty() { "failed".to_string() } else { system.join(", ") });
+    let server = split
+        .and_then(|(r, _)| r.nameservers.first())
+        .or_else(|| resolv.nameservers.iter().find(|s| *s != RESOLVED_STUB))
+        .or_else(|| resolvers.iter().flat_map(|r| r.nameservers.iter()).next());
+    if let Some(server) = server.and_then(|s| s.split('%').next()).and_then(|s| s.parse::<IpAddr>().ok()) {
+        match dns::query(server, &name, dns::TYPE_A, Duration::from_secs(3)) {
+            Ok(response) => {
+                let direct = response.values(dns::TYPE_A);
+                println!(
+                    "   Direct query to {}: {}",
+                    server,
+                    if direct.is_empty() { dns::rcode_name(response.rcode).to_string() } else { direct.join(", ") }
+                );
+                let v4: Vec<&String> = system.iter().filter(|a| !a.contains(':')).collect();
+                if !v4.is_empty() && !direct.is_empty() && !v4.iter().any(|a| direct.contains(a)) {
+                    println!(
+                        "   ⚠️  {} The system answer differs from DNS: a hosts entry, search domain or another resolver is overriding it",
+                        colorize("[MISMATCH]", "yellow")
+                    );
+                }
+            }
+            Err(e) => println!("   Direct query to {} failed: {}", server, e),
+        }
+    }
+    println!();
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet};
+use std::net::IpAddr;
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::dns;
+use crate::netinfo;
+
+/// Commonly filtered domains by category. Each resolves normally on an unfiltered resolver.
+const CATEGORIES: &[(&str, &[&str])] = &[
+    ("Ads", &["doubleclick.net", "googlesyndication.com", "adnxs.com", "taboola.com"]),
+    ("Trackers", &["google-analytics.com", "hotjar.com", "scorecardresearch.com"]),
+    ("Social media", &["facebook.com", "instagram.com", "tiktok.com", "x.com"]),
+    ("Adult", &["pornhub.com", "xvideos.com"]),
+    ("Gambling", &["bet365.com", "pokerstars.com"]),
+    // Published by filtering vendors so customers can confirm their policy is applied.
+    ("Security test domains", &["internetbadguys.com", "examplemalwaredomain.com", "malware.testcategory.com"]),
+];
+
+/// Reference resolvers without filtering. Quad9 is left out because it blocks malware domains.
+const UNFILTERED_RESOLVERS: &[&str] = &["1.1.1.1", "8.8.8.8"];
+
+/// Domains that must share an unexplained address before it counts as a block page.
+const BLOCK_PAGE_DOMAINS: usize = 3;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How the system resolver answered one domain compared with the unfiltered resolvers.
+#[derive(Debug, Clone, PartialEq)]
+enum Verdict {
+    Resolves,
+    /// Blocked, with how: an error code, a sinkhole address or a block page.
+    Blocked(String),
+    /// No usable answer from the system resolver or the references.
+    Unknown(String),
+}
+
+/// Answers for one domain from the system resolver and the references.
+struct Lookup {
+    domain: &'static str,
+    category: &'static str,
+    system: Result<(u8, Vec<String>), String>,
+    reference: Vec<String>,
+}
+
+/// Returns the `dns-filter` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dns-filter")
+        .about("Detects DNS-level filtering of ads, trackers, social media and other categories")
+}
+
+/// Runs the `dns-filter` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    println!();
+    filter_check();
+    println!();
+}
+
+/// Resolves the curated domains through the system resolver and unfiltered public resolvers,
+/// and prints which categories the network filters. Returns the filtered categories.
+pub fn filter_check() -> Vec<&'static str> {
+    println!("🔹 {}", colorize("Checking for DNS filtering", "blue"));
+    let system: IpAddr = match netinfo::dns_servers().iter().find_map(|s| s.parse().ok()) {
+        Some(server) => server,
+        None => {
+            println!("   ⚠️  {} Could not determine the system resolver.", colorize("[WARN]", "yellow"));
+            return Vec::new();
+        }
+    };
+    println!("   System resolver: {}", colorize(&system.to_string(), "cyan"));
+
+    let lookups: Vec<Lookup> = CATEGORIES
+        .iter()
+        .flat_map(|&(category, domains)| domains.iter().map(move |&domain| (category, domain)))
+        .map(|(category, domain)| thread::spawn(move || lookup(system, category, domain)))
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(|handle| handle.join().ok())
+        .collect();
+    let verdicts = classify(&lookups);
+
+    let mut filtered = Vec::new();
+    for &(category, _) in CATEGORIES {
+        let results: Vec<(&Lookup, &Verdict)> = lookups.iter().zip(&verdicts).filter(|(l, _)| l.category == category).collect();
+        let blocked: Vec<String> = results
+            .iter()
+            .filter_map(|(l, v)| match v {
+                Verdict::Blocked(how) => Some(format!("{} ({})", l.domain, how)),
+                _ => None,
+            })
+            .collect();
+        let unknown = results.iter().filter(|(_, v)| matches!(v, Verdict::Unknown(_))).count();
+        if !blocked.is_empty() {
+            filtered.push(category);
+            println!(
+                "   ❌ {} {}: {} of {} blocked: {}",
+                colorize("[FILTERED]", "red"),
+                category,
+                blocked.len(),
+                results.len(),
+                blocked.join(", ")
+            );
+        } else if unknown == results.len() {
+            println!("   ⚠️  {} {}: could not compare answers", colorize("[WARN]", "yellow"), category);
+        } else {
+            println!("   ✅ {}: not filtered", category);
+        }
+        for (l, v) in &results {
+            if let Verdict::Unknown(why) = v {
+                println!("      {} not compared: {}", l.domain, why);
+            }
+        }
+    }
+
+    if verdicts.iter().all(|v| matches!(v, Verdict::Unknown(_))) {
+        println!("⚠️  {} Could not tell whether DNS is filtered.", colorize("[WARN]", "yellow"));
+    } else if filtered.is_empty() {
+        println!("✅ {} No DNS filtering detected.", colorize("[SUCCESS]", "green"));
+    } else {
+        println!("📊 {} DNS filtering applied to: {}", colorize("[SUMMARY]", "blue"), filtered.join(", "));
+        println!("   • Blocked names still resolve via public resolvers (netdiag dns-propagation <domain>); if those are filtered too, port 53 may be intercepted (netdiag dns-hijack).");
+    }
+    filtered
+}
+
+/// Asks the system resolver and each unfiltered resolver for the domain's A records.
+fn lookup(system: IpAddr, category: &'static str, domain: &'static str) -> Lookup {
+    let system_answer = dns::query(system, domain, dns::TYPE_A, TIMEOUT).map(|r| (r.rcode, r.values(dns::TYPE_A))).map_err(|e| e.to_string());
+    let reference = UNFILTERED_RESOLVERS
+        .iter()
+        .filter_map(|ip| dns::query(ip.parse().ok()?, domain, dns::TYPE_A, TIMEOUT).ok())
+        .filter(|r| r.rcode == dns::RCODE_NOERROR)
+        .flat_map(|r| r.values(dns::TYPE_A))
+        .collect();
+    Lookup { domain, category, system: system_answer, reference }
+}
+
+/// Decides per domain whether the system resolver's answer is a block. An address the system
+/// returns for several domains, and no reference ever does, is a block page.
+fn classify(lookups: &[Lookup]) -> Vec<Verdict> {
+    let genuine: HashSet<&str> = lookups.iter().flat_map(|l| l.reference.iter().map(|a| a.as_str())).collect();
+    let mut shared: HashMap<&str, usize> = HashMap::new();
+    for l in lookups {
+        if let Ok((_, addrs)) = &l.system {
+            for addr in addrs.iter().filter(|a| !genuine.contains(a.as_str())) {
+                *shared.entry(addr.as_str()).or_insert(0) += 1;
+            }
+        }
+    }
+
+    lookups
+        .iter()
+        .map(|l| {
+            let (rcode, addrs) = match &l.system {
+                Ok(answer) => answer,
+                Err(e) => return Verdict::Unknown(format!("system resolver did not answer: {}", e)),
+            };
+            if l.reference.is_empty() {
+                return Verdict::Unknown("unfiltered resolvers did not answer".to_string());
+            }
+            if *rcode != dns::RCODE_NOERROR {
+                return Verdict::Blocked(dns::rcode_name(*rcode).to_string());
+            }
+            if addrs.is_empty() {
+                return Verdict::Blocked("empty answer".to_string());
+            }
+            if let Some(sinkhole) = addrs.iter().find(|a| is_sinkhole(a)) {
+                return Verdict::Blocked(format!("sinkholed to {}", sinkhole));
+            }
+            match addrs.iter().find(|a| !l.reference.contains(a) && shared.get(a.as_str()).copied().unwrap_or(0) >= BLOCK_PAGE_DOMAINS) {
+                Some(page) => Verdict::Blocked(format!("block page {}", page)),
+                None => Verdict::Resolves,
+            }
+        })
+        .collect()
+}
+
+/// Addresses filters answer with instead of the real one: unspecified, loopback or private.
+fn is_sinkhole(addr: &str) -> bool {
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => v4.is_unspecified() || v4.is_loopback() || v4.is_private() || v4.is_link_local(),
+        Ok(IpAddr::V6(v6)) => v6.is_unspecified() || v6.is_loopback(),
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,159 @@
+use std::net::IpAddr;
+use std::time::Duration;
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::{colorize, random_u64};
+use crate::dns;
+use crate::netinfo;
+use crate::telemetry;
+
+/// Stable domains whose answers should not depend on which resolver is asked.
+const KNOWN_DOMAINS: &[&str] = &["example.com", "wikipedia.org", "github.com"];
+
+/// TEST-NET-2 address that is never routed; any DNS reply "from" it was forged on the path.
+const UNROUTABLE_RESOLVER: &str = "198.51.100.53";
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Returns the `dns-hijack` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dns-hijack")
+        .about("Detects NXDOMAIN rewriting and DNS interception or hijacking")
+}
+
+/// Runs the `dns-hijack` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    println!();
+    hijack_check();
+    println!();
+}
+
+/// Compares the system resolver against public resolvers and prints any signs of tampering.
+/// Returns the number of problems found.
+pub fn hijack_check() -> usize {
+    println!("🔹 {}", colorize("Checking for DNS hijacking and interception", "blue"));
+    let mut problems = 0;
+
+    let system: Option<IpAddr> = netinfo::dns_servers().iter().filter_map(|s| s.parse().ok()).next();
+    match system {
+        Some(server) => println!("   System resolver: {}", colorize(&server.to_string(), "cyan")),
+        None => println!("   ⚠️  {} Could not determine the system resolver.", colorize("[WARN]", "yellow")),
+    }
+
+    // 1. A name that cannot exist must come back NXDOMAIN, from the system resolver and from
+    // public ones; a public resolver that rewrites it is being answered for on the path.
+    let bogus = format!("netdiag-{:016x}.com", random_u64());
+    let rewritten = |r: &dns::Response| r.rcode == dns::RCODE_NOERROR && !r.values(dns::TYPE_A).is_empty();
+    let mut public_rcodes: Vec<u8> = Vec::new();
+    for &(ip, name) in dns::PUBLIC_RESOLVERS {
+        match dns::query(ip.parse().expect("valid address"), &bogus, dns::TYPE_A, TIMEOUT) {
+            Ok(ref r) if rewritten(r) => {
+                problems += 1;
+                println!(
+                    "   ❌ {} NXDOMAIN rewriting on the path: {} ({}) resolved {} to {}",
+                    colorize("[HIJACK]", "red"),
+                    name,
+                    ip,
+                    bogus,
+                    r.values(dns::TYPE_A).join(", ")
+                );
+            }
+            Ok(r) => {
+                if !public_rcodes.contains(&r.rcode) {
+                    public_rcodes.push(r.rcode);
+                }
+            }
+            Err(e) => println!("   ⚠️  {} {} ({}) did not answer: {}", colorize("[WARN]", "yellow"), name, ip, e),
+        }
+    }
+    let public_names: Vec<&str> = public_rcodes.iter().map(|&c| dns::rcode_name(c)).collect();
+    match system.map(|server| dns::query(server, &bogus, dns::TYPE_A, TIMEOUT)) {
+        Some(Ok(ref r)) if rewritten(r) => {
+            problems += 1;
+            println!(
+                "   ❌ {} NXDOMAIN rewriting: {} resolved to {} (should not exist)",
+                colorize("[HIJACK]", "red"),
+                bogus,
+                r.values(dns::TYPE_A).join(", ")
+            );
+        }
+        Some(Ok(ref r)) if !public_rcodes.is_empty() && !public_rcodes.contains(&r.rcode) => println!(
+            "   ⚠️  {} Nonexistent domain returned {} from the system resolver but {} from public resolvers",
+            colorize("[WARN]", "yellow"),
+            dns::rcode_name(r.rcode),
+            public_names.join("/")
+        ),
+        Some(Ok(ref r)) => println!("   ✅ Nonexistent domain returned {}", dns::rcode_name(r.rcode)),
+        Some(Err(e)) => println!(
+            "   ⚠️  {} System resolver did not answer: {}; NXDOMAIN rewriting by it was not checked",
+            colorize("[WARN]", "yellow"),
+            e
+        ),
+        None => println!("   ⚠️  {} NXDOMAIN rewriting by the system resolver was not checked", colorize("[WARN]", "yellow")),
+    }
+
+    // 2. Nothing listens at an unroutable address, so a reply means port 53 is intercepted.
+    let unroutable: IpAddr = UNROUTABLE_RESOLVER.parse().expect("valid address");
+    match dns::query(unroutable, "example.com", dns::TYPE_A, TIMEOUT) {
+        Ok(_) => {
+            problems += 1;
+            println!(
+                "   ❌ {} DNS interception: a query to unroutable {} was answered; outbound port 53 is being redirected",
+                colorize("[HIJACK]", "red"),
+                UNROUTABLE_RESOLVER
+            );
+        }
+        Err(_) => println!("   ✅ No transparent DNS proxy detected on port 53"),
+    }
+
+    // 3. Known domains should resolve to public addresses that overlap with public resolvers.
+    for domain in KNOWN_DOMAINS {
+        let reference: Vec<String> = dns::PUBLIC_RESOLVERS
+            .iter()
+            .filter_map(|&(ip, _)| dns::query(ip.parse().ok()?, domain, dns::TYPE_A, TIMEOUT).ok())
+            .flat_map(|r| r.values(dns::TYPE_A))
+            .collect();
+        let local = match system.and_then(|s| dns::query(s, domain, dns::TYPE_A, TIMEOUT).ok()) {
+            Some(r) => r.values(dns::TYPE_A),
+            None => continue,
+        };
+
+        if let Some(private) = local.iter().find(|a| is_non_public(a)) {
+            problems += 1;
+            println!(
+                "   ❌ {} {} resolves to non-public address {} via the system resolver",
+                colorize("[HIJACK]", "red"),
+                domain,
+                private
+            );
+        } else if !reference.is_empty() && !local.is_empty() && !local.iter().any(|a| reference.contains(a)) {
+            println!(
+                "   ⚠️  {} {} answers differ from public resolvers ({} vs {}); may be a CDN difference",
+                colorize("[WARN]", "yellow"),
+                domain,
+                local.join(", "),
+                reference.join(", ")
+            );
+        } else {
+            println!("   ✅ {} resolves consistently", domain);
+        }
+    }
+
+    if problems == 0 {
+        println!("✅ {} No DNS tampering detected.", colorize("[SUCCESS]", "green"));
+    } else {
+        println!("❌ {} {} sign(s) of DNS tampering found.", colorize("[ERROR]", "red"), problems);
+        telemetry::fail(&format!("{} sign(s) of DNS tampering", problems));
+    }
+    problems
+}
+
+/// True for loopback, private, link-local and unspecified addresses.
+fn is_non_public(addr: &str) -> bool {
+    match addr.parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_unspecified(),
+        Ok(IpAddr::V6(v6)) => v6.is_loopback() || v6.is_unspecified() || (v6.segments()[0] & 0xfe00) == 0xfc00,
+        Err(_) => false,
+    }
+}
@@ -0,0 +1,138 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::dns;
+
+/// Public resolvers spread across operators and regions.
+const RESOLVERS: &[(&str, &str, &str)] = &[
+    ("1.1.1.1", "Cloudflare", "Anycast"),
+    ("8.8.8.8", "Google", "Anycast"),
+    ("9.9.9.9", "Quad9", "Anycast"),
+    ("208.67.222.222", "OpenDNS", "United States"),
+    ("4.2.2.1", "Level3", "United States"),
+    ("156.154.70.1", "Neustar", "United States"),
+    ("8.26.56.26", "Comodo", "United States"),
+    ("76.76.2.0", "Control D", "Canada"),
+    ("84.200.69.80", "DNS.WATCH", "Germany"),
+    ("94.140.14.14", "AdGuard", "Cyprus"),
+    ("185.228.168.9", "CleanBrowsing", "United Kingdom"),
+    ("77.88.8.8", "Yandex", "Russia"),
+    ("114.114.114.114", "114DNS", "China"),
+    ("223.5.5.5", "AliDNS", "China"),
+    ("168.126.63.1", "KT", "South Korea"),
+];
+
+/// Returns the `dns-propagation` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dns-propagation")
+        .about("Queries a record against public resolvers worldwide to check propagation")
+        .arg(Arg::with_name("domain").required(true).help("Domain name to look up"))
+        .arg(Arg::with_name("type").long("type").short("t").takes_value(true).default_value("A")
+            .possible_values(&["A", "AAAA", "CNAME", "MX", "NS", "TXT"])
+            .help("Record type"))
+        .arg(Arg::with_name("expect").long("expect").takes_value(true)
+            .help("New value to look for; defaults to the most common answer"))
+}
+
+/// Runs the `dns-propagation` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let domain = matches.value_of("domain").unwrap_or_default();
+    let rtype = matches.value_of("type").and_then(dns::type_from_name).unwrap_or(dns::TYPE_A);
+    check_propagation(domain, rtype, matches.value_of("expect"));
+}
+
+/// Queries every resolver concurrently and prints which ones return the expected value.
+fn check_propagation(domain: &str, rtype: u16, expect: Option<&str>) {
+    println!(
+        "\n🌍 {} Checking propagation of {} across {} resolvers\n",
+        colorize("[INFO]", "blue"),
+        colorize(domain, "cyan"),
+        RESOLVERS.len()
+    );
+
+    let handles: Vec<_> = RESOLVERS
+        .iter()
+        .map(|&(ip, _, _)| {
+            let domain = domain.to_string();
+            thread::spawn(move || {
+                let server: IpAddr = ip.parse().expect("valid resolver address");
+                dns::query(server, &domain, rtype, Duration::from_secs(3))
+            })
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().ok()).collect();
+
+    // Sort each answer set so resolvers returning the same records in a different order match.
+    let answers: Vec<Option<String>> = results
+        .iter()
+        .map(|r| match r {
+            Some(Ok(response)) => {
+                let mut values = response.values(rtype);
+                values.sort();
+                Some(values.join(", "))
+            }
+            _ => None,
+        })
+        .collect();
+
+    let target = match expect {
+        Some(value) => value.to_string(),
+        None => {
+            let mut counts: BTreeMap<&str, usize> = BTreeMap::new();
+            for answer in answers.iter().flatten().filter(|a| !a.is_empty()) {
+                *counts.entry(answer.as_str()).or_insert(0) += 1;
+            }
+            counts.into_iter().max_by_key(|&(_, n)| n).map(|(a, _)| a.to_string()).unwrap_or_default()
+        }
+    };
+
+    println!("{:<18} {:<16} {:<16} {:<10} {:>7}  Answer", "Resolver", "Operator", "Location", "Status", "TTL");
+    println!("{}", "-".repeat(100));
+
+    let mut matching = 0;
+    let mut answered = 0;
+    for (i, &(ip, operator, location)) in RESOLVERS.iter().enumerate() {
+        let (status, ttl, answer, color) = match results[i] {
+            Some(Ok(ref response)) => {
+                answered += 1;
+                let answer = answers[i].clone().unwrap_or_default();
+                let is_match = if expect.is_some() {
+                    answer.split(", ").any(|v| v.trim_end_matches('.') == target.trim_end_matches('.'))
+                } else {
+                    answer == target
+                };
+                if is_match {
+                    matching += 1;
+                }
+                let ttl = response.answers.iter().find(|r| r.rtype == rtype).map_or("-".to_string(), |r| r.ttl.to_string());
+                let color = if is_match { "green" } else { "yellow" };
+                (dns::rcode_name(response.rcode).to_string(), ttl, answer, color)
+            }
+            Some(Err(ref e)) => ("TIMEOUT".to_string(), "-".to_string(), e.to_string(), "red"),
+            None => ("ERROR".to_string(), "-".to_string(), String::new(), "red"),
+        };
+
+        println!(
+            "{:<18} {:<16} {:<16} {:<10} {:>7}  {}",
+            ip,
+            operator,
+            location,
+            status,
+            ttl,
+            colorize(if answer.is_empty() { "(no records)" } else { &answer }, color)
+        );
+    }
+
+    println!(
+        "\n📊 {} {}/{} responding resolvers return {}\n",
+        colorize("[SUMMARY]", "blue"),
+        matching,
+        answered,
+        colorize(if target.is_empty() { "(no records)" } else { &target }, "cyan")
+    );
+}
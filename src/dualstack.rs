@@ -0,0 +1,192 @@
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::chart;
+use crate::colorize;
+use crate::interrupt;
+use crate::repeat;
+use crate::resolver;
+use crate::routes;
+use crate::tcping::{self, Attempt};
+
+/// Delay before a Happy Eyeballs client (RFC 8305) starts the IPv4 attempt in parallel.
+const CONNECTION_ATTEMPT_DELAY_MS: f64 = 250.0;
+
+/// IPv6 handshakes this much slower than IPv4 on average are worth reporting.
+const SLOWER_THRESHOLD_MS: f64 = 50.0;
+
+/// Returns the `dualstack` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dualstack")
+        .about("Compares IPv4 and IPv6 connections to a host to find broken or slow IPv6")
+        .arg(Arg::with_name("host").required(true).help("Dual-stack host name"))
+        .arg(Arg::with_name("port").long("port").short("p").takes_value(true).default_value("443")
+            .help("TCP port to connect to"))
+        .arg(Arg::with_name("count").long("count").short("c").takes_value(true).default_value("10")
+            .help("Connection attempts per address family"))
+        .arg(Arg::with_name("timeout").long("timeout").takes_value(true).default_value("3000")
+            .help("Connect timeout per attempt in milliseconds"))
+}
+
+/// Runs the `dualstack` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let host = matches.value_of("host").unwrap_or_default();
+    let port = value_t!(matches, "port", u16).unwrap_or(443);
+    let count = value_t!(matches, "count", u32).unwrap_or(10);
+    let timeout = Duration::from_millis(value_t!(matches, "timeout", u64).unwrap_or(3000));
+
+    let addrs: Vec<SocketAddr> = match resolver::resolve(host) {
+        Ok(addrs) => addrs.into_iter().map(|ip| SocketAddr::new(ip, port)).collect(),
+        Err(e) => {
+            println!("❌ {} {}", colorize("[DNS]", "red"), e);
+            return;
+        }
+    };
+    let v4: Vec<SocketAddr> = addrs.iter().filter(|a| a.is_ipv4()).cloned().collect();
+    let v6: Vec<SocketAddr> = addrs.iter().filter(|a| a.is_ipv6()).cloned().collect();
+
+    println!("\n🌐 {} Dual-stack check for {} port {}\n", colorize("[INFO]", "blue"), colorize(host, "cyan"), port);
+    println!("   A records:    {}", describe(&v4));
+    println!("   AAAA records: {}", describe(&v6));
+    let (first4, first6) = match (v4.first(), v6.first()) {
+        (Some(a), Some(b)) => (*a, *b),
+        _ => {
+            println!("\nℹ️  {} {} is not dual-stack; nothing to compare\n", colorize("[INFO]", "blue"), host);
+            return;
+        }
+    };
+    if !routes::table().iter().any(|r| r.is_default() && r.destination.is_ipv6()) {
+        println!(
+            "⚠️  {} This host has no IPv6 default route; clients skip IPv6 immediately, so results below reflect only the local stack",
+            colorize("[WARN]", "yellow")
+        );
+    }
+
+    println!("\n🔹 {}", colorize("Racing IPv6 and IPv4 handshakes", "blue"));
+    let mut rounds = Vec::new();
+    for seq in 1..=count {
+        // Alternate which family goes first so neither benefits from a warm path.
+        let (a6, a4) = if seq % 2 == 1 {
+            let a6 = tcping::connect(first6, timeout);
+            (a6, tcping::connect(first4, timeout))
+        } else {
+            let a4 = tcping::connect(first4, timeout);
+            (tcping::connect(first6, timeout), a4)
+        };
+        println!("   seq={:<3} IPv6 {:<22} IPv4 {}", seq, label(a6), label(a4));
+        rounds.push((a6, a4));
+        if seq < count && !interrupt::sleep(Duration::from_millis(500)) {
+            break;
+        }
+    }
+    print_summary(&rounds, timeout);
+}
+
+/// Lists resolved addresses, or "none".
+fn describe(addrs: &[SocketAddr]) -> String {
+    if addrs.is_empty() {
+        return "none".to_string();
+    }
+    addrs.iter().map(|a| a.ip().to_string()).collect::<Vec<_>>().join(", ")
+}
+
+/// Short uncolored description of one attempt for the round table.
+fn label(attempt: Attempt) -> String {
+    match attempt {
+        Attempt::Connected(ms) => format!("{:.1} ms", ms),
+        Attempt::Refused(_) => "refused".to_string(),
+        Attempt::TimedOut => "timed out".to_string(),
+        Attempt::Failed => "failed".to_string(),
+    }
+}
+
+/// Handshake time of a completed attempt.
+fn latency(attempt: Attempt) -> Option<f64> {
+    match attempt {
+        Attempt::Connected(ms) => Some(ms),
+        _ => None,
+    }
+}
+
+/// Time a Happy Eyeballs client would take to get a connection for one round.
+fn happy_eyeballs(a6: Attempt, a4: Attempt) -> Option<f64> {
+    let fallback = latency(a4).map(|ms| ms + CONNECTION_ATTEMPT_DELAY_MS);
+    match (latency(a6), fallback) {
+        (Some(v6), Some(v4)) => Some(v6.min(v4)),
+        (v6, v4) => v6.or(v4),
+    }
+}
+
+/// Prints per-family statistics and the dual-stack verdict.
+fn print_summary(rounds: &[(Attempt, Attempt)], timeout: Duration) {
+    let v6: Vec<f64> = rounds.iter().filter_map(|(a, _)| latency(*a)).collect();
+    let v4: Vec<f64> = rounds.iter().filter_map(|(_, a)| latency(*a)).collect();
+    let total = rounds.len().max(1) as f64;
+    let (_, mean6, _, _) = chart::summarize(&v6);
+    let (_, mean4, _, _) = chart::summarize(&v4);
+
+    println!(
+        "\n📊 {} IPv6 {}/{} connected, IPv4 {}/{} connected",
+        colorize("[SUMMARY]", "blue"),
+        v6.len(),
+        rounds.len(),
+        v4.len(),
+        rounds.len()
+    );
+    chart::print_distribution("IPv6 handshake", &v6);
+    chart::print_distribution("IPv4 handshake", &v4);
+    if !v6.is_empty() {
+        repeat::record("IPv6 handshake avg (ms)", mean6);
+    }
+    if !v4.is_empty() {
+        repeat::record("IPv4 handshake avg (ms)", mean4);
+    }
+
+    let effective: Vec<f64> = rounds.iter().filter_map(|(a6, a4)| happy_eyeballs(*a6, *a4)).collect();
+    let (_, mean_he, _, _) = chart::summarize(&effective);
+    if !v4.is_empty() && !effective.is_empty() {
+        println!(
+            "   Happy Eyeballs client: avg {:.1} ms to connect ({:+.1} ms versus IPv4 alone)",
+            mean_he,
+            mean_he - mean4
+        );
+    }
+
+    let v6_loss = 1.0 - v6.len() as f64 / total;
+    let v4_loss = 1.0 - v4.len() as f64 / total;
+    println!();
+    if v6.is_empty() && !v4.is_empty() {
+        println!("🚨 {} IPv6 is broken: every IPv6 handshake failed while IPv4 worked.", colorize("[BROKEN IPv6]", "red"));
+        println!(
+            "   Happy Eyeballs clients lose ~{:.0} ms per new connection; older clients without it hang for the full timeout ({} ms here).",
+            CONNECTION_ATTEMPT_DELAY_MS,
+            timeout.as_millis()
+        );
+    } else if v4.is_empty() && !v6.is_empty() {
+        println!("⚠️  {} IPv4 failed on every attempt while IPv6 worked.", colorize("[WARN]", "yellow"));
+    } else if v6.is_empty() && v4.is_empty() {
+        println!("❌ {} Neither address family could connect.", colorize("[ERROR]", "red"));
+    } else {
+        if v6_loss > v4_loss {
+            println!(
+                "⚠️  {} IPv6 failed {:.0}% of attempts versus {:.0}% for IPv4: intermittent IPv6 timeouts stall some connections.",
+                colorize("[WARN]", "yellow"),
+                v6_loss * 100.0,
+                v4_loss * 100.0
+            );
+        }
+        if mean6 - mean4 > SLOWER_THRESHOLD_MS {
+            println!(
+                "⚠️  {} IPv6 handshakes are {:.1} ms slower than IPv4 on average; clients preferring IPv6 pay that on every connection.",
+                colorize("[WARN]", "yellow"),
+                mean6 - mean4
+            );
+        }
+        if v6_loss <= v4_loss && mean6 - mean4 <= SLOWER_THRESHOLD_MS {
+            println!("✅ {} IPv6 is as reliable and fast as IPv4.", colorize("[SUCCESS]", "green"));
+        }
+    }
+    println!();
+}
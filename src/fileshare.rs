@@ -0,0 +1,439 @@
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::resolver;
+use crate::scheduler;
+use crate::{colorize, random_u64};
+use crate::tcping::{self, Attempt};
+
+const SMB_PORT: u16 = 445;
+const NETBIOS_SESSION_PORT: u16 = 139;
+const NFS_PORT: u16 = 2049;
+const RPCBIND_PORT: u16 = 111;
+
+/// SMB2/3 dialects offered, oldest first.
+const DIALECTS: &[u16] = &[0x0202, 0x0210, 0x0300, 0x0302, 0x0311];
+
+const RPC_PROGRAM_PORTMAP: u32 = 100_000;
+const RPC_PROGRAM_NFS: u32 = 100_003;
+const RPC_PROGRAM_MOUNT: u32 = 100_005;
+const RPC_PROGRAM_NLM: u32 = 100_021;
+const PMAPPROC_DUMP: u32 = 4;
+const IPPROTO_TCP: u32 = 6;
+
+/// What the server answered to an SMB2 NEGOTIATE.
+struct Negotiation {
+    dialect: u16,
+    signing_required: bool,
+    /// Negotiated cipher for SMB 3.1.1, or whether SMB 3.0 encryption is supported.
+    encryption: Option<String>,
+}
+
+/// Result of one ONC RPC call.
+enum RpcReply {
+    Success(Vec<u8>),
+    /// The program runs, but only supports versions `low..=high`.
+    VersionMismatch(u32, u32),
+    Unavailable,
+}
+
+/// A program registered with rpcbind.
+struct Mapping {
+    program: u32,
+    version: u32,
+    protocol: u32,
+    port: u16,
+}
+
+/// Returns the `fileshare` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("fileshare")
+        .about("Checks SMB and NFS reachability of a file server and reports the negotiated SMB dialect")
+        .arg(Arg::with_name("server").required(true).help("File server name or address"))
+        .arg(Arg::with_name("timeout").long("timeout").takes_value(true).default_value("3000")
+            .help("Connect and reply timeout in milliseconds"))
+}
+
+/// Runs the `fileshare` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let server = matches.value_of("server").unwrap_or_default();
+    let timeout = Duration::from_millis(value_t!(matches, "timeout", u64).unwrap_or(3000));
+    let ip = match resolver::resolve_one(server) {
+        Ok(ip) => ip,
+        Err(e) => {
+            println!("❌ {} {}: drive mappings by name fail the same way", colorize("[DNS]", "red"), e);
+            return;
+        }
+    };
+
+    println!("\n📂 {} File share check for {} ({})\n", colorize("[INFO]", "blue"), colorize(server, "cyan"), ip);
+    let mut problems = Vec::new();
+    check_smb(SocketAddr::new(ip, SMB_PORT), timeout, &mut problems);
+    check_nfs(SocketAddr::new(ip, NFS_PORT), timeout, &mut problems);
+
+    if problems.is_empty() {
+        println!("📊 {} SMB and NFS look reachable; remaining mount failures are likely credentials or share permissions\n", colorize("[SUMMARY]", "blue"));
+        return;
+    }
+    println!("📊 {} {} problem(s) found:", colorize("[SUMMARY]", "blue"), problems.len());
+    for problem in &problems {
+        println!("   • {}", problem);
+    }
+    println!();
+}
+
+/// Prints one TCP port's state and returns whether it connected.
+fn check_port(label: &str, addr: SocketAddr, timeout: Duration) -> bool {
+    match tcping::connect(addr, timeout) {
+        Attempt::Connected(ms) => {
+            println!("✅ {} {} port {} open ({:.1} ms)", colorize("[SUCCESS]", "green"), label, addr.port(), ms);
+            true
+        }
+        Attempt::Refused(_) => {
+            println!("❌ {} {} port {} refused: the service is not running or not listening on this address", colorize("[ERROR]", "red"), label, addr.port());
+            false
+        }
+        _ => {
+            println!("❌ {} {} port {} timed out: a firewall on the path or on the server drops it", colorize("[ERROR]", "red"), label, addr.port());
+            false
+        }
+    }
+}
+
+/// Tests TCP 445, negotiates SMB2/3 and probes whether SMB1 is still enabled.
+fn check_smb(addr: SocketAddr, timeout: Duration, problems: &mut Vec<String>) {
+    println!("🔹 {}", colorize("SMB", "blue"));
+    if !check_port("SMB", addr, timeout) {
+        let netbios = SocketAddr::new(addr.ip(), NETBIOS_SESSION_PORT);
+        if matches!(tcping::connect(netbios, timeout), Attempt::Connected(_)) {
+            println!("   Port 139 (NetBIOS session) is open: only legacy clients that fall back to NetBIOS can connect");
+        }
+        problems.push("SMB port 445 is unreachable; many ISPs and guest networks block it outbound".to_string());
+        println!();
+        return;
+    }
+
+    match smb2_negotiate(addr, timeout) {
+        Ok(negotiation) => {
+            println!("✅ {} Negotiated {}", colorize("[SUCCESS]", "green"), dialect_name(negotiation.dialect));
+            println!("   Signing required: {}", if negotiation.signing_required { "yes" } else { "no" });
+            println!("   Encryption:       {}", negotiation.encryption.as_deref().unwrap_or("not supported"));
+            if negotiation.dialect < 0x0300 {
+                println!(
+                    "⚠️  {} The server tops out at {}: clients that require SMB 3 (or encryption) refuse to mount",
+                    colorize("[WARN]", "yellow"),
+                    dialect_name(negotiation.dialect)
+                );
+                problems.push(format!("server only speaks {}", dialect_name(negotiation.dialect)));
+            }
+        }
+        Err(e) => {
+            println!("❌ {} SMB2/3 negotiation failed: {}", colorize("[ERROR]", "red"), e);
+            problems.push("the server does not speak SMB2/3; Windows 10+ and macOS will not connect".to_string());
+        }
+    }
+    if smb1_enabled(addr, timeout) {
+        println!(
+            "⚠️  {} SMB1 is still enabled: insecure (EternalBlue/WannaCry) and disabled by default on current clients",
+            colorize("[WARN]", "yellow")
+        );
+    }
+    println!();
+}
+
+/// Frames an SMB message for direct TCP transport (RFC 1002 session message with a 24-bit length).
+fn frame(message: &[u8]) -> Vec<u8> {
+    let len = message.len() as u32;
+    let mut framed = vec![0, (len >> 16) as u8, (len >> 8) as u8, len as u8];
+    framed.extend_from_slice(message);
+    framed
+}
+
+/// Reads one framed SMB message.
+fn read_frame(stream: &mut TcpStream) -> io::Result<Vec<u8>> {
+    let mut header = [0u8; 4];
+    stream.read_exact(&mut header)?;
+    let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    let mut message = vec![0u8; len];
+    stream.read_exact(&mut message)?;
+    Ok(message)
+}
+
+/// Builds an SMB2 NEGOTIATE request offering every dialect up to 3.1.1, with the pre-auth
+/// integrity and encryption contexts 3.1.1 requires.
+fn negotiate_request() -> Vec<u8> {
+    let mut msg = Vec::with_capacity(200);
+    // 64-byte SMB2 header: protocol id, structure size, credit charge, status, command 0 (NEGOTIATE),
+    // credits requested, flags, next command, message id, reserved, tree id, session id, signature.
+    msg.extend_from_slice(b"\xfeSMB");
+    msg.extend_from_slice(&64u16.to_le_bytes());
+    msg.extend_from_slice(&[0; 2 + 4 + 2]);
+    msg.extend_from_slice(&1u16.to_le_bytes());
+    msg.extend_from_slice(&[0; 4 + 4 + 8 + 4 + 4 + 8 + 16]);
+
+    let context_offset = 64 + 36 + DIALECTS.len() * 2;
+    let context_offset = (context_offset + 7) & !7;
+    msg.extend_from_slice(&36u16.to_le_bytes());
+    msg.extend_from_slice(&(DIALECTS.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&1u16.to_le_bytes()); // signing enabled
+    msg.extend_from_slice(&[0; 2 + 4]);
+    msg.extend_from_slice(&random_u64().to_le_bytes()); // client GUID
+    msg.extend_from_slice(&random_u64().to_le_bytes());
+    msg.extend_from_slice(&(context_offset as u32).to_le_bytes());
+    msg.extend_from_slice(&2u16.to_le_bytes());
+    msg.extend_from_slice(&[0; 2]);
+    for dialect in DIALECTS {
+        msg.extend_from_slice(&dialect.to_le_bytes());
+    }
+    msg.resize(context_offset, 0);
+
+    // SMB2_PREAUTH_INTEGRITY_CAPABILITIES: one hash (SHA-512) and a 32-byte salt.
+    let mut preauth = vec![1, 0, 32, 0, 1, 0];
+    for _ in 0..4 {
+        preauth.extend_from_slice(&random_u64().to_le_bytes());
+    }
+    push_context(&mut msg, 1, &preauth);
+    msg.resize((msg.len() + 7) & !7, 0);
+    // SMB2_ENCRYPTION_CAPABILITIES: AES-128-GCM, then AES-128-CCM.
+    push_context(&mut msg, 2, &[2, 0, 2, 0, 1, 0]);
+    msg
+}
+
+fn push_context(msg: &mut Vec<u8>, kind: u16, data: &[u8]) {
+    msg.extend_from_slice(&kind.to_le_bytes());
+    msg.extend_from_slice(&(data.len() as u16).to_le_bytes());
+    msg.extend_from_slice(&[0; 4]);
+    msg.extend_from_slice(data);
+}
+
+fn le16(buf: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_le_bytes([*buf.get(at)?, *buf.get(at + 1)?]))
+}
+
+fn le32(buf: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_le_bytes([*buf.get(at)?, *buf.get(at + 1)?, *buf.get(at + 2)?, *buf.get(at + 3)?]))
+}
+
+/// Sends an SMB2 NEGOTIATE and parses the dialect, signing and encryption from the reply.
+fn smb2_negotiate(addr: SocketAddr, timeout: Duration) -> io::Result<Negotiation> {
+    let mut stream = scheduler::connect(addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    stream.write_all(&frame(&negotiate_request()))?;
+    let reply = read_frame(&mut stream)?;
+    let invalid = |what: &str| io::Error::new(io::ErrorKind::InvalidData, what.to_string());
+
+    if reply.get(..4) != Some(b"\xfeSMB") {
+        return Err(invalid("the reply is not SMB2"));
+    }
+    let status = le32(&reply, 8).ok_or_else(|| invalid("truncated header"))?;
+    if status != 0 {
+        return Err(invalid(&format!("server returned NTSTATUS 0x{:08x}", status)));
+    }
+    let body = 64;
+    let security_mode = le16(&reply, body + 2).ok_or_else(|| invalid("truncated reply"))?;
+    let dialect = le16(&reply, body + 4).ok_or_else(|| invalid("truncated reply"))?;
+    let capabilities = le32(&reply, body + 24).unwrap_or(0);
+
+    let encryption = if dialect == 0x0311 {
+        negotiated_cipher(&reply, le16(&reply, body + 6).unwrap_or(0), le32(&reply, body + 60).unwrap_or(0) as usize)
+    } else if dialect >= 0x0300 && capabilities & 0x40 != 0 {
+        Some("AES-128-CCM".to_string())
+    } else {
+        None
+    };
+    Ok(Negotiation { dialect, signing_required: security_mode & 0x02 != 0, encryption })
+}
+
+/// Finds the cipher in a 3.1.1 reply's SMB2_ENCRYPTION_CAPABILITIES context.
+fn negotiated_cipher(reply: &[u8], count: u16, offset: usize) -> Option<String> {
+    let mut at = offset;
+    for _ in 0..count {
+        let kind = le16(reply, at)?;
+        let len = le16(reply, at + 2)? as usize;
+        if kind == 2 {
+            let cipher = le16(reply, at + 10)?;
+            return Some(match cipher {
+                1 => "AES-128-CCM",
+                2 => "AES-128-GCM",
+                3 => "AES-256-CCM",
+                4 => "AES-256-GCM",
+                _ => return None,
+            }.to_string());
+        }
+        at = (at + 8 + len + 7) & !7;
+    }
+    None
+}
+
+/// Display name of an SMB2 dialect revision.
+fn dialect_name(dialect: u16) -> String {
+    match dialect {
+        0x0202 => "SMB 2.0.2".to_string(),
+        0x0210 => "SMB 2.1".to_string(),
+        0x0300 => "SMB 3.0".to_string(),
+        0x0302 => "SMB 3.0.2".to_string(),
+        0x0311 => "SMB 3.1.1".to_string(),
+        other => format!("SMB dialect 0x{:04x}", other),
+    }
+}
+
+/// Offers only the SMB1 "NT LM 0.12" dialect and reports whether the server accepts it.
+fn smb1_enabled(addr: SocketAddr, timeout: Duration) -> bool {
+    let mut msg = Vec::new();
+    msg.extend_from_slice(b"\xffSMB");
+    msg.push(0x72); // SMB_COM_NEGOTIATE
+    msg.extend_from_slice(&[0; 4]); // status
+    msg.push(0x18); // flags: canonical paths, case-insensitive
+    msg.extend_from_slice(&0xc001u16.to_le_bytes()); // flags2: unicode, NT status, long names
+    msg.extend_from_slice(&[0; 2 + 8 + 2 + 2 + 2 + 2 + 2]);
+    msg.push(0); // word count
+    let dialect = b"\x02NT LM 0.12\x00";
+    msg.extend_from_slice(&(dialect.len() as u16).to_le_bytes());
+    msg.extend_from_slice(dialect);
+
+    let reply = scheduler::connect(addr, timeout).and_then(|mut stream| {
+        stream.set_read_timeout(Some(timeout))?;
+        stream.write_all(&frame(&msg))?;
+        read_frame(&mut stream)
+    });
+    // Servers with SMB1 disabled reset the connection or answer with an error status.
+    matches!(reply, Ok(ref r) if r.get(..4) == Some(b"\xffSMB") && r.get(4) == Some(&0x72) && le32(r, 5) == Some(0))
+}
+
+/// Tests TCP 2049, asks NFS which versions it serves and checks the NFSv3 side services.
+fn check_nfs(addr: SocketAddr, timeout: Duration, problems: &mut Vec<String>) {
+    println!("🔹 {}", colorize("NFS", "blue"));
+    let nfs_open = check_port("NFS", addr, timeout);
+    if nfs_open {
+        let versions = nfs_versions(addr, timeout);
+        if versions.is_empty() {
+            println!("⚠️  {} Port 2049 is open but did not answer an NFS NULL call", colorize("[WARN]", "yellow"));
+        } else {
+            let names: Vec<String> = versions.iter().map(|v| format!("v{}", v)).collect();
+            println!("✅ {} NFS answers for {}", colorize("[SUCCESS]", "green"), names.join(", "));
+        }
+    } else {
+        problems.push("NFS port 2049 is unreachable".to_string());
+    }
+
+    let rpcbind = SocketAddr::new(addr.ip(), RPCBIND_PORT);
+    let mappings = match rpc_dump(rpcbind, timeout) {
+        Ok(mappings) => mappings,
+        Err(_) => {
+            println!("   rpcbind (port {}) does not answer: fine for NFSv4-only servers, but NFSv3 mounts need it", RPCBIND_PORT);
+            println!();
+            return;
+        }
+    };
+    println!("✅ {} rpcbind answers; registered over TCP:", colorize("[SUCCESS]", "green"));
+    for (program, name) in [(RPC_PROGRAM_NFS, "nfs"), (RPC_PROGRAM_MOUNT, "mountd"), (RPC_PROGRAM_NLM, "nlockmgr")] {
+        let entries: Vec<&Mapping> = mappings.iter().filter(|m| m.program == program && m.protocol == IPPROTO_TCP).collect();
+        if entries.is_empty() {
+            println!("   {:<9} not registered", name);
+            continue;
+        }
+        let versions: Vec<String> = entries.iter().map(|m| format!("v{}", m.version)).collect();
+        let port = entries[0].port;
+        println!("   {:<9} {} on port {}", name, versions.join(","), port);
+        // NFSv3 needs mountd and the lock manager too; firewalls often only open 2049 and 111.
+        if program != RPC_PROGRAM_NFS && !matches!(tcping::connect(SocketAddr::new(addr.ip(), port), timeout), Attempt::Connected(_)) {
+            let effect = if program == RPC_PROGRAM_NLM { "NFSv3 mounts work but file locking hangs" } else { "NFSv3 mounts fail while NFSv4 works" };
+            println!(
+                "❌ {} {} port {} is blocked: {}; pin it to a fixed port and open that",
+                colorize("[ERROR]", "red"),
+                name,
+                port,
+                effect
+            );
+            problems.push(format!("{} port {} is filtered", name, port));
+        }
+    }
+    println!();
+}
+
+/// Sends NFS NULL calls and returns the versions the server supports.
+fn nfs_versions(addr: SocketAddr, timeout: Duration) -> Vec<u32> {
+    let mut versions = Vec::new();
+    for version in [3, 4] {
+        match rpc_call(addr, RPC_PROGRAM_NFS, version, 0, &[], timeout) {
+            Ok(RpcReply::Success(_)) => versions.push(version),
+            Ok(RpcReply::VersionMismatch(low, high)) => {
+                // The mismatch reply lists the full supported range, so one call is enough.
+                return (low..=high).collect();
+            }
+            _ => {}
+        }
+    }
+    versions
+}
+
+/// Lists rpcbind registrations with PMAPPROC_DUMP.
+fn rpc_dump(addr: SocketAddr, timeout: Duration) -> io::Result<Vec<Mapping>> {
+    let body = match rpc_call(addr, RPC_PROGRAM_PORTMAP, 2, PMAPPROC_DUMP, &[], timeout)? {
+        RpcReply::Success(body) => body,
+        _ => return Err(io::Error::other("rpcbind rejected the call")),
+    };
+    let word = |at: usize| body.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let mut mappings = Vec::new();
+    let mut at = 0;
+    // XDR optional-data list: a 1 before each entry, 0 at the end.
+    while word(at) == Some(1) {
+        match (word(at + 4), word(at + 8), word(at + 12), word(at + 16)) {
+            (Some(program), Some(version), Some(protocol), Some(port)) => {
+                mappings.push(Mapping { program, version, protocol, port: port as u16 })
+            }
+            _ => break,
+        }
+        at += 20;
+    }
+    Ok(mappings)
+}
+
+/// Makes one ONC RPC call over TCP with AUTH_NONE and returns the decoded reply.
+fn rpc_call(addr: SocketAddr, program: u32, version: u32, procedure: u32, args: &[u8], timeout: Duration) -> io::Result<RpcReply> {
+    let xid = random_u64() as u32;
+    let mut call = Vec::new();
+    // xid, CALL, RPC version 2, program, version, procedure, null credentials, null verifier.
+    for word in [xid, 0, 2, program, version, procedure, 0, 0, 0, 0] {
+        call.extend_from_slice(&word.to_be_bytes());
+    }
+    call.extend_from_slice(args);
+
+    let mut stream = scheduler::connect(addr, timeout)?;
+    stream.set_read_timeout(Some(timeout))?;
+    // Record marking: the high bit flags the last fragment.
+    stream.write_all(&(0x8000_0000 | call.len() as u32).to_be_bytes())?;
+    stream.write_all(&call)?;
+
+    let mut reply = Vec::new();
+    loop {
+        let mut marker = [0u8; 4];
+        stream.read_exact(&mut marker)?;
+        let marker = u32::from_be_bytes(marker);
+        let mut fragment = vec![0u8; (marker & 0x7fff_ffff) as usize];
+        stream.read_exact(&mut fragment)?;
+        reply.extend_from_slice(&fragment);
+        if marker & 0x8000_0000 != 0 {
+            break;
+        }
+    }
+
+    let word = |at: usize| reply.get(at..at + 4).map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]));
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "malformed RPC reply");
+    if word(0) != Some(xid) || word(4) != Some(1) {
+        return Err(invalid());
+    }
+    if word(8) != Some(0) {
+        return Ok(RpcReply::Unavailable); // MSG_DENIED
+    }
+    let verifier_len = word(16).ok_or_else(invalid)? as usize;
+    let at = 20 + verifier_len.div_ceil(4) * 4;
+    match word(at).ok_or_else(invalid)? {
+        0 => Ok(RpcReply::Success(reply[at + 4..].to_vec())),
+        2 => Ok(RpcReply::VersionMismatch(word(at + 4).unwrap_or(0), word(at + 8).unwrap_or(0))),
+        _ => Ok(RpcReply::Unavailable),
+    }
+}
@@ -0,0 +1,401 @@
+use std::env;
+use std::io::{self, Read, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use crate::interrupt;
+use crate::resolver;
+use crate::scheduler::{self, Probe};
+
+/// Redirects followed before a site is reported as failing.
+const MAX_REDIRECTS: usize = 10;
+
+/// Response bodies are kept up to this size for content checks.
+const MAX_BODY: usize = 1 << 20;
+
+/// One response in a redirect chain.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hop {
+    pub version: String,
+    pub status: u16,
+    pub location: Option<String>,
+}
+
+/// What was negotiated when fetching a URL, following redirects.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SiteReport {
+    pub url: String,
+    pub hops: Vec<Hop>,
+    pub encoding: Option<String>,
+    pub h3_advertised: bool,
+    pub time_total: f64,
+    /// Whether the final body contained the expected text, when one was given.
+    pub content_match: Option<bool>,
+}
+
+impl SiteReport {
+    /// The final response after all redirects.
+    pub fn final_hop(&self) -> Option<&Hop> {
+        self.hops.last()
+    }
+
+    /// Protocol label of the final response, e.g. `HTTP/2`.
+    pub fn protocol(&self) -> String {
+        self.final_hop().map_or("-".to_string(), |h| format!("HTTP/{}", h.version))
+    }
+
+    /// Renders the redirect chain as `301 → 302 → 200 https://final/`.
+    pub fn redirect_chain(&self) -> String {
+        let codes: Vec<String> = self.hops.iter().map(|h| h.status.to_string()).collect();
+        match self.hops.iter().rev().nth(1).and_then(|h| h.location.as_ref()) {
+            Some(last) => format!("{} {}", codes.join(" → "), last),
+            None => codes.join(" → "),
+        }
+    }
+}
+
+/// Returns the proxy the environment configures for `url`, honouring `NO_PROXY`.
+pub fn proxy_from_env(url: &str) -> Option<String> {
+    let vars: &[&str] = if url.starts_with("https://") {
+        &["HTTPS_PROXY", "https_proxy", "ALL_PROXY", "all_proxy"]
+    } else {
+        &["HTTP_PROXY", "http_proxy", "ALL_PROXY", "all_proxy"]
+    };
+    let proxy = vars.iter().filter_map(|v| env::var(v).ok()).find(|v| !v.is_empty())?;
+    if bypasses_proxy(&host_of(url)) { None } else { Some(proxy) }
+}
+
+/// Extracts the host name from a URL, dropping scheme, credentials, port and path.
+pub fn host_of(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split('/').next().unwrap_or(rest);
+    let host_port = authority.rsplit('@').next().unwrap_or(authority);
+    host_port.split(':').next().unwrap_or(host_port).to_ascii_lowercase()
+}
+
+fn bypasses_proxy(host: &str) -> bool {
+    let no_proxy = env::var("NO_PROXY").or_else(|_| env::var("no_proxy")).unwrap_or_default();
+    no_proxy.split(',').map(|e| e.trim().trim_start_matches('.').to_ascii_lowercase()).any(|entry| {
+        entry == "*" || (!entry.is_empty() && (host == entry || host.ends_with(&format!(".{}", entry))))
+    })
+}
+
+/// Builds a curl command for `url` that goes through the environment's proxy, if any.
+/// The proxy is passed explicitly because curl ignores an upper-case `HTTP_PROXY`. Without a
+/// proxy the host is pinned to the address the shared resolver found. The transfer is scheduled
+/// as an `http` probe of the URL's host.
+pub fn curl(url: &str) -> Probe {
+    let mut command = Command::new("curl");
+    match proxy_from_env(url) {
+        Some(proxy) => {
+            command.args(["--proxy", &proxy]);
+        }
+        None => {
+            if let Some(pin) = pinned_address(url) {
+                command.args(["--resolve", &pin]);
+            }
+        }
+    }
+    command.arg(url);
+    let (host, port) = target_of(url);
+    scheduler::command("http", &host, Some(port), command)
+}
+
+/// The host and port a URL connects to, with the scheme's default port.
+pub fn target_of(url: &str) -> (String, u16) {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    let default = if url.starts_with("https://") { 443 } else { 80 };
+    match split_host_port(authority, default) {
+        Ok((host, port)) => (host.to_string(), port),
+        Err(_) => (authority.to_string(), default),
+    }
+}
+
+/// `host:port:address` for curl's `--resolve`, when the URL names a host that resolves.
+fn pinned_address(url: &str) -> Option<String> {
+    let host = host_of(url);
+    if host.parse::<IpAddr>().is_ok() || host.starts_with('[') {
+        return None;
+    }
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or(rest);
+    let port = authority.rsplit_once(':').and_then(|(_, p)| p.parse::<u16>().ok()).unwrap_or(if url.starts_with("https://") { 443 } else { 80 });
+    match resolver::resolve_one(&host).ok()? {
+        IpAddr::V6(v6) => Some(format!("{}:{}:[{}]", host, port, v6)),
+        ip => Some(format!("{}:{}:{}", host, port, ip)),
+    }
+}
+
+/// Times a single GET of `url` in milliseconds, or `None` if it failed.
+pub fn time_request(url: &str) -> Option<f64> {
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let output = curl(url)
+        .args(["-s", "-o", null, "--max-time", "15", "-w", "%{time_total}"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    String::from_utf8_lossy(&output.stdout).trim().parse::<f64>().ok().map(|secs| secs * 1000.0)
+}
+
+/// Fetches `url`, following redirects, and records the protocol and status of each hop, the
+/// total time, and whether the final body contains `expect`. Returns the error text when a hop fails.
+///
+/// This blocks until the chain finishes or `timeout` passes; callers wanting concurrency run it
+/// on a thread of its own. Only plain-HTTP hops are fetched in-process: HTTPS hops and proxied
+/// URLs are still handed to curl, one process per hop, because there is no TLS stack in-tree.
+pub fn probe(url: &str, expect: Option<&str>, timeout: Duration) -> Result<SiteReport, String> {
+    let start = Instant::now();
+    let deadline = start + timeout;
+    let mut report = SiteReport {
+        url: url.to_string(),
+        hops: Vec::new(),
+        encoding: None,
+        h3_advertised: false,
+        time_total: 0.0,
+        content_match: None,
+    };
+    let mut current = url.to_string();
+    loop {
+        let response = fetch(&current, deadline)?;
+        let location = match response.status {
+            300..=399 => response.header("location").map(|l| resolve(&current, l)),
+            _ => None,
+        };
+        report.hops.push(Hop { version: response.version.clone(), status: response.status, location: location.clone() });
+        if response.header("alt-svc").is_some_and(|v| v.contains("h3")) {
+            report.h3_advertised = true;
+        }
+        // Only the final response's encoding matters.
+        report.encoding = response.header("content-encoding").map(|e| e.to_string());
+        match location {
+            Some(_) if report.hops.len() > MAX_REDIRECTS => return Err(format!("more than {} redirects", MAX_REDIRECTS)),
+            Some(next) => current = next,
+            None => {
+                report.time_total = start.elapsed().as_secs_f64();
+                report.content_match = expect.map(|text| String::from_utf8_lossy(&response.body).contains(text));
+                return Ok(report);
+            }
+        }
+    }
+}
+
+/// One HTTP response without redirects followed; the body is cut at `MAX_BODY`.
+struct Response {
+    version: String,
+    status: u16,
+    headers: Vec<(String, String)>,
+    body: Vec<u8>,
+}
+
+impl Response {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers.iter().find(|(n, _)| n.eq_ignore_ascii_case(name)).map(|(_, v)| v.as_str())
+    }
+}
+
+/// Fetches a single URL: natively for plain HTTP, and through curl for HTTPS (there is no TLS
+/// stack in-tree) or when a proxy is configured. Both paths block the calling thread.
+fn fetch(url: &str, deadline: Instant) -> Result<Response, String> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err("timed out".to_string());
+    }
+    if proxy_from_env(url).is_some() {
+        return fetch_curl(url, remaining);
+    }
+    // Report a name that does not resolve as such, not as a failed connection.
+    resolver::resolve(&host_of(url)).map_err(|e| e.to_string())?;
+    match url.strip_prefix("http://") {
+        Some(rest) => fetch_native(rest, deadline),
+        None => fetch_curl(url, remaining),
+    }
+}
+
+/// Sends a `GET` over a plain TCP connection and reads the response until it is complete.
+fn fetch_native(rest: &str, deadline: Instant) -> Result<Response, String> {
+    let (authority, path) = match rest.find(['/', '?', '#']) {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let path = path.split('#').next().unwrap_or("/");
+    let path = if path.starts_with('?') { format!("/{}", path) } else { path.to_string() };
+    let authority = authority.rsplit('@').next().unwrap_or(authority);
+    let (host, port) = split_host_port(authority, 80)?;
+
+    let addrs: Vec<SocketAddr> = resolver::resolve(host).map_err(|e| e.to_string())?.into_iter().map(|ip| SocketAddr::new(ip, port)).collect();
+    let mut error = format!("no addresses for {}", host);
+    let mut stream = None;
+    for addr in addrs {
+        match scheduler::connect(addr, deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1))) {
+            Ok(connected) => {
+                stream = Some(connected);
+                break;
+            }
+            Err(e) => error = format!("connect to {}: {}", addr, e),
+        }
+    }
+    let mut stream = stream.ok_or(error)?;
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: netdiag/{}\r\nAccept: */*\r\nAccept-Encoding: identity\r\nConnection: close\r\n\r\n",
+        path,
+        authority,
+        env!("CARGO_PKG_VERSION")
+    );
+    stream.set_write_timeout(Some(deadline.saturating_duration_since(Instant::now()).max(Duration::from_millis(1)))).map_err(|e| e.to_string())?;
+    stream.write_all(request.as_bytes()).map_err(|e| e.to_string())?;
+
+    let mut raw = Vec::new();
+    let mut buf = [0u8; 16384];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() || interrupt::interrupted() {
+            return parse_response(&raw, true).ok_or_else(|| "timed out".to_string());
+        }
+        stream.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        match stream.read(&mut buf) {
+            Ok(0) => return parse_response(&raw, true).ok_or_else(|| "malformed HTTP response".to_string()),
+            Ok(n) => {
+                raw.extend_from_slice(&buf[..n]);
+                if let Some(response) = parse_response(&raw, false) {
+                    return Ok(response);
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => {}
+            Err(e) if raw.is_empty() => return Err(e.to_string()),
+            Err(_) => return parse_response(&raw, true).ok_or_else(|| "timed out".to_string()),
+        }
+    }
+}
+
+/// Splits `host:port` or `[v6]:port`, defaulting to port `default`.
+fn split_host_port(authority: &str, default: u16) -> Result<(&str, u16), String> {
+    let (host, port) = match authority.strip_prefix('[') {
+        Some(v6) => {
+            let (host, after) = v6.split_once(']').ok_or_else(|| format!("bad address {}", authority))?;
+            (host, after.strip_prefix(':'))
+        }
+        None => match authority.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (authority, None),
+        },
+    };
+    match port {
+        Some(port) => Ok((host, port.parse().map_err(|_| format!("bad port in {}", authority))?)),
+        None => Ok((host, default)),
+    }
+}
+
+/// Fetches a single URL with curl, keeping the headers and the decoded body.
+fn fetch_curl(url: &str, timeout: Duration) -> Result<Response, String> {
+    let max_time = format!("{:.0}", timeout.as_secs_f64().ceil().max(1.0));
+    let mut command = curl(url);
+    command.args(["-s", "--compressed", "--max-time", &max_time, "-D", "-"]);
+    let output = interrupt::output(&mut command).map_err(|e| match e.kind() {
+        io::ErrorKind::NotFound => "curl is not installed; it is needed for HTTPS and proxied sites".to_string(),
+        _ => e.to_string(),
+    })?;
+    if !output.status.success() {
+        let code = output.status.code().unwrap_or(-1);
+        return Err(format!("curl exited with status {}", code));
+    }
+
+    let mut raw: &[u8] = &output.stdout;
+    loop {
+        let (mut response, body_start) = parse_head(raw).ok_or_else(|| "malformed HTTP response".to_string())?;
+        // A proxy's reply to CONNECT is not part of the site's response.
+        let tunnel = response.status == 200 && raw.len() > body_start && raw[body_start..].starts_with(b"HTTP/");
+        if tunnel {
+            raw = &raw[body_start..];
+            continue;
+        }
+        // curl has already removed the transfer and content encodings, so the framing headers no longer apply.
+        response.body = raw[body_start..raw.len().min(body_start + MAX_BODY)].to_vec();
+        return Ok(response);
+    }
+}
+
+/// Parses the status line and headers, returning the response and where its body starts.
+fn parse_head(raw: &[u8]) -> Option<(Response, usize)> {
+    let end = raw.windows(4).position(|w| w == b"\r\n\r\n")?;
+    let head = String::from_utf8_lossy(&raw[..end]);
+    let mut lines = head.lines();
+    let mut status_line = lines.next()?.strip_prefix("HTTP/")?.split_whitespace();
+    let version = status_line.next()?.to_string();
+    let status = status_line.next()?.parse().ok()?;
+    let headers = lines.filter_map(|l| l.split_once(':')).map(|(n, v)| (n.trim().to_string(), v.trim().to_string())).collect();
+    Some((Response { version, status, headers, body: Vec::new() }, end + 4))
+}
+
+/// Parses a raw HTTP/1.x response. Returns `None` while more bytes are needed, unless `eof` says
+/// none are coming, in which case whatever body arrived is used.
+fn parse_response(raw: &[u8], eof: bool) -> Option<Response> {
+    let (mut response, body_start) = parse_head(raw)?;
+    let body = &raw[body_start..];
+    let chunked = response.header("transfer-encoding").is_some_and(|t| t.to_ascii_lowercase().contains("chunked"));
+    let length = response.header("content-length").and_then(|l| l.parse::<usize>().ok());
+    let (body, complete) = if (100..200).contains(&response.status) || response.status == 204 || response.status == 304 {
+        (Vec::new(), true)
+    } else if chunked {
+        dechunk(body)
+    } else if let Some(length) = length {
+        (body[..length.min(body.len())].to_vec(), body.len() >= length)
+    } else {
+        (body.to_vec(), false)
+    };
+    if !complete && !eof && body.len() < MAX_BODY {
+        return None;
+    }
+    response.body = body;
+    response.body.truncate(MAX_BODY);
+    Some(response)
+}
+
+/// Decodes a chunked body, returning what was decoded and whether the last chunk arrived.
+fn dechunk(mut data: &[u8]) -> (Vec<u8>, bool) {
+    let mut out = Vec::new();
+    loop {
+        let line_end = match data.windows(2).position(|w| w == b"\r\n") {
+            Some(i) => i,
+            None => return (out, false),
+        };
+        let size_field = String::from_utf8_lossy(&data[..line_end]);
+        let size = match usize::from_str_radix(size_field.split(';').next().unwrap_or("").trim(), 16) {
+            Ok(size) => size,
+            Err(_) => return (out, false),
+        };
+        if size == 0 {
+            return (out, true);
+        }
+        let start = line_end + 2;
+        if data.len() < start + size + 2 {
+            out.extend_from_slice(&data[start..data.len().min(start + size)]);
+            return (out, false);
+        }
+        out.extend_from_slice(&data[start..start + size]);
+        data = &data[start + size + 2..];
+    }
+}
+
+/// Resolves a `Location` header against the URL it came from.
+fn resolve(base: &str, location: &str) -> String {
+    if location.contains("://") {
+        return location.to_string();
+    }
+    let (scheme, rest) = base.split_once("://").unwrap_or(("http", base));
+    if let Some(network_path) = location.strip_prefix("//") {
+        return format!("{}://{}", scheme, network_path);
+    }
+    let authority_end = rest.find(['/', '?', '#']).unwrap_or(rest.len());
+    let origin = format!("{}://{}", scheme, &rest[..authority_end]);
+    if location.starts_with('/') {
+        return format!("{}{}", origin, location);
+    }
+    let path = rest[authority_end..].split(['?', '#']).next().unwrap_or("");
+    let directory = &path[..path.rfind('/').map_or(0, |i| i + 1)];
+    format!("{}{}{}", origin, if directory.is_empty() { "/" } else { directory }, location)
+}
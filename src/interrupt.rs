@@ -0,0 +1,179 @@
+use std::io;
+use std::process::{self, Child, Command, Output, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[cfg(unix)]
+use libc;
+
+use crate::colorize;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// PIDs of running child processes (tcpdump, ping, traceroute) to stop on interrupt.
+static CHILDREN: Mutex<Vec<u32>> = Mutex::new(Vec::new());
+
+/// Cleanup in progress (archiving a bundle, exporting telemetry); the grace period waits for it.
+static CLEANUPS: Mutex<usize> = Mutex::new(0);
+
+/// How long the current check gets to print its partial summary before the process exits.
+const GRACE: Duration = Duration::from_secs(5);
+
+/// Installs SIGINT/SIGTERM handlers. On the first signal child processes are stopped and the
+/// running check finishes at its next checkpoint with what it has; a second signal exits
+/// immediately. Once the grace period runs out the process exits too, but only after any
+/// cleanup holding a `Cleanup` guard is done.
+pub fn install() {
+    #[cfg(unix)]
+    unsafe {
+        libc::signal(libc::SIGINT, on_signal as *const () as libc::sighandler_t);
+        libc::signal(libc::SIGTERM, on_signal as *const () as libc::sighandler_t);
+    }
+    thread::spawn(|| {
+        while !interrupted() {
+            thread::sleep(Duration::from_millis(50));
+        }
+        eprintln!("\n🛑 {} Interrupted; stopping and summarizing what completed (Ctrl-C again to quit now)", colorize("[INTERRUPTED]", "yellow"));
+        stop_children();
+        thread::sleep(GRACE);
+        loop {
+            // Exiting with the lock held means no cleanup can start after the check.
+            let running = CLEANUPS.lock().unwrap_or_else(|e| e.into_inner());
+            if *running == 0 {
+                process::exit(130);
+            }
+            drop(running);
+            thread::sleep(Duration::from_millis(50));
+        }
+    });
+}
+
+/// Held while finishing work whose result would be lost if the process exited half way.
+pub struct Cleanup(());
+
+impl Drop for Cleanup {
+    fn drop(&mut self) {
+        *CLEANUPS.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+    }
+}
+
+/// Keeps the interrupt grace period from exiting the process until the guard is dropped.
+pub fn cleanup() -> Cleanup {
+    *CLEANUPS.lock().unwrap_or_else(|e| e.into_inner()) += 1;
+    Cleanup(())
+}
+
+#[cfg(unix)]
+extern "C" fn on_signal(_signal: libc::c_int) {
+    if INTERRUPTED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(130) };
+    }
+}
+
+/// Whether a SIGINT or SIGTERM has been received; long-running loops poll this.
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Sleeps for `duration` in short steps; returns `false` early if interrupted meanwhile.
+pub fn sleep(duration: Duration) -> bool {
+    let until = Instant::now() + duration;
+    while !interrupted() {
+        let left = until.saturating_duration_since(Instant::now());
+        if left.is_zero() {
+            return true;
+        }
+        thread::sleep(left.min(Duration::from_millis(100)));
+    }
+    false
+}
+
+/// Registers a child process to be stopped on interrupt.
+pub fn track(child: &Child) {
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.push(child.id());
+    }
+}
+
+/// Forgets a child process once it has been reaped.
+pub fn untrack(child: &Child) {
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.retain(|pid| *pid != child.id());
+    }
+}
+
+/// Like `Command::output`, but the child is stopped on interrupt, and nothing new is started
+/// once interrupted.
+pub fn output(command: &mut Command) -> io::Result<Output> {
+    if interrupted() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+    }
+    let child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    track(&child);
+    let pid = child.id();
+    let output = child.wait_with_output();
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.retain(|p| *p != pid);
+    }
+    output
+}
+
+/// Like [`output`], but the child is killed if it has not finished within `timeout`, so a peer
+/// that never answers cannot hang the run.
+pub fn output_timeout(command: &mut Command, timeout: Duration) -> io::Result<Output> {
+    if interrupted() {
+        return Err(io::Error::new(io::ErrorKind::Interrupted, "interrupted"));
+    }
+    let child = command.stdout(Stdio::piped()).stderr(Stdio::piped()).spawn()?;
+    track(&child);
+    let pid = child.id();
+    let done = Arc::new(AtomicBool::new(false));
+    let timed_out = Arc::new(AtomicBool::new(false));
+    let watchdog = {
+        let (done, timed_out) = (done.clone(), timed_out.clone());
+        thread::spawn(move || {
+            let until = Instant::now() + timeout;
+            while !done.load(Ordering::SeqCst) && Instant::now() < until {
+                thread::sleep(Duration::from_millis(50));
+            }
+            if !done.load(Ordering::SeqCst) {
+                timed_out.store(true, Ordering::SeqCst);
+                kill(pid);
+            }
+        })
+    };
+    let output = child.wait_with_output();
+    done.store(true, Ordering::SeqCst);
+    let _ = watchdog.join();
+    if let Ok(mut children) = CHILDREN.lock() {
+        children.retain(|p| *p != pid);
+    }
+    if timed_out.load(Ordering::SeqCst) {
+        return Err(io::Error::new(io::ErrorKind::TimedOut, format!("no answer within {}s", timeout.as_secs())));
+    }
+    output
+}
+
+fn kill(pid: u32) {
+    #[cfg(unix)]
+    unsafe {
+        libc::kill(pid as libc::pid_t, libc::SIGKILL);
+    }
+    #[cfg(not(unix))]
+    let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/F"]).output();
+}
+
+/// Sends SIGINT to every tracked child so tools like ping and tcpdump print and flush what they have.
+fn stop_children() {
+    let children = CHILDREN.lock().map(|c| c.clone()).unwrap_or_default();
+    for pid in children {
+        #[cfg(unix)]
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGINT);
+        }
+        #[cfg(not(unix))]
+        let _ = Command::new("taskkill").args(["/PID", &pid.to_string(), "/F"]).output();
+    }
+}
@@ -0,0 +1,252 @@
+use std::env;
+use std::fs;
+use std::net::{IpAddr, SocketAddr};
+use std::process::Command;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::dns;
+use crate::interrupt;
+use crate::netinfo;
+use crate::privileges;
+use crate::scheduler;
+use crate::tcping::{self, Attempt};
+
+/// Where the kubelet mounts the pod's service account, including its namespace.
+const SERVICE_ACCOUNT_NAMESPACE: &str = "/var/run/secrets/kubernetes.io/serviceaccount/namespace";
+
+/// Per-packet overhead of common CNI encapsulations, used to explain MTU problems.
+const OVERLAYS: &[(&str, u32)] = &[("VXLAN (Flannel, Cilium, Calico VXLAN)", 50), ("IP-in-IP (Calico)", 20), ("WireGuard", 80), ("Geneve (OVN)", 58)];
+
+/// Resolver settings the kubelet writes into the pod's resolv.conf.
+#[derive(Debug, Default)]
+struct ResolvConf {
+    nameservers: Vec<IpAddr>,
+    search: Vec<String>,
+    ndots: u32,
+}
+
+/// Returns the `k8s` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("k8s")
+        .about("Diagnoses pod networking from inside a pod: cluster DNS, service VIPs, NodePort egress and overlay MTU")
+        .arg(Arg::with_name("service").long("service").takes_value(true).multiple(true).number_of_values(1)
+            .help("Extra service to test as host:port, e.g. my-svc.my-ns:8080 (repeatable)"))
+        .arg(Arg::with_name("nodeport").long("nodeport").takes_value(true)
+            .help("NodePort to reach as NODE_IP:PORT, to test egress to nodes"))
+        .arg(Arg::with_name("peer").long("peer").takes_value(true)
+            .help("Pod IP on another node to probe the overlay MTU against (default: the default gateway)"))
+}
+
+/// Runs the `k8s` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let api_host = env::var("KUBERNETES_SERVICE_HOST").ok();
+    let namespace = fs::read_to_string(SERVICE_ACCOUNT_NAMESPACE).ok().map(|n| n.trim().to_string());
+    println!("\n☸️  {} Kubernetes pod network diagnostics\n", colorize("[INFO]", "blue"));
+    if api_host.is_none() {
+        println!(
+            "⚠️  {} KUBERNETES_SERVICE_HOST is not set: this does not look like a pod, so cluster checks will likely fail",
+            colorize("[WARN]", "yellow")
+        );
+    }
+    println!("   Namespace:   {}", namespace.as_deref().unwrap_or("unknown (no service account mounted)"));
+    let resolv = resolv_conf();
+    let cluster_domain = cluster_domain(&resolv.search);
+    println!("   Cluster DNS: {}", resolv.nameservers.iter().map(|s| s.to_string()).collect::<Vec<_>>().join(", "));
+    println!("   Domain:      {}\n", cluster_domain.as_deref().unwrap_or("unknown (no svc.* search domain)"));
+
+    let mut problems = Vec::new();
+    check_dns(&resolv, cluster_domain.as_deref(), api_host.as_deref(), &mut problems);
+
+    println!("🔹 {}", colorize("Service VIPs", "blue"));
+    if let Some(host) = &api_host {
+        let port = env::var("KUBERNETES_SERVICE_PORT").unwrap_or_else(|_| "443".to_string());
+        check_tcp("kubernetes API service", &format!("{}:{}", host, port), &mut problems);
+    }
+    if let Some(dns) = resolv.nameservers.first() {
+        check_tcp("cluster DNS service (TCP)", &SocketAddr::new(*dns, 53).to_string(), &mut problems);
+    }
+    for service in matches.values_of("service").into_iter().flatten() {
+        check_tcp(service, service, &mut problems);
+    }
+    println!();
+
+    println!("🔹 {}", colorize("Egress", "blue"));
+    match matches.value_of("nodeport") {
+        Some(target) => check_tcp("NodePort", target, &mut problems),
+        None => println!("   NodePort: skipped (pass --nodeport NODE_IP:PORT)"),
+    }
+    check_tcp("internet", "1.1.1.1:443", &mut problems);
+    println!();
+
+    let peer = matches.value_of("peer").map(|p| p.to_string()).or_else(netinfo::default_gateway);
+    check_mtu(peer.as_deref(), &mut problems);
+
+    print_summary(&problems);
+}
+
+/// Parses nameservers, search domains and `options ndots` from /etc/resolv.conf.
+fn resolv_conf() -> ResolvConf {
+    let mut conf = ResolvConf { ndots: 1, ..ResolvConf::default() };
+    for line in fs::read_to_string("/etc/resolv.conf").unwrap_or_default().lines() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("nameserver") => conf.nameservers.extend(words.next().and_then(|w| w.parse::<IpAddr>().ok())),
+            Some("search") => conf.search = words.map(|w| w.to_string()).collect(),
+            Some("options") => {
+                for option in words {
+                    if let Some(n) = option.strip_prefix("ndots:").and_then(|n| n.parse().ok()) {
+                        conf.ndots = n;
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    conf
+}
+
+/// Derives the cluster domain from the `svc.<domain>` search entry the kubelet adds.
+fn cluster_domain(search: &[String]) -> Option<String> {
+    search.iter().find_map(|d| d.strip_prefix("svc.")).map(|d| d.to_string())
+}
+
+/// Resolves the API service and an external name through cluster DNS, and explains ndots.
+fn check_dns(resolv: &ResolvConf, domain: Option<&str>, api_host: Option<&str>, problems: &mut Vec<String>) {
+    println!("🔹 {}", colorize("Cluster DNS (kube-dns / CoreDNS)", "blue"));
+    let server = match resolv.nameservers.first() {
+        Some(server) => *server,
+        None => {
+            println!("❌ {} resolv.conf lists no nameserver", colorize("[ERROR]", "red"));
+            problems.push("no cluster DNS server configured".to_string());
+            return;
+        }
+    };
+    let internal = format!("kubernetes.default.svc.{}", domain.unwrap_or("cluster.local"));
+    for (name, what) in [(internal.as_str(), "service record"), ("example.com", "upstream forwarding")] {
+        match dns::query(server, name, dns::TYPE_A, Duration::from_secs(3)) {
+            Ok(response) => {
+                let addrs = response.values(dns::TYPE_A);
+                if addrs.is_empty() {
+                    println!("❌ {} {} → {} ({})", colorize("[ERROR]", "red"), name, dns::rcode_name(response.rcode), what);
+                    problems.push(format!("CoreDNS cannot resolve {} ({})", name, what));
+                } else {
+                    println!("✅ {} {} → {} in {} ms", colorize("[SUCCESS]", "green"), name, addrs.join(", "), response.elapsed.as_millis());
+                    if name == internal && api_host.is_some_and(|h| !addrs.iter().any(|a| a == h)) {
+                        println!("⚠️  {} DNS disagrees with KUBERNETES_SERVICE_HOST={}", colorize("[WARN]", "yellow"), api_host.unwrap_or_default());
+                    }
+                }
+            }
+            Err(e) => {
+                println!("❌ {} {} did not answer for {}: {}", colorize("[ERROR]", "red"), server, name, e);
+                problems.push(format!("cluster DNS {} does not answer: check the CoreDNS pods and NetworkPolicies allowing UDP 53", server));
+            }
+        }
+    }
+    if resolv.ndots >= 5 && !resolv.search.is_empty() {
+        println!(
+            "   ndots:{}: a name like api.example.com is first tried with each of the {} search domains, costing {} failed lookups; add a trailing dot or lower ndots for external-heavy workloads",
+            resolv.ndots,
+            resolv.search.len(),
+            resolv.search.len()
+        );
+    }
+    println!();
+}
+
+/// Connects to `target` (host:port) and records a problem when nothing answers.
+fn check_tcp(label: &str, target: &str, problems: &mut Vec<String>) {
+    let addr = match tcping::resolve(target) {
+        Ok(addr) => addr,
+        Err(e) => {
+            println!("❌ {} {}: {}", colorize("[ERROR]", "red"), label, e);
+            problems.push(format!("{} does not resolve", label));
+            return;
+        }
+    };
+    match tcping::connect(addr, Duration::from_secs(3)) {
+        Attempt::Connected(ms) => println!("✅ {} {} ({}) connected in {:.1} ms", colorize("[SUCCESS]", "green"), label, addr, ms),
+        Attempt::Refused(_) => {
+            println!("⚠️  {} {} ({}) refused: no ready endpoints behind it, or the wrong port", colorize("[WARN]", "yellow"), label, addr);
+            problems.push(format!("{} refuses connections", label));
+        }
+        _ => {
+            println!(
+                "❌ {} {} ({}) timed out: kube-proxy rules missing, a NetworkPolicy dropping egress, or the node unreachable",
+                colorize("[ERROR]", "red"),
+                label,
+                addr
+            );
+            problems.push(format!("{} is unreachable", label));
+        }
+    }
+}
+
+/// Compares the pod's MTU with the largest packet that crosses the overlay unfragmented.
+fn check_mtu(peer: Option<&str>, problems: &mut Vec<String>) {
+    println!("🔹 {}", colorize("MTU across the CNI overlay", "blue"));
+    let interface = netinfo::route_interface("1.1.1.1").unwrap_or_else(|| "eth0".to_string());
+    let mtu = match netinfo::mtu(&interface) {
+        Some(mtu) => mtu,
+        None => {
+            println!("   Could not read the MTU of {}", interface);
+            return;
+        }
+    };
+    println!("   {} MTU: {}", interface, mtu);
+    let peer = match peer {
+        Some(peer) if cfg!(target_os = "linux") && privileges::icmp_allowed() => peer,
+        _ => {
+            println!("   Path MTU probe skipped: needs ping with ICMP allowed on Linux, and --peer or a default gateway\n");
+            return;
+        }
+    };
+    // ICMP payload that fills one frame of `mtu` bytes: minus 20 bytes IPv4 and 8 bytes ICMP header.
+    let full = mtu.saturating_sub(28);
+    if df_ping(peer, full) {
+        println!("✅ {} Full {}-byte packets reach {} unfragmented", colorize("[SUCCESS]", "green"), mtu, peer);
+    } else if !df_ping(peer, 548) {
+        println!("⚠️  {} {} does not answer ping, so the path MTU is unknown", colorize("[WARN]", "yellow"), peer);
+    } else {
+        let (mut low, mut high) = (548, full);
+        while high - low > 1 && !interrupt::interrupted() {
+            let mid = (low + high) / 2;
+            if df_ping(peer, mid) {
+                low = mid;
+            } else {
+                high = mid;
+            }
+        }
+        let path = low + 28;
+        let gap = mtu - path;
+        println!("❌ {} Only {}-byte packets reach {}, but the pod MTU is {}", colorize("[ERROR]", "red"), path, peer, mtu);
+        if let Some((overlay, _)) = OVERLAYS.iter().find(|(_, overhead)| gap.abs_diff(*overhead) <= 4) {
+            println!("   The {}-byte gap matches {} overhead: the CNI MTU was not lowered for the encapsulation", gap, overlay);
+        }
+        problems.push(format!("pod MTU {} exceeds the overlay path MTU {}: large transfers hang; set the CNI MTU to {}", mtu, path, path));
+    }
+    println!();
+}
+
+/// Sends one ping of `size` payload bytes with Don't Fragment set.
+fn df_ping(target: &str, size: u32) -> bool {
+    let size = size.to_string();
+    let mut ping = Command::new("ping");
+    ping.args(["-c", "1", "-W", "1", "-M", "do", "-s", &size, target]);
+    interrupt::output(&mut scheduler::command("icmp-echo(1)", target, None, ping)).is_ok_and(|o| o.status.success())
+}
+
+/// Prints the list of cluster networking problems found.
+fn print_summary(problems: &[String]) {
+    if problems.is_empty() {
+        println!("📊 {} Pod networking looks healthy\n", colorize("[SUMMARY]", "blue"));
+        return;
+    }
+    println!("📊 {} {} problem(s) found:", colorize("[SUMMARY]", "blue"), problems.len());
+    for problem in problems {
+        println!("   • {}", problem);
+    }
+    println!();
+}
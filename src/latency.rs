@@ -0,0 +1,89 @@
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::chart;
+use crate::colorize;
+use crate::http;
+use crate::netinfo;
+use crate::privileges;
+use crate::repeat;
+use crate::resolver;
+use crate::telemetry;
+
+/// Returns the `latency` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("latency")
+        .about("Samples ping or HTTP latency repeatedly and charts the distribution")
+        .arg(Arg::with_name("target").required(true).help("Host to ping, or URL with --http"))
+        .arg(Arg::with_name("count").long("count").short("c").takes_value(true).default_value("20")
+            .help("Number of samples"))
+        .arg(Arg::with_name("http").long("http")
+            .help("Time HTTP requests to the target URL instead of pinging"))
+}
+
+/// Runs the `latency` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let target = matches.value_of("target").unwrap_or_default();
+    let count = value_t!(matches, "count", u32).unwrap_or(20);
+
+    println!();
+    if matches.is_present("http") {
+        http_summary(target, count);
+    } else {
+        ping_summary(target, count);
+    }
+    println!();
+}
+
+/// Pings `host` and prints loss plus a latency sparkline and histogram.
+pub fn ping_summary(host: &str, count: u32) {
+    println!("🔹 {}", colorize(&format!("Pinging {} ({} probes)", host, count), "blue"));
+    if !privileges::icmp_allowed() {
+        println!("{}", privileges::hint("ICMP ping", &format!("timing TCP handshakes to port 443 instead; {}", privileges::icmp_remediation())));
+    }
+    if let Err(e) = resolver::resolve(host) {
+        println!("❌ {} {}", colorize("[DNS]", "red"), e);
+        return;
+    }
+    match netinfo::ping(host, count) {
+        Some(stats) if stats.received > 0 => {
+            println!(
+                "✅ {} {}/{} replies",
+                colorize("[SUCCESS]", "green"),
+                stats.received,
+                stats.transmitted
+            );
+            chart::print_distribution("RTT", &stats.samples);
+            repeat::record_target("ping avg RTT (ms)", host, stats.avg_ms);
+            repeat::record_target("ping loss (%)", host, 100.0 - stats.received as f64 * 100.0 / stats.transmitted.max(1) as f64);
+        }
+        Some(stats) => {
+            println!("❌ {} 0/{} replies from {}", colorize("[ERROR]", "red"), stats.transmitted, host);
+            repeat::record_target("ping loss (%)", host, 100.0);
+            telemetry::fail(&format!("no replies from {}", host));
+        }
+        None => {
+            println!("❌ {} Could not run ping against {}", colorize("[ERROR]", "red"), host);
+            telemetry::fail(&format!("could not run ping against {}", host));
+        }
+    }
+}
+
+/// Times `count` sequential HTTP requests and prints their distribution.
+pub fn http_summary(url: &str, count: u32) {
+    println!("🔹 {}", colorize(&format!("Timing {} HTTP requests to {}", count, url), "blue"));
+    let mut samples = Vec::new();
+    let mut failures = 0;
+    for _ in 0..count {
+        match http::time_request(url) {
+            Some(ms) => samples.push(ms),
+            None => failures += 1,
+        }
+    }
+    if failures > 0 {
+        println!("⚠️  {} {} of {} requests failed", colorize("[WARN]", "yellow"), failures, count);
+    }
+    chart::print_distribution("Total time", &samples);
+    if !samples.is_empty() {
+        repeat::record_target("HTTP avg time (ms)", url, chart::summarize(&samples).1);
+    }
+}
@@ -0,0 +1,219 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json;
+
+use crate::{colorize, data_dir};
+use crate::chart;
+use crate::clock;
+use crate::netinfo;
+use crate::repeat;
+
+/// One loss run as stored in the history file.
+#[derive(Debug, Serialize, Deserialize)]
+struct HistoryEntry {
+    timestamp: u64,
+    host: String,
+    sent: usize,
+    lost: usize,
+}
+
+/// Loss pattern derived from per-probe outcomes.
+#[derive(Debug)]
+pub struct LossAnalysis {
+    pub sent: usize,
+    pub lost: usize,
+    /// Burst length → number of bursts of that length.
+    pub bursts: BTreeMap<usize, usize>,
+    /// Probability that a probe is lost given the previous one was lost.
+    pub loss_after_loss: f64,
+    /// Average spacing in probes between burst starts, when bursts recur regularly.
+    pub period: Option<f64>,
+}
+
+impl LossAnalysis {
+    pub fn loss_rate(&self) -> f64 {
+        if self.sent == 0 { 0.0 } else { self.lost as f64 / self.sent as f64 }
+    }
+
+    /// Loss clusters when a loss makes the next loss much more likely than the base rate.
+    pub fn is_bursty(&self) -> bool {
+        let p = self.loss_rate();
+        self.lost >= 3 && self.loss_after_loss > (2.0 * p).max(p + 0.2)
+    }
+}
+
+/// Returns the `loss` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("loss")
+        .about("Pings for a longer run and analyzes whether packet loss is random, bursty or periodic")
+        .arg(Arg::with_name("host").default_value("8.8.8.8").help("Host to ping"))
+        .arg(Arg::with_name("count").long("count").short("c").takes_value(true).default_value("300")
+            .help("Number of probes (one per second)"))
+}
+
+/// Runs the `loss` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let host = matches.value_of("host").unwrap_or("8.8.8.8");
+    let count = value_t!(matches, "count", u32).unwrap_or(300);
+
+    println!("\n📉 {} Pinging {} {} times to analyze loss (about {} min)\n", colorize("[INFO]", "blue"), colorize(host, "cyan"), count, count.div_ceil(60));
+    let stats = match netinfo::ping(host, count) {
+        Some(stats) => stats,
+        None => {
+            println!("❌ {} Could not run ping against {}", colorize("[ERROR]", "red"), host);
+            return;
+        }
+    };
+
+    let analysis = analyze(&stats.outcomes);
+    record_history(host, &analysis);
+    repeat::record_target("ping loss (%)", host, analysis.loss_rate() * 100.0);
+    print_analysis(&analysis, &stats.outcomes);
+    print_time_of_day(host);
+    println!();
+}
+
+/// Computes burst lengths, conditional loss and periodicity from per-probe outcomes.
+pub fn analyze(outcomes: &[Option<f64>]) -> LossAnalysis {
+    let mut bursts = BTreeMap::new();
+    let mut burst_starts = Vec::new();
+    let mut run = 0;
+    for (i, outcome) in outcomes.iter().enumerate() {
+        if outcome.is_none() {
+            if run == 0 {
+                burst_starts.push(i);
+            }
+            run += 1;
+        } else if run > 0 {
+            *bursts.entry(run).or_insert(0) += 1;
+            run = 0;
+        }
+    }
+    if run > 0 {
+        *bursts.entry(run).or_insert(0) += 1;
+    }
+
+    let lost = outcomes.iter().filter(|o| o.is_none()).count();
+    let pairs = outcomes.windows(2).filter(|w| w[0].is_none()).count();
+    let repeated = outcomes.windows(2).filter(|w| w[0].is_none() && w[1].is_none()).count();
+    let loss_after_loss = if pairs == 0 { 0.0 } else { repeated as f64 / pairs as f64 };
+
+    LossAnalysis { sent: outcomes.len(), lost, bursts, loss_after_loss, period: regular_spacing(&burst_starts) }
+}
+
+/// Returns the mean gap between burst starts when the gaps are nearly constant.
+fn regular_spacing(starts: &[usize]) -> Option<f64> {
+    if starts.len() < 3 {
+        return None;
+    }
+    let gaps: Vec<f64> = starts.windows(2).map(|w| (w[1] - w[0]) as f64).collect();
+    let (_, mean, _, stddev) = chart::summarize(&gaps);
+    if mean >= 3.0 && stddev / mean < 0.25 { Some(mean) } else { None }
+}
+
+fn print_analysis(analysis: &LossAnalysis, outcomes: &[Option<f64>]) {
+    println!(
+        "🔹 {} {}/{} probes lost ({:.1}%)",
+        colorize("Loss:", "blue"),
+        analysis.lost,
+        analysis.sent,
+        analysis.loss_rate() * 100.0
+    );
+    // One character per probe: a dot for replies, a red ✗ for losses.
+    let timeline: String = outcomes
+        .iter()
+        .map(|o| match o {
+            Some(_) => "·".to_string(),
+            None => colorize("✗", "red"),
+        })
+        .collect();
+    println!("   {}", timeline);
+
+    if analysis.lost == 0 {
+        println!("✅ {} No packet loss.", colorize("[SUCCESS]", "green"));
+        return;
+    }
+
+    println!("   Burst lengths:");
+    for (length, count) in &analysis.bursts {
+        println!("   {:>4} probe(s) │{} {}", length, "█".repeat(*count), count);
+    }
+    println!(
+        "   P(loss | previous lost) = {:.2} vs base rate {:.2}",
+        analysis.loss_after_loss,
+        analysis.loss_rate()
+    );
+
+    if let Some(period) = analysis.period {
+        println!(
+            "⚠️  {} Loss recurs about every {:.0} s. A periodic cause is likely: Wi-Fi background scans, DHCP/ARP renewals or routing timers.",
+            colorize("[PERIODIC]", "yellow"),
+            period
+        );
+    }
+    if analysis.is_bursty() {
+        println!(
+            "⚠️  {} Losses cluster together. Bursty loss points to queue overflow (bufferbloat), Wi-Fi roaming or link flaps.",
+            colorize("[BURSTY]", "yellow")
+        );
+    } else {
+        println!(
+            "ℹ️  {} Losses look independent. Random loss points to RF interference, a marginal cable or a steadily congested link.",
+            colorize("[RANDOM]", "blue")
+        );
+    }
+}
+
+fn history_path() -> PathBuf {
+    data_dir().join("loss_history.jsonl")
+}
+
+fn record_history(host: &str, analysis: &LossAnalysis) {
+    let entry = HistoryEntry { timestamp: clock::unix_now(), host: host.to_string(), sent: analysis.sent, lost: analysis.lost };
+    let _ = fs::create_dir_all(data_dir());
+    if let (Ok(mut file), Ok(line)) = (
+        OpenOptions::new().create(true).append(true).open(history_path()),
+        serde_json::to_string(&entry),
+    ) {
+        let _ = writeln!(file, "{}", line);
+    }
+}
+
+/// Aggregates past runs for `host` by hour of day and highlights hours with elevated loss.
+fn print_time_of_day(host: &str) {
+    let text = fs::read_to_string(history_path()).unwrap_or_default();
+    let entries: Vec<HistoryEntry> = text
+        .lines()
+        .filter_map(|l| serde_json::from_str::<HistoryEntry>(l).ok())
+        .filter(|e| e.host == host)
+        .collect();
+    if entries.len() < 2 {
+        println!("\nℹ️  Run `netdiag loss` at different times of day to correlate loss with time.");
+        return;
+    }
+
+    let mut by_hour: BTreeMap<u64, (usize, usize)> = BTreeMap::new();
+    for entry in &entries {
+        let slot = by_hour.entry(entry.timestamp % 86_400 / 3600).or_insert((0, 0));
+        slot.0 += entry.sent;
+        slot.1 += entry.lost;
+    }
+    let total_sent: usize = entries.iter().map(|e| e.sent).sum();
+    let total_lost: usize = entries.iter().map(|e| e.lost).sum();
+    let overall = total_lost as f64 / total_sent.max(1) as f64;
+
+    println!("\n🔹 {} ({} runs)", colorize("Loss by hour of day (UTC)", "blue"), entries.len());
+    for (hour, &(sent, lost)) in &by_hour {
+        let rate = lost as f64 / sent.max(1) as f64;
+        let line = format!("   {:02}:00  {:>5.1}%  ({} probes)", hour, rate * 100.0, sent);
+        if rate > overall * 2.0 && lost > 0 {
+            println!("{}  {}", line, colorize("← elevated", "yellow"));
+        } else {
+            println!("{}", line);
+        }
+    }
+}
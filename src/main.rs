@@ -1,7 +1,83 @@
-use std::process::{Command, Stdio};
-use std::io::{BufRead, BufReader};
-use std::time::{Duration, Instant};
-use std::thread;
+#[macro_use]
+extern crate clap;
+#[cfg(unix)]
+extern crate libc;
+#[macro_use]
+extern crate serde_derive;
+extern crate serde_json;
+extern crate serde_yaml;
+
+mod afpacket;
+mod analyze;
+mod anonymize;
+mod baseline;
+mod bufferbloat;
+mod bundle;
+mod capture;
+mod certs;
+mod chart;
+mod clock;
+mod config;
+mod container;
+mod dns;
+mod dns_config;
+mod dns_filter;
+mod dns_hijack;
+mod dns_propagation;
+mod dualstack;
+mod fileshare;
+mod http;
+mod interrupt;
+mod k8s;
+mod latency;
+mod loss;
+mod matrix;
+mod monitor;
+mod ndp;
+mod netinfo;
+mod packet;
+mod pathgraph;
+mod pcap;
+mod peer;
+mod portscan;
+mod privileges;
+mod profile;
+mod proxy;
+mod qos;
+mod quic;
+mod repeat;
+mod report;
+mod resolver;
+mod route_lookup;
+mod routes;
+mod scheduler;
+mod selftest;
+mod service;
+mod session;
+mod sockets;
+mod stability;
+mod tcping;
+mod telemetry;
+mod template;
+mod throughput;
+mod traceroute;
+mod traffic;
+mod triage;
+mod vendor;
+mod verify;
+mod voip;
+mod vpn;
+mod websites;
+mod wifi;
+
+use std::collections::hash_map::RandomState;
+use std::env;
+use std::hash::{BuildHasher, Hasher};
+use std::path::PathBuf;
+use std::process::Command;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches};
 
 /// Adds color to terminal output for better readability.
 fn colorize(text: &str, color: &str) -> String {
@@ -16,10 +92,24 @@ fn colorize(text: &str, color: &str) -> String {
     format!("{}{}{}", color_code, text, "\x1b[0m")
 }
 
+/// Directory where saved state (baseline, history) is kept: `~/.netdiag`.
+fn data_dir() -> PathBuf {
+    env::var_os("HOME")
+        .or_else(|| env::var_os("USERPROFILE"))
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."))
+        .join(".netdiag")
+}
+
+/// Returns a random 64-bit value using the hasher keys std seeds per process.
+fn random_u64() -> u64 {
+    RandomState::new().build_hasher().finish()
+}
+
 /// Executes a shell command and prints the result.
 fn run_command(command: &str, args: &[&str], description: &str) {
     println!("🔹 {}", colorize(description, "blue"));
-    let output = Command::new(command).args(args).output();
+    let output = interrupt::output(Command::new(command).args(args));
 
     match output {
         Ok(result) => {
@@ -27,147 +117,241 @@ fn run_command(command: &str, args: &[&str], description: &str) {
                 println!("✅ {}\n{}", colorize("[SUCCESS]", "green"), String::from_utf8_lossy(&result.stdout));
             } else {
                 println!("❌ {}\n{}", colorize("[ERROR]", "red"), String::from_utf8_lossy(&result.stderr));
+                telemetry::fail(&format!("{} exited with {}", command, result.status));
             }
         }
-        Err(e) => println!("❌ {} {}", colorize("[ERROR]", "red"), e),
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            telemetry::fail(&e.to_string());
+        }
     }
-    thread::sleep(Duration::from_secs(1));
+    interrupt::sleep(Duration::from_secs(1));
 }
 
-/// Runs basic network tests.
-fn network_test() {
-    println!("\n🌐 {} Running Network Diagnostics...\n", colorize("[INFO]", "blue"));
-
-    run_command("ping", &["-c", "4", "8.8.8.8"], "Pinging Google DNS Server (8.8.8.8)");
-    run_command("curl", &["ifconfig.me"], "Fetching Public IP Address");
-    run_command("sh", &["-c", "ifconfig -a | grep 'inet '"], "Fetching Private IP Address");
-    run_command("sh", &["-c", "netstat -an | grep 'ESTABLISHED'"], "Checking Open Listening Ports");
-    run_command("sh", &["-c", "traceroute google.com"], "Running Traceroute to Google");
-    run_command("netstat", &["-rn", "-f", "inet"], "Displaying Routing Table");
-
-    println!("🌍 {}\n", colorize("[INFO] Network tests completed.", "blue"));
+/// Looks up the profile `name`, printing why when it does not exist or is invalid.
+fn select_profile(name: &str, config: &config::Config) -> Option<profile::Profile> {
+    match profile::select(name, config) {
+        Ok(profile) => Some(profile),
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            None
+        }
+    }
 }
 
-/// Captures network packets using `tcpdump` while visiting websites.
-fn capture_traffic(interface: &str, port: &str, max_packets: usize, timeout_secs: u64) {
-    println!("\n📡 {} Capturing {} packets on {} (port {})\n",
-        colorize("[INFO]", "blue"), max_packets, colorize(interface, "cyan"), colorize(port, "cyan"));
-
-    // Spawn tcpdump process
-    let mut child = Command::new("tcpdump")
-        .args(&["-i", interface, "port", port, "-c", &max_packets.to_string(), "-nn", "-vvv"])
-        .stdout(Stdio::piped())
-        .spawn()
-        .expect("Failed to start tcpdump");
-
-    let stdout = child.stdout.take().expect("Failed to capture stdout");
-    let reader = BufReader::new(stdout);
-    let start_time = Instant::now();
-    let mut packet_count = 0;
+/// Runs the checks of `profile`, saving progress to `session` after each step so an
+/// interrupted run can be resumed.
+fn network_test(session: &mut session::Session, profile: &profile::Profile, config: &config::Config) {
+    telemetry::attribute("netdiag.profile", &profile.name);
+    telemetry::attribute("netdiag.session", &session.id);
+    println!("\n🌐 {} Running Network Diagnostics ({} profile)...\n", colorize("[INFO]", "blue"), colorize(&profile.name, "cyan"));
+    privileges::report();
+    // Every name the checks below use, resolved once up front.
+    let names = profile.names();
+    let names: Vec<&str> = names.iter().map(|n| n.as_str()).collect();
+    if resolver::preflight(&names) > 0 {
+        println!("   Checks against these names will fail on DNS, not on connectivity.\n");
+    }
 
-    // Start a separate thread for visiting websites while capturing traffic
-    let site_thread = thread::spawn(|| visit_websites());
+    let steps = profile.steps(&config.certs);
+    for (name, step) in &steps {
+        if session.is_done(name) {
+            println!("⏭️  {} {} (completed earlier in session {})", colorize("[SKIP]", "blue"), name, session.id);
+            continue;
+        }
+        telemetry::begin(name);
+        telemetry::attribute("netdiag.check", name);
+        step();
+        // A step cut short by the interrupt does not count as completed.
+        if interrupt::interrupted() {
+            telemetry::fail("interrupted");
+            telemetry::end();
+            break;
+        }
+        telemetry::end();
+        session.complete(name);
+    }
 
-    println!("\n🌍 {} Visiting Websites While Capturing Traffic...\n", colorize("[INFO]", "blue"));
+    if interrupt::interrupted() {
+        let remaining: Vec<&str> = steps.iter().map(|(name, _)| *name).filter(|name| !session.is_done(name)).collect();
+        println!("\n📊 {} Interrupted after {} of {} checks", colorize("[SUMMARY]", "blue"), session.completed.len(), steps.len());
+        println!("   Completed: {}", if session.completed.is_empty() { "none".to_string() } else { session.completed.join(", ") });
+        println!("   Cut short or skipped: {}", remaining.join(", "));
+        println!("   Resume with: netdiag resume {}\n", session.id);
+        return;
+    }
+    session.finish();
+    println!("🌍 {}\n", colorize("[INFO] Network tests completed.", "blue"));
+}
 
-    println!(
-        "{:<20} {:<20} {:<10} {:<40}",
-        colorize("Timestamp", "yellow"),
-        colorize("Source", "cyan"),
-        colorize("Protocol", "blue"),
-        colorize("Info", "green")
-    );
-    println!("{}", "-".repeat(90));
+/// **Main function: Runs network tests and captures traffic.**
+fn main() {
+    let matches = App::new("netdiag")
+        .version(crate_version!())
+        .author(crate_authors!())
+        .about("Network diagnostic tool")
+        .arg(Arg::with_name("config").long("config").takes_value(true).global(true)
+            .help("Config file to use instead of ~/.netdiag/config.yaml"))
+        .arg(Arg::with_name("repeat").long("repeat").takes_value(true).default_value("1").global(true)
+            .help("Run the selected checks N times and report mean, median, p95 and σ of each metric; metrics come from ping, latency, loss, tcping, dualstack, bufferbloat, quic, voip and Wi-Fi signal, other checks just run N times"))
+        .arg(Arg::with_name("netns").long("netns").takes_value(true).global(true)
+            .help("Run the checks inside another Linux network namespace (ip netns name or /proc/<pid>/ns/net)"))
+        .arg(Arg::with_name("profile").long("profile").takes_value(true).value_name("name")
+            .help("Run a named set of checks: full (default), quick, wifi, vpn, server, triage, or one from the config; `list` shows them"))
+        .arg(Arg::with_name("resolve-via").long("resolve-via").takes_value(true).value_name("server").global(true)
+            .help("Resolve every name through this DNS server instead of the system resolver"))
+        .arg(Arg::with_name("otlp-endpoint").long("otlp-endpoint").takes_value(true).value_name("url").global(true)
+            .help("Export the run as an OpenTelemetry trace to this OTLP/HTTP collector, e.g. http://localhost:4318"))
+        .subcommand(baseline::subcommand())
+        .subcommand(bufferbloat::subcommand())
+        .subcommand(dns_hijack::subcommand())
+        .subcommand(dns_propagation::subcommand())
+        .subcommand(quic::subcommand())
+        .subcommand(proxy::subcommand())
+        .subcommand(vpn::subcommand())
+        .subcommand(certs::subcommand())
+        .subcommand(portscan::subcommand())
+        .subcommand(traceroute::subcommand())
+        .subcommand(monitor::subcommand())
+        .subcommand(latency::subcommand())
+        .subcommand(loss::subcommand())
+        .subcommand(routes::subcommand())
+        .subcommand(route_lookup::subcommand())
+        .subcommand(capture::subcommand())
+        .subcommand(ndp::subcommand())
+        .subcommand(tcping::subcommand())
+        .subcommand(stability::subcommand())
+        .subcommand(qos::subcommand())
+        .subcommand(dualstack::subcommand())
+        .subcommand(session::subcommand())
+        .subcommand(bundle::subcommand())
+        .subcommand(container::subcommand())
+        .subcommand(k8s::subcommand())
+        .subcommand(dns_config::subcommand())
+        .subcommand(matrix::subcommand())
+        .subcommand(fileshare::subcommand())
+        .subcommand(anonymize::subcommand())
+        .subcommand(analyze::subcommand())
+        .subcommand(report::subcommand())
+        .subcommand(dns_filter::subcommand())
+        .subcommand(triage::subcommand())
+        .subcommand(voip::subcommand())
+        .subcommand(service::subcommand())
+        .subcommand(vendor::subcommand())
+        .subcommand(verify::subcommand())
+        .subcommand(selftest::subcommand())
+        .get_matches();
 
-    for line in reader.lines() {
-        match line {
-            Ok(packet) => {
-                if let Some((timestamp, src, protocol, info)) = parse_packet(&packet) {
-                    println!(
-                        "{:<20} {:<20} {:<10} {:<40}",
-                        colorize(&timestamp, "yellow"),
-                        colorize(&src, "cyan"),
-                        colorize(&protocol, "blue"),
-                        colorize(&info, "green")
-                    );
-                }
-                packet_count += 1;
-            }
-            Err(e) => {
-                println!("❌ {} Error reading packet: {}", colorize("[ERROR]", "red"), e);
-                break;
+    // Global args land in the subcommand's matches when given after its name.
+    let selected = matches.subcommand().1.unwrap_or(&matches);
+    if let Some(netns) = selected.value_of("netns") {
+        if let Err(e) = container::enter_netns(netns) {
+            eprintln!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+        // Notices go to stderr so they do not end up in ndjson or other piped output.
+        eprintln!("ℹ️  {} Running inside network namespace {}", colorize("[NETNS]", "blue"), colorize(netns, "cyan"));
+    }
+    if let Some(server) = selected.value_of("resolve-via") {
+        match server.parse() {
+            Ok(server) => resolver::set_resolve_via(server),
+            Err(_) => {
+                eprintln!("❌ {} --resolve-via needs an IP address, not {}", colorize("[ERROR]", "red"), server);
+                return;
             }
         }
-
-        if packet_count >= max_packets || start_time.elapsed() >= Duration::from_secs(timeout_secs) {
-            println!("\n⏳ {} Stopping capture after {} packets or {} seconds.",
-                     colorize("[TIMEOUT]", "yellow"), packet_count, timeout_secs);
+        eprintln!("ℹ️  {} Resolving names via {}", colorize("[DNS]", "blue"), colorize(server, "cyan"));
+    }
+    // Loaded once here, so its warnings are printed once, and handed to whatever needs it.
+    let config = config::load(selected.value_of("config"));
+    scheduler::configure(config.probes.clone());
+    telemetry::configure(config.telemetry.clone(), selected.value_of("otlp-endpoint"));
+    interrupt::install();
+    let command = match matches.subcommand_name() {
+        Some(name) => name.to_string(),
+        None => format!("--profile {}", matches.value_of("profile").unwrap_or(profile::DEFAULT)),
+    };
+    let runs = value_t!(selected, "repeat", u32).unwrap_or(1).max(1);
+    let mut completed = 0;
+    for run in 1..=runs {
+        if runs > 1 {
+            println!("\n🔁 {} Run {} of {}", colorize("[REPEAT]", "blue"), run, runs);
+        }
+        repeat::start_run(run);
+        telemetry::begin(&format!("netdiag {}", command));
+        telemetry::attribute("netdiag.command", &command);
+        telemetry::attribute("netdiag.run", &run.to_string());
+        dispatch(&matches, &config);
+        if interrupt::interrupted() {
+            telemetry::fail("interrupted");
+            telemetry::end();
             break;
         }
+        telemetry::end();
+        completed += 1;
     }
-
-    // Ensure tcpdump exits cleanly
-    let _ = child.kill();
-    let _ = site_thread.join();
-
-    println!("\n📊 {} Summary: Captured {} packets.\n", colorize("[SUMMARY]", "blue"), packet_count);
+    if runs > 1 {
+        repeat::print_report(completed);
+    }
+    telemetry::export();
 }
 
-/// Visits multiple websites in parallel while traffic is being captured.
-fn visit_websites() {
-    let sites = vec![
-        ("https://www.google.com/search?q=network+diagnostics", "Google"),
-        ("http://www.microsoft.com", "Microsoft"),
-        ("http://www.amazon.com.au", "Amazon"),
-        ("http://www.facebook.com", "Facebook"),
-        ("https://www.youtube.com", "YouTube"),
-        ("http://www.apple.com", "Apple"),
-        ("http://www.github.com", "GitHub"),
-        ("http://www.linkedin.com", "LinkedIn"),
-        ("http://www.reddit.com", "Reddit"),
-        ("http://www.twitter.com", "Twitter"),
-        ("http://www.wikipedia.org", "Wikipedia"),
-        ("http://www.instagram.com", "Instagram"),
-        ("http://www.netflix.com", "Netflix"),
-        ("http://www.spotify.com", "Spotify"),
-        ("http://www.stackoverflow.com", "StackOverflow"),
-        ("http://www.medium.com", "Medium"),
-        ("http://www.quora.com", "Quora"),
-        ("http://www.udemy.com", "Udemy"),
-        ("http://www.coursera.org", "Coursera"),
-        ("http://www.khanacademy.org", "Khan Academy"),
-    ];
-
-    for (url, name) in &sites {
-        let result = Command::new("curl").args(&["-I", url]).output();
-        match result {
-            Ok(response) => {
-                if response.status.success() {
-                    println!("✅ {} Visited: {}", colorize("[SUCCESS]", "green"), colorize(name, "cyan"));
-                } else {
-                    println!("❌ {} Failed to visit {}", colorize("[ERROR]", "red"), name);
+/// Runs the selected subcommand, or the default diagnostics when none is given.
+fn dispatch(matches: &ArgMatches, config: &config::Config) {
+    match matches.subcommand() {
+        ("baseline", Some(sub)) => baseline::run(sub),
+        ("bufferbloat", Some(sub)) => bufferbloat::run(sub),
+        ("dns-hijack", Some(sub)) => dns_hijack::run(sub),
+        ("dns-propagation", Some(sub)) => dns_propagation::run(sub),
+        ("http3", Some(sub)) => quic::run(sub),
+        ("proxy-test", Some(sub)) => proxy::run(sub),
+        ("vpn-check", Some(sub)) => vpn::run(sub),
+        ("certs", Some(sub)) => certs::run(sub, config),
+        ("scan", Some(sub)) => portscan::run(sub),
+        ("traceroute", Some(sub)) => traceroute::run(sub),
+        ("monitor", Some(sub)) => monitor::run(sub, config),
+        ("latency", Some(sub)) => latency::run(sub),
+        ("loss", Some(sub)) => loss::run(sub),
+        ("routes", Some(sub)) => routes::run(sub),
+        ("route", Some(sub)) => route_lookup::run(sub),
+        ("capture", Some(sub)) => capture::run(sub, config),
+        ("ndp", Some(sub)) => ndp::run(sub),
+        ("tcping", Some(sub)) => tcping::run(sub),
+        ("stability", Some(sub)) => stability::run(sub),
+        ("dscp", Some(sub)) => qos::run(sub),
+        ("dualstack", Some(sub)) => dualstack::run(sub),
+        ("bundle", Some(sub)) => bundle::run(sub),
+        ("container", Some(sub)) => container::run(sub),
+        ("k8s", Some(sub)) => k8s::run(sub),
+        ("dns-config", Some(sub)) => dns_config::run(sub),
+        ("matrix", Some(sub)) => matrix::run(sub),
+        ("fileshare", Some(sub)) => fileshare::run(sub),
+        ("anonymize", Some(sub)) => anonymize::run(sub),
+        ("analyze", Some(sub)) => analyze::run(sub),
+        ("report", Some(sub)) => report::run(sub),
+        ("dns-filter", Some(sub)) => dns_filter::run(sub),
+        ("auto-triage", Some(sub)) => triage::run(sub),
+        ("voip", Some(sub)) => voip::run(sub),
+        ("install-service", Some(sub)) => service::run(sub),
+        ("oui", Some(sub)) => vendor::run(sub),
+        ("verify", Some(sub)) => verify::run(sub),
+        ("selftest", Some(sub)) => selftest::run(sub),
+        ("resume", Some(sub)) => {
+            if let Some(mut session) = session::open(sub) {
+                let name = session.profile.clone().unwrap_or_else(|| profile::DEFAULT.to_string());
+                if let Some(profile) = select_profile(&name, config) {
+                    network_test(&mut session, &profile, config);
                 }
             }
-            Err(e) => println!("❌ {} {}", colorize("[ERROR]", "red"), e),
         }
+        _ => match matches.value_of("profile").unwrap_or(profile::DEFAULT) {
+            "list" => profile::print_list(config),
+            // Checked before the session is saved, so a misspelt name leaves nothing for `resume`.
+            name => {
+                if let Some(profile) = select_profile(name, config) {
+                    network_test(&mut session::Session::start(name), &profile, config);
+                }
+            }
+        },
     }
 }
-
-/// Parses a `tcpdump` packet line into structured fields.
-fn parse_packet(packet: &str) -> Option<(String, String, String, String)> {
-    let parts: Vec<&str> = packet.split_whitespace().collect();
-    if parts.len() < 6 { return None; }
-
-    Some((
-        parts[0].to_string(), // Timestamp
-        parts[2].to_string(), // Source IP
-        parts[4].to_string(), // Protocol
-        parts[5..].join(" "), // Packet details
-    ))
-}
-
-/// **Main function: Runs network tests and captures traffic.**
-fn main() {
-    network_test();
-    capture_traffic("en0", "53", 10, 1); // Capture packets while visiting sites
-}
@@ -0,0 +1,287 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_yaml;
+
+use crate::colorize;
+use crate::dns;
+use crate::http;
+use crate::netinfo;
+use crate::resolver;
+use crate::scheduler;
+use crate::tcping::{self, Attempt};
+use crate::telemetry;
+
+/// A `services.yaml` file: a list of named endpoints under `services:`.
+#[derive(Debug, Deserialize)]
+struct ServiceFile {
+    services: Vec<Service>,
+}
+
+/// One endpoint this machine should (or should not) be able to reach.
+#[derive(Debug, Clone, Deserialize)]
+struct Service {
+    name: String,
+    host: String,
+    #[serde(default)]
+    port: Option<u16>,
+    #[serde(default)]
+    protocol: Protocol,
+    #[serde(default)]
+    expect: Expect,
+    /// HTTP status the endpoint must return; any status below 400 when omitted.
+    #[serde(default)]
+    status: Option<u16>,
+    /// Name to look up when `protocol` is `dns`.
+    #[serde(default)]
+    query: Option<String>,
+    #[serde(default = "default_timeout_ms")]
+    timeout_ms: u64,
+}
+
+fn default_timeout_ms() -> u64 {
+    3000
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Protocol {
+    #[default]
+    Tcp,
+    Udp,
+    Http,
+    Https,
+    Icmp,
+    Dns,
+}
+
+/// What a passing result looks like.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum Expect {
+    /// The service answers.
+    #[default]
+    Reachable,
+    /// The host answers but refuses the port (RST or ICMP port unreachable).
+    Closed,
+    /// Nothing gets through: refused, dropped or unresolvable. For checking firewall rules.
+    Blocked,
+}
+
+/// What happened when the service was tested.
+#[derive(Debug, Clone)]
+enum Outcome {
+    Answered(f64),
+    HttpStatus(u16, f64),
+    Refused,
+    TimedOut,
+    /// UDP datagram sent without an error or a reply: open or silently filtered.
+    NoReply,
+    Failed(String),
+}
+
+impl Outcome {
+    fn describe(&self) -> String {
+        match self {
+            Outcome::Answered(ms) => format!("answered {:.0} ms", ms),
+            Outcome::HttpStatus(code, ms) => format!("HTTP {} {:.0} ms", code, ms),
+            Outcome::Refused => "refused".to_string(),
+            Outcome::TimedOut => "timed out".to_string(),
+            Outcome::NoReply => "no reply (open|filtered)".to_string(),
+            Outcome::Failed(reason) => reason.clone(),
+        }
+    }
+}
+
+/// Returns the `matrix` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("matrix")
+        .about("Tests reachability of every endpoint in a services file concurrently and prints a pass/fail matrix")
+        .arg(Arg::with_name("services").long("services").short("s").takes_value(true).required(true)
+            .help("YAML file listing services (name, host, port, protocol, expect)"))
+}
+
+/// Runs the `matrix` subcommand.
+pub fn run(matches: &ArgMatches) {
+    test_file(matches.value_of("services").unwrap_or_default());
+}
+
+/// Tests every service listed in the YAML file at `path` and prints the matrix.
+pub fn test_file(path: &str) {
+    let services = match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|t| {
+        serde_yaml::from_str::<ServiceFile>(&t).map_err(|e| e.to_string())
+    }) {
+        Ok(file) => file.services,
+        Err(e) => {
+            println!("❌ {} Could not load {}: {}", colorize("[ERROR]", "red"), path, e);
+            telemetry::fail(&format!("could not load {}", path));
+            return;
+        }
+    };
+
+    println!("\n🧪 {} Testing {} service(s) from {}\n", colorize("[INFO]", "blue"), services.len(), path);
+    let probes: Vec<_> = services
+        .iter()
+        .cloned()
+        .map(|service| thread::spawn(move || test(&service)))
+        .collect();
+    let outcomes: Vec<Outcome> = probes
+        .into_iter()
+        .map(|p| p.join().unwrap_or_else(|_| Outcome::Failed("probe panicked".to_string())))
+        .collect();
+    print_matrix(&services, &outcomes);
+}
+
+/// Tests one service according to its protocol.
+fn test(service: &Service) -> Outcome {
+    let timeout = Duration::from_millis(service.timeout_ms);
+    let port = service.port.unwrap_or(match service.protocol {
+        Protocol::Http => 80,
+        Protocol::Https => 443,
+        Protocol::Dns => 53,
+        _ => 0,
+    });
+    match service.protocol {
+        Protocol::Http | Protocol::Https => test_http(service, port, timeout),
+        Protocol::Icmp => match netinfo::ping(&service.host, 2) {
+            Some(stats) if stats.received > 0 => Outcome::Answered(stats.avg_ms),
+            Some(_) => Outcome::TimedOut,
+            None => Outcome::Failed("ping unavailable".to_string()),
+        },
+        _ if port == 0 => Outcome::Failed("no port given".to_string()),
+        protocol => {
+            let addr = match resolver::socket_addr(&service.host, port) {
+                Ok(addr) => addr,
+                Err(e) => return Outcome::Failed(e.to_string()),
+            };
+            match protocol {
+                Protocol::Udp => test_udp(addr, timeout),
+                Protocol::Dns => test_dns(addr.ip(), service.query.as_deref().unwrap_or("example.com"), timeout),
+                _ => match tcping::connect(addr, timeout) {
+                    Attempt::Connected(ms) => Outcome::Answered(ms),
+                    Attempt::Refused(_) => Outcome::Refused,
+                    Attempt::TimedOut => Outcome::TimedOut,
+                    Attempt::Failed => Outcome::Failed("unreachable".to_string()),
+                },
+            }
+        }
+    }
+}
+
+/// Fetches the service's root URL and reports the status code.
+fn test_http(service: &Service, port: u16, timeout: Duration) -> Outcome {
+    let scheme = if service.protocol == Protocol::Https { "https" } else { "http" };
+    let url = format!("{}://{}:{}/", scheme, service.host, port);
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let max_time = format!("{:.1}", timeout.as_secs_f64());
+    let start = Instant::now();
+    let output = match http::curl(&url).args(["-s", "-o", null, "--max-time", &max_time, "-w", "%{http_code}"]).output() {
+        Ok(output) => output,
+        Err(e) => return Outcome::Failed(format!("curl: {}", e)),
+    };
+    let ms = start.elapsed().as_secs_f64() * 1000.0;
+    match output.status.code() {
+        Some(0) => Outcome::HttpStatus(String::from_utf8_lossy(&output.stdout).trim().parse().unwrap_or(0), ms),
+        Some(6) => Outcome::Failed("does not resolve".to_string()),
+        Some(7) => Outcome::Refused,
+        Some(28) => Outcome::TimedOut,
+        Some(35) | Some(60) => Outcome::Failed("TLS handshake failed".to_string()),
+        code => Outcome::Failed(format!("curl exited with status {}", code.unwrap_or(-1))),
+    }
+}
+
+/// Sends an empty datagram; an ICMP port unreachable surfaces as a refused `recv`.
+fn test_udp(addr: SocketAddr, timeout: Duration) -> Outcome {
+    let bind: SocketAddr = if addr.is_ipv4() { ([0, 0, 0, 0], 0).into() } else { ([0u16; 8], 0).into() };
+    let socket = match UdpSocket::bind(bind).and_then(|s| s.connect(addr).map(|_| s)) {
+        Ok(socket) => socket,
+        Err(e) => return Outcome::Failed(e.to_string()),
+    };
+    let _ = socket.set_read_timeout(Some(timeout));
+    let _permit = scheduler::acquire("udp", addr.ip(), Some(addr.port()));
+    let start = Instant::now();
+    if let Err(e) = socket.send(&[]) {
+        return Outcome::Failed(e.to_string());
+    }
+    let mut buf = [0u8; 512];
+    match socket.recv(&mut buf) {
+        Ok(_) => Outcome::Answered(start.elapsed().as_secs_f64() * 1000.0),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => Outcome::Refused,
+        Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => Outcome::NoReply,
+        Err(e) => Outcome::Failed(e.to_string()),
+    }
+}
+
+/// Asks the server to resolve `name`; a real answer, even NXDOMAIN, means it is reachable, while
+/// SERVFAIL or REFUSED mean it is up but not serving this client.
+fn test_dns(server: IpAddr, name: &str, timeout: Duration) -> Outcome {
+    match dns::query(server, name, dns::TYPE_A, timeout) {
+        Ok(response) if response.rcode == dns::RCODE_NOERROR || response.rcode == dns::RCODE_NXDOMAIN => Outcome::Answered(response.elapsed.as_secs_f64() * 1000.0),
+        Ok(response) => Outcome::Failed(format!("answered {}", dns::rcode_name(response.rcode))),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => Outcome::Refused,
+        Err(_) => Outcome::TimedOut,
+    }
+}
+
+/// Whether `outcome` is what the service's `expect` asks for.
+fn passes(service: &Service, outcome: &Outcome) -> bool {
+    match (service.expect, outcome) {
+        (Expect::Reachable, Outcome::HttpStatus(code, _)) => match service.status {
+            Some(expected) => *code == expected,
+            None => *code > 0 && *code < 400,
+        },
+        (Expect::Reachable, Outcome::Answered(_)) | (Expect::Reachable, Outcome::NoReply) => true,
+        (Expect::Closed, Outcome::Refused) => true,
+        (Expect::Blocked, Outcome::Refused) | (Expect::Blocked, Outcome::TimedOut) | (Expect::Blocked, Outcome::Failed(_)) => true,
+        _ => false,
+    }
+}
+
+/// Prints one row per service, colored by pass/fail, and a tally.
+fn print_matrix(services: &[Service], outcomes: &[Outcome]) {
+    let name_width = services.iter().map(|s| s.name.chars().count()).max().unwrap_or(4).max(4);
+    let target_width = services.iter().map(|s| target(s).len()).max().unwrap_or(6).max(6);
+    println!(
+        "   {:<nw$}  {:<tw$}  {:<6} {:<10} Result",
+        "Name", "Target", "Proto", "Expect",
+        nw = name_width,
+        tw = target_width
+    );
+    let mut failed = 0;
+    for (service, outcome) in services.iter().zip(outcomes) {
+        let ok = passes(service, outcome);
+        if !ok {
+            failed += 1;
+        }
+        let result = outcome.describe();
+        println!(
+            "{} {:<nw$}  {:<tw$}  {:<6} {:<10} {}",
+            if ok { "✅" } else { "❌" },
+            service.name,
+            target(service),
+            format!("{:?}", service.protocol).to_lowercase(),
+            format!("{:?}", service.expect).to_lowercase(),
+            colorize(&result, if ok { "green" } else { "red" }),
+            nw = name_width,
+            tw = target_width
+        );
+    }
+    if failed == 0 {
+        println!("\n📊 {} All {} services behave as expected\n", colorize("[SUMMARY]", "blue"), services.len());
+    } else {
+        println!("\n📊 {} {} of {} services failed\n", colorize("[SUMMARY]", "blue"), failed, services.len());
+        telemetry::fail(&format!("{} of {} services failed", failed, services.len()));
+    }
+}
+
+/// `host:port`, or just the host when the protocol has no port.
+fn target(service: &Service) -> String {
+    match service.port {
+        Some(port) => format!("{}:{}", service.host, port),
+        None => service.host.clone(),
+    }
+}
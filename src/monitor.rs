@@ -0,0 +1,171 @@
+use std::collections::BTreeMap;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::{colorize, data_dir};
+use crate::certs::{self, CertStatus};
+use crate::clock;
+use crate::config::{CertsConfig, Config};
+use crate::interrupt;
+use crate::service;
+use crate::traceroute;
+
+/// Returns the `monitor` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("monitor")
+        .about("Watches the network continuously, alerting when routing paths change or certificates near expiry")
+        .arg(Arg::with_name("interval").long("interval").takes_value(true)
+            .help("Seconds between cycles (default from config, 300)"))
+        .arg(Arg::with_name("targets").long("targets").takes_value(true).use_delimiter(true)
+            .help("Comma-separated hosts to trace (default from config)"))
+        .arg(Arg::with_name("cycles").long("cycles").takes_value(true)
+            .help("Stop after this many cycles instead of running forever"))
+}
+
+/// Runs the `monitor` subcommand.
+pub fn run(matches: &ArgMatches, config: &Config) {
+    let certs = config.certs.clone();
+    let mut settings = config.monitor.clone();
+    if let Ok(interval) = value_t!(matches, "interval", u64) {
+        settings.interval_secs = interval;
+    }
+    if let Some(targets) = matches.values_of("targets") {
+        settings.targets = targets.map(|t| t.to_string()).collect();
+    }
+    let cycles = value_t!(matches, "cycles", u64).ok();
+
+    println!(
+        "\n👀 {} Monitoring {} every {}s; path changes are logged to {}\n",
+        colorize("[INFO]", "blue"),
+        settings.targets.join(", "),
+        settings.interval_secs,
+        log_path().display()
+    );
+    let cert_interval = Duration::from_secs(settings.cert_interval_hours * 3600);
+    if !certs.hosts.is_empty() && !cert_interval.is_zero() {
+        println!(
+            "   Certificates of {} host(s) are checked every {}h; alerting under {} days left\n",
+            certs.hosts.len(),
+            settings.cert_interval_hours,
+            certs.warn_days
+        );
+    }
+
+    service::notify("READY=1");
+    let mut paths: BTreeMap<String, Vec<String>> = BTreeMap::new();
+    let mut cycle = 0;
+    let mut certs_checked: Option<Instant> = None;
+    loop {
+        cycle += 1;
+        check_paths(&settings.targets, &mut paths);
+        if !certs.hosts.is_empty() && !cert_interval.is_zero() && certs_checked.is_none_or(|t| t.elapsed() >= cert_interval) {
+            check_certificates(&certs);
+            certs_checked = Some(Instant::now());
+        }
+        service::notify(&format!("STATUS=Cycle {} done at {}", cycle, clock::format_timestamp(clock::unix_now())));
+
+        if cycles.is_some_and(|n| cycle >= n) {
+            break;
+        }
+        if !wait(Duration::from_secs(settings.interval_secs)) {
+            println!("\n📊 {} Stopped after {} monitoring cycle(s); events are in {}", colorize("[SUMMARY]", "blue"), cycle, log_path().display());
+            break;
+        }
+    }
+    service::notify("STOPPING=1");
+}
+
+/// Sleeps until the next cycle, pinging the systemd watchdog at half its interval meanwhile.
+/// Returns `false` if interrupted.
+fn wait(duration: Duration) -> bool {
+    let step = service::watchdog_interval().map_or(duration, |w| w / 2);
+    let mut left = duration;
+    while !left.is_zero() {
+        let chunk = left.min(step);
+        if !interrupt::sleep(chunk) {
+            return false;
+        }
+        service::watchdog();
+        left -= chunk;
+    }
+    true
+}
+
+fn log_path() -> PathBuf {
+    data_dir().join("monitor.log")
+}
+
+/// Appends a timestamped line to the monitor log.
+pub fn log_event(kind: &str, message: &str) {
+    let line = format!("{} {} {}\n", clock::format_timestamp(clock::unix_now()), kind, message);
+    let _ = fs::create_dir_all(data_dir());
+    if let Ok(mut file) = OpenOptions::new().create(true).append(true).open(log_path()) {
+        let _ = file.write_all(line.as_bytes());
+    }
+}
+
+/// Re-traces every target and reports differences from the previous cycle.
+fn check_paths(targets: &[String], paths: &mut BTreeMap<String, Vec<String>>) {
+    let now = clock::format_timestamp(clock::unix_now());
+    for target in targets {
+        service::watchdog();
+        let path = traceroute::path(target);
+        if path.is_empty() {
+            println!("{} ⚠️  {} Could not trace {}", now, colorize("[WARN]", "yellow"), target);
+            continue;
+        }
+
+        match paths.get(target) {
+            None => println!("{} 🔹 {} path recorded ({} hops)", now, target, path.len()),
+            Some(previous) => {
+                let changes = path_changes(previous, &path);
+                if changes.is_empty() {
+                    println!("{} ✅ {} path unchanged ({} hops)", now, target, path.len());
+                } else {
+                    println!("{} 🚨 {} Path to {} changed:", now, colorize("[ALERT]", "red"), colorize(target, "cyan"));
+                    for change in &changes {
+                        println!("      • {}", colorize(change, "yellow"));
+                        log_event("path-change", &format!("{} {}", target, change));
+                    }
+                }
+            }
+        }
+        paths.insert(target.clone(), path);
+    }
+}
+
+/// Checks the configured certificates and alerts on any inside the warning window or unreachable.
+fn check_certificates(settings: &CertsConfig) {
+    let now = clock::format_timestamp(clock::unix_now());
+    for report in certs::check_certs(settings) {
+        let problem = match (&report.error, report.status, report.days_left) {
+            (Some(error), _, _) => format!("could not be checked: {}", error),
+            (None, CertStatus::Ok, _) => continue,
+            (None, _, Some(days)) => format!("expires in {} day(s) ({})", days, report.not_after.as_deref().unwrap_or("?")),
+            (None, _, None) => "has an unreadable expiry date".to_string(),
+        };
+        println!("{} 🚨 {} Certificate of {} {}", now, colorize("[ALERT]", "red"), colorize(&report.host, "cyan"), problem);
+        log_event("cert-expiry", &format!("{} {}", report.host, problem));
+    }
+}
+
+/// Describes hop-level differences between two paths. Hops that did not answer (`*`) in
+/// either trace are not treated as changes, since probes are routinely dropped.
+pub fn path_changes(before: &[String], after: &[String]) -> Vec<String> {
+    let mut changes = Vec::new();
+    for i in 0..before.len().max(after.len()) {
+        match (before.get(i), after.get(i)) {
+            (Some(a), Some(b)) if a != b && a != "*" && b != "*" => {
+                changes.push(format!("hop {} replaced: {} → {}", i + 1, a, b))
+            }
+            (None, Some(b)) => changes.push(format!("hop {} added: {}", i + 1, b)),
+            (Some(a), None) => changes.push(format!("hop {} removed: {}", i + 1, a)),
+            _ => {}
+        }
+    }
+    changes
+}
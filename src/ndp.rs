@@ -0,0 +1,394 @@
+use std::net::{IpAddr, Ipv6Addr};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::capture;
+use crate::colorize;
+use crate::interrupt;
+use crate::netinfo;
+use crate::packet::{self, MacAddr};
+use crate::vendor;
+use crate::privileges;
+use crate::routes;
+use crate::scheduler;
+
+/// An entry in the IPv6 neighbor cache.
+#[derive(Debug)]
+struct Neighbor {
+    addr: String,
+    mac: Option<String>,
+    interface: String,
+    state: String,
+    router: bool,
+}
+
+/// A prefix information option from a router advertisement.
+#[derive(Debug)]
+struct PrefixInfo {
+    prefix: Ipv6Addr,
+    length: u8,
+    /// Hosts may autoconfigure addresses from this prefix (SLAAC).
+    autonomous: bool,
+    valid_secs: u32,
+    preferred_secs: u32,
+}
+
+/// The parts of a router advertisement that matter for host configuration.
+#[derive(Debug)]
+struct RouterAdvert {
+    router: IpAddr,
+    router_mac: Option<MacAddr>,
+    /// Zero means the router must not be used as a default router.
+    lifetime_secs: u16,
+    managed: bool,
+    other_config: bool,
+    prefixes: Vec<PrefixInfo>,
+    rdnss: Vec<Ipv6Addr>,
+    mtu: Option<u32>,
+}
+
+/// Returns the `ndp` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("ndp")
+        .about("Shows the IPv6 neighbor cache, solicits router advertisements and checks SLAAC/DAD")
+        .arg(Arg::with_name("interface").long("interface").short("i").takes_value(true)
+            .help("Interface to solicit on (default: the default-route interface)"))
+        .arg(Arg::with_name("wait").long("wait").takes_value(true).default_value("5")
+            .help("Seconds to listen for router advertisements"))
+}
+
+/// Runs the `ndp` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let table = routes::table();
+    let interface = matches
+        .value_of("interface")
+        .map(|i| i.to_string())
+        .or_else(|| table.iter().find(|r| r.is_default()).map(|r| r.interface.clone()))
+        .unwrap_or_else(|| "en0".to_string());
+    let wait = value_t!(matches, "wait", u64).unwrap_or(5);
+
+    println!("\n🔎 {} IPv6 neighbor discovery on {}\n", colorize("[INFO]", "blue"), colorize(&interface, "cyan"));
+    print_neighbors(&neighbors());
+
+    let adverts = solicit_routers(&interface, wait);
+    let issues = {
+        let mut issues = check_adverts(&adverts, table.iter().any(|r| r.is_default() && r.destination.is_ipv6()));
+        issues.extend(dad_failures());
+        issues
+    };
+
+    if issues.is_empty() {
+        println!("✅ {} Neighbor discovery looks healthy", colorize("[SUCCESS]", "green"));
+    } else {
+        println!("📊 {} {} IPv6 issue(s):", colorize("[SUMMARY]", "blue"), issues.len());
+        for issue in &issues {
+            println!("⚠️  {} {}", colorize("[WARN]", "yellow"), issue);
+        }
+    }
+    println!();
+}
+
+/// Reads the neighbor cache with `ip -6 neigh`, `ndp -an` or `netsh`.
+fn neighbors() -> Vec<Neighbor> {
+    if let Some(out) = netinfo::command_stdout("ip", &["-6", "neigh", "show"]) {
+        return out
+            .lines()
+            .filter_map(|line| {
+                let words: Vec<&str> = line.split_whitespace().collect();
+                let value_of = |key: &str| words.iter().position(|w| *w == key).and_then(|i| words.get(i + 1));
+                Some(Neighbor {
+                    addr: words.first()?.to_string(),
+                    mac: value_of("lladdr").map(|m| m.to_string()),
+                    interface: value_of("dev").map(|d| d.to_string()).unwrap_or_default(),
+                    state: words.last()?.to_string(),
+                    router: words.contains(&"router"),
+                })
+            })
+            .collect();
+    }
+
+    // macOS: Neighbor  Linklayer Address  Netif  Expire  S  Flags
+    if let Some(out) = netinfo::command_stdout("ndp", &["-an"]) {
+        return out
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let cols: Vec<&str> = line.split_whitespace().collect();
+                if cols.len() < 5 {
+                    return None;
+                }
+                Some(Neighbor {
+                    addr: cols[0].split('%').next().unwrap_or(cols[0]).to_string(),
+                    mac: Some(cols[1].to_string()).filter(|m| m.contains(':')),
+                    interface: cols[2].to_string(),
+                    state: ndp_state(cols[4]).to_string(),
+                    router: cols.get(5).is_some_and(|f| f.contains('R')),
+                })
+            })
+            .collect();
+    }
+
+    let out = netinfo::command_stdout("netsh", &["interface", "ipv6", "show", "neighbors"]).unwrap_or_default();
+    let mut interface = String::new();
+    let mut found = Vec::new();
+    for line in out.lines() {
+        if let Some(rest) = line.strip_prefix("Interface ") {
+            interface = rest.split_once(':').map(|(_, name)| name.trim().to_string()).unwrap_or_default();
+            continue;
+        }
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        if cols.len() >= 3 && cols[0].parse::<Ipv6Addr>().is_ok() {
+            found.push(Neighbor {
+                addr: cols[0].to_string(),
+                mac: Some(cols[1].to_string()),
+                interface: interface.clone(),
+                state: cols[2].to_string(),
+                router: line.contains("(Router)"),
+            });
+        }
+    }
+    found
+}
+
+/// Expands the single-letter state column of BSD `ndp -a`.
+fn ndp_state(letter: &str) -> &str {
+    match letter {
+        "R" => "REACHABLE",
+        "S" => "STALE",
+        "D" => "DELAY",
+        "P" => "PROBE",
+        "I" => "INCOMPLETE",
+        "N" => "NOSTATE",
+        other => other,
+    }
+}
+
+fn print_neighbors(neighbors: &[Neighbor]) {
+    println!("🔹 {}", colorize("IPv6 neighbor cache", "blue"));
+    if neighbors.is_empty() {
+        println!("   (empty)\n");
+        return;
+    }
+    println!("   {:<40} {:<20} {:<24} {:<10} State", "Address", "MAC", "Vendor", "Interface");
+    for n in neighbors {
+        let state = if n.state.eq_ignore_ascii_case("failed") || n.state.eq_ignore_ascii_case("incomplete") {
+            colorize(&n.state, "red")
+        } else {
+            n.state.clone()
+        };
+        let vendor = n.mac.as_deref().and_then(MacAddr::parse).map(|m| vendor::label(&m)).unwrap_or_else(|| "-".to_string());
+        println!(
+            "   {:<40} {:<20} {:<24} {:<10} {}{}",
+            n.addr,
+            n.mac.as_deref().unwrap_or("-"),
+            vendor,
+            n.interface,
+            state,
+            if n.router { colorize(" (router)", "cyan") } else { String::new() }
+        );
+    }
+    println!();
+}
+
+/// Sends a router solicitation with `rdisc6` or `rtsol` and collects the advertisements seen while listening.
+fn solicit_routers(interface: &str, wait: u64) -> Vec<RouterAdvert> {
+    println!("🔹 {}", colorize(&format!("Soliciting routers on {} ({}s)", interface, wait), "blue"));
+    // Type 134 is a router advertisement; ip6[40] is the ICMPv6 type when there are no extension headers.
+    if let Err(reason) = privileges::can_capture() {
+        println!("{}\n", privileges::hint("Router solicitation", &reason));
+        return Vec::new();
+    }
+    let (mut child, rx) = match capture::spawn_tcpdump(interface, "icmp6 and ip6[40] == 134") {
+        Ok(started) => started,
+        Err(e) => {
+            println!("⚠️  {} Cannot listen for router advertisements without tcpdump: {}\n", colorize("[WARN]", "yellow"), e);
+            return Vec::new();
+        }
+    };
+
+    // Solicitations go to the all-routers group on the interface.
+    let all_routers = format!("ff02::2%{}", interface);
+    let solicited = [("rdisc6", vec!["-1", interface]), ("rtsol", vec![interface])].iter().any(|(tool, args)| {
+        let mut command = Command::new(tool);
+        command.args(args);
+        interrupt::output(&mut scheduler::command("router-solicit", &all_routers, None, command)).is_ok_and(|o| o.status.success())
+    });
+    if !solicited {
+        println!("   Could not send a solicitation (install ndisc6, or run as root for rtsol); listening for periodic RAs only.");
+    }
+
+    let deadline = Instant::now() + Duration::from_secs(wait);
+    let mut adverts: Vec<RouterAdvert> = Vec::new();
+    while let Ok((linktype, record)) = rx.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+        let decoded = packet::decode(linktype, &record.data);
+        if let (Some(router), Some(icmp)) = (decoded.src, &decoded.icmp) {
+            if let Some(advert) = parse_advert(router, &icmp.body) {
+                if !adverts.iter().any(|a| a.router == advert.router) {
+                    print_advert(&advert);
+                    adverts.push(advert);
+                }
+            }
+        }
+    }
+    let _ = child.kill();
+    let _ = child.wait();
+    interrupt::untrack(&child);
+
+    if adverts.is_empty() {
+        println!("   No router advertisements received.\n");
+    }
+    adverts
+}
+
+/// Parses the body of an ICMPv6 router advertisement (RFC 4861 §4.2) and its options.
+fn parse_advert(router: IpAddr, body: &[u8]) -> Option<RouterAdvert> {
+    if body.len() < 12 {
+        return None;
+    }
+    let mut advert = RouterAdvert {
+        router,
+        router_mac: None,
+        lifetime_secs: u16::from_be_bytes([body[2], body[3]]),
+        managed: body[1] & 0x80 != 0,
+        other_config: body[1] & 0x40 != 0,
+        prefixes: Vec::new(),
+        rdnss: Vec::new(),
+        mtu: None,
+    };
+
+    let be32 = |b: &[u8]| u32::from_be_bytes([b[0], b[1], b[2], b[3]]);
+    let mut options = &body[12..];
+    while options.len() >= 8 {
+        let length = options[1] as usize * 8;
+        if length == 0 || length > options.len() {
+            break;
+        }
+        let option = &options[..length];
+        match option[0] {
+            1 => advert.router_mac = Some(MacAddr::from_slice(&option[2..8])),
+            3 if length >= 32 => {
+                let mut prefix = [0u8; 16];
+                prefix.copy_from_slice(&option[16..32]);
+                advert.prefixes.push(PrefixInfo {
+                    prefix: Ipv6Addr::from(prefix),
+                    length: option[2],
+                    autonomous: option[3] & 0x40 != 0,
+                    valid_secs: be32(&option[4..8]),
+                    preferred_secs: be32(&option[8..12]),
+                });
+            }
+            5 => advert.mtu = Some(be32(&option[4..8])),
+            25 => {
+                for chunk in option[8..].chunks_exact(16) {
+                    let mut addr = [0u8; 16];
+                    addr.copy_from_slice(chunk);
+                    advert.rdnss.push(Ipv6Addr::from(addr));
+                }
+            }
+            _ => {}
+        }
+        options = &options[length..];
+    }
+    Some(advert)
+}
+
+fn print_advert(advert: &RouterAdvert) {
+    println!(
+        "✅ {} RA from {}{}",
+        colorize("[SUCCESS]", "green"),
+        colorize(&advert.router.to_string(), "cyan"),
+        advert.router_mac.map(|m| format!(" ({})", m)).unwrap_or_default()
+    );
+    println!(
+        "   Router lifetime {}s, flags: {}{}",
+        advert.lifetime_secs,
+        if advert.managed { "M (DHCPv6 addresses) " } else { "" },
+        if advert.other_config { "O (DHCPv6 options)" } else if !advert.managed { "none (SLAAC only)" } else { "" }
+    );
+    for p in &advert.prefixes {
+        println!(
+            "   Prefix {}/{}{}  valid {}s, preferred {}s",
+            p.prefix,
+            p.length,
+            if p.autonomous { " [SLAAC]" } else { "" },
+            p.valid_secs,
+            p.preferred_secs
+        );
+    }
+    if !advert.rdnss.is_empty() {
+        let servers: Vec<String> = advert.rdnss.iter().map(|a| a.to_string()).collect();
+        println!("   RDNSS: {}", servers.join(", "));
+    }
+    if let Some(mtu) = advert.mtu {
+        println!("   MTU: {}", mtu);
+    }
+    println!();
+}
+
+/// Turns what was (or wasn't) advertised into configuration problems.
+fn check_adverts(adverts: &[RouterAdvert], has_v6_default: bool) -> Vec<String> {
+    let mut issues = Vec::new();
+    if adverts.is_empty() {
+        if !has_v6_default {
+            issues.push("No router advertisements and no IPv6 default route: IPv6 is not being provided on this link".to_string());
+        }
+        return issues;
+    }
+
+    for advert in adverts {
+        if advert.lifetime_secs == 0 {
+            issues.push(format!("{} advertises router lifetime 0, so hosts will not use it as a default router", advert.router));
+        }
+        let slaac = advert.prefixes.iter().any(|p| p.autonomous);
+        if !slaac && !advert.managed {
+            issues.push(format!("{} offers no SLAAC prefix and no DHCPv6 (M flag): hosts get no global address", advert.router));
+        }
+        if advert.rdnss.is_empty() && !advert.other_config && !advert.managed {
+            issues.push(format!("{} advertises no RDNSS and no DHCPv6: IPv6-only hosts will have no DNS server", advert.router));
+        }
+        if let Some(p) = advert.prefixes.iter().find(|p| p.autonomous && p.length != 64) {
+            issues.push(format!("SLAAC prefix {}/{} is not a /64; most hosts will ignore it", p.prefix, p.length));
+        }
+    }
+
+    // More than one router announcing different prefixes is how rogue RAs show up.
+    let mut announced: Vec<String> = adverts
+        .iter()
+        .flat_map(|a| a.prefixes.iter().map(move |p| format!("{}/{} from {}", p.prefix, p.length, a.router)))
+        .collect();
+    announced.sort();
+    let routers = adverts.iter().filter(|a| a.lifetime_secs > 0).count();
+    if routers > 1 {
+        issues.push(format!("{} routers claim to be default routers (possible rogue RA): {}", routers, announced.join("; ")));
+    }
+    issues
+}
+
+/// Addresses whose duplicate address detection failed or is still pending.
+fn dad_failures() -> Vec<String> {
+    let mut issues = Vec::new();
+    if let Some(out) = netinfo::command_stdout("ip", &["-6", "addr", "show"]) {
+        for line in out.lines().map(str::trim).filter(|l| l.starts_with("inet6")) {
+            let addr = line.split_whitespace().nth(1).unwrap_or("");
+            if line.contains("dadfailed") {
+                issues.push(format!("Duplicate address detection failed for {}: another host uses this address", addr));
+            } else if line.contains("tentative") {
+                issues.push(format!("{} is still tentative (DAD in progress or the link is down)", addr));
+            }
+        }
+        return issues;
+    }
+
+    let out = netinfo::command_stdout("ifconfig", &["-a"])
+        .or_else(|| netinfo::command_stdout("netsh", &["interface", "ipv6", "show", "addresses"]))
+        .unwrap_or_default();
+    for line in out.lines().map(str::trim) {
+        if line.contains("duplicated") || line.contains("Duplicate") {
+            let addr = line.split_whitespace().find(|w| w.split('%').next().is_some_and(|a| a.parse::<Ipv6Addr>().is_ok())).unwrap_or(line);
+            issues.push(format!("Duplicate address detection failed for {}: another host uses this address", addr));
+        }
+    }
+    issues
+}
@@ -0,0 +1,311 @@
+use std::fs;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use crate::chart;
+use crate::http;
+use crate::interrupt;
+use crate::privileges;
+use crate::resolver;
+use crate::scheduler;
+use crate::tcping::{self, Attempt};
+
+/// Runs a command and returns its stdout, or `None` if it failed to run or exited non-zero.
+pub fn command_stdout(command: &str, args: &[&str]) -> Option<String> {
+    let output = interrupt::output(Command::new(command).args(args)).ok()?;
+    if output.status.success() {
+        Some(String::from_utf8_lossy(&output.stdout).into_owned())
+    } else {
+        None
+    }
+}
+
+/// Summary statistics parsed from the last lines of `ping` output.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PingStats {
+    pub transmitted: u32,
+    pub received: u32,
+    pub min_ms: f64,
+    pub avg_ms: f64,
+    pub max_ms: f64,
+    /// Round-trip time of each reply, in the order received.
+    #[serde(default)]
+    pub samples: Vec<f64>,
+    /// Outcome of each probe by sequence number: the RTT, or `None` if it was lost.
+    #[serde(default)]
+    pub outcomes: Vec<Option<f64>>,
+}
+
+/// Pings `host` `count` times and parses the summary. Where ICMP is not permitted, TCP
+/// handshakes to port 443 are timed instead.
+pub fn ping(host: &str, count: u32) -> Option<PingStats> {
+    if !privileges::icmp_allowed() {
+        return tcp_ping(host, count);
+    }
+    let ip = resolver::resolve_one(host).ok()?;
+    let target = ip.to_string();
+    let _permit = scheduler::acquire(&format!("icmp-echo({})", count), ip, None);
+    let count = count.to_string();
+    let args: [&str; 3] = if cfg!(windows) { ["-n", &count, &target] } else { ["-c", &count, &target] };
+    // A host that never answers makes ping exit non-zero, but the summary is still useful.
+    let output = interrupt::output(Command::new("ping").args(args)).ok()?;
+    parse_ping_summary(&String::from_utf8_lossy(&output.stdout))
+}
+
+/// Times `count` TCP handshakes to `host:443`, one a second, as a stand-in for ping. A refusal
+/// still proves the host answered, so it counts as a reply.
+fn tcp_ping(host: &str, count: u32) -> Option<PingStats> {
+    let addr = tcping::resolve(&format!("{}:443", host)).ok()?;
+    let mut outcomes = Vec::new();
+    for seq in 0..count {
+        if interrupt::interrupted() {
+            break;
+        }
+        outcomes.push(match tcping::connect(addr, Duration::from_secs(2)) {
+            Attempt::Connected(ms) | Attempt::Refused(ms) => Some(ms),
+            Attempt::TimedOut | Attempt::Failed => None,
+        });
+        if seq + 1 < count {
+            thread::sleep(Duration::from_secs(1));
+        }
+    }
+    let samples: Vec<f64> = outcomes.iter().flatten().cloned().collect();
+    let (min_ms, avg_ms, max_ms, _) = chart::summarize(&samples);
+    Some(PingStats { transmitted: outcomes.len() as u32, received: samples.len() as u32, min_ms, avg_ms, max_ms, samples, outcomes })
+}
+
+/// Parses the "packets transmitted" and "min/avg/max" lines printed by BSD and Linux ping.
+fn parse_ping_summary(output: &str) -> Option<PingStats> {
+    let mut stats = PingStats { transmitted: 0, received: 0, min_ms: 0.0, avg_ms: 0.0, max_ms: 0.0, samples: Vec::new(), outcomes: Vec::new() };
+    let mut saw_counts = false;
+    let mut replies: Vec<(Option<usize>, f64)> = Vec::new();
+
+    for line in output.lines() {
+        if let Some(rtt) = parse_reply_time(line) {
+            stats.samples.push(rtt);
+            replies.push((parse_sequence(line), rtt));
+        } else if line.contains("packets transmitted") {
+            let numbers: Vec<u32> = line
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter_map(|word| word.parse().ok())
+                .collect();
+            if numbers.len() >= 2 {
+                stats.transmitted = numbers[0];
+                stats.received = numbers[1];
+                saw_counts = true;
+            }
+        } else if line.contains("min/avg/max") {
+            let values: Vec<f64> = line
+                .split('=')
+                .nth(1)?
+                .trim()
+                .trim_end_matches("ms")
+                .split('/')
+                .filter_map(|v| v.trim().parse().ok())
+                .collect();
+            if values.len() >= 3 {
+                stats.min_ms = values[0];
+                stats.avg_ms = values[1];
+                stats.max_ms = values[2];
+            }
+        }
+    }
+
+    if !saw_counts {
+        return None;
+    }
+
+    // Linux numbers probes from 1, BSD and macOS from 0. Without sequence numbers
+    // (Windows) replies are assumed to be in order.
+    let base = if cfg!(target_os = "linux") { 1 } else { 0 };
+    stats.outcomes = vec![None; stats.transmitted as usize];
+    for (i, &(seq, rtt)) in replies.iter().enumerate() {
+        let slot = seq.map_or(i, |s| s.saturating_sub(base));
+        if let Some(outcome) = stats.outcomes.get_mut(slot) {
+            *outcome = Some(rtt);
+        }
+    }
+    Some(stats)
+}
+
+/// Extracts `icmp_seq=N` (or `seq=N`) from a reply line.
+fn parse_sequence(line: &str) -> Option<usize> {
+    let start = line.find("seq=")? + 4;
+    line[start..].split(|c: char| !c.is_ascii_digit()).next()?.parse().ok()
+}
+
+/// Extracts the round-trip time from a reply line such as `... time=12.3 ms` or `time<1ms`.
+fn parse_reply_time(line: &str) -> Option<f64> {
+    let start = line.find("time=").or_else(|| line.find("time<"))? + 5;
+    let value: String = line[start..].chars().take_while(|c| c.is_ascii_digit() || *c == '.').collect();
+    value.parse().ok()
+}
+
+/// Returns the IPv4 default gateway, trying `ip`, BSD `route`, then `netstat`.
+pub fn default_gateway() -> Option<String> {
+    if let Some(out) = command_stdout("ip", &["route", "show", "default"]) {
+        let words: Vec<&str> = out.split_whitespace().collect();
+        if let Some(pos) = words.iter().position(|w| *w == "via") {
+            return words.get(pos + 1).map(|w| w.to_string());
+        }
+    }
+
+    if let Some(out) = command_stdout("route", &["-n", "get", "default"]) {
+        for line in out.lines() {
+            if let Some(gateway) = line.trim().strip_prefix("gateway:") {
+                return Some(gateway.trim().to_string());
+            }
+        }
+    }
+
+    let out = command_stdout("netstat", &["-rn"])?;
+    out.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|cols| cols.len() >= 2 && (cols[0] == "default" || cols[0] == "0.0.0.0"))
+        .map(|cols| cols[1].to_string())
+}
+
+/// Returns the configured DNS resolvers, in order, without duplicates.
+pub fn dns_servers() -> Vec<String> {
+    let mut servers: Vec<String> = Vec::new();
+
+    if let Some(out) = command_stdout("scutil", &["--dns"]) {
+        for line in out.lines() {
+            let line = line.trim();
+            if line.starts_with("nameserver[") {
+                if let Some(addr) = line.split(':').nth(1) {
+                    servers.push(addr.trim().to_string());
+                }
+            }
+        }
+    }
+
+    if servers.is_empty() {
+        if let Ok(conf) = fs::read_to_string("/etc/resolv.conf") {
+            for line in conf.lines() {
+                let mut words = line.split_whitespace();
+                if words.next() == Some("nameserver") {
+                    if let Some(addr) = words.next() {
+                        servers.push(addr.to_string());
+                    }
+                }
+            }
+        }
+    }
+
+    let mut unique = Vec::new();
+    for server in servers {
+        if !unique.contains(&server) {
+            unique.push(server);
+        }
+    }
+    unique
+}
+
+/// Fetches the public IP address as seen by ifconfig.me.
+pub fn public_ip() -> Option<String> {
+    let output = http::curl("https://ifconfig.me").args(["-s", "--max-time", "5"]).output().ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    let out = String::from_utf8_lossy(&output.stdout);
+    let ip = out.trim();
+    if ip.is_empty() { None } else { Some(ip.to_string()) }
+}
+
+/// A network interface and the addresses assigned to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Interface {
+    pub name: String,
+    pub addrs: Vec<String>,
+}
+
+/// Lists interfaces with their IPv4/IPv6 addresses using `ip` on Linux or `ifconfig` elsewhere.
+pub fn interfaces() -> Vec<Interface> {
+    let mut found: Vec<Interface> = Vec::new();
+
+    if let Some(out) = command_stdout("ip", &["-o", "addr", "show"]) {
+        for line in out.lines() {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            if fields.len() < 4 || (fields[2] != "inet" && fields[2] != "inet6") {
+                continue;
+            }
+            let addr = fields[3].split('/').next().unwrap_or(fields[3]).to_string();
+            add_interface_addr(&mut found, fields[1], addr);
+        }
+        return found;
+    }
+
+    if let Some(out) = command_stdout("ifconfig", &["-a"]) {
+        let mut current = String::new();
+        for line in out.lines() {
+            if !line.starts_with(char::is_whitespace) {
+                current = line.split(':').next().unwrap_or("").to_string();
+                if !current.is_empty() && !found.iter().any(|i| i.name == current) {
+                    found.push(Interface { name: current.clone(), addrs: Vec::new() });
+                }
+                continue;
+            }
+            let mut words = line.split_whitespace();
+            if let (Some(family), Some(addr)) = (words.next(), words.next()) {
+                if family == "inet" || family == "inet6" {
+                    let addr = addr.split('%').next().unwrap_or(addr).to_string();
+                    add_interface_addr(&mut found, &current, addr);
+                }
+            }
+        }
+    }
+    found
+}
+
+fn add_interface_addr(found: &mut Vec<Interface>, name: &str, addr: String) {
+    match found.iter_mut().find(|i| i.name == name) {
+        Some(iface) => iface.addrs.push(addr),
+        None => found.push(Interface { name: name.to_string(), addrs: vec![addr] }),
+    }
+}
+
+/// Returns an interface's MTU from sysfs on Linux or `ifconfig` elsewhere.
+pub fn mtu(interface: &str) -> Option<u32> {
+    if let Ok(text) = fs::read_to_string(format!("/sys/class/net/{}/mtu", interface)) {
+        return text.trim().parse().ok();
+    }
+    let out = command_stdout("ifconfig", &[interface])?;
+    let words: Vec<&str> = out.split_whitespace().collect();
+    let pos = words.iter().position(|w| w.eq_ignore_ascii_case("mtu"))?;
+    words.get(pos + 1)?.parse().ok()
+}
+
+/// Returns the interface the kernel would use to reach `dest`.
+pub fn route_interface(dest: &str) -> Option<String> {
+    if let Some(out) = command_stdout("ip", &["route", "get", dest]) {
+        let words: Vec<&str> = out.split_whitespace().collect();
+        if let Some(pos) = words.iter().position(|w| *w == "dev") {
+            return words.get(pos + 1).map(|w| w.to_string());
+        }
+    }
+
+    let out = command_stdout("route", &["-n", "get", dest])?;
+    out.lines()
+        .find_map(|line| line.trim().strip_prefix("interface:").map(|i| i.trim().to_string()))
+}
+
+/// Returns the system's IPv4/IPv6 neighbor cache as (address, MAC) pairs, via `ip neigh` or `arp -a`.
+pub fn neighbors() -> Vec<(String, String)> {
+    let out = match command_stdout("ip", &["neigh", "show"]).or_else(|| command_stdout("arp", &["-a"])) {
+        Some(out) => out,
+        None => return Vec::new(),
+    };
+    out.lines()
+        .filter_map(|line| {
+            let words: Vec<&str> = line.split_whitespace().collect();
+            let addr = words
+                .iter()
+                .map(|w| w.trim_matches(|c| c == '(' || c == ')'))
+                .find(|w| w.parse::<std::net::IpAddr>().is_ok())?;
+            let mac = words.iter().find(|w| w.len() >= 11 && w.split([':', '-']).count() == 6)?;
+            Some((addr.to_string(), mac.to_string()))
+        })
+        .collect()
+}
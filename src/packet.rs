@@ -0,0 +1,723 @@
+use std::fmt;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use crate::pcap;
+
+const ETHERTYPE_IPV4: u16 = 0x0800;
+const ETHERTYPE_ARP: u16 = 0x0806;
+const ETHERTYPE_IPV6: u16 = 0x86dd;
+const ETHERTYPE_VLAN: u16 = 0x8100;
+const ETHERTYPE_QINQ: u16 = 0x88a8;
+/// GRE payload type for bridged Ethernet frames (NVGRE, Ethernet-over-GRE).
+const ETHERTYPE_TEB: u16 = 0x6558;
+
+const PROTO_ICMP: u8 = 1;
+const PROTO_IPIP: u8 = 4;
+const PROTO_TCP: u8 = 6;
+const PROTO_UDP: u8 = 17;
+const PROTO_IPV6_IN_IP: u8 = 41;
+const PROTO_GRE: u8 = 47;
+const PROTO_ICMPV6: u8 = 58;
+
+const VXLAN_PORT: u16 = 4789;
+
+/// Nested VLAN tags and tunnels followed before a frame is given up on.
+pub const MAX_LAYERS: usize = 8;
+
+/// Bytes of each UDP payload kept in `Packet::udp_head`.
+const UDP_HEAD: usize = 32;
+
+/// A 48-bit Ethernet hardware address.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct MacAddr(pub [u8; 6]);
+
+impl MacAddr {
+    pub fn from_slice(bytes: &[u8]) -> MacAddr {
+        let mut mac = [0u8; 6];
+        mac.copy_from_slice(&bytes[..6]);
+        MacAddr(mac)
+    }
+
+    /// Parses colon- or dash-separated hex, accepting the unpadded octets BSD `arp` prints.
+    pub fn parse(text: &str) -> Option<MacAddr> {
+        let parts: Vec<&str> = text.split([':', '-']).collect();
+        if parts.len() != 6 {
+            return None;
+        }
+        let mut mac = [0u8; 6];
+        for (octet, part) in mac.iter_mut().zip(&parts) {
+            *octet = u8::from_str_radix(part, 16).ok()?;
+        }
+        Some(MacAddr(mac))
+    }
+}
+
+impl fmt::Display for MacAddr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let m = self.0;
+        write!(f, "{:02x}:{:02x}:{:02x}:{:02x}:{:02x}:{:02x}", m[0], m[1], m[2], m[3], m[4], m[5])
+    }
+}
+
+/// An IPv4-over-Ethernet ARP message.
+#[derive(Debug, Clone)]
+pub struct Arp {
+    /// 1 for a request, 2 for a reply.
+    pub operation: u16,
+    pub sender_mac: MacAddr,
+    pub sender_ip: Ipv4Addr,
+    pub target_ip: Ipv4Addr,
+}
+
+impl Arp {
+    /// An unsolicited announcement of the sender's own binding.
+    pub fn is_gratuitous(&self) -> bool {
+        self.sender_ip == self.target_ip && !self.sender_ip.is_unspecified()
+    }
+}
+
+/// An ICMP or ICMPv6 message.
+#[derive(Debug, Clone)]
+pub struct Icmp {
+    pub v6: bool,
+    pub kind: u8,
+    pub code: u8,
+    /// Everything after the type, code and checksum fields.
+    pub body: Vec<u8>,
+}
+
+/// What went wrong according to an ICMP error message.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum IcmpError {
+    NetUnreachable,
+    HostUnreachable,
+    ProtocolUnreachable,
+    PortUnreachable,
+    /// Fragmentation needed (IPv4) or packet too big (IPv6), with the next-hop MTU if given.
+    TooBig(Option<u32>),
+    AdminProhibited,
+    TtlExceeded,
+    ReassemblyTimeout,
+    OtherUnreachable(u8),
+}
+
+impl IcmpError {
+    pub fn name(&self) -> String {
+        match self {
+            IcmpError::NetUnreachable => "network unreachable".to_string(),
+            IcmpError::HostUnreachable => "host unreachable".to_string(),
+            IcmpError::ProtocolUnreachable => "protocol unreachable".to_string(),
+            IcmpError::PortUnreachable => "port unreachable".to_string(),
+            IcmpError::TooBig(Some(mtu)) => format!("fragmentation needed (MTU {})", mtu),
+            IcmpError::TooBig(None) => "fragmentation needed".to_string(),
+            IcmpError::AdminProhibited => "administratively prohibited".to_string(),
+            IcmpError::TtlExceeded => "TTL exceeded".to_string(),
+            IcmpError::ReassemblyTimeout => "fragment reassembly time exceeded".to_string(),
+            IcmpError::OtherUnreachable(code) => format!("destination unreachable (code {})", code),
+        }
+    }
+
+    /// Describes the error as `reporter` telling us about traffic to `target`.
+    pub fn explain(&self, reporter: &str, target: &str) -> String {
+        match self {
+            IcmpError::NetUnreachable => format!("host {} says it has no route to {}", reporter, target),
+            IcmpError::HostUnreachable => {
+                format!("host {} says {} is unreachable (nothing answered ARP/neighbor discovery)", reporter, target)
+            }
+            IcmpError::ProtocolUnreachable => format!("host {} says it does not speak the protocol used for {}", reporter, target),
+            IcmpError::PortUnreachable => format!("host {} says nothing is listening on {}", reporter, target),
+            IcmpError::TooBig(mtu) => format!(
+                "host {} says packets for {} are too big{}; if this message is filtered, large transfers stall (PMTUD blackhole)",
+                reporter,
+                target,
+                mtu.map(|m| format!(" (next-hop MTU {})", m)).unwrap_or_default()
+            ),
+            IcmpError::AdminProhibited => format!("host {} says {} is administratively filtered", reporter, target),
+            IcmpError::TtlExceeded => format!(
+                "host {} says the TTL expired on the way to {} (normal during traceroute; otherwise a routing loop)",
+                reporter, target
+            ),
+            IcmpError::ReassemblyTimeout => {
+                format!("host {} gave up reassembling fragments for {}; some fragments are being dropped", reporter, target)
+            }
+            IcmpError::OtherUnreachable(code) => format!("host {} says {} is unreachable (code {})", reporter, target, code),
+        }
+    }
+}
+
+/// The packet an ICMP error refers to, recovered from the header quoted in its body.
+#[derive(Debug, Clone)]
+pub struct Quoted {
+    pub dst: IpAddr,
+    pub protocol: u8,
+    pub dst_port: Option<u16>,
+    /// The TOS / traffic class byte as the reporting router received it.
+    pub tos: u8,
+}
+
+impl Quoted {
+    /// e.g. `tcp/443 to 10.0.0.5`, or `10.0.0.5` when no port applies.
+    fn target(&self) -> String {
+        match (self.protocol, self.dst_port) {
+            (PROTO_TCP, Some(port)) => format!("tcp/{} to {}", port, self.dst),
+            (PROTO_UDP, Some(port)) => format!("udp/{} to {}", port, self.dst),
+            _ => self.dst.to_string(),
+        }
+    }
+}
+
+impl Icmp {
+    /// Names the message type where it is a common one, otherwise `type N code M`.
+    pub fn describe(&self) -> String {
+        if let Some(error) = self.error() {
+            return match self.quoted() {
+                Some(quoted) => format!("{} ({})", error.name(), quoted.target()),
+                None => error.name(),
+            };
+        }
+        let name = match (self.v6, self.kind) {
+            (false, 0) | (true, 129) => "echo reply",
+            (false, 8) | (true, 128) => "echo request",
+            (true, 133) => "router solicitation",
+            (true, 134) => "router advertisement",
+            (true, 135) => "neighbor solicitation",
+            (true, 136) => "neighbor advertisement",
+            _ => return format!("type {} code {}", self.kind, self.code),
+        };
+        name.to_string()
+    }
+
+    /// Classifies destination-unreachable, too-big and time-exceeded messages.
+    pub fn error(&self) -> Option<IcmpError> {
+        let mtu = |bytes: &[u8]| Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])).filter(|m| *m > 0);
+        let error = match (self.v6, self.kind, self.code) {
+            (false, 3, 0) | (false, 3, 6) => IcmpError::NetUnreachable,
+            (false, 3, 1) | (false, 3, 7) => IcmpError::HostUnreachable,
+            (false, 3, 2) => IcmpError::ProtocolUnreachable,
+            (false, 3, 3) => IcmpError::PortUnreachable,
+            (false, 3, 4) => IcmpError::TooBig(self.body.get(..4).and_then(|b| mtu(&[0, 0, b[2], b[3]]))),
+            (false, 3, 9) | (false, 3, 10) | (false, 3, 13) => IcmpError::AdminProhibited,
+            (false, 3, code) => IcmpError::OtherUnreachable(code),
+            (false, 11, 0) | (true, 3, 0) => IcmpError::TtlExceeded,
+            (false, 11, 1) | (true, 3, 1) => IcmpError::ReassemblyTimeout,
+            (true, 1, 0) | (true, 1, 6) => IcmpError::NetUnreachable,
+            (true, 1, 1) | (true, 1, 5) => IcmpError::AdminProhibited,
+            (true, 1, 3) => IcmpError::HostUnreachable,
+            (true, 1, 4) => IcmpError::PortUnreachable,
+            (true, 1, code) => IcmpError::OtherUnreachable(code),
+            (true, 2, _) => IcmpError::TooBig(self.body.get(..4).and_then(mtu)),
+            _ => return None,
+        };
+        Some(error)
+    }
+
+    /// Recovers destination, protocol and port from the original header quoted after the first 4 body bytes.
+    pub fn quoted(&self) -> Option<Quoted> {
+        let original = self.body.get(4..)?;
+        let (dst, protocol, transport) = match original.first().map(|b| b >> 4) {
+            Some(4) if original.len() >= 20 => {
+                let header_len = ((original[0] & 0x0f) as usize) * 4;
+                let dst = IpAddr::V4(Ipv4Addr::new(original[16], original[17], original[18], original[19]));
+                (dst, original[9], original.get(header_len..).unwrap_or(&[]))
+            }
+            Some(6) if original.len() >= 40 => {
+                let mut dst = [0u8; 16];
+                dst.copy_from_slice(&original[24..40]);
+                (IpAddr::V6(Ipv6Addr::from(dst)), original[6], &original[40..])
+            }
+            _ => return None,
+        };
+        let dst_port = match protocol {
+            PROTO_TCP | PROTO_UDP if transport.len() >= 4 => Some(u16::from_be_bytes([transport[2], transport[3]])),
+            _ => None,
+        };
+        Some(Quoted { dst, protocol, dst_port, tos: traffic_class(original) })
+    }
+
+    /// Explains an ICMP error in plain words from the point of view of the host that sent it.
+    pub fn diagnosis(&self, reporter: IpAddr) -> Option<String> {
+        let target = self.quoted().map(|q| q.target()).unwrap_or_else(|| "the destination".to_string());
+        Some(self.error()?.explain(&reporter.to_string(), &target))
+    }
+}
+
+/// An encapsulation layer that was unwrapped, with the outer (tunnel endpoint) addresses.
+#[derive(Debug, Clone, Serialize)]
+pub struct Tunnel {
+    /// e.g. `GRE`, `GRE key 7`, `VXLAN vni 42`, `IPIP`.
+    pub kind: String,
+    pub src: IpAddr,
+    pub dst: IpAddr,
+}
+
+/// Options negotiated in a SYN or SYN-ACK.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct TcpHandshake {
+    /// Set on the SYN-ACK.
+    pub ack: bool,
+    pub mss: Option<u16>,
+    pub window_scale: Option<u8>,
+    pub sack_permitted: bool,
+}
+
+impl TcpHandshake {
+    /// Formats the options the way tcpdump does, e.g. `mss 1460,sackOK,wscale 7`.
+    pub fn describe(&self) -> String {
+        let mut options = Vec::new();
+        if let Some(mss) = self.mss {
+            options.push(format!("mss {}", mss));
+        }
+        if self.sack_permitted {
+            options.push("sackOK".to_string());
+        }
+        if let Some(scale) = self.window_scale {
+            options.push(format!("wscale {}", scale));
+        }
+        options.join(",")
+    }
+}
+
+/// Sequence-space fields of a TCP segment, for retransmission and throughput analysis.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct TcpSegment {
+    pub seq: u32,
+    pub ack: u32,
+    /// Raw flag bits: 0x01 FIN, 0x02 SYN, 0x04 RST, 0x10 ACK.
+    pub flags: u8,
+    /// Advertised receive window before scaling.
+    pub window: u16,
+    /// Payload bytes on the wire, even when the capture kept only the headers.
+    pub payload_len: u32,
+    header_len: u32,
+}
+
+/// The fields of a frame the capture table and analyses care about. Addresses and ports are
+/// those of the innermost packet; outer headers are kept in `vlans` and `tunnels`.
+#[derive(Debug, Clone, Default)]
+pub struct Packet {
+    pub src: Option<IpAddr>,
+    pub dst: Option<IpAddr>,
+    /// Sender's hardware address, from the Ethernet or Linux cooked header.
+    pub src_mac: Option<MacAddr>,
+    /// TOS (IPv4) or traffic class (IPv6) byte of the innermost IP header.
+    pub tos: Option<u8>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+    /// Short protocol label, e.g. `TCP`, `UDP`, `ARP`.
+    pub protocol: String,
+    /// Human-readable detail for the table's Info column.
+    pub info: String,
+    pub arp: Option<Arp>,
+    pub icmp: Option<Icmp>,
+    /// Options of a SYN or SYN-ACK.
+    pub handshake: Option<TcpHandshake>,
+    pub tcp: Option<TcpSegment>,
+    /// First bytes of a UDP payload, enough to recognise netdiag's own probes.
+    pub udp_head: Vec<u8>,
+    /// 802.1Q/802.1ad VLAN IDs, outermost first.
+    pub vlans: Vec<u16>,
+    /// Tunnels unwrapped to reach the inner packet, outermost first.
+    pub tunnels: Vec<Tunnel>,
+}
+
+impl Packet {
+    /// `addr:port`, `addr`, or `-` for frames without an IP source.
+    pub fn source(&self) -> String {
+        endpoint(self.src, self.src_port)
+    }
+
+    pub fn destination(&self) -> String {
+        endpoint(self.dst, self.dst_port)
+    }
+
+    /// Describes VLAN tags and tunnel layers, e.g. `[vlan 10] [GRE 198.51.100.1 → 198.51.100.2]`.
+    pub fn encapsulation(&self) -> String {
+        let vlans = self.vlans.iter().map(|id| format!("[vlan {}]", id));
+        let tunnels = self.tunnels.iter().map(|t| format!("[{} {} → {}]", t.kind, t.src, t.dst));
+        vlans.chain(tunnels).collect::<Vec<_>>().join(" ")
+    }
+
+    /// Moves the current addresses into a new outer tunnel layer before decoding the inner packet.
+    fn push_tunnel(&mut self, kind: String) {
+        if let (Some(src), Some(dst)) = (self.src, self.dst) {
+            self.tunnels.push(Tunnel { kind, src, dst });
+        }
+        self.src_port = None;
+        self.dst_port = None;
+    }
+}
+
+fn endpoint(addr: Option<IpAddr>, port: Option<u16>) -> String {
+    match (addr, port) {
+        (Some(IpAddr::V6(a)), Some(p)) => format!("[{}]:{}", a, p),
+        (Some(a), Some(p)) => format!("{}:{}", a, p),
+        (Some(a), None) => a.to_string(),
+        _ => "-".to_string(),
+    }
+}
+
+/// Decodes a captured frame of the given pcap link type. Unknown or truncated layers are
+/// reported in `protocol`/`info` rather than failing.
+pub fn decode(linktype: u32, data: &[u8]) -> Packet {
+    let mut packet = Packet::default();
+    match linktype {
+        pcap::LINKTYPE_ETHERNET => decode_ethernet(&mut packet, data, 0),
+        pcap::LINKTYPE_LINUX_SLL if data.len() >= 16 => {
+            // Address length 6 means the sender address is a MAC.
+            if u16::from_be_bytes([data[4], data[5]]) == 6 {
+                packet.src_mac = Some(MacAddr::from_slice(&data[6..12]));
+            }
+            decode_ethertype(&mut packet, u16::from_be_bytes([data[14], data[15]]), &data[16..], 0)
+        }
+        pcap::LINKTYPE_NULL if data.len() >= 4 => decode_ip(&mut packet, &data[4..], 0),
+        pcap::LINKTYPE_RAW => decode_ip(&mut packet, data, 0),
+        _ => {
+            packet.protocol = "?".to_string();
+            packet.info = format!("link type {}, {} bytes", linktype, data.len());
+        }
+    }
+    packet
+}
+
+fn decode_ethernet(packet: &mut Packet, data: &[u8], depth: usize) {
+    if data.len() < 14 {
+        packet.protocol = "ETH".to_string();
+        packet.info = "truncated Ethernet header".to_string();
+        return;
+    }
+    packet.src_mac = Some(MacAddr::from_slice(&data[6..12]));
+    decode_ethertype(packet, u16::from_be_bytes([data[12], data[13]]), &data[14..], depth)
+}
+
+/// Whether `depth` nested VLAN tags or tunnels is too many to follow; a crafted frame could
+/// otherwise nest them until the stack overflows.
+fn too_deep(packet: &mut Packet, depth: usize) -> bool {
+    if depth <= MAX_LAYERS {
+        return false;
+    }
+    packet.protocol = "?".to_string();
+    packet.info = format!("more than {} nested VLAN tags or tunnels", MAX_LAYERS);
+    true
+}
+
+fn decode_ethertype(packet: &mut Packet, ethertype: u16, payload: &[u8], depth: usize) {
+    if too_deep(packet, depth) {
+        return;
+    }
+    match ethertype {
+        ETHERTYPE_VLAN | ETHERTYPE_QINQ if payload.len() >= 4 => {
+            packet.vlans.push(u16::from_be_bytes([payload[0], payload[1]]) & 0x0fff);
+            decode_ethertype(packet, u16::from_be_bytes([payload[2], payload[3]]), &payload[4..], depth + 1)
+        }
+        ETHERTYPE_IPV4 | ETHERTYPE_IPV6 => decode_ip(packet, payload, depth),
+        ETHERTYPE_ARP => decode_arp(packet, payload),
+        other => {
+            packet.protocol = "ETH".to_string();
+            packet.info = format!("ethertype 0x{:04x}", other);
+        }
+    }
+}
+
+fn decode_arp(packet: &mut Packet, data: &[u8]) {
+    packet.protocol = "ARP".to_string();
+    // Only Ethernet/IPv4 ARP: hardware length 6, protocol length 4.
+    if data.len() < 28 || data[4] != 6 || data[5] != 4 {
+        packet.info = "malformed or non-IPv4 ARP".to_string();
+        return;
+    }
+    let arp = Arp {
+        operation: u16::from_be_bytes([data[6], data[7]]),
+        sender_mac: MacAddr::from_slice(&data[8..14]),
+        sender_ip: Ipv4Addr::new(data[14], data[15], data[16], data[17]),
+        target_ip: Ipv4Addr::new(data[24], data[25], data[26], data[27]),
+    };
+    packet.info = match arp.operation {
+        1 if arp.is_gratuitous() => format!("gratuitous {} is-at {}", arp.sender_ip, arp.sender_mac),
+        1 => format!("who-has {} tell {}", arp.target_ip, arp.sender_ip),
+        2 => format!("{} is-at {}", arp.sender_ip, arp.sender_mac),
+        op => format!("operation {}", op),
+    };
+    packet.src = Some(IpAddr::V4(arp.sender_ip));
+    packet.dst = Some(IpAddr::V4(arp.target_ip));
+    packet.arp = Some(arp);
+}
+
+/// TOS byte of an IPv4 header, or the traffic class of an IPv6 one; the DSCP is the top six bits.
+fn traffic_class(header: &[u8]) -> u8 {
+    match header.first().map(|b| b >> 4) {
+        Some(6) if header.len() >= 2 => (header[0] & 0x0f) << 4 | header[1] >> 4,
+        _ => header.get(1).cloned().unwrap_or(0),
+    }
+}
+
+fn decode_ip(packet: &mut Packet, data: &[u8], depth: usize) {
+    if too_deep(packet, depth) {
+        return;
+    }
+    let version = data.first().map(|b| b >> 4);
+    // `wire` is the payload length the IP header declares, which survives header-only captures.
+    let (protocol, payload, wire) = match version {
+        Some(4) if data.len() >= 20 => {
+            let header_len = (((data[0] & 0x0f) as usize) * 4).clamp(20, data.len());
+            let end = (u16::from_be_bytes([data[2], data[3]]) as usize).clamp(header_len, data.len());
+            packet.src = Some(IpAddr::V4(Ipv4Addr::new(data[12], data[13], data[14], data[15])));
+            packet.dst = Some(IpAddr::V4(Ipv4Addr::new(data[16], data[17], data[18], data[19])));
+            packet.tos = Some(traffic_class(data));
+            let wire = (u16::from_be_bytes([data[2], data[3]]) as usize).saturating_sub(header_len);
+            (data[9], &data[header_len..end], wire)
+        }
+        Some(6) if data.len() >= 40 => {
+            let mut src = [0u8; 16];
+            let mut dst = [0u8; 16];
+            src.copy_from_slice(&data[8..24]);
+            dst.copy_from_slice(&data[24..40]);
+            packet.src = Some(IpAddr::V6(Ipv6Addr::from(src)));
+            packet.dst = Some(IpAddr::V6(Ipv6Addr::from(dst)));
+            packet.tos = Some(traffic_class(data));
+            (data[6], &data[40..], u16::from_be_bytes([data[4], data[5]]) as usize)
+        }
+        _ => {
+            packet.protocol = "IP".to_string();
+            packet.info = "truncated IP header".to_string();
+            return;
+        }
+    };
+    decode_transport(packet, protocol, payload, depth);
+    if protocol == PROTO_TCP {
+        if let Some(tcp) = packet.tcp.as_mut() {
+            tcp.payload_len = (wire as u32).saturating_sub(tcp.header_len).max(tcp.payload_len);
+        }
+    }
+}
+
+fn decode_transport(packet: &mut Packet, protocol: u8, data: &[u8], depth: usize) {
+    match protocol {
+        PROTO_TCP if data.len() >= 20 => {
+            packet.protocol = "TCP".to_string();
+            packet.src_port = Some(u16::from_be_bytes([data[0], data[1]]));
+            packet.dst_port = Some(u16::from_be_bytes([data[2], data[3]]));
+            let header_len = ((data[12] >> 4) as usize) * 4;
+            packet.info = format!("[{}] len {}", tcp_flags(data[13]), data.len().saturating_sub(header_len));
+            packet.tcp = Some(TcpSegment {
+                seq: u32::from_be_bytes([data[4], data[5], data[6], data[7]]),
+                ack: u32::from_be_bytes([data[8], data[9], data[10], data[11]]),
+                flags: data[13],
+                window: u16::from_be_bytes([data[14], data[15]]),
+                payload_len: data.len().saturating_sub(header_len) as u32,
+                header_len: header_len as u32,
+            });
+            if data[13] & 0x02 != 0 {
+                let handshake = tcp_options(data[13] & 0x10 != 0, data.get(20..header_len).unwrap_or(&[]));
+                packet.info = format!("{} <{}>", packet.info, handshake.describe());
+                packet.handshake = Some(handshake);
+            }
+        }
+        PROTO_IPIP | PROTO_IPV6_IN_IP => {
+            packet.push_tunnel(if protocol == PROTO_IPIP { "IPIP" } else { "6in4" }.to_string());
+            decode_ip(packet, data, depth + 1);
+        }
+        PROTO_GRE if data.len() >= 4 => decode_gre(packet, data, depth + 1),
+        // VXLAN: 8-byte header with the I flag set, then a full Ethernet frame.
+        PROTO_UDP if data.len() >= 16 + 14 && u16::from_be_bytes([data[2], data[3]]) == VXLAN_PORT && data[8] & 0x08 != 0 => {
+            let vni = u32::from_be_bytes([0, data[12], data[13], data[14]]);
+            packet.push_tunnel(format!("VXLAN vni {}", vni));
+            decode_ethernet(packet, &data[16..], depth + 1);
+        }
+        PROTO_UDP if data.len() >= 8 => {
+            packet.protocol = "UDP".to_string();
+            packet.src_port = Some(u16::from_be_bytes([data[0], data[1]]));
+            packet.dst_port = Some(u16::from_be_bytes([data[2], data[3]]));
+            packet.info = format!("len {}", data.len() - 8);
+            packet.udp_head = data[8..].iter().take(UDP_HEAD).copied().collect();
+        }
+        PROTO_ICMP | PROTO_ICMPV6 if data.len() >= 4 => {
+            let v6 = protocol == PROTO_ICMPV6;
+            let icmp = Icmp { v6, kind: data[0], code: data[1], body: data[4..].to_vec() };
+            packet.protocol = if v6 { "ICMPv6" } else { "ICMP" }.to_string();
+            packet.info = icmp.describe();
+            packet.icmp = Some(icmp);
+        }
+        other => {
+            packet.protocol = "IP".to_string();
+            packet.info = format!("protocol {}", other);
+        }
+    }
+}
+
+/// Unwraps a GRE header (RFC 2784/2890), skipping the optional checksum, key and sequence fields.
+fn decode_gre(packet: &mut Packet, data: &[u8], depth: usize) {
+    let flags = u16::from_be_bytes([data[0], data[1]]);
+    let ethertype = u16::from_be_bytes([data[2], data[3]]);
+    let mut offset = 4;
+    if flags & 0x8000 != 0 {
+        offset += 4;
+    }
+    let key = if flags & 0x2000 != 0 && data.len() >= offset + 4 {
+        offset += 4;
+        Some(u32::from_be_bytes([data[offset - 4], data[offset - 3], data[offset - 2], data[offset - 1]]))
+    } else {
+        None
+    };
+    if flags & 0x1000 != 0 {
+        offset += 4;
+    }
+    packet.push_tunnel(match key {
+        Some(key) => format!("GRE key {}", key),
+        None => "GRE".to_string(),
+    });
+    let inner = data.get(offset..).unwrap_or(&[]);
+    if ethertype == ETHERTYPE_TEB {
+        decode_ethernet(packet, inner, depth);
+    } else {
+        decode_ethertype(packet, ethertype, inner, depth);
+    }
+}
+
+/// Parses the MSS, window scale and SACK-permitted options of a SYN or SYN-ACK.
+fn tcp_options(ack: bool, mut options: &[u8]) -> TcpHandshake {
+    let mut handshake = TcpHandshake { ack, ..TcpHandshake::default() };
+    while let Some(&kind) = options.first() {
+        match kind {
+            0 => break,
+            1 => {
+                options = &options[1..];
+                continue;
+            }
+            _ => {}
+        }
+        let len = options.get(1).map_or(0, |&l| l as usize);
+        if len < 2 || len > options.len() {
+            break;
+        }
+        match (kind, len) {
+            (2, 4) => handshake.mss = Some(u16::from_be_bytes([options[2], options[3]])),
+            (3, 3) => handshake.window_scale = Some(options[2]),
+            (4, 2) => handshake.sack_permitted = true,
+            _ => {}
+        }
+        options = &options[len..];
+    }
+    handshake
+}
+
+/// Renders TCP flags the way tcpdump does: `S`, `S.`, `P.`, `F.`, `R`.
+fn tcp_flags(flags: u8) -> String {
+    let mut out = String::new();
+    for (bit, name) in [(0x02, 'S'), (0x01, 'F'), (0x04, 'R'), (0x08, 'P'), (0x20, 'U')] {
+        if flags & bit != 0 {
+            out.push(name);
+        }
+    }
+    if flags & 0x10 != 0 {
+        out.push('.');
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ipv4(protocol: u8, payload: &[u8]) -> Vec<u8> {
+        let mut header = vec![0x45, 0x10, 0, 0, 0, 0, 0, 0, 64, protocol, 0, 0, 192, 0, 2, 1, 198, 51, 100, 2];
+        header[2..4].copy_from_slice(&((20 + payload.len()) as u16).to_be_bytes());
+        header.extend_from_slice(payload);
+        header
+    }
+
+    fn ethernet(ethertype: u16, payload: &[u8]) -> Vec<u8> {
+        let mut frame = vec![0xff; 6];
+        frame.extend_from_slice(&[0x02, 0, 0, 0, 0, 1]);
+        frame.extend_from_slice(&ethertype.to_be_bytes());
+        frame.extend_from_slice(payload);
+        frame
+    }
+
+    /// A SYN from port 40000 to 443 carrying an MSS option.
+    fn syn() -> Vec<u8> {
+        let mut tcp = vec![0x9c, 0x40, 0x01, 0xbb, 0, 0, 0, 1, 0, 0, 0, 0, 0x60, 0x02, 0xff, 0xff, 0, 0, 0, 0];
+        tcp.extend_from_slice(&[2, 4, 0x05, 0xb4]);
+        tcp
+    }
+
+    /// `tags` VLAN headers around an IPv4 SYN.
+    fn vlans(tags: usize) -> Vec<u8> {
+        let mut payload = Vec::new();
+        for id in 0..tags {
+            let inner = if id + 1 == tags { ETHERTYPE_IPV4 } else { ETHERTYPE_VLAN };
+            payload.extend_from_slice(&(id as u16 + 1).to_be_bytes());
+            payload.extend_from_slice(&inner.to_be_bytes());
+        }
+        payload.extend(ipv4(PROTO_TCP, &syn()));
+        ethernet(ETHERTYPE_VLAN, &payload)
+    }
+
+    #[test]
+    fn decodes_a_tcp_syn() {
+        let packet = decode(pcap::LINKTYPE_ETHERNET, &ethernet(ETHERTYPE_IPV4, &ipv4(PROTO_TCP, &syn())));
+        assert_eq!(packet.protocol, "TCP");
+        assert_eq!(packet.source(), "192.0.2.1:40000");
+        assert_eq!(packet.destination(), "198.51.100.2:443");
+        assert_eq!(packet.src_mac, MacAddr::parse("02:00:00:00:00:01"));
+        assert_eq!(packet.tos, Some(0x10));
+        assert_eq!(packet.handshake.unwrap().mss, Some(1460));
+        assert_eq!(packet.info, "[S] len 0 <mss 1460>");
+    }
+
+    #[test]
+    fn reports_truncated_frames() {
+        let frame = ethernet(ETHERTYPE_IPV4, &ipv4(PROTO_TCP, &syn()));
+        for len in 0..frame.len() {
+            // Must not panic at any cut.
+            decode(pcap::LINKTYPE_ETHERNET, &frame[..len]);
+        }
+        assert_eq!(decode(pcap::LINKTYPE_ETHERNET, &frame[..10]).info, "truncated Ethernet header");
+        assert_eq!(decode(pcap::LINKTYPE_ETHERNET, &frame[..30]).info, "truncated IP header");
+        assert_eq!(decode(pcap::LINKTYPE_ETHERNET, &frame[..40]).info, "protocol 6");
+        assert_eq!(decode(pcap::LINKTYPE_RAW, &[]).info, "truncated IP header");
+        assert_eq!(decode(pcap::LINKTYPE_LINUX_SLL, &[0; 8]).protocol, "?");
+        assert_eq!(decode(pcap::LINKTYPE_ETHERNET, &ethernet(ETHERTYPE_ARP, &[0; 27])).info, "malformed or non-IPv4 ARP");
+    }
+
+    #[test]
+    fn tolerates_malformed_headers() {
+        // IPv4 header length and total length that disagree with the frame.
+        let mut packet = ipv4(PROTO_TCP, &syn());
+        packet[0] = 0x4f;
+        packet[2..4].copy_from_slice(&0xffffu16.to_be_bytes());
+        decode(pcap::LINKTYPE_RAW, &packet);
+        // A TCP data offset past the end of the segment, and an option with a zero length.
+        let mut tcp = syn();
+        tcp[12] = 0xf0;
+        decode(pcap::LINKTYPE_RAW, &ipv4(PROTO_TCP, &tcp));
+        let mut tcp = syn();
+        tcp[20..24].copy_from_slice(&[2, 0, 0, 0]);
+        assert_eq!(decode(pcap::LINKTYPE_RAW, &ipv4(PROTO_TCP, &tcp)).handshake.unwrap().mss, None);
+        // GRE with every optional field flagged but none present.
+        let packet = decode(pcap::LINKTYPE_RAW, &ipv4(PROTO_GRE, &[0xb0, 0, 0x08, 0x00]));
+        assert_eq!(packet.tunnels.len(), 1);
+        assert_eq!(packet.info, "truncated IP header");
+    }
+
+    #[test]
+    fn follows_up_to_max_layers() {
+        let packet = decode(pcap::LINKTYPE_ETHERNET, &vlans(MAX_LAYERS));
+        assert_eq!(packet.protocol, "TCP");
+        assert_eq!(packet.vlans.len(), MAX_LAYERS);
+
+        let packet = decode(pcap::LINKTYPE_ETHERNET, &vlans(MAX_LAYERS + 1));
+        assert_eq!(packet.protocol, "?");
+        assert_eq!(packet.info, format!("more than {} nested VLAN tags or tunnels", MAX_LAYERS));
+    }
+
+    #[test]
+    fn stops_deeply_nested_tunnels() {
+        let mut packet = ipv4(PROTO_TCP, &syn());
+        for _ in 0..10_000 {
+            packet = ipv4(PROTO_IPIP, &packet);
+        }
+        let decoded = decode(pcap::LINKTYPE_RAW, &packet);
+        assert_eq!(decoded.protocol, "?");
+        assert_eq!(decoded.tunnels.len(), MAX_LAYERS + 1);
+    }
+}
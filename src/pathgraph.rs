@@ -0,0 +1,229 @@
+use std::fs;
+use std::net::IpAddr;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use crate::dns;
+use crate::netinfo;
+use crate::traceroute::Trace;
+
+/// Graph languages a path can be exported to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Dot,
+    Mermaid,
+}
+
+impl Format {
+    /// Picks the format from the file extension: `.mmd`, `.mermaid` and `.md` are Mermaid, the rest DOT.
+    pub fn from_path(path: &str) -> Format {
+        match Path::new(path).extension().and_then(|e| e.to_str()).map(|e| e.to_ascii_lowercase()).as_deref() {
+            Some("mmd") | Some("mermaid") | Some("md") => Format::Mermaid,
+            _ => Format::Dot,
+        }
+    }
+}
+
+/// Origin AS of an address, from Team Cymru's IP-to-ASN DNS service.
+#[derive(Debug, Clone)]
+pub struct Asn {
+    pub number: u32,
+    pub name: Option<String>,
+}
+
+/// One router in the merged graph. Hops that did not answer are keyed by the last hop that did
+/// and their TTL, so traces sharing a silent router still merge.
+#[derive(Debug)]
+struct Node {
+    id: String,
+    addr: Option<String>,
+    rtts: Vec<f64>,
+    asn: Option<Asn>,
+    /// Targets whose trace ends at this node.
+    targets: Vec<String>,
+}
+
+/// Hops of several traces merged at shared addresses.
+#[derive(Debug, Default)]
+struct Graph {
+    nodes: Vec<Node>,
+    edges: Vec<(String, String)>,
+}
+
+/// Merges `traces` into one graph, looks up hop ASNs and writes it to `path`.
+pub fn export(path: &str, format: Format, traces: &[(String, Trace)]) -> Result<usize, String> {
+    let mut graph = build(traces);
+    annotate_asns(&mut graph);
+    let text = match format {
+        Format::Dot => render_dot(&graph),
+        Format::Mermaid if path.ends_with(".md") => format!("```mermaid\n{}```\n", render_mermaid(&graph)),
+        Format::Mermaid => render_mermaid(&graph),
+    };
+    fs::write(path, text).map_err(|e| e.to_string())?;
+    Ok(graph.nodes.len())
+}
+
+/// Builds nodes and de-duplicated edges from the local host through each trace.
+fn build(traces: &[(String, Trace)]) -> Graph {
+    let mut graph = Graph::default();
+    graph.nodes.push(Node { id: "src".to_string(), addr: None, rtts: Vec::new(), asn: None, targets: Vec::new() });
+    for (host, trace) in traces {
+        let mut previous = "src".to_string();
+        let mut answered = "src".to_string();
+        for hop in &trace.hops {
+            let id = match &hop.addr {
+                Some(addr) => format!("h_{}", addr.replace(['.', ':'], "_")),
+                None => format!("x_{}_ttl{}", answered, hop.ttl),
+            };
+            if hop.addr.is_some() {
+                answered = id.clone();
+            }
+            match graph.nodes.iter_mut().find(|n| n.id == id) {
+                Some(node) => node.rtts.extend(&hop.rtts),
+                None => graph.nodes.push(Node { id: id.clone(), addr: hop.addr.clone(), rtts: hop.rtts.clone(), asn: None, targets: Vec::new() }),
+            }
+            let edge = (previous, id.clone());
+            if !graph.edges.contains(&edge) {
+                graph.edges.push(edge);
+            }
+            previous = id;
+        }
+        if let Some(node) = graph.nodes.iter_mut().find(|n| n.id == previous && n.id != "src") {
+            node.targets.push(host.clone());
+        }
+    }
+    graph
+}
+
+/// Looks up the origin AS of every public hop address concurrently.
+fn annotate_asns(graph: &mut Graph) {
+    let server = netinfo::dns_servers().iter().find_map(|s| s.parse::<IpAddr>().ok()).unwrap_or(IpAddr::from([1, 1, 1, 1]));
+    let lookups: Vec<_> = graph
+        .nodes
+        .iter()
+        .enumerate()
+        .filter_map(|(i, n)| Some((i, n.addr.as_ref()?.parse::<IpAddr>().ok()?)))
+        .filter(|(_, addr)| is_public(addr))
+        .map(|(i, addr)| (i, thread::spawn(move || lookup_asn(server, addr))))
+        .collect();
+    for (i, lookup) in lookups {
+        graph.nodes[i].asn = lookup.join().ok().flatten();
+    }
+}
+
+/// Private, loopback and link-local hops have no public origin AS.
+pub fn is_public(addr: &IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(v4) => {
+            // 100.64.0.0/10 is carrier-grade NAT space.
+            let cgnat = v4.octets()[0] == 100 && (v4.octets()[1] & 0xc0) == 64;
+            !(v4.is_private() || v4.is_loopback() || v4.is_link_local() || cgnat)
+        }
+        IpAddr::V6(v6) => v6.segments()[0] & 0xe000 == 0x2000,
+    }
+}
+
+/// Queries `<reversed>.origin.asn.cymru.com`, then `AS<n>.asn.cymru.com` for the AS name.
+pub fn lookup_asn(server: IpAddr, addr: IpAddr) -> Option<Asn> {
+    let name = match addr {
+        IpAddr::V4(v4) => {
+            let o = v4.octets();
+            format!("{}.{}.{}.{}.origin.asn.cymru.com", o[3], o[2], o[1], o[0])
+        }
+        IpAddr::V6(v6) => {
+            let nibbles: String = v6.octets().iter().rev().map(|b| format!("{:x}.{:x}.", b & 0xf, b >> 4)).collect();
+            format!("{}origin6.asn.cymru.com", nibbles)
+        }
+    };
+    // Answers look like "15169 | 8.8.8.0/24 | US | arin | 2000-03-30"; multi-origin prefixes list several ASNs.
+    let txt = |name: &str| dns::query(server, name, dns::TYPE_TXT, Duration::from_secs(2)).ok()?.values(dns::TYPE_TXT).into_iter().next();
+    let number: u32 = txt(&name)?.split('|').next()?.split_whitespace().next()?.parse().ok()?;
+    let name = txt(&format!("AS{}.asn.cymru.com", number)).and_then(|t| t.rsplit('|').next().map(|n| n.trim().to_string()));
+    Some(Asn { number, name })
+}
+
+/// Multi-line node label: address, mean RTT and AS, plus the targets reached there.
+fn label(node: &Node) -> Vec<String> {
+    if node.id == "src" {
+        return vec!["this host".to_string()];
+    }
+    let mut lines = vec![node.addr.clone().unwrap_or_else(|| "*".to_string())];
+    if !node.rtts.is_empty() {
+        lines.push(format!("{:.1} ms", node.rtts.iter().sum::<f64>() / node.rtts.len() as f64));
+    }
+    if let Some(asn) = &node.asn {
+        match &asn.name {
+            Some(name) => lines.push(format!("AS{} {}", asn.number, name)),
+            None => lines.push(format!("AS{}", asn.number)),
+        }
+    }
+    for target in &node.targets {
+        lines.push(format!("→ {}", target));
+    }
+    lines
+}
+
+/// ASNs present in the graph, in first-seen order, for grouping nodes into clusters.
+fn asn_groups(graph: &Graph) -> Vec<u32> {
+    let mut groups = Vec::new();
+    for asn in graph.nodes.iter().filter_map(|n| n.asn.as_ref()) {
+        if !groups.contains(&asn.number) {
+            groups.push(asn.number);
+        }
+    }
+    groups
+}
+
+/// Renders Graphviz DOT with one cluster per AS.
+fn render_dot(graph: &Graph) -> String {
+    let escape = |s: &str| s.replace('\\', "\\\\").replace('"', "\\\"");
+    let node_line = |node: &Node, indent: &str| {
+        let style = if node.addr.is_none() && node.id != "src" { ", style=dashed" } else { "" };
+        let label = label(node).iter().map(|l| escape(l)).collect::<Vec<_>>().join("\\n");
+        format!("{}{} [label=\"{}\"{}];\n", indent, node.id, label, style)
+    };
+    let mut out = String::from("digraph netdiag_path {\n    rankdir=LR;\n    node [shape=box, fontname=\"Helvetica\"];\n");
+    for number in asn_groups(graph) {
+        out.push_str(&format!("    subgraph cluster_as{} {{\n        label=\"AS{}\";\n        style=rounded;\n", number, number));
+        for node in graph.nodes.iter().filter(|n| n.asn.as_ref().map(|a| a.number) == Some(number)) {
+            out.push_str(&node_line(node, "        "));
+        }
+        out.push_str("    }\n");
+    }
+    for node in graph.nodes.iter().filter(|n| n.asn.is_none()) {
+        out.push_str(&node_line(node, "    "));
+    }
+    for (from, to) in &graph.edges {
+        out.push_str(&format!("    {} -> {};\n", from, to));
+    }
+    out.push_str("}\n");
+    out
+}
+
+/// Renders a Mermaid flowchart with one subgraph per AS.
+fn render_mermaid(graph: &Graph) -> String {
+    let node_line = |node: &Node, indent: &str| {
+        let label = label(node).iter().map(|l| l.replace('"', "#quot;")).collect::<Vec<_>>().join("<br/>");
+        if node.addr.is_none() && node.id != "src" {
+            format!("{}{}([\"{}\"])\n", indent, node.id, label)
+        } else {
+            format!("{}{}[\"{}\"]\n", indent, node.id, label)
+        }
+    };
+    let mut out = String::from("flowchart LR\n");
+    for number in asn_groups(graph) {
+        out.push_str(&format!("    subgraph as{}[\"AS{}\"]\n", number, number));
+        for node in graph.nodes.iter().filter(|n| n.asn.as_ref().map(|a| a.number) == Some(number)) {
+            out.push_str(&node_line(node, "        "));
+        }
+        out.push_str("    end\n");
+    }
+    for node in graph.nodes.iter().filter(|n| n.asn.is_none()) {
+        out.push_str(&node_line(node, "    "));
+    }
+    for (from, to) in &graph.edges {
+        out.push_str(&format!("    {} --> {}\n", from, to));
+    }
+    out
+}
@@ -0,0 +1,174 @@
+use std::io::{self, Read, Write};
+
+/// Link-layer types (`LINKTYPE_*`) the decoder understands.
+pub const LINKTYPE_NULL: u32 = 0;
+pub const LINKTYPE_ETHERNET: u32 = 1;
+pub const LINKTYPE_RAW: u32 = 101;
+pub const LINKTYPE_LINUX_SLL: u32 = 113;
+
+/// One captured frame.
+#[derive(Debug, Clone)]
+pub struct Record {
+    pub ts_sec: u32,
+    /// Sub-second part of the timestamp, always in microseconds.
+    pub ts_usec: u32,
+    /// Length of the frame on the wire; more than `data.len()` when the capture was truncated.
+    pub orig_len: u32,
+    pub data: Vec<u8>,
+}
+
+/// Streaming reader for the classic libpcap file format, in either byte order.
+pub struct Reader<R: Read> {
+    input: R,
+    swapped: bool,
+    nanos: bool,
+    pub linktype: u32,
+}
+
+impl<R: Read> Reader<R> {
+    /// Reads and validates the global header.
+    pub fn new(mut input: R) -> io::Result<Reader<R>> {
+        let mut header = [0u8; 24];
+        input.read_exact(&mut header)?;
+        let (swapped, nanos) = match u32::from_le_bytes([header[0], header[1], header[2], header[3]]) {
+            0xa1b2_c3d4 => (false, false),
+            0xa1b2_3c4d => (false, true),
+            0xd4c3_b2a1 => (true, false),
+            0x4d3c_b2a1 => (true, true),
+            _ => return Err(io::Error::new(io::ErrorKind::InvalidData, "not a pcap file")),
+        };
+        let mut reader = Reader { input, swapped, nanos, linktype: 0 };
+        reader.linktype = reader.u32_at(&header, 20);
+        Ok(reader)
+    }
+
+    fn u32_at(&self, bytes: &[u8], offset: usize) -> u32 {
+        let raw = [bytes[offset], bytes[offset + 1], bytes[offset + 2], bytes[offset + 3]];
+        if self.swapped { u32::from_be_bytes(raw) } else { u32::from_le_bytes(raw) }
+    }
+
+    /// Returns the next record, or `None` at the end of the stream. A last record cut short, as
+    /// a capture killed mid-write leaves behind, also ends the stream rather than failing it.
+    pub fn next_record(&mut self) -> io::Result<Option<Record>> {
+        let mut header = [0u8; 16];
+        match self.input.read_exact(&mut header) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let ts_sec = self.u32_at(&header, 0);
+        let frac = self.u32_at(&header, 4);
+        let incl_len = self.u32_at(&header, 8);
+        let orig_len = self.u32_at(&header, 12);
+        if incl_len > 256 * 1024 {
+            return Err(io::Error::new(io::ErrorKind::InvalidData, "implausible pcap record length"));
+        }
+        let mut data = vec![0u8; incl_len as usize];
+        match self.input.read_exact(&mut data) {
+            Ok(()) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e),
+        }
+        let ts_usec = if self.nanos { frac / 1000 } else { frac };
+        Ok(Some(Record { ts_sec, ts_usec, orig_len, data }))
+    }
+}
+
+/// Writer for the classic libpcap format, little-endian with microsecond timestamps.
+pub struct Writer<W: Write> {
+    output: W,
+}
+
+impl<W: Write> Writer<W> {
+    /// Writes the global header for frames of `linktype`.
+    pub fn new(mut output: W, linktype: u32) -> io::Result<Writer<W>> {
+        let mut header = Vec::with_capacity(24);
+        header.extend_from_slice(&0xa1b2_c3d4u32.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        // Timezone offset and timestamp accuracy, both always zero.
+        header.extend_from_slice(&[0u8; 8]);
+        header.extend_from_slice(&(256 * 1024u32).to_le_bytes());
+        header.extend_from_slice(&linktype.to_le_bytes());
+        output.write_all(&header)?;
+        Ok(Writer { output })
+    }
+
+    /// Appends one record, keeping its original wire length.
+    pub fn write_record(&mut self, record: &Record) -> io::Result<()> {
+        let mut header = [0u8; 16];
+        header[0..4].copy_from_slice(&record.ts_sec.to_le_bytes());
+        header[4..8].copy_from_slice(&record.ts_usec.to_le_bytes());
+        header[8..12].copy_from_slice(&(record.data.len() as u32).to_le_bytes());
+        header[12..16].copy_from_slice(&record.orig_len.max(record.data.len() as u32).to_le_bytes());
+        self.output.write_all(&header)?;
+        self.output.write_all(&record.data)
+    }
+
+    /// Flushes buffered records to the underlying writer.
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.output.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(ts_sec: u32, data: &[u8]) -> Record {
+        Record { ts_sec, ts_usec: 250, orig_len: data.len() as u32, data: data.to_vec() }
+    }
+
+    fn capture(records: &[Record]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        {
+            let mut writer = Writer::new(&mut bytes, LINKTYPE_ETHERNET).unwrap();
+            for record in records {
+                writer.write_record(record).unwrap();
+            }
+        }
+        bytes
+    }
+
+    #[test]
+    fn reads_back_written_records() {
+        let bytes = capture(&[record(1, b"first"), record(2, b"second")]);
+        let mut reader = Reader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.linktype, LINKTYPE_ETHERNET);
+        let first = reader.next_record().unwrap().unwrap();
+        assert_eq!((first.ts_sec, first.ts_usec, first.data.as_slice()), (1, 250, &b"first"[..]));
+        assert_eq!(reader.next_record().unwrap().unwrap().data, b"second");
+        assert!(reader.next_record().unwrap().is_none());
+    }
+
+    #[test]
+    fn reads_big_endian_nanosecond_files() {
+        let mut bytes = vec![0xa1, 0xb2, 0x3c, 0x4d, 0, 2, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0, 0, 0, 101];
+        bytes.extend_from_slice(&[0, 0, 0, 7, 0, 0, 0x07, 0xd0, 0, 0, 0, 2, 0, 0, 0, 2, 0x45, 0]);
+        let mut reader = Reader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.linktype, LINKTYPE_RAW);
+        let record = reader.next_record().unwrap().unwrap();
+        assert_eq!((record.ts_sec, record.ts_usec, record.data.len()), (7, 2, 2));
+    }
+
+    #[test]
+    fn truncated_last_record_ends_the_stream() {
+        let bytes = capture(&[record(1, b"complete"), record(2, b"cut short")]);
+        // Cut inside the last record's data, then inside its header.
+        for cut in [bytes.len() - 3, bytes.len() - b"cut short".len() - 5] {
+            let mut reader = Reader::new(&bytes[..cut]).unwrap();
+            assert_eq!(reader.next_record().unwrap().unwrap().data, b"complete");
+            assert!(reader.next_record().unwrap().is_none());
+        }
+    }
+
+    #[test]
+    fn rejects_bad_headers_and_lengths() {
+        assert_eq!(Reader::new(&[0u8; 24][..]).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        assert_eq!(Reader::new(&[0xd4, 0xc3, 0xb2, 0xa1][..]).err().unwrap().kind(), io::ErrorKind::UnexpectedEof);
+        let mut bytes = capture(&[]);
+        bytes.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 0, 0xff, 0xff, 0xff, 0x7f, 0, 0, 0, 0]);
+        let mut reader = Reader::new(&bytes[..]).unwrap();
+        assert_eq!(reader.next_record().unwrap_err().kind(), io::ErrorKind::InvalidData);
+    }
+}
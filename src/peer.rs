@@ -0,0 +1,631 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{BufRead, BufReader, Cursor, Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use crate::capture::{self, Backend, CaptureOptions};
+use crate::chart;
+use crate::clock;
+use crate::interrupt;
+use crate::loss;
+use crate::packet;
+use crate::pcap;
+use crate::privileges;
+use crate::resolver;
+use crate::scheduler;
+use crate::route_lookup;
+use crate::{colorize, data_dir, random_u64};
+
+/// Control port each side listens on; the test flow uses the same UDP port.
+pub const DEFAULT_PORT: u16 = 7475;
+
+/// Bumped whenever the messages below change, so mismatched versions refuse to pair.
+const PROTOCOL_VERSION: u32 = 1;
+
+/// Marks netdiag's test datagrams: magic, direction, run id, sequence number, send time.
+const MAGIC: &[u8; 4] = b"NDPF";
+const PROBE_LEN: usize = 64;
+
+/// Round trips used to estimate the clock offset; the one with the least delay wins.
+const SYNC_ROUNDS: usize = 8;
+
+/// How long before the flow both captures start, and how long they keep running after it.
+const LEAD_IN: Duration = Duration::from_secs(2);
+const GRACE: Duration = Duration::from_secs(1);
+
+/// Characters in the one-way delay sparkline.
+const SPARKLINE_WIDTH: usize = 60;
+
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Largest capture either side sends or accepts; frames past it are left out of the local one.
+const MAX_PCAP_LEN: usize = 64 * 1024 * 1024;
+
+/// Which way a probe travels: from the side that opened the control connection or towards it.
+const FROM_LEADER: u8 = 0;
+const FROM_FOLLOWER: u8 = 1;
+
+/// What to coordinate with the peer.
+pub struct PeerOptions {
+    pub peer: String,
+    pub listen_port: u16,
+    /// Probes per second in each direction.
+    pub rate: u32,
+    pub seconds: u64,
+    /// How long to wait for the peer to come up.
+    pub wait_secs: u64,
+    /// Capture interface; defaults to the one the route to the peer uses.
+    pub interface: Option<String>,
+    pub backend: Backend,
+}
+
+/// Control messages, one JSON object per line.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+enum Message {
+    Hello { version: u32, flow_port: u16 },
+    TimeRequest,
+    /// When the request arrived and when the reply left, on the replying side's clock.
+    TimeReply { received_us: i64, sent_us: i64 },
+    /// Sent by the leader. `start_us` is on the follower's clock; `offset_us` is the follower's
+    /// clock minus the leader's, known to within `uncertainty_us`.
+    Plan { run: u32, start_us: i64, rate: u32, seconds: u64, offset_us: i64, uncertainty_us: i64 },
+    /// Followed on the connection by `pcap_len` bytes of pcap holding this side's probes.
+    Done { sent: u32, delivered: Vec<u32>, captured: bool, capture_drops: Option<u32>, pcap_len: usize },
+}
+
+/// The control connection to the peer.
+struct Control {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+}
+
+impl Control {
+    fn new(stream: TcpStream) -> Result<Control, String> {
+        let writer = stream.try_clone().map_err(|e| e.to_string())?;
+        Ok(Control { reader: BufReader::new(stream), writer })
+    }
+
+    fn send(&mut self, message: &Message) -> Result<(), String> {
+        let mut line = serde_json::to_string(message).map_err(|e| e.to_string())?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes()).map_err(|e| format!("lost the connection to the peer: {}", e))
+    }
+
+    fn recv(&mut self) -> Result<Message, String> {
+        let mut line = String::new();
+        match self.reader.read_line(&mut line) {
+            Ok(0) => Err("the peer closed the connection".to_string()),
+            Ok(_) => serde_json::from_str(&line).map_err(|e| format!("unexpected message from the peer: {}", e)),
+            Err(e) => Err(format!("lost the connection to the peer: {}", e)),
+        }
+    }
+
+    fn set_timeout(&self, timeout: Option<Duration>) {
+        let _ = self.writer.set_read_timeout(timeout);
+    }
+}
+
+/// One side's view of the test: what it sent and received, and what its capture saw.
+struct Side {
+    sent: u32,
+    delivered: Vec<u32>,
+    captured: bool,
+    capture_drops: Option<u32>,
+    pcap: Vec<u8>,
+}
+
+/// Microseconds since the Unix epoch on this host's clock.
+fn now_us() -> i64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_micros() as i64)
+}
+
+/// Splits `host[:port]` (or `[v6]:port`), defaulting the port.
+fn parse_peer(peer: &str) -> Result<SocketAddr, String> {
+    let (host, port) = match peer.rsplit_once(':') {
+        Some((host, port)) if !host.contains(':') || host.ends_with(']') => {
+            (host, port.parse().map_err(|_| format!("invalid port in --peer {}", peer))?)
+        }
+        _ => (peer, DEFAULT_PORT),
+    };
+    resolver::socket_addr(host, port).map_err(|e| e.to_string())
+}
+
+/// Pairs with the peer, runs the test flow in both directions while both ends capture it, then
+/// correlates the two captures.
+pub fn run(options: &PeerOptions) {
+    println!("\n🤝 {} Two-ended capture with {}\n", colorize("[INFO]", "blue"), colorize(&options.peer, "cyan"));
+    if let Err(e) = run_test(options) {
+        println!("❌ {} {}\n", colorize("[ERROR]", "red"), e);
+    }
+}
+
+fn run_test(options: &PeerOptions) -> Result<(), String> {
+    let peer = parse_peer(&options.peer)?;
+    let unspecified = if peer.is_ipv4() { IpAddr::V4(Ipv4Addr::UNSPECIFIED) } else { IpAddr::V6(Ipv6Addr::UNSPECIFIED) };
+    let listener = TcpListener::bind(SocketAddr::new(unspecified, options.listen_port))
+        .map_err(|e| format!("cannot listen on TCP port {}: {}", options.listen_port, e))?;
+    let flow = UdpSocket::bind(SocketAddr::new(unspecified, options.listen_port))
+        .map_err(|e| format!("cannot bind UDP port {} for the test flow: {}", options.listen_port, e))?;
+
+    println!("🔹 {}", colorize(&format!("Waiting for the peer at {} (up to {}s)", peer, options.wait_secs), "blue"));
+    println!("   Run `netdiag capture --peer <this host>` on the other machine.");
+    let (stream, leader, peer_flow_port) = pair(&listener, peer, Duration::from_secs(options.wait_secs))?;
+    let mut control = Control::new(stream)?;
+    println!("✅ {} Paired with {} ({})", colorize("[SUCCESS]", "green"), peer, if leader { "this side leads" } else { "the peer leads" });
+
+    // The leader measures the clock offset and decides the schedule; the follower answers.
+    let plan = if leader { lead(&mut control, options)? } else { follow(&mut control)? };
+    let (run, start_us, rate, seconds, offset_us, uncertainty_us) = match plan {
+        Message::Plan { run, start_us, rate, seconds, offset_us, uncertainty_us } => (run, start_us, rate, seconds, offset_us, uncertainty_us),
+        _ => return Err("the peer sent no test plan".to_string()),
+    };
+    println!(
+        "   Peer clock offset {:+.3} ms (±{:.3} ms); {} probes/s each way for {}s",
+        offset_us as f64 / 1000.0 * if leader { 1.0 } else { -1.0 },
+        uncertainty_us as f64 / 1000.0,
+        rate,
+        seconds
+    );
+
+    let interface = options
+        .interface
+        .clone()
+        .or_else(|| route_lookup::selected_path(peer.ip()).0)
+        .unwrap_or_else(|| "en0".to_string());
+    let outgoing = if leader { FROM_LEADER } else { FROM_FOLLOWER };
+    let local = run_flow(
+        &flow,
+        SocketAddr::new(peer.ip(), peer_flow_port),
+        &interface,
+        options,
+        (options.listen_port, peer_flow_port),
+        Probe { direction: outgoing, run, seq: 0, sent_us: 0 },
+        start_us,
+        rate,
+        seconds,
+    );
+    if interrupt::interrupted() {
+        return Err("interrupted".to_string());
+    }
+    let remote = exchange(&mut control, &local)?;
+
+    // Every timestamp is put on the leader's clock.
+    let (local_offset, remote_offset) = if leader { (0, offset_us) } else { (offset_us, 0) };
+    let saved = save(&local, &remote, leader);
+    println!();
+    report("This host → peer", &local, local_offset, &remote, remote_offset, outgoing, run, uncertainty_us);
+    report("Peer → this host", &remote, remote_offset, &local, local_offset, 1 - outgoing, run, uncertainty_us);
+    if let Some((local_path, remote_path)) = saved {
+        println!("   Captures saved to {} and {}\n", local_path, remote_path);
+    }
+    Ok(())
+}
+
+/// Connects to the peer while accepting its connection, whichever works first. Both sides
+/// start the same way, so when both connections cross and each handshake times out, a random
+/// pause lets one side connect while the other is accepting. Returns the connection, whether
+/// this side opened it (and so leads), and the peer's flow port.
+fn pair(listener: &TcpListener, peer: SocketAddr, wait: Duration) -> Result<(TcpStream, bool, u16), String> {
+    listener.set_nonblocking(true).map_err(|e| e.to_string())?;
+    let flow_port = listener.local_addr().map_err(|e| e.to_string())?.port();
+    let deadline = Instant::now() + wait;
+    while Instant::now() < deadline && !interrupt::interrupted() {
+        if let Ok((stream, from)) = listener.accept() {
+            if from.ip().to_canonical() != peer.ip().to_canonical() {
+                println!("⚠️  {} Ignoring a connection from {}, which is not the peer", colorize("[WARN]", "yellow"), from);
+            } else if let Ok(port) = handshake(&stream, flow_port) {
+                return Ok((stream, false, port));
+            }
+        }
+        if let Ok(stream) = scheduler::connect(peer, Duration::from_secs(1)) {
+            if let Ok(port) = handshake(&stream, flow_port) {
+                return Ok((stream, true, port));
+            }
+        }
+        interrupt::sleep(Duration::from_millis(200 + random_u64() % 800));
+    }
+    Err(format!("the peer at {} did not answer; is `netdiag capture --peer` running there and TCP port {} open?", peer, peer.port()))
+}
+
+/// Exchanges hellos on a fresh connection and returns the peer's flow port.
+fn handshake(stream: &TcpStream, flow_port: u16) -> Result<u16, String> {
+    stream.set_nonblocking(false).map_err(|e| e.to_string())?;
+    let _ = stream.set_nodelay(true);
+    let mut control = Control::new(stream.try_clone().map_err(|e| e.to_string())?)?;
+    control.set_timeout(Some(HANDSHAKE_TIMEOUT));
+    control.send(&Message::Hello { version: PROTOCOL_VERSION, flow_port })?;
+    let reply = control.recv();
+    control.set_timeout(None);
+    match reply? {
+        Message::Hello { version, flow_port } if version == PROTOCOL_VERSION => Ok(flow_port),
+        Message::Hello { version, .. } => {
+            Err(format!("the peer speaks protocol version {}, this side {}; run the same netdiag version on both", version, PROTOCOL_VERSION))
+        }
+        _ => Err("the peer did not say hello".to_string()),
+    }
+}
+
+/// Estimates the follower's clock offset NTP-style, keeping the round trip with the least
+/// delay, then sends the plan with a start time on the follower's clock.
+fn lead(control: &mut Control, options: &PeerOptions) -> Result<Message, String> {
+    let mut best: Option<(i64, i64)> = None;
+    for _ in 0..SYNC_ROUNDS {
+        let t1 = now_us();
+        control.send(&Message::TimeRequest)?;
+        let (t2, t3) = match control.recv()? {
+            Message::TimeReply { received_us, sent_us } => (received_us, sent_us),
+            _ => return Err("the peer did not answer the clock probe".to_string()),
+        };
+        let t4 = now_us();
+        let delay = (t4 - t1) - (t3 - t2);
+        let offset = ((t2 - t1) + (t3 - t4)) / 2;
+        if best.is_none_or(|(d, _)| delay < d) {
+            best = Some((delay, offset));
+        }
+    }
+    let (delay, offset) = best.ok_or("no clock probes were answered")?;
+    let start = now_us() + LEAD_IN.as_micros() as i64;
+    let (run, rate, seconds) = (random_u64() as u32, options.rate.max(1), options.seconds.max(1));
+    let uncertainty_us = delay / 2;
+    control.send(&Message::Plan { run, start_us: start + offset, rate, seconds, offset_us: offset, uncertainty_us })?;
+    // The follower's start time is on its clock; this side starts at the same instant on its own.
+    Ok(Message::Plan { run, start_us: start, rate, seconds, offset_us: offset, uncertainty_us })
+}
+
+/// Answers clock probes until the plan arrives.
+fn follow(control: &mut Control) -> Result<Message, String> {
+    loop {
+        match control.recv()? {
+            Message::TimeRequest => {
+                let received_us = now_us();
+                control.send(&Message::TimeReply { received_us, sent_us: now_us() })?;
+            }
+            plan @ Message::Plan { .. } => return Ok(plan),
+            _ => return Err("unexpected message while synchronizing clocks".to_string()),
+        }
+    }
+}
+
+/// The fields of a test datagram.
+#[derive(Debug, Clone, Copy)]
+struct Probe {
+    direction: u8,
+    run: u32,
+    seq: u32,
+    sent_us: i64,
+}
+
+impl Probe {
+    fn encode(&self) -> [u8; PROBE_LEN] {
+        let mut bytes = [0u8; PROBE_LEN];
+        bytes[0..4].copy_from_slice(MAGIC);
+        bytes[4] = self.direction;
+        bytes[8..12].copy_from_slice(&self.run.to_be_bytes());
+        bytes[12..16].copy_from_slice(&self.seq.to_be_bytes());
+        bytes[16..24].copy_from_slice(&self.sent_us.to_be_bytes());
+        bytes
+    }
+
+    fn decode(bytes: &[u8]) -> Option<Probe> {
+        if bytes.len() < 24 || &bytes[0..4] != MAGIC {
+            return None;
+        }
+        Some(Probe {
+            direction: bytes[4],
+            run: u32::from_be_bytes([bytes[8], bytes[9], bytes[10], bytes[11]]),
+            seq: u32::from_be_bytes([bytes[12], bytes[13], bytes[14], bytes[15]]),
+            sent_us: i64::from_be_bytes([bytes[16], bytes[17], bytes[18], bytes[19], bytes[20], bytes[21], bytes[22], bytes[23]]),
+        })
+    }
+}
+
+/// Captures on `interface` from now until the flow is over, sending `rate` probes a second to
+/// `to` from `start_us` on and recording which of the peer's probes arrive.
+#[allow(clippy::too_many_arguments)]
+fn run_flow(
+    flow: &UdpSocket,
+    to: SocketAddr,
+    interface: &str,
+    options: &PeerOptions,
+    ports: (u16, u16),
+    template: Probe,
+    start_us: i64,
+    rate: u32,
+    seconds: u64,
+) -> Side {
+    let capture_options = CaptureOptions { interface: interface.to_string(), backend: options.backend, ..CaptureOptions::default() };
+    let filter = format!("udp and (port {} or port {})", ports.0, ports.1);
+    let started = capture::open_source(&capture_options, &filter);
+    let (source, collector) = match started {
+        Ok((source, rx)) => {
+            println!("   Capturing on {}", colorize(interface, "cyan"));
+            (Some(source), Some(thread::spawn(move || collect(rx))))
+        }
+        Err(reason) => {
+            println!("{}", privileges::hint("Packet capture", &reason));
+            println!("   Continuing without a capture: loss is measured end to end only.");
+            (None, None)
+        }
+    };
+
+    let total = rate as u64 * seconds;
+    let end_us = start_us + (seconds as i64) * 1_000_000;
+    let receiver = flow.try_clone().map(|socket| {
+        let incoming = 1 - template.direction;
+        let run = template.run;
+        thread::spawn(move || receive(socket, incoming, run, end_us + GRACE.as_micros() as i64))
+    });
+
+    let wait = start_us - now_us();
+    if wait > 0 {
+        interrupt::sleep(Duration::from_micros(wait as u64));
+    }
+    println!("🔹 {}", colorize(&format!("Sending {} probes to {} while receiving the peer's", total, to), "blue"));
+    // The flow is one scheduled probe; its packets are paced by `rate`, not the rate limit.
+    let _permit = scheduler::acquire(&format!("peer-flow({})", total), to.ip(), Some(to.port()));
+    let interval = 1_000_000 / i64::from(rate.max(1));
+    let mut sent = 0;
+    for seq in 0..total as u32 {
+        if interrupt::interrupted() {
+            break;
+        }
+        let due = start_us + i64::from(seq) * interval;
+        let early = due - now_us();
+        if early > 0 {
+            thread::sleep(Duration::from_micros(early as u64));
+        }
+        let probe = Probe { seq, sent_us: now_us(), ..template };
+        // A failed send counts as sent: the sending host dropped it.
+        let _ = flow.send_to(&probe.encode(), to);
+        sent += 1;
+    }
+    let delivered = receiver.ok().and_then(|r| r.join().ok()).unwrap_or_default();
+
+    let capture_drops = source.and_then(|s| s.stop()).map(|stats| stats.dropped);
+    let (captured, pcap) = match collector.and_then(|c| c.join().ok()) {
+        Some(pcap) => (true, pcap),
+        None => (false, Vec::new()),
+    };
+    Side { sent, delivered, captured, capture_drops, pcap }
+}
+
+/// Reads the peer's probes until `until_us`, returning their sequence numbers in arrival order.
+fn receive(socket: UdpSocket, direction: u8, run: u32, until_us: i64) -> Vec<u32> {
+    let _ = socket.set_read_timeout(Some(Duration::from_millis(100)));
+    let mut seqs = Vec::new();
+    let mut buffer = [0u8; 2048];
+    while now_us() < until_us && !interrupt::interrupted() {
+        if let Ok((n, _)) = socket.recv_from(&mut buffer) {
+            if let Some(probe) = Probe::decode(&buffer[..n]).filter(|p| p.direction == direction && p.run == run) {
+                seqs.push(probe.seq);
+            }
+        }
+    }
+    seqs
+}
+
+/// Keeps the captured frames that carry probes, as a pcap file of at most `MAX_PCAP_LEN` bytes.
+fn collect(rx: mpsc::Receiver<(u32, pcap::Record)>) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    let mut writer: Option<pcap::Writer<&mut Vec<u8>>> = None;
+    // The file header plus the records written so far.
+    let mut len = 24;
+    for (linktype, record) in rx {
+        if Probe::decode(&packet::decode(linktype, &record.data).udp_head).is_none() {
+            continue;
+        }
+        if len + 16 + record.data.len() > MAX_PCAP_LEN {
+            continue;
+        }
+        len += 16 + record.data.len();
+        if writer.is_none() {
+            writer = pcap::Writer::new(&mut bytes, linktype).ok();
+        }
+        if let Some(writer) = writer.as_mut() {
+            let _ = writer.write_record(&record);
+        }
+    }
+    bytes
+}
+
+/// Sends this side's results and capture while reading the peer's; sending happens on its own
+/// thread so two large captures cannot fill both sockets' buffers and stall.
+fn exchange(control: &mut Control, local: &Side) -> Result<Side, String> {
+    let done = Message::Done {
+        sent: local.sent,
+        delivered: local.delivered.clone(),
+        captured: local.captured,
+        capture_drops: local.capture_drops,
+        pcap_len: local.pcap.len(),
+    };
+    let mut line = serde_json::to_string(&done).map_err(|e| e.to_string())?;
+    line.push('\n');
+    let mut writer = control.writer.try_clone().map_err(|e| e.to_string())?;
+    let pcap = local.pcap.clone();
+    let sender = thread::spawn(move || writer.write_all(line.as_bytes()).and_then(|_| writer.write_all(&pcap)));
+
+    control.set_timeout(Some(Duration::from_secs(60)));
+    let remote = match control.recv()? {
+        Message::Done { sent, delivered, captured, capture_drops, pcap_len } => {
+            if pcap_len > MAX_PCAP_LEN {
+                return Err(format!("the peer's capture is {} bytes, more than the {} allowed", pcap_len, MAX_PCAP_LEN));
+            }
+            let mut pcap = Vec::new();
+            (&mut control.reader)
+                .take(pcap_len as u64)
+                .read_to_end(&mut pcap)
+                .map_err(|e| format!("the peer's capture was cut short: {}", e))?;
+            if pcap.len() < pcap_len {
+                return Err(format!("the peer's capture was cut short after {} of {} bytes", pcap.len(), pcap_len));
+            }
+            Side { sent, delivered, captured, capture_drops, pcap }
+        }
+        _ => return Err("the peer sent no results".to_string()),
+    };
+    match sender.join() {
+        Ok(Ok(())) => Ok(remote),
+        Ok(Err(e)) => Err(format!("could not send results to the peer: {}", e)),
+        Err(_) => Err("sending results to the peer failed".to_string()),
+    }
+}
+
+/// Writes both captures under `~/.netdiag/peer/`, named by time and side.
+fn save(local: &Side, remote: &Side, leader: bool) -> Option<(String, String)> {
+    let dir = data_dir().join("peer");
+    fs::create_dir_all(&dir).ok()?;
+    let stamp = clock::file_stamp(clock::unix_now());
+    let (local_name, remote_name) = if leader { ("leader", "follower") } else { ("follower", "leader") };
+    let local_path = dir.join(format!("{}-{}-local.pcap", stamp, local_name));
+    let remote_path = dir.join(format!("{}-{}-peer.pcap", stamp, remote_name));
+    if !local.pcap.is_empty() {
+        fs::write(&local_path, &local.pcap).ok()?;
+    }
+    if !remote.pcap.is_empty() {
+        fs::write(&remote_path, &remote.pcap).ok()?;
+    }
+    if local.pcap.is_empty() && remote.pcap.is_empty() {
+        return None;
+    }
+    Some((local_path.display().to_string(), remote_path.display().to_string()))
+}
+
+/// Capture times in microseconds of the probes going `direction` in a pcap, by sequence
+/// number (first sighting), and how many were seen more than once.
+fn sightings(pcap: &[u8], direction: u8, run: u32) -> (HashMap<u32, i64>, usize) {
+    let mut seen = HashMap::new();
+    let mut duplicates = 0;
+    let mut reader = match pcap::Reader::new(Cursor::new(pcap)) {
+        Ok(reader) => reader,
+        Err(_) => return (seen, 0),
+    };
+    while let Ok(Some(record)) = reader.next_record() {
+        let probe = match Probe::decode(&packet::decode(reader.linktype, &record.data).udp_head) {
+            Some(probe) if probe.direction == direction && probe.run == run => probe,
+            _ => continue,
+        };
+        let at = i64::from(record.ts_sec) * 1_000_000 + i64::from(record.ts_usec);
+        if seen.insert(probe.seq, at).is_some() {
+            duplicates += 1;
+        }
+    }
+    (seen, duplicates)
+}
+
+/// Reports one direction: how many probes each stage saw, where the missing ones went, and the
+/// one-way delay. Offsets put each side's timestamps on the leader's clock.
+#[allow(clippy::too_many_arguments)]
+fn report(title: &str, sender: &Side, sender_offset: i64, receiver: &Side, receiver_offset: i64, direction: u8, run: u32, uncertainty_us: i64) {
+    println!("🔹 {}", colorize(title, "blue"));
+    let sent = sender.sent;
+    let delivered: HashSet<u32> = receiver.delivered.iter().copied().collect();
+    println!("   {:<32} {}", "Sent", sent);
+
+    let both = sender.captured && receiver.captured;
+    let (departed, _) = sightings(&sender.pcap, direction, run);
+    let (arrived, duplicates) = sightings(&receiver.pcap, direction, run);
+    if sender.captured {
+        println!("   {:<32} {}", "Left the sender (capture)", departed.len());
+    }
+    if receiver.captured {
+        println!("   {:<32} {}", "Reached the receiver (capture)", arrived.len());
+    }
+    println!("   {:<32} {}", "Delivered to netdiag", delivered.len());
+
+    let lost = (sent as usize).saturating_sub(delivered.len());
+    if lost == 0 {
+        println!("✅ {} No loss", colorize("[SUCCESS]", "green"));
+    } else if both {
+        let on_sender = (0..sent).filter(|s| !departed.contains_key(s)).count();
+        let in_network = departed.keys().filter(|s| !arrived.contains_key(s)).count();
+        let on_receiver = arrived.keys().filter(|s| !delivered.contains(s)).count();
+        println!(
+            "❌ {} {} of {} lost ({:.1}%): {} on the sending host, {} in the network, {} on the receiving host",
+            colorize("[LOSS]", "red"),
+            lost,
+            sent,
+            lost as f64 * 100.0 / f64::from(sent.max(1)),
+            on_sender,
+            in_network,
+            on_receiver
+        );
+        let verdict = if in_network >= on_sender && in_network >= on_receiver {
+            "Packets disappear between the two hosts: on the path or a middlebox, not on either machine."
+        } else if on_sender >= on_receiver {
+            "Packets never leave the sending host: its firewall, traffic shaping or NIC queue drops them."
+        } else {
+            "Packets reach the receiving host but not the application: its firewall or socket buffers drop them."
+        };
+        println!("   {}", verdict);
+    } else {
+        println!(
+            "❌ {} {} of {} lost ({:.1}%); without captures at both ends the location is unknown",
+            colorize("[LOSS]", "red"),
+            lost,
+            sent,
+            lost as f64 * 100.0 / f64::from(sent.max(1))
+        );
+    }
+    for (side, capture) in [("sender", sender), ("receiver", receiver)] {
+        if let Some(drops) = capture.capture_drops.filter(|d| *d > 0) {
+            println!(
+                "⚠️  {} The {}'s capture dropped {} packets; losses may be misplaced",
+                colorize("[WARN]", "yellow"),
+                side,
+                drops
+            );
+        }
+    }
+
+    if lost > 0 {
+        let outcomes: Vec<Option<f64>> = (0..sent).map(|s| if delivered.contains(&s) { Some(0.0) } else { None }).collect();
+        let analysis = loss::analyze(&outcomes);
+        let longest = analysis.bursts.keys().next_back().copied().unwrap_or(0);
+        println!(
+            "   {} burst(s), longest {} probes; P(loss | previous lost) = {:.2}",
+            analysis.bursts.values().sum::<usize>(),
+            longest,
+            analysis.loss_after_loss
+        );
+    }
+
+    if both {
+        let mut order: Vec<(i64, u32)> = arrived.iter().map(|(seq, at)| (*at, *seq)).collect();
+        order.sort();
+        let reordered = order.windows(2).filter(|w| w[1].1 < w[0].1).count();
+        let delays: Vec<f64> = order
+            .iter()
+            .filter_map(|(at, seq)| {
+                let left = departed.get(seq)?;
+                Some(((at - receiver_offset) - (left - sender_offset)) as f64 / 1000.0)
+            })
+            .collect();
+        if !delays.is_empty() {
+            let (min, mean, max, jitter) = chart::summarize(&delays);
+            println!(
+                "   One-way delay: min {:.2} / avg {:.2} / p95 {:.2} / max {:.2} ms, jitter (σ) {:.2} ms (clock offset ±{:.2} ms)",
+                min,
+                mean,
+                chart::percentile(&delays, 95.0),
+                max,
+                jitter,
+                uncertainty_us as f64 / 1000.0
+            );
+            if delays.len() >= 3 {
+                // One character per stretch of the flow, the mean of its probes.
+                let chunk = delays.len().div_ceil(SPARKLINE_WIDTH);
+                let means: Vec<f64> = delays.chunks(chunk).map(|c| c.iter().sum::<f64>() / c.len() as f64).collect();
+                println!("   {}", colorize(&chart::sparkline(&means), "cyan"));
+            }
+        }
+        if reordered > 0 || duplicates > 0 {
+            println!("   Reordered: {}, duplicated: {}", reordered, duplicates);
+        }
+    }
+    println!();
+}
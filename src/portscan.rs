@@ -0,0 +1,230 @@
+use std::io::{Read, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::resolver;
+use crate::scheduler;
+
+/// Ports scanned when `--ports` is not given.
+const COMMON_PORTS: &[u16] = &[
+    21, 22, 23, 25, 53, 80, 110, 143, 443, 445, 587, 993, 995, 1433, 3306, 3389, 5432, 5900, 6379, 8080, 8443,
+];
+
+/// Number of concurrent connection attempts.
+const WORKERS: usize = 64;
+
+/// Longest banner shown, after escaping.
+const MAX_BANNER_LEN: usize = 80;
+
+/// An open port and, when banner grabbing is enabled, what answered on it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenPort {
+    pub port: u16,
+    pub service: String,
+    pub banner: Option<String>,
+}
+
+/// Returns the `scan` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("scan")
+        .about("Scans TCP ports on a host and optionally identifies the services behind them")
+        .arg(Arg::with_name("host").required(true).help("Host name or address to scan"))
+        .arg(Arg::with_name("ports").long("ports").short("p").takes_value(true)
+            .help("Ports to scan, e.g. 22,80,8000-8100 (default: common service ports)"))
+        .arg(Arg::with_name("banners").long("banners").short("b")
+            .help("Read and display service banners from open ports"))
+        .arg(Arg::with_name("timeout").long("timeout").takes_value(true).default_value("1000")
+            .help("Connect timeout per port in milliseconds"))
+}
+
+/// Runs the `scan` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let host = matches.value_of("host").unwrap_or_default();
+    let ports = match matches.value_of("ports").map(parse_ports) {
+        Some(Ok(ports)) => ports,
+        Some(Err(e)) => {
+            println!("❌ {} Invalid --ports: {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+        None => COMMON_PORTS.to_vec(),
+    };
+    let timeout = Duration::from_millis(value_t!(matches, "timeout", u64).unwrap_or(1000));
+
+    let addr = match resolver::resolve_one(host) {
+        Ok(ip) => ip,
+        Err(e) => {
+            println!("❌ {} {}", colorize("[DNS]", "red"), e);
+            return;
+        }
+    };
+
+    println!("\n🔍 {} Scanning {} ports on {} ({})", colorize("[INFO]", "blue"), ports.len(), colorize(host, "cyan"), addr);
+    println!("   Probes: {}\n", scheduler::describe());
+    let open = scan(addr, host, &ports, timeout, matches.is_present("banners"));
+
+    println!("{:<8} {:<12} Banner", "Port", "Service");
+    println!("{}", "-".repeat(90));
+    for port in &open {
+        println!(
+            "{:<8} {:<12} {}",
+            colorize(&port.port.to_string(), "green"),
+            port.service,
+            port.banner.as_deref().unwrap_or("")
+        );
+    }
+    println!("\n📊 {} {} of {} ports open ({} probes sent).\n", colorize("[SUMMARY]", "blue"), open.len(), ports.len(), scheduler::sent());
+}
+
+/// Parses a list like `22,80,8000-8100`.
+pub fn parse_ports(spec: &str) -> Result<Vec<u16>, String> {
+    let mut ports = Vec::new();
+    for part in spec.split(',').map(str::trim).filter(|p| !p.is_empty()) {
+        match part.split_once('-') {
+            Some((start, end)) => {
+                let start: u16 = start.parse().map_err(|_| format!("bad port '{}'", start))?;
+                let end: u16 = end.parse().map_err(|_| format!("bad port '{}'", end))?;
+                if start > end {
+                    return Err(format!("empty range {}", part));
+                }
+                ports.extend(start..=end);
+            }
+            None => ports.push(part.parse().map_err(|_| format!("bad port '{}'", part))?),
+        }
+    }
+    ports.sort_unstable();
+    ports.dedup();
+    Ok(ports)
+}
+
+/// Connect-scans `ports` with a pool of worker threads and returns the open ones in order.
+pub fn scan(addr: IpAddr, host: &str, ports: &[u16], timeout: Duration, banners: bool) -> Vec<OpenPort> {
+    let queue = Arc::new(Mutex::new(ports.to_vec()));
+    let open = Arc::new(Mutex::new(Vec::new()));
+
+    let workers: Vec<_> = (0..WORKERS.min(ports.len()))
+        .map(|_| {
+            let queue = Arc::clone(&queue);
+            let open = Arc::clone(&open);
+            let host = host.to_string();
+            thread::spawn(move || loop {
+                let port = match queue.lock().map(|mut q| q.pop()) {
+                    Ok(Some(port)) => port,
+                    _ => break,
+                };
+                let target = SocketAddr::new(addr, port);
+                let _permit = scheduler::acquire("tcp-connect", addr, Some(port));
+                if let Ok(stream) = TcpStream::connect_timeout(&target, timeout) {
+                    let banner = if banners { grab_banner(stream, &host, port) } else { None };
+                    if let Ok(mut open) = open.lock() {
+                        open.push(OpenPort { port, service: service_name(port).to_string(), banner });
+                    }
+                }
+            })
+        })
+        .collect();
+
+    for worker in workers {
+        let _ = worker.join();
+    }
+    let mut open = Arc::try_unwrap(open).map(|m| m.into_inner().unwrap_or_default()).unwrap_or_default();
+    open.sort_by_key(|p| p.port);
+    open
+}
+
+/// Well-known service name for a port.
+pub fn service_name(port: u16) -> &'static str {
+    match port {
+        21 => "ftp",
+        22 => "ssh",
+        23 => "telnet",
+        25 | 587 => "smtp",
+        53 => "dns",
+        80 | 8000 | 8080 => "http",
+        110 => "pop3",
+        143 => "imap",
+        443 | 8443 => "https",
+        445 => "smb",
+        993 => "imaps",
+        995 => "pop3s",
+        1433 => "mssql",
+        3306 => "mysql",
+        3389 => "rdp",
+        5432 => "postgres",
+        5900 => "vnc",
+        6379 => "redis",
+        _ => "unknown",
+    }
+}
+
+/// Reads what the service says first, sending a protocol-appropriate probe when the
+/// service waits for the client to speak.
+fn grab_banner(mut stream: TcpStream, host: &str, port: u16) -> Option<String> {
+    let _ = stream.set_read_timeout(Some(Duration::from_secs(2)));
+    let _ = stream.set_write_timeout(Some(Duration::from_secs(2)));
+
+    match service_name(port) {
+        "http" => {
+            let request = format!("HEAD / HTTP/1.0\r\nHost: {}\r\nUser-Agent: netdiag\r\n\r\n", host);
+            stream.write_all(request.as_bytes()).ok()?;
+            let response = read_some(&mut stream)?;
+            let text = String::from_utf8_lossy(&response);
+            let status = text.lines().next().unwrap_or("").to_string();
+            let server = text
+                .lines()
+                .find(|l| l.to_ascii_lowercase().starts_with("server:"))
+                .map(|l| l[7..].trim().to_string());
+            Some(render(match server {
+                Some(server) => format!("{} (Server: {})", status, server),
+                None => status,
+            }.as_bytes()))
+        }
+        "https" | "imaps" | "pop3s" => Some("TLS service (banner encrypted)".to_string()),
+        "redis" => {
+            stream.write_all(b"PING\r\n").ok()?;
+            read_some(&mut stream).map(|b| render(&b))
+        }
+        _ => {
+            // SSH, SMTP, FTP, POP3, IMAP and MySQL all greet first.
+            if let Some(greeting) = read_some(&mut stream) {
+                return Some(render(&greeting));
+            }
+            stream.write_all(b"\r\n").ok()?;
+            read_some(&mut stream).map(|b| render(&b))
+        }
+    }
+}
+
+fn read_some(stream: &mut TcpStream) -> Option<Vec<u8>> {
+    let mut buf = [0u8; 512];
+    match stream.read(&mut buf) {
+        Ok(n) if n > 0 => Some(buf[..n].to_vec()),
+        _ => None,
+    }
+}
+
+/// Renders untrusted bytes safely: only the first line, printable ASCII kept, everything
+/// else (including terminal escape sequences) shown as `\xNN`, and truncated.
+pub fn render(bytes: &[u8]) -> String {
+    let first_line = bytes.split(|&b| b == b'\n').next().unwrap_or(bytes);
+    let mut out = String::new();
+    for &b in first_line {
+        if b == b'\r' {
+            continue;
+        }
+        if (0x20..0x7f).contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("\\x{:02x}", b));
+        }
+        if out.len() >= MAX_BANNER_LEN {
+            out.push('…');
+            break;
+        }
+    }
+    out
+}
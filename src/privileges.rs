@@ -0,0 +1,118 @@
+use std::fs;
+use std::io::ErrorKind;
+use std::process::Command;
+use std::sync::OnceLock;
+
+use crate::colorize;
+use crate::netinfo::command_stdout;
+
+/// Whether the process runs as root (or an elevated Administrator on Windows).
+pub fn is_root() -> bool {
+    static ROOT: OnceLock<bool> = OnceLock::new();
+    *ROOT.get_or_init(|| {
+        if cfg!(windows) {
+            // `net session` only succeeds from an elevated prompt.
+            return command_stdout("net", &["session"]).is_some();
+        }
+        command_stdout("id", &["-u"]).is_some_and(|uid| uid.trim() == "0")
+    })
+}
+
+/// Whether `ping` can send ICMP echo requests: it is setuid, has `CAP_NET_RAW`, or the kernel
+/// allows unprivileged ICMP sockets. Probed once by pinging loopback.
+pub fn icmp_allowed() -> bool {
+    static ALLOWED: OnceLock<bool> = OnceLock::new();
+    *ALLOWED.get_or_init(|| {
+        let args: &[&str] = if cfg!(windows) { &["-n", "1", "-w", "1000", "127.0.0.1"] } else { &["-c", "1", "-W", "1", "127.0.0.1"] };
+        match Command::new("ping").args(args).output() {
+            Ok(output) => {
+                let text = format!("{}{}", String::from_utf8_lossy(&output.stdout), String::from_utf8_lossy(&output.stderr)).to_lowercase();
+                !(text.contains("not permitted") || text.contains("permission denied"))
+            }
+            Err(_) => false,
+        }
+    })
+}
+
+/// Checks that tcpdump is installed and may open capture devices, returning the exact commands
+/// that fix it when not.
+pub fn can_capture() -> Result<(), String> {
+    match Command::new("tcpdump").arg("--version").output() {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Err(install_hint()),
+        Err(e) => return Err(format!("cannot run tcpdump: {}", e)),
+        Ok(_) => {}
+    }
+    if is_root() {
+        return Ok(());
+    }
+    if cfg!(target_os = "linux") {
+        let path = command_stdout("sh", &["-c", "command -v tcpdump"]).unwrap_or_default();
+        let caps = command_stdout("getcap", &[path.trim()]).unwrap_or_default();
+        if caps.contains("cap_net_raw") {
+            return Ok(());
+        }
+        return Err(format!(
+            "capturing needs root or CAP_NET_RAW on tcpdump. Either rerun with sudo, or grant it once:\n   sudo setcap cap_net_raw,cap_net_admin=eip {}",
+            if path.trim().is_empty() { "$(command -v tcpdump)" } else { path.trim() }
+        ));
+    }
+    if cfg!(target_os = "macos") || cfg!(target_os = "freebsd") {
+        if fs::OpenOptions::new().read(true).open("/dev/bpf0").is_ok() {
+            return Ok(());
+        }
+        return Err("capturing needs read access to /dev/bpf*. Either rerun with sudo, or grant it for this boot:\n   \
+                    sudo chmod o+r /dev/bpf*\n   (Wireshark's ChmodBPF package makes this permanent)"
+            .to_string());
+    }
+    Err("capturing needs an elevated prompt (Run as administrator) and Npcap".to_string())
+}
+
+/// How to install tcpdump on this platform.
+fn install_hint() -> String {
+    let command = if cfg!(target_os = "macos") {
+        "tcpdump ships with macOS; check that /usr/sbin is on PATH"
+    } else if cfg!(windows) {
+        "install Npcap and WinDump, or run netdiag under WSL"
+    } else if fs::metadata("/etc/debian_version").is_ok() {
+        "sudo apt install tcpdump"
+    } else if fs::metadata("/etc/alpine-release").is_ok() {
+        "sudo apk add tcpdump"
+    } else {
+        "sudo dnf install tcpdump   (or your distribution's package manager)"
+    };
+    format!("tcpdump is not installed:\n   {}", command)
+}
+
+/// Explains why a privileged check is skipped or degraded and how to enable it.
+pub fn hint(what: &str, reason: &str) -> String {
+    format!("⚠️  {} {} unavailable: {}", colorize("[PRIVILEGES]", "yellow"), what, reason)
+}
+
+/// How to let unprivileged users ping on this platform.
+pub fn icmp_remediation() -> &'static str {
+    if cfg!(target_os = "linux") {
+        "allow unprivileged ICMP with `sudo sysctl -w net.ipv4.ping_group_range=\"0 2147483647\"` \
+         or `sudo setcap cap_net_raw+ep $(command -v ping)`"
+    } else {
+        "rerun with sudo"
+    }
+}
+
+/// Prints what this run can do with its current privileges and what is degraded.
+pub fn report() {
+    if is_root() {
+        println!("✅ {} Running as root", colorize("[PRIVILEGES]", "green"));
+    } else {
+        println!("ℹ️  {} Running unprivileged", colorize("[PRIVILEGES]", "blue"));
+        if !icmp_allowed() {
+            println!("   • ICMP ping is not permitted; TCP connect times are used instead ({})", icmp_remediation());
+        }
+        if cfg!(target_os = "linux") {
+            println!("   • ICMP/TCP traceroute needs root; UDP probes are used instead");
+        }
+    }
+    if let Err(reason) = can_capture() {
+        println!("   • Packet capture is skipped: {}", reason);
+    }
+    println!();
+}
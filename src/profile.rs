@@ -0,0 +1,277 @@
+use std::collections::HashSet;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use crate::baseline;
+use crate::bufferbloat;
+use crate::capture;
+use crate::certs;
+use crate::config::{CertsConfig, Config};
+use crate::container;
+use crate::dns_filter;
+use crate::dns_hijack;
+use crate::interrupt;
+use crate::latency;
+use crate::matrix;
+use crate::netinfo;
+use crate::quic;
+use crate::routes;
+use crate::selftest;
+use crate::sockets;
+use crate::telemetry;
+use crate::traceroute;
+use crate::triage;
+use crate::vpn;
+use crate::wifi;
+use crate::{colorize, data_dir, run_command};
+
+/// Profile run when `--profile` is not given.
+pub const DEFAULT: &str = "full";
+
+/// One named check of a diagnostics run.
+pub type Step = (&'static str, Box<dyn Fn()>);
+
+/// Checks a profile can list: name, title (shown in output and saved in sessions), description.
+const CHECKS: &[(&str, &str, &str)] = &[
+    ("selftest", "Local stack self-test", "Loopback, ephemeral sockets, conntrack and descriptor limits, own hostname"),
+    ("baseline", "Baseline comparison", "Differences from the saved baseline"),
+    ("container", "Container networking", "Network namespace, veth and DNS setup (only inside a container)"),
+    ("signal", "Wi-Fi signal", "Signal strength, noise and link rate of the Wi-Fi association"),
+    ("gateway", "Gateway", "Ping the default gateway"),
+    ("latency", "Latency", "Ping the profile's target (8.8.8.8)"),
+    ("bufferbloat", "Bufferbloat", "Latency growth while the link is saturated (about 30 seconds)"),
+    ("public-ip", "Public IP", "Fetch the public IP address"),
+    ("private-ip", "Private IP", "List the local IP addresses"),
+    ("connections", "Open connections", "List established connections"),
+    ("listening", "Listening ports", "Ports this host accepts connections on, and their processes"),
+    ("traceroute", "Traceroute", "Trace the path to google.com"),
+    ("routes", "Routing table", "Print the routing table and flag suspicious routes"),
+    ("dns-hijack", "DNS hijack check", "Compare answers from the system and public resolvers"),
+    ("dns-filter", "DNS filtering", "Detect DNS-level filtering of ads, trackers and other categories"),
+    ("quic", "QUIC check", "Check HTTP/3 reachability"),
+    ("vpn", "VPN leak check", "Check that traffic and DNS stay inside the VPN"),
+    ("certs", "Certificate expiry", "Days until the certificates in `certs.hosts` expire"),
+    ("matrix", "Reachability matrix", "Test every endpoint in the profile's services file"),
+    ("triage", "Triage", "Locate a fault between link, gateway, DNS, ISP and beyond"),
+    ("capture", "Traffic capture", "Capture packets while visiting sites"),
+];
+
+/// Built-in profiles: name, description, checks.
+const BUILTIN: &[(&str, &str, &[&str])] = &[
+    ("full", "The default run", &[
+        "baseline", "container", "latency", "public-ip", "private-ip", "connections", "traceroute", "routes",
+        "dns-hijack", "quic", "capture",
+    ]),
+    ("quick", "Basic reachability in under a minute", &["gateway", "latency", "public-ip", "dns-hijack"]),
+    ("wifi", "Wireless link quality and whether the link queues under load", &["signal", "gateway", "latency", "bufferbloat"]),
+    ("vpn", "Whether traffic and DNS stay inside the VPN", &["routes", "public-ip", "vpn", "dns-filter"]),
+    ("server", "What this host exposes and whether its dependencies are reachable", &["listening", "certs", "matrix", "routes"]),
+    ("triage", "Where the connection is broken", &["selftest", "triage", "traceroute", "dns-hijack"]),
+];
+
+/// A resolved profile: the checks to run and the settings they use.
+#[derive(Debug, Clone)]
+pub struct Profile {
+    pub name: String,
+    pub description: String,
+    /// Check names, validated against `CHECKS`.
+    pub checks: Vec<&'static str>,
+    pub target: Option<String>,
+    pub services: Option<String>,
+}
+
+impl Profile {
+    /// Names the checks will look up, so they can be resolved once up front.
+    pub fn names(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        for check in &self.checks {
+            match *check {
+                "latency" => names.push(self.target.clone().unwrap_or_else(|| "8.8.8.8".to_string())),
+                "bufferbloat" => names.push(self.target.clone().unwrap_or_else(|| "1.1.1.1".to_string())),
+                "public-ip" => names.push("ifconfig.me".to_string()),
+                "traceroute" => names.push("google.com".to_string()),
+                "quic" => names.extend(quic::QUIC_SITES.iter().map(|s| s.to_string())),
+                _ => {}
+            }
+        }
+        // Several checks can look up the same name, not necessarily one after another.
+        let mut seen = HashSet::new();
+        names.retain(|name| seen.insert(name.clone()));
+        names
+    }
+
+    /// The checks as runnable steps. Checks that do not apply to this host are left out.
+    pub fn steps(&self, certs: &CertsConfig) -> Vec<Step> {
+        self.checks
+            .iter()
+            .filter(|check| **check != "container" || container::detect().is_some())
+            .map(|check| (title(check), self.step(check, certs)))
+            .collect()
+    }
+
+    fn step(&self, check: &str, certs: &CertsConfig) -> Box<dyn Fn()> {
+        let target = self.target.clone();
+        match check {
+            "selftest" => Box::new(|| {
+                selftest::selftest();
+                println!();
+            }),
+            "baseline" => Box::new(baseline::report_deviations),
+            "container" => Box::new(container::report),
+            "signal" => Box::new(wifi::report),
+            "gateway" => Box::new(|| match netinfo::default_gateway() {
+                Some(gateway) => {
+                    latency::ping_summary(&gateway, 10);
+                    interrupt::sleep(Duration::from_secs(1));
+                }
+                None => {
+                    println!("❌ {} No default gateway found\n", colorize("[ERROR]", "red"));
+                    telemetry::fail("no default gateway");
+                }
+            }),
+            "latency" => Box::new(move || {
+                latency::ping_summary(target.as_deref().unwrap_or("8.8.8.8"), 10);
+                interrupt::sleep(Duration::from_secs(1));
+            }),
+            "bufferbloat" => Box::new(move || bufferbloat::bufferbloat_test(target.as_deref().unwrap_or("1.1.1.1"), 4, 10)),
+            "public-ip" => Box::new(|| {
+                println!("🔹 {}", colorize("Fetching Public IP Address", "blue"));
+                match netinfo::public_ip() {
+                    Some(ip) => println!("✅ {}\n{}\n", colorize("[SUCCESS]", "green"), ip),
+                    None => {
+                        println!("❌ {} Could not reach ifconfig.me\n", colorize("[ERROR]", "red"));
+                        telemetry::fail("could not fetch the public IP");
+                    }
+                }
+                interrupt::sleep(Duration::from_secs(1));
+            }),
+            "private-ip" => Box::new(|| run_command("sh", &["-c", "ifconfig -a | grep 'inet '"], "Fetching Private IP Address")),
+            "connections" => Box::new(|| run_command("sh", &["-c", "netstat -an | grep 'ESTABLISHED'"], "Checking Open Listening Ports")),
+            "listening" => Box::new(sockets::print_listening),
+            "traceroute" => Box::new(|| traceroute::run_trace("google.com")),
+            "routes" => Box::new(|| routes::print_table(&routes::table())),
+            "dns-hijack" => Box::new(|| { dns_hijack::hijack_check(); }),
+            "dns-filter" => Box::new(|| {
+                let filtered = dns_filter::filter_check();
+                if !filtered.is_empty() {
+                    telemetry::fail(&format!("DNS filtering of {}", filtered.join(", ")));
+                }
+                println!();
+            }),
+            "quic" => Box::new(|| {
+                if !quic::quic_check() {
+                    telemetry::fail("QUIC is not usable");
+                }
+            }),
+            "vpn" => Box::new(|| vpn::vpn_check(8)),
+            "certs" => {
+                let certs = certs.clone();
+                Box::new(move || {
+                    if certs.hosts.is_empty() {
+                        println!("⚠️  {} No hosts under `certs: hosts:` in the config; skipping certificate expiry\n", colorize("[WARN]", "yellow"));
+                        return;
+                    }
+                    let failing: Vec<String> = certs::check_certs(&certs)
+                        .into_iter()
+                        .filter(|r| r.error.is_some() || r.status != certs::CertStatus::Ok)
+                        .map(|r| r.host)
+                        .collect();
+                    if !failing.is_empty() {
+                        telemetry::fail(&format!("certificate problems on {}", failing.join(", ")));
+                    }
+                    println!();
+                })
+            }
+            "matrix" => {
+                let services = self.services.clone().map(PathBuf::from).unwrap_or_else(|| data_dir().join("services.yaml"));
+                Box::new(move || {
+                    if !services.exists() {
+                        println!(
+                            "⚠️  {} No services file at {}; set `services:` in the profile to test reachability\n",
+                            colorize("[WARN]", "yellow"),
+                            services.display()
+                        );
+                        return;
+                    }
+                    matrix::test_file(&services.to_string_lossy());
+                })
+            }
+            "triage" => Box::new(triage::diagnose),
+            // Capture packets while visiting sites
+            "capture" => Box::new(|| capture::capture_traffic(&capture::CaptureOptions::default())),
+            // `select` only lets through names in `CHECKS`, so this is a check with no step yet.
+            other => {
+                let other = other.to_string();
+                Box::new(move || println!("❌ {} Check {} is not implemented\n", colorize("[ERROR]", "red"), other))
+            }
+        }
+    }
+}
+
+/// The title of a check, as shown in output and saved in sessions.
+fn title(check: &str) -> &'static str {
+    CHECKS.iter().find(|(name, _, _)| *name == check).map_or("", |(_, title, _)| title)
+}
+
+/// Looks up `name` among the config's profiles, then the built-in ones. A config profile
+/// without checks borrows those of the built-in profile of the same name.
+pub fn select(name: &str, config: &Config) -> Result<Profile, String> {
+    let builtin = BUILTIN.iter().find(|(n, _, _)| *n == name);
+    let custom = config.profiles.get(name);
+    let (description, checks): (String, Vec<String>) = match (custom, builtin) {
+        (Some(custom), _) if !custom.checks.is_empty() => (custom.description.clone().unwrap_or_else(|| "Defined in the config".to_string()), custom.checks.clone()),
+        (custom, Some((_, description, checks))) => (
+            custom.and_then(|c| c.description.clone()).unwrap_or_else(|| description.to_string()),
+            checks.iter().map(|c| c.to_string()).collect(),
+        ),
+        (Some(_), None) => return Err(format!("Profile {} has no checks", name)),
+        (None, None) => return Err(format!("No profile named {}. Available profiles: {}", name, available(config).join(", "))),
+    };
+
+    let mut resolved = Vec::new();
+    for check in &checks {
+        match CHECKS.iter().find(|(n, _, _)| n == check) {
+            Some((n, _, _)) => resolved.push(*n),
+            None => {
+                return Err(format!(
+                    "Profile {} lists unknown check {}. Available checks: {}",
+                    name,
+                    check,
+                    CHECKS.iter().map(|(n, _, _)| *n).collect::<Vec<_>>().join(", ")
+                ))
+            }
+        }
+    }
+    let settings = custom.cloned().unwrap_or_default();
+    Ok(Profile { name: name.to_string(), description, checks: resolved, target: settings.target, services: settings.services })
+}
+
+/// Names of every profile, built-in ones first.
+fn available(config: &Config) -> Vec<String> {
+    let mut names: Vec<String> = BUILTIN.iter().map(|(n, _, _)| n.to_string()).collect();
+    let mut custom: Vec<&String> = config.profiles.keys().filter(|n| !names.contains(n)).collect();
+    custom.sort();
+    names.extend(custom.into_iter().cloned());
+    names
+}
+
+/// Prints every profile with its checks, then every check.
+pub fn print_list(config: &Config) {
+    println!("\n🔹 {}", colorize("Diagnostic profiles", "blue"));
+    for name in available(config) {
+        let source = if config.profiles.contains_key(&name) { " (from config)" } else { "" };
+        match select(&name, config) {
+            Ok(profile) => {
+                println!("   {}{}: {}", colorize(&name, "cyan"), source, profile.description);
+                println!("      {}", profile.checks.join(", "));
+            }
+            Err(e) => println!("   {}{}: {}", colorize(&name, "cyan"), source, colorize(&e, "red")),
+        }
+    }
+    println!("\n🔹 {}", colorize("Checks", "blue"));
+    for (name, _, description) in CHECKS {
+        println!("   {:<14} {}", name, description);
+    }
+    println!("\n   Add your own under `profiles:` in the config, e.g. `office: {{ checks: [gateway, latency, certs] }}`.\n");
+}
+
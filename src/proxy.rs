@@ -0,0 +1,153 @@
+use std::process::Command;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::http;
+use crate::scheduler;
+
+/// URLs fetched directly and through the proxy to measure its overhead.
+const TEST_URLS: &[&str] = &["http://example.com/", "https://www.google.com/", "https://www.cloudflare.com/"];
+
+/// Timings reported by curl's `--write-out` for one request.
+struct Timing {
+    http_code: u16,
+    connect_code: u16,
+    connect_ms: f64,
+    total_ms: f64,
+}
+
+/// Returns the `proxy-test` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("proxy-test")
+        .about("Measures latency through an HTTP proxy and verifies CONNECT tunneling")
+        .arg(Arg::with_name("proxy").long("proxy").takes_value(true)
+            .help("Proxy URL, e.g. http://proxy:3128 (defaults to HTTPS_PROXY/HTTP_PROXY)"))
+}
+
+/// Runs the `proxy-test` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let proxy = matches
+        .value_of("proxy")
+        .map(|p| p.to_string())
+        .or_else(|| http::proxy_from_env("https://www.google.com/"))
+        .or_else(|| http::proxy_from_env("http://example.com/"));
+
+    match proxy {
+        Some(proxy) => proxy_test(&proxy),
+        None => println!(
+            "❌ {} No proxy given and none set in HTTPS_PROXY/HTTP_PROXY. Use --proxy http://host:port.",
+            colorize("[ERROR]", "red")
+        ),
+    }
+}
+
+/// Compares direct and proxied fetches and checks that the proxy tunnels with CONNECT.
+fn proxy_test(proxy: &str) {
+    println!("\n🔌 {} Testing proxy {}\n", colorize("[INFO]", "blue"), colorize(proxy, "cyan"));
+    println!(
+        "{:<30} {:>10} {:>12} {:>10} {:>14}  Result",
+        "URL", "Direct", "Via proxy", "Overhead", "Proxy connect"
+    );
+    println!("{}", "-".repeat(100));
+
+    let mut failures = 0;
+    for url in TEST_URLS {
+        let direct = fetch(url, None);
+        let proxied = fetch(url, Some(proxy));
+
+        let direct_text = direct.as_ref().map_or("failed".to_string(), |t| format!("{:.0} ms", t.total_ms));
+        let (proxied_text, overhead, connect, result) = match proxied {
+            Some(ref t) if t.http_code > 0 => (
+                format!("{:.0} ms", t.total_ms),
+                direct.as_ref().map_or("-".to_string(), |d| format!("{:+.0} ms", t.total_ms - d.total_ms)),
+                format!("{:.0} ms", t.connect_ms),
+                colorize(&format!("HTTP {}", t.http_code), "green"),
+            ),
+            Some(ref t) => {
+                failures += 1;
+                ("failed".to_string(), "-".to_string(), format!("{:.0} ms", t.connect_ms), colorize(&explain_connect(t.connect_code), "red"))
+            }
+            None => {
+                failures += 1;
+                ("failed".to_string(), "-".to_string(), "-".to_string(), colorize("proxy unreachable", "red"))
+            }
+        };
+
+        println!("{:<30} {:>10} {:>12} {:>10} {:>14}  {}", url, direct_text, proxied_text, overhead, connect, result);
+    }
+
+    // Force a CONNECT tunnel even for plain HTTP to prove tunneling works beyond port 443.
+    println!();
+    println!("🔹 {}", colorize("Verifying CONNECT tunneling", "blue"));
+    for &(url, label) in &[("https://www.google.com/", "port 443"), ("http://example.com/", "port 80")] {
+        match tunnel(url, proxy) {
+            Some(t) if t.connect_code == 200 => println!("   ✅ CONNECT to {} ({}) established", http::host_of(url), label),
+            Some(t) => {
+                failures += 1;
+                println!("   ❌ CONNECT to {} ({}) refused: {}", http::host_of(url), label, explain_connect(t.connect_code));
+            }
+            None => {
+                failures += 1;
+                println!("   ❌ CONNECT to {} ({}) failed: proxy unreachable", http::host_of(url), label);
+            }
+        }
+    }
+
+    if failures == 0 {
+        println!("\n✅ {} Proxy is healthy.\n", colorize("[SUCCESS]", "green"));
+    } else {
+        println!("\n❌ {} {} proxy test(s) failed.\n", colorize("[ERROR]", "red"), failures);
+    }
+}
+
+fn explain_connect(code: u16) -> String {
+    match code {
+        0 => "no response from proxy".to_string(),
+        407 => "407 proxy authentication required".to_string(),
+        403 => "403 forbidden by proxy policy".to_string(),
+        code => format!("proxy answered {}", code),
+    }
+}
+
+/// Fetches `url` directly (bypassing any environment proxy) or through `proxy`.
+fn fetch(url: &str, proxy: Option<&str>) -> Option<Timing> {
+    let proxy_args = match proxy {
+        Some(proxy) => ["--proxy", proxy],
+        None => ["--noproxy", "*"],
+    };
+    curl_timing(url, &proxy_args)
+}
+
+/// Fetches `url` through `proxy` using a CONNECT tunnel regardless of scheme.
+fn tunnel(url: &str, proxy: &str) -> Option<Timing> {
+    curl_timing(url, &["--proxy", proxy, "--proxytunnel"])
+}
+
+fn curl_timing(url: &str, extra: &[&str]) -> Option<Timing> {
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let (host, port) = http::target_of(url);
+    let output = scheduler::command("http", &host, Some(port), Command::new("curl"))
+        .args(["-s", "-o", null, "--max-time", "15"])
+        .args(extra)
+        .args(["-w", "%{http_code} %{http_connect} %{time_connect} %{time_total}", url])
+        .output()
+        .ok()?;
+
+    let text = String::from_utf8_lossy(&output.stdout);
+    let fields: Vec<&str> = text.split_whitespace().collect();
+    if fields.len() < 4 {
+        return None;
+    }
+    let timing = Timing {
+        http_code: fields[0].parse().unwrap_or(0),
+        connect_code: fields[1].parse().unwrap_or(0),
+        connect_ms: fields[2].parse::<f64>().unwrap_or(0.0) * 1000.0,
+        total_ms: fields[3].parse::<f64>().unwrap_or(0.0) * 1000.0,
+    };
+    // Nothing at all came back: the proxy could not even be reached.
+    if timing.http_code == 0 && timing.connect_code == 0 && timing.connect_ms == 0.0 {
+        return None;
+    }
+    Some(timing)
+}
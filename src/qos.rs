@@ -0,0 +1,203 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::process::Command;
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::capture;
+use crate::colorize;
+use crate::interrupt;
+use crate::packet;
+use crate::privileges;
+use crate::resolver;
+use crate::routes;
+use crate::scheduler;
+
+/// Markings tested: (name, DSCP value). EF carries voice, AF41 interactive video.
+const CLASSES: [(&str, u8); 2] = [("EF", 46), ("AF41", 34)];
+
+/// First destination port traceroute uses for UDP probes; each later probe adds one.
+const TRACEROUTE_BASE_PORT: u16 = 33434;
+
+/// DSCP values seen for one marking along the path.
+#[derive(Debug, Default)]
+struct Observation {
+    /// Hop number → (router, DSCP quoted back in its time-exceeded message).
+    hops: BTreeMap<u16, (IpAddr, u8)>,
+    /// DSCP of echo replies from the target, if any came back.
+    echo: Option<u8>,
+}
+
+/// Returns the `dscp` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("dscp")
+        .about("Checks whether EF/AF41 DSCP markings survive the path or get bleached")
+        .arg(Arg::with_name("target").required(true).help("Host to probe (IPv4)"))
+        .arg(Arg::with_name("max-hops").long("max-hops").takes_value(true).default_value("15")
+            .help("Furthest hop to probe"))
+        .arg(Arg::with_name("interface").long("interface").short("i").takes_value(true)
+            .help("Interface to listen on (default: the default-route interface)"))
+}
+
+/// Runs the `dscp` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let target = matches.value_of("target").unwrap_or_default();
+    let max_hops = value_t!(matches, "max-hops", u8).unwrap_or(15);
+    let interface = matches
+        .value_of("interface")
+        .map(|i| i.to_string())
+        .or_else(|| routes::table().into_iter().find(|r| r.is_default()).map(|r| r.interface))
+        .unwrap_or_else(|| "en0".to_string());
+
+    let addr = match resolver::resolve(target).map(|addrs| addrs.into_iter().find(|a| a.is_ipv4())) {
+        Ok(Some(addr)) => addr,
+        Ok(None) => {
+            println!("❌ {} {} has no IPv4 address", colorize("[ERROR]", "red"), target);
+            return;
+        }
+        Err(e) => {
+            println!("❌ {} {}", colorize("[DNS]", "red"), e);
+            return;
+        }
+    };
+
+    if let Err(reason) = privileges::can_capture() {
+        println!("{}", privileges::hint("DSCP test", &reason));
+        return;
+    }
+    println!("\n🎯 {} Testing DSCP preservation toward {} ({})\n", colorize("[INFO]", "blue"), colorize(target, "cyan"), addr);
+    let mut results = Vec::new();
+    for (name, dscp) in CLASSES.iter() {
+        println!("🔹 {}", colorize(&format!("Sending {} (DSCP {}) probes", name, dscp), "blue"));
+        match observe(&interface, addr, *dscp, max_hops) {
+            Some(observation) => results.push((*name, *dscp, observation)),
+            None => {
+                println!("❌ {} Could not capture replies; this check needs tcpdump (and usually root)\n", colorize("[ERROR]", "red"));
+                return;
+            }
+        }
+    }
+    print_results(&results);
+}
+
+/// Sends marked traceroute and ping probes while capturing the ICMP that comes back.
+fn observe(interface: &str, addr: IpAddr, dscp: u8, max_hops: u8) -> Option<Observation> {
+    let filter = format!("(src host {} and icmp[icmptype] == icmp-echoreply) or icmp[icmptype] == icmp-timxceed", addr);
+    let (mut child, rx) = capture::spawn_tcpdump(interface, &filter).ok()?;
+    // Give tcpdump a moment to attach before the first probe leaves.
+    thread::sleep(Duration::from_millis(500));
+
+    let tos = (dscp << 2).to_string();
+    let target = addr.to_string();
+    let max_hops = max_hops.to_string();
+    let mut traceroute = Command::new("traceroute");
+    traceroute.args(["-n", "-q", "1", "-w", "1", "-m", &max_hops, "-t", &tos, &target]);
+    let _ = interrupt::output(&mut scheduler::command("traceroute-udp", &target, None, traceroute));
+    let tos_flag = if cfg!(target_os = "macos") { "-z" } else { "-Q" };
+    let mut ping = Command::new("ping");
+    ping.args(["-c", "3", tos_flag, &tos, &target]);
+    let _ = interrupt::output(&mut scheduler::command("icmp-echo(3)", &target, None, ping));
+
+    thread::sleep(Duration::from_millis(500));
+    let _ = child.kill();
+    let _ = child.wait();
+    interrupt::untrack(&child);
+
+    let mut observation = Observation::default();
+    while let Ok((linktype, record)) = rx.recv_timeout(Duration::from_millis(200)) {
+        let decoded = packet::decode(linktype, &record.data);
+        let (icmp, reporter) = match (&decoded.icmp, decoded.src) {
+            (Some(icmp), Some(reporter)) => (icmp, reporter),
+            _ => continue,
+        };
+        match icmp.kind {
+            0 if reporter == addr => observation.echo = decoded.tos.map(|t| t >> 2),
+            11 => {
+                if let Some(quoted) = icmp.quoted().filter(|q| q.dst == addr) {
+                    if let Some(port) = quoted.dst_port.filter(|p| *p >= TRACEROUTE_BASE_PORT) {
+                        observation.hops.insert(port - TRACEROUTE_BASE_PORT + 1, (reporter, quoted.tos >> 2));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    Some(observation)
+}
+
+/// Standard name for a DSCP code point, or its number.
+fn dscp_name(dscp: u8) -> String {
+    match dscp {
+        0 => "CS0".to_string(),
+        46 => "EF".to_string(),
+        44 => "VOICE-ADMIT".to_string(),
+        d if d % 8 == 0 => format!("CS{}", d / 8),
+        d if d % 2 == 0 && (10..=38).contains(&d) && (d % 8) / 2 >= 1 => format!("AF{}{}", d / 8, (d % 8) / 2),
+        d => format!("DSCP {}", d),
+    }
+}
+
+/// Prints the hop-by-hop DSCP table and a verdict per marking.
+fn print_results(results: &[(&str, u8, Observation)]) {
+    let hops: Vec<u16> = {
+        let mut all: Vec<u16> = results.iter().flat_map(|(_, _, o)| o.hops.keys().cloned()).collect();
+        all.sort();
+        all.dedup();
+        all
+    };
+    if hops.is_empty() {
+        println!("⚠️  {} No routers answered with time-exceeded; hop-by-hop markings are not observable", colorize("[WARN]", "yellow"));
+    } else {
+        print!("\n   {:>3}  {:<16}", "Hop", "Router");
+        for (name, _, _) in results {
+            print!(" {:<12}", name);
+        }
+        println!();
+        for hop in &hops {
+            let router = results.iter().find_map(|(_, _, o)| o.hops.get(hop).map(|(r, _)| r.to_string()));
+            print!("   {:>3}  {:<16}", hop, router.unwrap_or_else(|| "*".to_string()));
+            for (_, sent, observation) in results {
+                let cell = match observation.hops.get(hop) {
+                    Some((_, seen)) => colorize(&format!("{:<12}", dscp_name(*seen)), if seen == sent { "green" } else { "red" }),
+                    None => format!("{:<12}", "-"),
+                };
+                print!(" {}", cell);
+            }
+            println!();
+        }
+    }
+
+    println!();
+    for (name, sent, observation) in results {
+        match observation.hops.iter().find(|(_, (_, seen))| seen != sent) {
+            Some((hop, (router, seen))) => println!(
+                "⚠️  {} {} was rewritten to {} before reaching hop {} ({}){}",
+                colorize(if *seen == 0 { "[BLEACHED]" } else { "[REMARKED]" }, "yellow"),
+                name,
+                dscp_name(*seen),
+                hop,
+                router,
+                if *seen == 0 { "; traffic past that point gets best-effort treatment" } else { "" }
+            ),
+            None if !observation.hops.is_empty() => println!(
+                "✅ {} {} preserved through hop {}",
+                colorize("[SUCCESS]", "green"),
+                name,
+                observation.hops.keys().last().cloned().unwrap_or(0)
+            ),
+            None => {}
+        }
+        match observation.echo {
+            Some(echo) if echo == *sent => println!("   {} echo replies came back marked {}", name, dscp_name(echo)),
+            Some(echo) => println!(
+                "   {} echo replies came back as {} (lost on the way there or back, or the target does not reflect TOS)",
+                name,
+                dscp_name(echo)
+            ),
+            None => println!("   {} no echo replies from the target", name),
+        }
+    }
+    println!();
+}
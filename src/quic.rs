@@ -0,0 +1,169 @@
+use std::net::{SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::resolver;
+use crate::scheduler;
+use crate::{colorize, random_u64};
+use crate::http;
+use crate::netinfo;
+use crate::repeat;
+
+/// Sites known to serve HTTP/3.
+pub const QUIC_SITES: &[&str] = &["www.google.com", "cloudflare.com", "www.youtube.com", "www.facebook.com"];
+
+/// A reserved "greased" version (RFC 9000 §15) that every server must answer with Version Negotiation.
+const GREASE_VERSION: u32 = 0x1a2a_3a4a;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Outcome of probing one site over UDP/443 and TCP/443.
+struct SiteResult {
+    site: String,
+    quic: Result<(Duration, Vec<u32>), String>,
+    tcp: Option<Duration>,
+}
+
+/// Returns the `http3` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("http3")
+        .about("Tests whether HTTP/3 (QUIC over UDP/443) works on this network")
+}
+
+/// Runs the `http3` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    println!();
+    quic_check();
+    println!();
+}
+
+/// Probes QUIC-capable sites and reports whether UDP/443 works or browsers will fall back to TCP.
+/// Returns true when QUIC is usable.
+pub fn quic_check() -> bool {
+    println!("🔹 {}", colorize("Checking HTTP/3 (QUIC) connectivity", "blue"));
+
+    let results: Vec<SiteResult> = QUIC_SITES.iter().map(|site| probe_site(site)).collect();
+    let mut quic_ok = 0;
+    let mut tcp_ok = 0;
+
+    for result in &results {
+        let tcp = result.tcp.map_or("failed".to_string(), |d| format!("{} ms", d.as_millis()));
+        match result.quic {
+            Ok((rtt, ref versions)) => {
+                quic_ok += 1;
+                repeat::record_target("QUIC handshake (ms)", &result.site, rtt.as_secs_f64() * 1000.0);
+                println!(
+                    "   ✅ {:<18} QUIC {} ms ({})   TCP {}",
+                    result.site,
+                    rtt.as_millis(),
+                    versions.iter().map(|v| version_name(*v)).collect::<Vec<_>>().join(", "),
+                    tcp
+                );
+            }
+            Err(ref e) => println!("   ❌ {:<18} QUIC {}   TCP {}", result.site, colorize(e, "red"), tcp),
+        }
+        if result.tcp.is_some() {
+            tcp_ok += 1;
+        }
+    }
+
+    if let Some(version) = curl_http3_version(QUIC_SITES[0]) {
+        println!("   curl --http3 to {} negotiated HTTP/{}", QUIC_SITES[0], version);
+    }
+
+    if quic_ok > 0 {
+        println!("✅ {} UDP/443 QUIC works; browsers can use HTTP/3.", colorize("[SUCCESS]", "green"));
+    } else if tcp_ok > 0 {
+        println!(
+            "⚠️  {} QUIC is blocked but TCP/443 works: browsers will fall back to HTTP/2 after a delay, which can make first page loads feel slow.",
+            colorize("[WARN]", "yellow")
+        );
+    } else {
+        println!("❌ {} Neither QUIC nor TCP/443 reached any test site.", colorize("[ERROR]", "red"));
+    }
+    quic_ok > 0
+}
+
+fn probe_site(site: &str) -> SiteResult {
+    let addr = match resolver::resolve(site).map(|addrs| addrs.into_iter().find(|a| a.is_ipv4())) {
+        Ok(Some(ip)) => SocketAddr::new(ip, 443),
+        Ok(None) => return SiteResult { site: site.to_string(), quic: Err("no IPv4 address".to_string()), tcp: None },
+        Err(e) => return SiteResult { site: site.to_string(), quic: Err(e.to_string()), tcp: None },
+    };
+
+    let tcp = {
+        let start = Instant::now();
+        scheduler::connect(addr, TIMEOUT).ok().map(|_| start.elapsed())
+    };
+    SiteResult { site: site.to_string(), quic: quic_version_negotiation(addr), tcp }
+}
+
+/// Sends a padded QUIC Initial with an unsupported version and waits for the server's
+/// Version Negotiation reply, which proves UDP/443 is open end to end.
+fn quic_version_negotiation(addr: SocketAddr) -> Result<(Duration, Vec<u32>), String> {
+    let socket = UdpSocket::bind("0.0.0.0:0").map_err(|e| e.to_string())?;
+    socket.set_read_timeout(Some(TIMEOUT)).map_err(|e| e.to_string())?;
+    socket.connect(addr).map_err(|e| e.to_string())?;
+
+    let dcid = random_u64().to_be_bytes();
+    let scid = random_u64().to_be_bytes();
+    let mut packet = vec![0xc0];
+    packet.extend_from_slice(&GREASE_VERSION.to_be_bytes());
+    packet.push(dcid.len() as u8);
+    packet.extend_from_slice(&dcid);
+    packet.push(scid.len() as u8);
+    packet.extend_from_slice(&scid);
+    // Servers ignore Initials smaller than 1200 bytes to limit amplification.
+    packet.resize(1200, 0);
+
+    let _permit = scheduler::acquire("quic-initial", addr.ip(), Some(addr.port()));
+    let start = Instant::now();
+    socket.send(&packet).map_err(|e| e.to_string())?;
+    let mut buf = [0u8; 1500];
+    let len = socket.recv(&mut buf).map_err(|_| "no reply (UDP/443 blocked?)".to_string())?;
+    let rtt = start.elapsed();
+
+    let reply = &buf[..len];
+    if len < 7 || reply[0] & 0x80 == 0 || reply[1..5] != [0, 0, 0, 0] {
+        return Err("unexpected reply".to_string());
+    }
+    let dcid_len = reply[5] as usize;
+    let scid_len = *reply.get(6 + dcid_len).ok_or("truncated reply")? as usize;
+    let versions = reply
+        .get(7 + dcid_len + scid_len..)
+        .ok_or("truncated reply")?
+        .chunks(4)
+        .filter(|c| c.len() == 4)
+        .map(|c| u32::from_be_bytes([c[0], c[1], c[2], c[3]]))
+        .filter(|v| v & 0x0f0f_0f0f != 0x0a0a_0a0a)
+        .collect();
+    Ok((rtt, versions))
+}
+
+fn version_name(version: u32) -> String {
+    match version {
+        0x0000_0001 => "QUICv1".to_string(),
+        0x6b33_43cf => "QUICv2".to_string(),
+        v if v >> 8 == 0x00ff_0000 => format!("draft-{}", v & 0xff),
+        v => format!("0x{:08x}", v),
+    }
+}
+
+/// Makes a real HTTP/3 request when the local curl was built with HTTP/3 support.
+fn curl_http3_version(site: &str) -> Option<String> {
+    let features = netinfo::command_stdout("curl", &["-V"])?;
+    if !features.contains("HTTP3") {
+        return None;
+    }
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let url = format!("https://{}/", site);
+    let output = http::curl(&url)
+        .args(["--http3", "-s", "-o", null, "--max-time", "5", "-w", "%{http_version}"])
+        .output()
+        .ok()?;
+    if !output.status.success() {
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
@@ -0,0 +1,114 @@
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Mutex;
+
+use crate::chart;
+use crate::colorize;
+use crate::telemetry;
+
+/// Headline numbers reported by checks, in the order they were first seen.
+static METRICS: Mutex<Vec<Series>> = Mutex::new(Vec::new());
+
+/// The run values are recorded against, counting from 1.
+static RUN: AtomicU32 = AtomicU32::new(1);
+
+/// One metric's values, one per run it was measured in.
+struct Series {
+    label: String,
+    values: Vec<f64>,
+    /// The run the last value belongs to, and how many samples were averaged into it.
+    last_run: u32,
+    samples: u32,
+}
+
+/// Starts run `run` of `--repeat`; later values are recorded against it.
+pub fn start_run(run: u32) {
+    RUN.store(run, Ordering::Relaxed);
+}
+
+/// Records one run's value of `metric`, e.g. `("idle latency (ms)", 14.2)`. Checks call this for
+/// their summary figures so `--repeat` can aggregate them across runs; the value is also set on
+/// the current trace span. Values recorded more than once in a run are averaged, so the run
+/// still counts once.
+pub fn record(metric: &str, value: f64) {
+    store(metric.to_string(), None, metric, value);
+}
+
+/// Records one run's value of `metric` measured against `target`, e.g.
+/// `("ping avg RTT (ms)", "8.8.8.8", 14.2)`, aggregated per target.
+pub fn record_target(metric: &str, target: &str, value: f64) {
+    store(format!("{} {}", target, metric), Some(target), metric, value);
+}
+
+fn store(label: String, target: Option<&str>, metric: &str, value: f64) {
+    if !value.is_finite() {
+        return;
+    }
+    telemetry::metric(metric, target, value);
+    let run = RUN.load(Ordering::Relaxed);
+    if let Ok(mut metrics) = METRICS.lock() {
+        match metrics.iter_mut().find(|series| series.label == label) {
+            Some(series) if series.last_run == run => {
+                series.samples += 1;
+                if let Some(last) = series.values.last_mut() {
+                    *last += (value - *last) / f64::from(series.samples);
+                }
+            }
+            Some(series) => {
+                series.values.push(value);
+                series.last_run = run;
+                series.samples = 1;
+            }
+            None => metrics.push(Series { label, values: vec![value], last_run: run, samples: 1 }),
+        }
+    }
+}
+
+/// Prints mean, median, p95 and standard deviation of every metric recorded over `runs` runs.
+pub fn print_report(runs: u32) {
+    let metrics: Vec<(String, Vec<f64>)> = METRICS
+        .lock()
+        .map(|m| m.iter().map(|series| (series.label.clone(), series.values.clone())).collect())
+        .unwrap_or_default();
+    println!("\n📊 {} Aggregated over {} run(s)", colorize("[SUMMARY]", "blue"), runs);
+    if metrics.is_empty() {
+        println!("   This check reports no numeric metrics to aggregate.\n");
+        return;
+    }
+    let width = metrics.iter().map(|(name, _)| name.chars().count()).max().unwrap_or(0);
+    println!("   {:<width$} {:>5} {:>9} {:>9} {:>9} {:>9}", "Metric", "Runs", "Mean", "Median", "p95", "σ", width = width);
+    for (name, values) in &metrics {
+        let (_, mean, _, stddev) = chart::summarize(values);
+        println!(
+            "   {:<width$} {:>5} {:>9.1} {:>9.1} {:>9.1} {:>9.1}",
+            name,
+            values.len(),
+            mean,
+            chart::percentile(values, 50.0),
+            chart::percentile(values, 95.0),
+            stddev,
+            width = width
+        );
+    }
+    // A metric missing from some runs means that check failed outright in those runs.
+    for (name, values) in metrics.iter().filter(|(_, values)| (values.len() as u32) < runs) {
+        println!("⚠️  {} {} was only measured in {} of {} runs", colorize("[WARN]", "yellow"), name, values.len(), runs);
+    }
+    println!();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn counts_each_run_once() {
+        start_run(1);
+        record("test metric", 10.0);
+        record("test metric", 20.0);
+        start_run(2);
+        record("test metric", 30.0);
+        let metrics = METRICS.lock().unwrap();
+        let series = metrics.iter().find(|s| s.label == "test metric").unwrap();
+        assert_eq!(series.values, vec![15.0, 30.0]);
+    }
+}
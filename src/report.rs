@@ -0,0 +1,111 @@
+use std::fs;
+use std::path::Path;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json::{self, Value};
+
+use crate::baseline::{self, Snapshot};
+use crate::clock;
+use crate::colorize;
+use crate::netinfo;
+use crate::routes::{self, RouteEntry};
+use crate::template::Template;
+use crate::triage::Triage;
+
+/// Structured results: the same data `baseline` records, plus routes and anything unusual.
+/// This is what `bundle` saves as `report.json` and what report templates are rendered from.
+#[derive(Serialize)]
+pub struct Report {
+    pub generated_at: String,
+    pub netdiag_version: String,
+    pub hostname: Option<String>,
+    /// Plain-English verdict on where the fault is, for templates to lead with.
+    pub triage: Triage,
+    pub snapshot: Snapshot,
+    pub baseline_deviations: Vec<String>,
+    pub routes: Vec<RouteEntry>,
+    pub suspicious_routes: Vec<String>,
+}
+
+impl Report {
+    /// Probes key hosts and reads the routing table.
+    pub fn collect() -> Report {
+        let snapshot = Snapshot::take();
+        let routes = routes::table();
+        Report {
+            generated_at: clock::format_timestamp(snapshot.taken_at),
+            netdiag_version: env!("CARGO_PKG_VERSION").to_string(),
+            hostname: netinfo::command_stdout("hostname", &[]).map(|h| h.trim().to_string()),
+            baseline_deviations: baseline::load().map(|b| baseline::deviations(&b, &snapshot)).unwrap_or_default(),
+            suspicious_routes: routes::suspicious(&routes),
+            triage: Triage::run(&snapshot),
+            snapshot,
+            routes,
+        }
+    }
+}
+
+/// Returns the `report` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("report")
+        .about("Collects the structured results and prints them as JSON or through a template in a subset of Tera/Handlebars syntax")
+        .arg(Arg::with_name("template").long("template").short("t").takes_value(true).value_name("FILE")
+            .help("Template to render, e.g. report.tera, report.hbs or report.html; HTML templates escape values"))
+        .arg(Arg::with_name("input").long("input").takes_value(true).value_name("report.json")
+            .help("Render a saved report.json (e.g. from a bundle) instead of probing now"))
+        .arg(Arg::with_name("output").long("output").short("o").takes_value(true)
+            .help("File to write instead of stdout"))
+}
+
+/// Runs the `report` subcommand.
+pub fn run(matches: &ArgMatches) {
+    // Parse the template first so a typo is reported before a minute of probing.
+    let template = match matches.value_of("template").map(load_template).transpose() {
+        Ok(template) => template,
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+    };
+    let context = match matches.value_of("input") {
+        Some(path) => match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|t| serde_json::from_str::<Value>(&t).map_err(|e| e.to_string())) {
+            Ok(context) => context,
+            Err(e) => {
+                println!("❌ {} Could not read {}: {}", colorize("[ERROR]", "red"), path, e);
+                return;
+            }
+        },
+        None => {
+            eprintln!("🔹 {}", colorize("Probing key hosts for the report", "blue"));
+            let report = Report::collect();
+            eprintln!("🩺 {} {}", colorize("[TRIAGE]", "blue"), report.triage.conclusion);
+            serde_json::to_value(report).unwrap_or(Value::Null)
+        }
+    };
+    let text = match &template {
+        Some(template) => match template.render(&context) {
+            Ok(text) => text,
+            Err(e) => {
+                println!("❌ {} Template error: {}", colorize("[ERROR]", "red"), e);
+                return;
+            }
+        },
+        None => serde_json::to_string_pretty(&context).unwrap_or_default() + "\n",
+    };
+    match matches.value_of("output") {
+        Some(path) => match fs::write(path, &text) {
+            Ok(()) => eprintln!("✅ {} Report written to {}", colorize("[SUCCESS]", "green"), path),
+            Err(e) => println!("❌ {} Could not write {}: {}", colorize("[ERROR]", "red"), path, e),
+        },
+        None => print!("{}", text),
+    }
+}
+
+/// Reads and parses a template, escaping HTML when the file is an HTML template.
+fn load_template(path: &str) -> Result<Template, String> {
+    let source = fs::read_to_string(path).map_err(|e| format!("Could not read template {}: {}", path, e))?;
+    // report.html.tera and report.html.hbs are HTML too.
+    let name = Path::new(path).file_name().and_then(|n| n.to_str()).unwrap_or_default().to_ascii_lowercase();
+    let html = name.ends_with(".html") || name.ends_with(".htm") || name.contains(".html.");
+    Template::parse(&source, html).map_err(|e| format!("{}: {}", path, e))
+}
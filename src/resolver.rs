@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+#[cfg(unix)]
+use std::ffi::{CStr, CString};
+use std::fmt;
+#[cfg(unix)]
+use std::io;
+#[cfg(unix)]
+use std::mem;
+#[cfg(unix)]
+use std::net::{Ipv4Addr, Ipv6Addr};
+use std::net::{IpAddr, SocketAddr};
+#[cfg(not(unix))]
+use std::net::ToSocketAddrs;
+#[cfg(unix)]
+use std::ptr;
+use std::sync::{Mutex, OnceLock};
+use std::thread;
+use std::time::Duration;
+
+use crate::colorize;
+use crate::dns;
+
+const TIMEOUT: Duration = Duration::from_secs(3);
+
+/// Server given with `--resolve-via`; when unset the operating system resolves names.
+static RESOLVE_VIA: OnceLock<IpAddr> = OnceLock::new();
+
+/// Addresses of a name, or why it has none.
+type Answer = Result<Vec<IpAddr>, ResolveError>;
+
+/// Every answer so far, keyed by lower-cased name, so each name is looked up once per run.
+static CACHE: OnceLock<Mutex<HashMap<String, Answer>>> = OnceLock::new();
+
+/// Why a name did not resolve. Kept apart from connection errors so a DNS fault is not
+/// reported as the host being down.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ResolveError {
+    /// The resolver answered: the name does not exist or has no addresses.
+    NotFound { name: String, detail: String },
+    /// The resolver failed or did not answer.
+    Failed { name: String, detail: String },
+}
+
+impl fmt::Display for ResolveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ResolveError::NotFound { name, detail } => write!(f, "{} does not resolve ({})", name, detail),
+            ResolveError::Failed { name, detail } => write!(f, "DNS lookup of {} failed ({})", name, detail),
+        }
+    }
+}
+
+/// Sends every later lookup to `server` instead of the system resolver.
+pub fn set_resolve_via(server: IpAddr) {
+    let _ = RESOLVE_VIA.set(server);
+}
+
+/// The `--resolve-via` server, if one was given.
+pub fn resolve_via() -> Option<IpAddr> {
+    RESOLVE_VIA.get().copied()
+}
+
+/// Resolves `host` to its addresses, once per run. IP literals are returned as they are.
+pub fn resolve(host: &str) -> Answer {
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    if let Ok(ip) = host.parse::<IpAddr>() {
+        return Ok(vec![ip]);
+    }
+    let key = host.trim_end_matches('.').to_ascii_lowercase();
+    let cache = CACHE.get_or_init(|| Mutex::new(HashMap::new()));
+    if let Some(answer) = cache.lock().unwrap_or_else(|e| e.into_inner()).get(&key) {
+        return answer.clone();
+    }
+    let answer = match resolve_via() {
+        Some(server) => lookup_via(server, &key),
+        None => lookup_system(&key),
+    };
+    cache.lock().unwrap_or_else(|e| e.into_inner()).insert(key, answer.clone());
+    answer
+}
+
+/// The first address of `host`, preferring IPv4 as the rest of the checks do.
+pub fn resolve_one(host: &str) -> Result<IpAddr, ResolveError> {
+    let addrs = resolve(host)?;
+    addrs
+        .iter()
+        .find(|a| a.is_ipv4())
+        .or_else(|| addrs.first())
+        .copied()
+        .ok_or_else(|| ResolveError::NotFound { name: host.to_string(), detail: "no addresses".to_string() })
+}
+
+/// Resolves `host` and pairs its first address with `port`.
+pub fn socket_addr(host: &str, port: u16) -> Result<SocketAddr, ResolveError> {
+    resolve_one(host).map(|ip| SocketAddr::new(ip, port))
+}
+
+/// Resolves `names` concurrently so the checks that follow find them cached. Prints each name
+/// that failed and returns how many did.
+pub fn preflight(names: &[&str]) -> usize {
+    let lookups: Vec<_> = names
+        .iter()
+        .filter(|n| n.parse::<IpAddr>().is_err())
+        .map(|n| n.to_string())
+        .map(|name| thread::spawn(move || resolve(&name).err()))
+        .collect();
+    let mut failures = 0;
+    for lookup in lookups {
+        if let Ok(Some(e)) = lookup.join() {
+            failures += 1;
+            println!("❌ {} {}", colorize("[DNS]", "red"), e);
+        }
+    }
+    failures
+}
+
+/// Asks `server` for A and AAAA records directly.
+fn lookup_via(server: IpAddr, name: &str) -> Answer {
+    let mut addrs = Vec::new();
+    let mut failure = None;
+    for rtype in [dns::TYPE_A, dns::TYPE_AAAA] {
+        match dns::query(server, name, rtype, TIMEOUT) {
+            Ok(r) if r.rcode == dns::RCODE_NXDOMAIN => {
+                return Err(ResolveError::NotFound { name: name.to_string(), detail: format!("NXDOMAIN from {}", server) });
+            }
+            Ok(r) if r.rcode != dns::RCODE_NOERROR => failure = Some(format!("{} from {}", dns::rcode_name(r.rcode), server)),
+            Ok(r) => addrs.extend(r.values(rtype).iter().filter_map(|a| a.parse::<IpAddr>().ok())),
+            Err(e) => failure = Some(format!("{} did not answer: {}", server, e)),
+        }
+    }
+    match (addrs.is_empty(), failure) {
+        (false, _) => Ok(addrs),
+        (true, Some(detail)) => Err(ResolveError::Failed { name: name.to_string(), detail }),
+        (true, None) => Err(ResolveError::NotFound { name: name.to_string(), detail: format!("no addresses from {}", server) }),
+    }
+}
+
+/// "The name has no addresses"; FreeBSD folded it into `EAI_NONAME`.
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios"))]
+const EAI_NODATA: libc::c_int = libc::EAI_NODATA;
+#[cfg(all(unix, not(any(target_os = "linux", target_os = "android", target_os = "macos", target_os = "ios"))))]
+const EAI_NODATA: libc::c_int = libc::EAI_NONAME;
+
+/// Resolves through the operating system's getaddrinfo, telling "no such name" (`EAI_NONAME`,
+/// `EAI_NODATA`) apart from resolver failures (`EAI_AGAIN`, `EAI_FAIL` and anything else) by
+/// its error code.
+#[cfg(unix)]
+fn lookup_system(name: &str) -> Answer {
+    let c_name = CString::new(name).map_err(|_| ResolveError::NotFound { name: name.to_string(), detail: "contains a NUL byte".to_string() })?;
+    let mut hints: libc::addrinfo = unsafe { mem::zeroed() };
+    hints.ai_family = libc::AF_UNSPEC;
+    // One entry per address rather than one per socket type.
+    hints.ai_socktype = libc::SOCK_STREAM;
+    let mut list: *mut libc::addrinfo = ptr::null_mut();
+    let code = unsafe { libc::getaddrinfo(c_name.as_ptr(), ptr::null(), &hints, &mut list) };
+    if code != 0 {
+        let detail = match code {
+            libc::EAI_SYSTEM => io::Error::last_os_error().to_string(),
+            _ => unsafe { CStr::from_ptr(libc::gai_strerror(code)) }.to_string_lossy().into_owned(),
+        };
+        return Err(match code {
+            libc::EAI_NONAME | EAI_NODATA => ResolveError::NotFound { name: name.to_string(), detail },
+            _ => ResolveError::Failed { name: name.to_string(), detail },
+        });
+    }
+    let mut addrs: Vec<IpAddr> = Vec::new();
+    let mut entry = list;
+    while !entry.is_null() {
+        let info = unsafe { &*entry };
+        let addr = match info.ai_family {
+            libc::AF_INET if !info.ai_addr.is_null() => {
+                let sin = unsafe { &*(info.ai_addr as *const libc::sockaddr_in) };
+                Some(IpAddr::V4(Ipv4Addr::from(u32::from_be(sin.sin_addr.s_addr))))
+            }
+            libc::AF_INET6 if !info.ai_addr.is_null() => {
+                let sin6 = unsafe { &*(info.ai_addr as *const libc::sockaddr_in6) };
+                Some(IpAddr::V6(Ipv6Addr::from(sin6.sin6_addr.s6_addr)))
+            }
+            _ => None,
+        };
+        if let Some(addr) = addr.filter(|a| !addrs.contains(a)) {
+            addrs.push(addr);
+        }
+        entry = info.ai_next;
+    }
+    unsafe { libc::freeaddrinfo(list) };
+    if addrs.is_empty() {
+        return Err(ResolveError::NotFound { name: name.to_string(), detail: "no addresses".to_string() });
+    }
+    Ok(addrs)
+}
+
+/// Resolves through the operating system, telling "no such name" apart from resolver failures
+/// by the error text, as the standard library does not expose the resolver's error code here.
+#[cfg(not(unix))]
+fn lookup_system(name: &str) -> Answer {
+    match (name, 0).to_socket_addrs() {
+        Ok(addrs) => {
+            let addrs: Vec<IpAddr> = addrs.map(|a| a.ip()).collect();
+            if addrs.is_empty() {
+                Err(ResolveError::NotFound { name: name.to_string(), detail: "no addresses".to_string() })
+            } else {
+                Ok(addrs)
+            }
+        }
+        Err(e) => {
+            let detail = e.to_string();
+            let lower = detail.to_ascii_lowercase();
+            if lower.contains("not known") || lower.contains("no address") || lower.contains("no such host") {
+                Err(ResolveError::NotFound { name: name.to_string(), detail })
+            } else {
+                Err(ResolveError::Failed { name: name.to_string(), detail })
+            }
+        }
+    }
+}
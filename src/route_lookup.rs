@@ -0,0 +1,211 @@
+use std::net::IpAddr;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::netinfo;
+use crate::resolver;
+use crate::routes::{self, RouteEntry};
+use crate::vpn;
+
+/// The route the operating system itself reports for a destination.
+#[derive(Debug, Default)]
+struct KernelRoute {
+    interface: Option<String>,
+    gateway: Option<IpAddr>,
+    source: Option<IpAddr>,
+}
+
+/// Returns the `route` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("route")
+        .about("Shows which interface, gateway and source address are used to reach a destination")
+        .arg(Arg::with_name("destination").required(true).help("IP address or hostname"))
+}
+
+/// Runs the `route` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let destination = matches.value_of("destination").unwrap_or_default();
+    let addr = match resolve(destination) {
+        Some(addr) => addr,
+        None => {
+            println!("❌ {} Could not resolve {}", colorize("[ERROR]", "red"), destination);
+            return;
+        }
+    };
+    println!("\n🧭 {} Route lookup for {} ({})\n", colorize("[INFO]", "blue"), colorize(destination, "cyan"), addr);
+
+    let table = routes::table();
+    let candidates = matching_routes(&table, addr);
+    let kernel = kernel_lookup(addr);
+
+    print_candidates(&candidates);
+
+    let interface = kernel.interface.clone().or_else(|| candidates.first().map(|r| r.interface.clone()));
+    let gateway = kernel.gateway.or_else(|| candidates.first().and_then(|r| r.gateway));
+    let source = kernel.source.or_else(|| interface.as_deref().and_then(|i| source_address(i, addr)));
+
+    println!("\n🔹 {}", colorize("Selected path", "blue"));
+    println!("   Interface: {}", colorize(interface.as_deref().unwrap_or("unknown"), "cyan"));
+    println!("   Gateway:   {}", gateway.map(|g| g.to_string()).unwrap_or_else(|| "on-link (no gateway)".to_string()));
+    println!("   Source:    {}", source.map(|s| s.to_string()).unwrap_or_else(|| "unknown".to_string()));
+
+    if let (Some(kernel_if), Some(best)) = (&kernel.interface, candidates.first()) {
+        if *kernel_if != best.interface {
+            println!(
+                "⚠️  {} The OS uses {} but the main table prefers {} via {}; policy routing or an interface-scoped route is overriding it.",
+                colorize("[WARN]", "yellow"),
+                kernel_if,
+                best.cidr(),
+                best.interface
+            );
+        }
+    }
+    if let Some(interface) = interface {
+        check_split_tunnel(&table, addr, &interface, candidates.first().cloned());
+    }
+    println!();
+}
+
+/// The interface and gateway used to reach `addr`: what the OS reports, or else the best route in
+/// the table. The two are never mixed, since an on-link kernel answer has no gateway.
+pub fn selected_path(addr: IpAddr) -> (Option<String>, Option<IpAddr>) {
+    let kernel = kernel_lookup(addr);
+    if kernel.interface.is_some() {
+        return (kernel.interface, kernel.gateway);
+    }
+    let table = routes::table();
+    let best = matching_routes(&table, addr).first().map(|r| (r.interface.clone(), r.gateway));
+    best.map_or((None, None), |(interface, gateway)| (Some(interface), gateway))
+}
+
+fn resolve(destination: &str) -> Option<IpAddr> {
+    if let Ok(addr) = destination.parse() {
+        return Some(addr);
+    }
+    resolver::resolve_one(destination).ok()
+}
+
+/// Routes containing `addr` in the order they are consulted: longest prefix first, then lowest metric.
+fn matching_routes(table: &[RouteEntry], addr: IpAddr) -> Vec<&RouteEntry> {
+    let mut matches: Vec<&RouteEntry> = table.iter().filter(|r| r.contains(addr)).collect();
+    matches.sort_by_key(|r| (std::cmp::Reverse(r.prefix), r.metric.unwrap_or(0)));
+    matches
+}
+
+fn print_candidates(candidates: &[&RouteEntry]) {
+    println!("🔹 {}", colorize("Matching routes, in the order they are checked", "blue"));
+    if candidates.is_empty() {
+        println!("⚠️  {} No route in the table covers this destination", colorize("[WARN]", "yellow"));
+        return;
+    }
+    for (i, route) in candidates.iter().enumerate() {
+        let gateway = route.gateway.map(|g| g.to_string()).unwrap_or_else(|| "on-link".to_string());
+        let metric = route.metric.map(|m| format!(" metric {}", m)).unwrap_or_default();
+        let line = format!("{:<30} via {:<25} dev {}{}", route.cidr(), gateway, route.interface, metric);
+        if i == 0 {
+            println!("   {} {}", colorize(&line, "green"), colorize(&format!("← selected (longest prefix /{})", route.prefix), "green"));
+        } else {
+            println!("   {}", line);
+        }
+    }
+}
+
+/// Asks the OS which route it would actually use, via `ip route get`, `route get` or `Find-NetRoute`.
+fn kernel_lookup(addr: IpAddr) -> KernelRoute {
+    let dest = addr.to_string();
+    if let Some(out) = netinfo::command_stdout("ip", &["route", "get", &dest]) {
+        let words: Vec<&str> = out.split_whitespace().collect();
+        let value_of = |key: &str| words.iter().position(|w| *w == key).and_then(|i| words.get(i + 1));
+        return KernelRoute {
+            interface: value_of("dev").map(|w| w.to_string()),
+            gateway: value_of("via").and_then(|w| w.parse().ok()),
+            source: value_of("src").and_then(|w| w.parse().ok()),
+        };
+    }
+
+    if let Some(out) = netinfo::command_stdout("route", &["-n", "get", &dest]) {
+        let field = |key: &str| out.lines().find_map(|l| l.trim().strip_prefix(key).map(|v| v.trim().to_string()));
+        return KernelRoute {
+            interface: field("interface:"),
+            gateway: field("gateway:").and_then(|g| g.parse().ok()),
+            source: None,
+        };
+    }
+
+    if cfg!(windows) {
+        let script = format!("Find-NetRoute -RemoteIPAddress {} | Format-List IPAddress,InterfaceAlias,NextHop", dest);
+        if let Some(out) = netinfo::command_stdout("powershell", &["-NoProfile", "-Command", &script]) {
+            let field = |key: &str| {
+                out.lines()
+                    .filter_map(|l| l.split_once(':'))
+                    .find(|(k, v)| k.trim() == key && !v.trim().is_empty())
+                    .map(|(_, v)| v.trim().to_string())
+            };
+            return KernelRoute {
+                interface: field("InterfaceAlias"),
+                gateway: field("NextHop").and_then(|g| g.parse().ok()).filter(|g: &IpAddr| !g.is_unspecified()),
+                source: field("IPAddress").and_then(|s| s.parse().ok()),
+            };
+        }
+    }
+    KernelRoute::default()
+}
+
+/// First address of the destination's family on `interface`, skipping IPv6 link-local ones.
+fn source_address(interface: &str, dest: IpAddr) -> Option<IpAddr> {
+    netinfo::interfaces()
+        .into_iter()
+        .find(|i| i.name == interface)?
+        .addrs
+        .iter()
+        .filter_map(|a| a.parse::<IpAddr>().ok())
+        .find(|a| a.is_ipv4() == dest.is_ipv4() && !a.to_string().starts_with("fe80"))
+}
+
+/// Flags destinations that a VPN's routes send the wrong way.
+fn check_split_tunnel(table: &[RouteEntry], addr: IpAddr, interface: &str, selected: Option<&RouteEntry>) {
+    let vpn_routes: Vec<&RouteEntry> = table.iter().filter(|r| vpn::is_vpn_interface(&r.interface)).collect();
+    if vpn_routes.is_empty() {
+        return;
+    }
+    let full_tunnel = vpn_routes.iter().any(|r| r.prefix <= 4 && r.destination.is_ipv4() == addr.is_ipv4());
+    let via_vpn = vpn::is_vpn_interface(interface);
+    let local = vpn::is_local(&addr.to_string());
+    let selected = selected.map(|r| r.cidr()).unwrap_or_default();
+
+    println!("\n🔹 {}", colorize("VPN routing", "blue"));
+    match (local, via_vpn, full_tunnel) {
+        (false, false, true) => println!(
+            "❌ {} The VPN claims all traffic, but {} bypasses the tunnel via {} ({}). Check for a split-tunnel exclusion or a stale host route.",
+            colorize("[LEAK]", "red"),
+            addr,
+            interface,
+            selected
+        ),
+        (false, false, false) => println!(
+            "ℹ️  Split tunnel: {} goes direct via {}; only the VPN's {} route(s) use the tunnel.",
+            addr,
+            interface,
+            vpn_routes.len()
+        ),
+        (true, true, _) => {
+            let lan = table
+                .iter()
+                .find(|r| r.contains(addr) && r.gateway.is_none() && !vpn::is_vpn_interface(&r.interface));
+            match lan {
+                Some(lan) => println!(
+                    "⚠️  {} {} is on the local network {} ({}), but the VPN route {} captures it; local devices in that range are unreachable.",
+                    colorize("[WARN]", "yellow"),
+                    addr,
+                    lan.cidr(),
+                    lan.interface,
+                    selected
+                ),
+                None => println!("✅ {} {} is a private address routed through the tunnel ({})", colorize("[OK]", "green"), addr, selected),
+            }
+        }
+        (_, true, _) => println!("✅ {} Traffic to {} uses the tunnel via {}", colorize("[OK]", "green"), addr, interface),
+        (true, false, _) => println!("✅ {} {} is local and stays off the tunnel", colorize("[OK]", "green"), addr),
+    }
+}
@@ -0,0 +1,315 @@
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json;
+
+use crate::colorize;
+use crate::netinfo;
+use crate::vpn;
+
+/// One row of the kernel routing table, normalised across platforms.
+#[derive(Debug, Clone, Serialize)]
+pub struct RouteEntry {
+    pub destination: IpAddr,
+    pub prefix: u8,
+    /// Next hop, or `None` for on-link routes.
+    pub gateway: Option<IpAddr>,
+    pub interface: String,
+    /// Route metric, where the platform reports one (BSD `netstat` does not).
+    pub metric: Option<u32>,
+}
+
+impl RouteEntry {
+    pub fn is_default(&self) -> bool {
+        self.prefix == 0
+    }
+
+    /// True when `addr` falls inside this route's destination prefix.
+    pub fn contains(&self, addr: IpAddr) -> bool {
+        match (self.destination, addr) {
+            (IpAddr::V4(net), IpAddr::V4(a)) => {
+                let mask = if self.prefix == 0 { 0 } else { u32::MAX << (32 - self.prefix.min(32) as u32) };
+                u32::from(net) & mask == u32::from(a) & mask
+            }
+            (IpAddr::V6(net), IpAddr::V6(a)) => {
+                let mask = if self.prefix == 0 { 0 } else { u128::MAX << (128 - self.prefix.min(128) as u32) };
+                u128::from(net) & mask == u128::from(a) & mask
+            }
+            _ => false,
+        }
+    }
+
+    pub fn cidr(&self) -> String {
+        if self.is_default() {
+            "default".to_string()
+        } else {
+            format!("{}/{}", self.destination, self.prefix)
+        }
+    }
+}
+
+/// Returns the `routes` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("routes")
+        .about("Shows the routing table and flags conflicting default routes")
+        .arg(Arg::with_name("json").long("json").help("Print the parsed table as JSON"))
+}
+
+/// Runs the `routes` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let routes = table();
+    if matches.is_present("json") {
+        println!("{}", serde_json::to_string_pretty(&routes).unwrap_or_default());
+        return;
+    }
+    println!();
+    print_table(&routes);
+}
+
+/// Reads the IPv4 and IPv6 routing tables with the platform's native tool.
+pub fn table() -> Vec<RouteEntry> {
+    if cfg!(windows) {
+        let mut routes = netinfo::command_stdout("route", &["print", "-4"]).map(|o| parse_windows(&o)).unwrap_or_default();
+        routes.extend(netinfo::command_stdout("route", &["print", "-6"]).map(|o| parse_windows(&o)).unwrap_or_default());
+        return routes;
+    }
+    if let Some(v4) = netinfo::command_stdout("ip", &["-4", "route", "show"]) {
+        let mut routes = parse_iproute(&v4, false);
+        routes.extend(netinfo::command_stdout("ip", &["-6", "route", "show"]).map(|o| parse_iproute(&o, true)).unwrap_or_default());
+        return routes;
+    }
+    netinfo::command_stdout("netstat", &["-rn"]).map(|o| parse_netstat(&o)).unwrap_or_default()
+}
+
+/// Parses `ip route show` output (Linux).
+fn parse_iproute(output: &str, v6: bool) -> Vec<RouteEntry> {
+    const TYPES: [&str; 8] = ["unicast", "local", "broadcast", "multicast", "unreachable", "blackhole", "prohibit", "throw"];
+    let mut routes = Vec::new();
+    for line in output.lines() {
+        let mut words: Vec<&str> = line.split_whitespace().collect();
+        if words.first().is_some_and(|w| TYPES.contains(w)) {
+            words.remove(0);
+        }
+        let destination = match words.first() {
+            Some(&"default") if v6 => Some((IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0)),
+            Some(&"default") => Some((IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0)),
+            Some(dest) => parse_cidr(dest),
+            None => None,
+        };
+        let (destination, prefix) = match destination {
+            Some(d) => d,
+            None => continue,
+        };
+
+        let value_of = |key: &str| words.iter().position(|w| *w == key).and_then(|i| words.get(i + 1)).cloned();
+        let gateway = words
+            .iter()
+            .position(|w| *w == "via")
+            .and_then(|i| words[i + 1..].iter().find(|w| **w != "inet" && **w != "inet6"))
+            .and_then(|w| w.parse().ok());
+        routes.push(RouteEntry {
+            destination,
+            prefix,
+            gateway,
+            interface: value_of("dev").unwrap_or("").to_string(),
+            metric: value_of("metric").and_then(|m| m.parse().ok()),
+        });
+    }
+    routes
+}
+
+/// Parses BSD/macOS `netstat -rn` output, which abbreviates networks (`10/8`, `192.168.1`).
+fn parse_netstat(output: &str) -> Vec<RouteEntry> {
+    let mut routes = Vec::new();
+    let mut v6 = false;
+    let mut netif_column = None;
+    for line in output.lines() {
+        let cols: Vec<&str> = line.split_whitespace().collect();
+        match cols.first() {
+            Some(&"Internet:") => v6 = false,
+            Some(&"Internet6:") => v6 = true,
+            Some(&"Destination") => netif_column = cols.iter().position(|c| *c == "Netif" || *c == "Iface"),
+            _ => {}
+        }
+        let netif = match netif_column {
+            Some(column) if cols.len() > column && cols[0] != "Destination" => cols[column],
+            _ => continue,
+        };
+
+        let destination = if cols[0] == "default" {
+            Some(if v6 { (IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0) } else { (IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0) })
+        } else if v6 {
+            parse_cidr(cols[0])
+        } else {
+            parse_bsd_v4(cols[0])
+        };
+        if let Some((destination, prefix)) = destination {
+            routes.push(RouteEntry {
+                destination,
+                prefix,
+                gateway: strip_scope(cols[1]).parse().ok(),
+                interface: netif.to_string(),
+                metric: None,
+            });
+        }
+    }
+    routes
+}
+
+/// Parses the "Active Routes" sections of Windows `route print`.
+fn parse_windows(output: &str) -> Vec<RouteEntry> {
+    let mut routes = Vec::new();
+    let mut active = false;
+    for line in output.lines() {
+        let trimmed = line.trim();
+        if trimmed.starts_with("Active Routes") {
+            active = true;
+            continue;
+        }
+        if trimmed.starts_with("Persistent Routes") || trimmed.starts_with("====") {
+            active = false;
+            continue;
+        }
+        if !active {
+            continue;
+        }
+        let cols: Vec<&str> = trimmed.split_whitespace().collect();
+        match cols.len() {
+            // IPv4: Network Destination, Netmask, Gateway, Interface, Metric
+            5 => {
+                let (destination, mask) = match (cols[0].parse::<Ipv4Addr>(), cols[1].parse::<Ipv4Addr>()) {
+                    (Ok(d), Ok(m)) => (d, m),
+                    _ => continue,
+                };
+                routes.push(RouteEntry {
+                    destination: IpAddr::V4(destination),
+                    prefix: u32::from(mask).count_ones() as u8,
+                    gateway: cols[2].parse().ok(),
+                    interface: cols[3].to_string(),
+                    metric: cols[4].parse().ok(),
+                });
+            }
+            // IPv6: If, Metric, Network Destination, Gateway
+            4 => {
+                if let Some((destination, prefix)) = parse_cidr(cols[2]) {
+                    routes.push(RouteEntry {
+                        destination,
+                        prefix,
+                        gateway: cols[3].parse().ok(),
+                        interface: cols[0].to_string(),
+                        metric: cols[1].parse().ok(),
+                    });
+                }
+            }
+            _ => {}
+        }
+    }
+    routes
+}
+
+/// Parses `addr/prefix`, or a bare address as a host route.
+fn parse_cidr(text: &str) -> Option<(IpAddr, u8)> {
+    let (addr, prefix) = match text.split_once('/') {
+        Some((addr, prefix)) => (addr, Some(prefix.parse().ok()?)),
+        None => (text, None),
+    };
+    let addr: IpAddr = strip_scope(addr).parse().ok()?;
+    let full = if addr.is_ipv4() { 32 } else { 128 };
+    Some((addr, prefix.unwrap_or(full)))
+}
+
+/// Expands BSD shorthand such as `127`, `192.168.1` or `10/8` into a full network.
+fn parse_bsd_v4(text: &str) -> Option<(IpAddr, u8)> {
+    let (net, prefix) = match text.split_once('/') {
+        Some((net, prefix)) => (net, Some(prefix.parse::<u8>().ok()?)),
+        None => (text, None),
+    };
+    let mut octets = [0u8; 4];
+    let parts: Vec<&str> = net.split('.').collect();
+    if parts.is_empty() || parts.len() > 4 {
+        return None;
+    }
+    for (octet, part) in octets.iter_mut().zip(&parts) {
+        *octet = part.parse().ok()?;
+    }
+    let prefix = prefix.unwrap_or(parts.len() as u8 * 8);
+    Some((IpAddr::V4(Ipv4Addr::from(octets)), prefix))
+}
+
+/// Drops an IPv6 zone suffix such as `%en0`.
+fn strip_scope(addr: &str) -> &str {
+    addr.split('%').next().unwrap_or(addr)
+}
+
+/// Describes routes that commonly explain "traffic goes the wrong way" problems.
+pub fn suspicious(routes: &[RouteEntry]) -> Vec<String> {
+    let mut findings = Vec::new();
+    for v6 in [false, true] {
+        let family = if v6 { "IPv6" } else { "IPv4" };
+        let defaults: Vec<&RouteEntry> = routes.iter().filter(|r| r.is_default() && r.destination.is_ipv6() == v6).collect();
+        let interfaces: Vec<&str> = defaults.iter().map(|r| r.interface.as_str()).collect();
+        let distinct = {
+            let mut names = interfaces.clone();
+            names.sort();
+            names.dedup();
+            names.len()
+        };
+        if distinct > 1 {
+            let tied = defaults.iter().any(|a| defaults.iter().any(|b| a.interface != b.interface && a.metric == b.metric));
+            findings.push(format!(
+                "{} {} default routes via {}{}",
+                defaults.len(),
+                family,
+                interfaces.join(", "),
+                if tied { " with equal (or no) metrics; the OS may pick either" } else { "" }
+            ));
+        }
+
+        // Split-default routes such as 0.0.0.0/1 + 128.0.0.0/1 override the default without replacing it.
+        for route in routes.iter().filter(|r| r.destination.is_ipv6() == v6 && r.prefix > 0 && r.prefix <= 4) {
+            let via_vpn = if vpn::is_vpn_interface(&route.interface) { " (VPN client)" } else { "" };
+            findings.push(format!(
+                "{} overrides the default route via {}{}",
+                route.cidr(),
+                route.interface,
+                via_vpn
+            ));
+        }
+    }
+    findings
+}
+
+/// Prints the table followed by default-route and overlap findings.
+pub fn print_table(routes: &[RouteEntry]) {
+    println!("🔹 {}", colorize("Displaying Routing Table", "blue"));
+    if routes.is_empty() {
+        println!("❌ {} Could not read the routing table\n", colorize("[ERROR]", "red"));
+        return;
+    }
+
+    println!(
+        "   {:<44} {:<40} {:<21} {:>15}",
+        colorize("Destination", "yellow"),
+        colorize("Gateway", "cyan"),
+        colorize("Interface", "blue"),
+        colorize("Metric", "green")
+    );
+    for route in routes {
+        let gateway = route.gateway.map(|g| g.to_string()).unwrap_or_else(|| "on-link".to_string());
+        let metric = route.metric.map(|m| m.to_string()).unwrap_or_else(|| "-".to_string());
+        println!("   {:<35} {:<31} {:<12} {:>6}", route.cidr(), gateway, route.interface, metric);
+    }
+
+    match routes.iter().find(|r| r.is_default() && r.destination.is_ipv4()) {
+        Some(route) => println!(
+            "\n   Default route: {} via {}",
+            colorize(&route.interface, "cyan"),
+            route.gateway.map(|g| g.to_string()).unwrap_or_else(|| "on-link".to_string())
+        ),
+        None => println!("\n⚠️  {} No IPv4 default route; only local networks are reachable", colorize("[WARN]", "yellow")),
+    }
+    for finding in suspicious(routes) {
+        println!("⚠️  {} {}", colorize("[WARN]", "yellow"), finding);
+    }
+    println!();
+}
@@ -0,0 +1,175 @@
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr, TcpStream};
+use std::ops::{Deref, DerefMut};
+use std::path::PathBuf;
+use std::process::Command;
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+use crate::clock;
+use crate::config::ProbesConfig;
+use crate::data_dir;
+use crate::interrupt;
+
+/// Limits from the config, set once at startup; the defaults apply until then.
+static LIMITS: OnceLock<ProbesConfig> = OnceLock::new();
+
+static SCHEDULER: OnceLock<Scheduler> = OnceLock::new();
+
+/// Longest gap the rate limit puts between two probes.
+const MAX_INTERVAL_SECS: f64 = 3600.0;
+
+/// Shared by every thread that sends probes.
+struct Scheduler {
+    state: Mutex<State>,
+    /// Signalled whenever a probe finishes and frees a per-target slot.
+    freed: Condvar,
+    /// The audit log, opened on the first probe.
+    log: Mutex<Option<File>>,
+}
+
+#[derive(Default)]
+struct State {
+    /// Earliest time the next probe may go out under the global rate limit.
+    next_slot: Option<Instant>,
+    /// Probes in progress per target: an address, or a host name when a subprocess resolves it.
+    active: HashMap<String, usize>,
+    sent: u64,
+}
+
+/// Permission to send one probe. The target's concurrency slot is released when it is dropped,
+/// so hold it until the probe has been answered or timed out.
+pub struct Permit {
+    target: String,
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        let scheduler = scheduler();
+        let mut state = scheduler.state.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(active) = state.active.get_mut(&self.target) {
+            *active -= 1;
+            if *active == 0 {
+                state.active.remove(&self.target);
+            }
+        }
+        scheduler.freed.notify_all();
+    }
+}
+
+/// Applies the `probes:` section of the config to every probe sent from now on.
+pub fn configure(limits: ProbesConfig) {
+    let _ = LIMITS.set(limits);
+}
+
+fn limits() -> &'static ProbesConfig {
+    LIMITS.get_or_init(ProbesConfig::default)
+}
+
+fn scheduler() -> &'static Scheduler {
+    SCHEDULER.get_or_init(|| Scheduler { state: Mutex::new(State::default()), freed: Condvar::new(), log: Mutex::new(None) })
+}
+
+/// Where every probe is recorded.
+pub fn audit_log_path() -> PathBuf {
+    data_dir().join("probes.log")
+}
+
+/// Probes sent so far in this run.
+pub fn sent() -> u64 {
+    scheduler().state.lock().map(|s| s.sent).unwrap_or(0)
+}
+
+/// One line describing the limits in force, for commands that send many probes.
+pub fn describe() -> String {
+    let limits = limits();
+    let rate = if limits.max_rate > 0.0 { format!("{} probes/s", limits.max_rate) } else { "no rate limit".to_string() };
+    let audit = if limits.audit_log { format!("logged to {}", audit_log_path().display()) } else { "not logged".to_string() };
+    format!("{}, at most {} at a time per target, {}", rate, limits.per_target.max(1), audit)
+}
+
+/// Blocks until a probe of `kind` (e.g. "tcp-connect") to `target` is allowed by the global rate
+/// and the target's concurrency cap, records it in the audit log, and returns its permit.
+pub fn acquire(kind: &str, target: IpAddr, port: Option<u16>) -> Permit {
+    acquire_host(kind, &target.to_string(), port)
+}
+
+/// Like [`acquire`], for probes that name their target, e.g. a URL handed to curl.
+pub fn acquire_host(kind: &str, host: &str, port: Option<u16>) -> Permit {
+    let target = host.trim_start_matches('[').trim_end_matches(']').to_ascii_lowercase();
+    let limits = limits();
+    let scheduler = scheduler();
+    let wait = {
+        let mut state = scheduler.state.lock().unwrap_or_else(|e| e.into_inner());
+        while state.active.get(&target).copied().unwrap_or(0) >= limits.per_target.max(1) {
+            state = scheduler.freed.wait(state).unwrap_or_else(|e| e.into_inner());
+        }
+        *state.active.entry(target.clone()).or_insert(0) += 1;
+        state.sent += 1;
+        // Reserve the next free slot while holding the lock, then sleep outside it.
+        let now = Instant::now();
+        let slot = state.next_slot.map_or(now, |next| next.max(now));
+        if limits.max_rate > 0.0 {
+            // At least one probe an hour, so a tiny rate cannot overflow the interval.
+            state.next_slot = Some(slot + Duration::from_secs_f64((1.0 / limits.max_rate).min(MAX_INTERVAL_SECS)));
+        }
+        slot.saturating_duration_since(now)
+    };
+    // An interrupt cuts the wait short; the probe's own command or socket then notices it.
+    interrupt::sleep(wait);
+    if limits.audit_log {
+        audit(scheduler, kind, &target, port);
+    }
+    Permit { target }
+}
+
+/// Opens a TCP connection as a scheduled `tcp-connect` probe. The permit covers the handshake only.
+pub fn connect(addr: SocketAddr, timeout: Duration) -> io::Result<TcpStream> {
+    let _permit = acquire("tcp-connect", addr.ip(), Some(addr.port()));
+    TcpStream::connect_timeout(&addr, timeout)
+}
+
+/// A subprocess that sends probes (curl, ping, openssl, ...), holding its permit until dropped.
+/// Derefs to the `Command`, so it is built and run like one.
+pub struct Probe {
+    command: Command,
+    _permit: Permit,
+}
+
+impl Deref for Probe {
+    type Target = Command;
+
+    fn deref(&self) -> &Command {
+        &self.command
+    }
+}
+
+impl DerefMut for Probe {
+    fn deref_mut(&mut self) -> &mut Command {
+        &mut self.command
+    }
+}
+
+/// Schedules `command` as a probe of `kind` to `host`; run it before the `Probe` is dropped.
+pub fn command(kind: &str, host: &str, port: Option<u16>, command: Command) -> Probe {
+    Probe { _permit: acquire_host(kind, host, port), command }
+}
+
+/// Appends `time kind target[:port]` to the audit log.
+fn audit(scheduler: &Scheduler, kind: &str, target: &str, port: Option<u16>) {
+    let mut log = scheduler.log.lock().unwrap_or_else(|e| e.into_inner());
+    if log.is_none() {
+        let _ = fs::create_dir_all(data_dir());
+        *log = OpenOptions::new().create(true).append(true).open(audit_log_path()).ok();
+    }
+    let target = match port {
+        Some(port) if target.contains(':') => format!("[{}]:{}", target, port),
+        Some(port) => format!("{}:{}", target, port),
+        None => target.to_string(),
+    };
+    if let Some(file) = log.as_mut() {
+        let _ = writeln!(file, "{} {} {}", clock::format_timestamp(clock::unix_now()), kind, target);
+    }
+}
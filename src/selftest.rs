@@ -0,0 +1,305 @@
+use std::fs;
+use std::io::{Read, Write};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream, ToSocketAddrs, UdpSocket};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::netinfo;
+use crate::scheduler;
+use crate::telemetry;
+
+const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Payload echoed over the loopback sockets.
+const PAYLOAD: &[u8] = b"netdiag selftest";
+
+/// Looking up the machine's own name slower than this stalls sudo, ssh and mail delivery.
+const SLOW_LOOKUP: Duration = Duration::from_secs(1);
+
+/// Share of a table in use at which it is reported as nearly full, and as exhausted.
+const WARN_USAGE: f64 = 0.75;
+const FAIL_USAGE: f64 = 0.90;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Verdict {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// One local-stack check and what it found.
+struct Check {
+    name: &'static str,
+    verdict: Verdict,
+    detail: String,
+}
+
+impl Check {
+    fn new(name: &'static str, verdict: Verdict, detail: String) -> Check {
+        Check { name, verdict, detail }
+    }
+}
+
+/// Returns the `selftest` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("selftest")
+        .about("Checks the local network stack (loopback, sockets, conntrack and descriptor limits, own hostname) before blaming the network")
+}
+
+/// Runs the `selftest` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    println!();
+    selftest();
+    println!();
+}
+
+/// Runs every local-stack check and prints the results. Returns how many failed.
+pub fn selftest() -> usize {
+    println!("🔹 {}", colorize("Checking the local network stack", "blue"));
+    let mut checks = vec![loopback_ping()];
+    for ip in [IpAddr::V4(Ipv4Addr::LOCALHOST), IpAddr::V6(Ipv6Addr::LOCALHOST)] {
+        checks.push(tcp_round_trip(ip));
+        checks.push(udp_round_trip(ip));
+    }
+    checks.extend(conntrack());
+    checks.extend(ephemeral_ports());
+    checks.extend(file_descriptors());
+    checks.push(own_hostname());
+
+    for check in &checks {
+        match check.verdict {
+            Verdict::Pass => println!("   ✅ {:<18} {}", check.name, check.detail),
+            Verdict::Warn => println!("   ⚠️  {:<18} {}", check.name, colorize(&check.detail, "yellow")),
+            Verdict::Fail => println!("   ❌ {:<18} {}", check.name, colorize(&check.detail, "red")),
+        }
+    }
+    let failed = checks.iter().filter(|c| c.verdict == Verdict::Fail).count();
+    if failed == 0 {
+        println!("✅ {} The local stack works; problems lie beyond this host.", colorize("[SUCCESS]", "green"));
+    } else {
+        println!(
+            "❌ {} {} local check(s) failed; fix this host before troubleshooting the network.",
+            colorize("[ERROR]", "red"),
+            failed
+        );
+        telemetry::fail(&format!("{} local check(s) failed", failed));
+    }
+    failed
+}
+
+/// 127.0.0.1 must answer: if it does not, a firewall or a broken `lo` interface is at fault.
+fn loopback_ping() -> Check {
+    match netinfo::ping("127.0.0.1", 3) {
+        Some(stats) if stats.received > 0 => Check::new(
+            "Loopback ping",
+            Verdict::Pass,
+            format!("127.0.0.1 answered {}/{} in {:.2} ms on average", stats.received, stats.transmitted, stats.avg_ms),
+        ),
+        Some(stats) => Check::new(
+            "Loopback ping",
+            Verdict::Fail,
+            format!("127.0.0.1 answered 0/{}; check that `lo` is up and the firewall accepts loopback traffic", stats.transmitted),
+        ),
+        None => Check::new("Loopback ping", Verdict::Fail, "could not ping 127.0.0.1".to_string()),
+    }
+}
+
+/// Binds an ephemeral TCP port on `ip`, connects to it and echoes a payload through it.
+fn tcp_round_trip(ip: IpAddr) -> Check {
+    let name = if ip.is_ipv4() { "TCP on 127.0.0.1" } else { "TCP on ::1" };
+    let listener = match TcpListener::bind(SocketAddr::new(ip, 0)) {
+        Ok(listener) => listener,
+        Err(e) => return unbindable(name, ip, e),
+    };
+    let addr = match listener.local_addr() {
+        Ok(addr) => addr,
+        Err(e) => return Check::new(name, Verdict::Fail, format!("bound, but has no local address: {}", e)),
+    };
+    let echo = thread::spawn(move || -> std::io::Result<()> {
+        let (mut stream, _) = listener.accept()?;
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf)?;
+        stream.write_all(&buf[..n])
+    });
+
+    let start = Instant::now();
+    let result = scheduler::connect(addr, TIMEOUT).and_then(|mut stream| {
+        stream.set_read_timeout(Some(TIMEOUT))?;
+        stream.write_all(PAYLOAD)?;
+        let mut buf = [0u8; 64];
+        let n = stream.read(&mut buf)?;
+        Ok(buf[..n].to_vec())
+    });
+    let elapsed = start.elapsed();
+    // Unblocks the echo thread's accept if the connect never arrived.
+    let _ = TcpStream::connect_timeout(&addr, Duration::from_millis(100));
+    let _ = echo.join();
+    match result {
+        Ok(echoed) if echoed == PAYLOAD => Check::new(
+            name,
+            Verdict::Pass,
+            format!("bound port {}, connected and echoed in {:.2} ms", addr.port(), elapsed.as_secs_f64() * 1000.0),
+        ),
+        Ok(_) => Check::new(name, Verdict::Fail, format!("port {} echoed different bytes than were sent", addr.port())),
+        Err(e) => Check::new(name, Verdict::Fail, format!("bound port {} but could not connect to it: {}", addr.port(), e)),
+    }
+}
+
+/// Binds two ephemeral UDP ports on `ip` and sends a datagram from one to the other.
+fn udp_round_trip(ip: IpAddr) -> Check {
+    let name = if ip.is_ipv4() { "UDP on 127.0.0.1" } else { "UDP on ::1" };
+    let (receiver, sender) = match (UdpSocket::bind(SocketAddr::new(ip, 0)), UdpSocket::bind(SocketAddr::new(ip, 0))) {
+        (Ok(receiver), Ok(sender)) => (receiver, sender),
+        (Err(e), _) | (_, Err(e)) => return unbindable(name, ip, e),
+    };
+    let start = Instant::now();
+    let result = receiver.set_read_timeout(Some(TIMEOUT)).and_then(|_| {
+        let addr = receiver.local_addr()?;
+        sender.send_to(PAYLOAD, addr)?;
+        let mut buf = [0u8; 64];
+        let (n, _) = receiver.recv_from(&mut buf)?;
+        Ok((addr.port(), buf[..n].to_vec()))
+    });
+    match result {
+        Ok((port, received)) if received == PAYLOAD => Check::new(
+            name,
+            Verdict::Pass,
+            format!("bound port {}, datagram delivered in {:.2} ms", port, start.elapsed().as_secs_f64() * 1000.0),
+        ),
+        Ok((port, _)) => Check::new(name, Verdict::Fail, format!("port {} received different bytes than were sent", port)),
+        Err(e) => Check::new(name, Verdict::Fail, format!("datagram to itself was lost: {}", e)),
+    }
+}
+
+/// A bind that failed. IPv6 disabled on the host only warrants a warning; running out of
+/// ports or descriptors does not.
+fn unbindable(name: &'static str, ip: IpAddr, e: std::io::Error) -> Check {
+    if ip.is_ipv6() && e.kind() == std::io::ErrorKind::AddrNotAvailable {
+        return Check::new(name, Verdict::Warn, "::1 is not configured; IPv6 is disabled on this host".to_string());
+    }
+    Check::new(name, Verdict::Fail, format!("could not bind an ephemeral port on {}: {}", ip, e))
+}
+
+/// Grades `used` of `limit` entries of a kernel table.
+fn usage(name: &'static str, what: &str, used: u64, limit: u64, hint: &str) -> Check {
+    let share = used as f64 / limit.max(1) as f64;
+    let detail = format!("{} of {} {} in use ({:.0}%)", used, limit, what, share * 100.0);
+    match share {
+        s if s >= FAIL_USAGE => Check::new(name, Verdict::Fail, format!("{}; {}", detail, hint)),
+        s if s >= WARN_USAGE => Check::new(name, Verdict::Warn, format!("{}; {}", detail, hint)),
+        _ => Check::new(name, Verdict::Pass, detail),
+    }
+}
+
+fn read_number(path: &str) -> Option<u64> {
+    fs::read_to_string(path).ok()?.trim().parse().ok()
+}
+
+/// A full connection-tracking table drops new connections ("nf_conntrack: table full").
+fn conntrack() -> Option<Check> {
+    let count = read_number("/proc/sys/net/netfilter/nf_conntrack_count")?;
+    let max = read_number("/proc/sys/net/netfilter/nf_conntrack_max")?;
+    Some(usage("Conntrack table", "tracked connections", count, max, "raise net.netfilter.nf_conntrack_max or find what opens so many connections"))
+}
+
+/// Sockets holding a local port in the ephemeral range (connected or in TIME_WAIT). When the
+/// range runs out, outgoing connects fail with "Cannot assign requested address".
+fn ephemeral_ports() -> Option<Check> {
+    let range = fs::read_to_string("/proc/sys/net/ipv4/ip_local_port_range").ok()?;
+    let mut bounds = range.split_whitespace().filter_map(|n| n.parse::<u16>().ok());
+    let (low, high) = (bounds.next()?, bounds.next()?);
+    let mut used = 0;
+    for table in ["/proc/net/tcp", "/proc/net/tcp6"] {
+        let text = fs::read_to_string(table).unwrap_or_default();
+        // `  0: 0100007F:A2C4 0100007F:1F90 01 ...`: local address:port in hex, then remote, then state.
+        used += text
+            .lines()
+            .skip(1)
+            .filter_map(|line| {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                let port = u16::from_str_radix(fields.get(1)?.rsplit(':').next()?, 16).ok()?;
+                Some((port, *fields.get(3)?))
+            })
+            .filter(|(port, state)| *port >= low && *port <= high && *state != "0A")
+            .count() as u64;
+    }
+    Some(usage(
+        "Ephemeral ports",
+        &format!("ports in {}-{}", low, high),
+        used,
+        u64::from(high - low) + 1,
+        "widen net.ipv4.ip_local_port_range or reuse connections",
+    ))
+}
+
+/// Open files system-wide, and this process's descriptor limit, which sockets count against.
+fn file_descriptors() -> Vec<Check> {
+    let mut checks = Vec::new();
+    // `allocated  free  max`
+    if let Ok(text) = fs::read_to_string("/proc/sys/fs/file-nr") {
+        let fields: Vec<u64> = text.split_whitespace().filter_map(|n| n.parse().ok()).collect();
+        if let [allocated, free, max] = fields[..] {
+            checks.push(usage("System open files", "file handles", allocated - free, max, "raise fs.file-max"));
+        }
+    }
+    #[cfg(unix)]
+    {
+        let mut limit = libc::rlimit { rlim_cur: 0, rlim_max: 0 };
+        let open = fs::read_dir("/proc/self/fd").or_else(|_| fs::read_dir("/dev/fd")).map(|d| d.count() as u64);
+        if unsafe { libc::getrlimit(libc::RLIMIT_NOFILE, &mut limit) } == 0 {
+            if let Ok(open) = open {
+                let mut check = usage("Descriptor limit", "descriptors", open, limit.rlim_cur, "raise the limit with `ulimit -n`");
+                if check.verdict == Verdict::Pass && limit.rlim_cur < 1024 {
+                    check.verdict = Verdict::Warn;
+                    check.detail = format!("{}; a limit this low runs out under load, raise it with `ulimit -n`", check.detail);
+                }
+                checks.push(check);
+            }
+        }
+    }
+    checks
+}
+
+/// The machine's own name must resolve, quickly, to one of its own addresses.
+fn own_hostname() -> Check {
+    let hostname = match netinfo::command_stdout("hostname", &[]).map(|h| h.trim().to_string()).filter(|h| !h.is_empty()) {
+        Some(hostname) => hostname,
+        None => return Check::new("Own hostname", Verdict::Warn, "could not determine the hostname".to_string()),
+    };
+    // The system resolver on purpose: /etc/hosts and mDNS are what local programs consult.
+    let start = Instant::now();
+    let result = (hostname.as_str(), 0).to_socket_addrs();
+    let elapsed = start.elapsed();
+    let mut addrs: Vec<IpAddr> = match result {
+        Ok(addrs) => addrs.map(|a| a.ip()).collect(),
+        Err(e) => {
+            return Check::new(
+                "Own hostname",
+                Verdict::Fail,
+                format!("{} does not resolve ({}); add it to /etc/hosts, or sudo and other tools stall looking it up", hostname, e),
+            )
+        }
+    };
+    addrs.dedup();
+    let listed = addrs.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(", ");
+    if elapsed > SLOW_LOOKUP {
+        return Check::new(
+            "Own hostname",
+            Verdict::Warn,
+            format!("{} resolves to {} but took {:.1} s; add it to /etc/hosts", hostname, listed, elapsed.as_secs_f64()),
+        );
+    }
+    let local: Vec<String> = netinfo::interfaces().into_iter().flat_map(|i| i.addrs).collect();
+    if !addrs.iter().any(|a| a.is_loopback() || local.contains(&a.to_string())) {
+        return Check::new(
+            "Own hostname",
+            Verdict::Warn,
+            format!("{} resolves to {}, which is not assigned to this host", hostname, listed),
+        );
+    }
+    Check::new("Own hostname", Verdict::Pass, format!("{} resolves to {} in {:.0} ms", hostname, listed, elapsed.as_secs_f64() * 1000.0))
+}
@@ -0,0 +1,382 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::{self, Command};
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::config;
+use crate::{colorize, data_dir};
+
+/// Name of the unit, launchd label suffix and scheduled task.
+const SERVICE_NAME: &str = "netdiag-monitor";
+
+const LAUNCHD_LABEL: &str = "com.netdiag.monitor";
+
+/// Home of the system-wide systemd service, created by systemd through `StateDirectory=`.
+const SYSTEMD_STATE_DIR: &str = "/var/lib/netdiag";
+
+/// Output of the system-wide launchd daemon.
+const LAUNCHD_SYSTEM_LOG: &str = "/Library/Logs/netdiag-monitor.out";
+
+/// How long a monitor cycle may go without a watchdog ping before systemd restarts it. A cycle
+/// traces every target, and a trace that falls back through all protocols can take minutes.
+const WATCHDOG_SECS: u64 = 600;
+
+/// Service manager the unit is generated for.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Manager {
+    Systemd,
+    Launchd,
+    /// Windows has no way to supervise a plain executable as a service, so the monitor is
+    /// registered as a scheduled task that starts at boot. Unlike the other two it is not
+    /// restarted if it exits and has no watchdog.
+    TaskScheduler,
+}
+
+/// Returns the `install-service` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("install-service")
+        .about("Installs monitor mode as a supervised service (systemd unit, launchd plist or Windows scheduled task)")
+        .arg(Arg::with_name("interval").long("interval").takes_value(true)
+            .help("Seconds between monitor cycles (default from config)"))
+        .arg(Arg::with_name("targets").long("targets").takes_value(true).use_delimiter(true)
+            .help("Comma-separated hosts to trace (default from config)"))
+        .arg(Arg::with_name("user").long("user")
+            .help("Install for the current user only (systemd --user unit or LaunchAgent) instead of system-wide"))
+        .arg(Arg::with_name("print").long("print")
+            .help("Print the generated service definition instead of installing it"))
+        .arg(Arg::with_name("uninstall").long("uninstall")
+            .help("Stop and remove a previously installed service"))
+}
+
+/// Runs the `install-service` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let manager = if cfg!(windows) {
+        Manager::TaskScheduler
+    } else if cfg!(target_os = "macos") {
+        Manager::Launchd
+    } else {
+        Manager::Systemd
+    };
+    let user = matches.is_present("user");
+    if user && manager == Manager::TaskScheduler {
+        println!("⚠️  {} --user has no effect on Windows; the task runs as SYSTEM.", colorize("[WARN]", "yellow"));
+    }
+    let path = definition_path(manager, user);
+
+    println!();
+    if matches.is_present("uninstall") {
+        uninstall(manager, user, path.as_deref());
+        println!();
+        return;
+    }
+
+    let command = monitor_command(matches);
+    let definition = match manager {
+        Manager::Systemd => systemd_unit(&command, user),
+        Manager::Launchd => launchd_plist(&command, user),
+        Manager::TaskScheduler => String::new(),
+    };
+    if matches.is_present("print") {
+        match manager {
+            Manager::TaskScheduler => println!("{}", task_arguments(&command).iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ")),
+            _ => print!("{}", definition),
+        }
+        return;
+    }
+
+    let kind = if manager == Manager::TaskScheduler { "scheduled task" } else { "service" };
+    println!("🔹 {}", colorize(&format!("Installing the netdiag monitor {}", kind), "blue"));
+    if let Some(path) = &path {
+        if let Err(e) = path.parent().map_or(Ok(()), fs::create_dir_all).and_then(|_| fs::write(path, &definition)) {
+            println!("❌ {} Could not write {}: {}", colorize("[ERROR]", "red"), path.display(), e);
+            if !user && manager != Manager::TaskScheduler {
+                println!("   Rerun with sudo, or install for your user only with --user.");
+            }
+            println!();
+            return;
+        }
+        println!("   Wrote {}", colorize(&path.display().to_string(), "cyan"));
+    }
+
+    let steps: Vec<Vec<String>> = match manager {
+        Manager::Systemd => vec![
+            systemctl(user, &["daemon-reload"]),
+            systemctl(user, &["enable", "--now", &format!("{}.service", SERVICE_NAME)]),
+        ],
+        Manager::Launchd => vec![strings(&["launchctl", "load", "-w", &path_text(&path)])],
+        // A boot task would otherwise first run at the next boot.
+        Manager::TaskScheduler => vec![task_arguments(&command), strings(&["schtasks", "/Run", "/TN", SERVICE_NAME])],
+    };
+    if steps.iter().all(|step| execute(step)) {
+        println!("✅ {} Monitor mode is installed and running.", colorize("[SUCCESS]", "green"));
+        match manager {
+            Manager::Systemd => {
+                let scope = if user { " --user" } else { "" };
+                println!("   Status: systemctl{} status {}", scope, SERVICE_NAME);
+                println!("   Output: journalctl{} -u {} -f", scope, SERVICE_NAME);
+            }
+            Manager::Launchd => println!("   Output: {}", launchd_log(user).display()),
+            Manager::TaskScheduler => {
+                println!("   Status: schtasks /Query /TN {}", SERVICE_NAME);
+                println!("   The task starts at boot but, unlike a service, is not restarted if it stops.");
+            }
+        }
+        println!("   Path changes are logged to {}", service_data_dir(manager, user).join("monitor.log").display());
+    }
+    println!();
+}
+
+/// Stops the service and removes its definition.
+fn uninstall(manager: Manager, user: bool, path: Option<&Path>) {
+    println!("🔹 {}", colorize("Removing the netdiag monitor service", "blue"));
+    let stopped = match manager {
+        Manager::Systemd => execute(&systemctl(user, &["disable", "--now", &format!("{}.service", SERVICE_NAME)])),
+        Manager::Launchd => execute(&strings(&["launchctl", "unload", "-w", &path.map(|p| p.display().to_string()).unwrap_or_default()])),
+        Manager::TaskScheduler => execute(&strings(&["schtasks", "/Delete", "/TN", SERVICE_NAME, "/F"])),
+    };
+    if let Some(path) = path {
+        match fs::remove_file(path) {
+            Ok(()) => println!("   Removed {}", path.display()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => println!("   {} was not installed", path.display()),
+            Err(e) => {
+                println!("❌ {} Could not remove {}: {}", colorize("[ERROR]", "red"), path.display(), e);
+                return;
+            }
+        }
+    }
+    if manager == Manager::Systemd {
+        execute(&systemctl(user, &["daemon-reload"]));
+    }
+    if stopped {
+        println!("✅ {} Monitor service removed.", colorize("[SUCCESS]", "green"));
+    }
+}
+
+/// Where the definition is installed; scheduled tasks live in the Task Scheduler, not a file.
+fn definition_path(manager: Manager, user: bool) -> Option<PathBuf> {
+    let home = || env::var_os("HOME").map(PathBuf::from).unwrap_or_else(|| PathBuf::from("."));
+    match (manager, user) {
+        (Manager::Systemd, false) => Some(PathBuf::from(format!("/etc/systemd/system/{}.service", SERVICE_NAME))),
+        (Manager::Systemd, true) => {
+            let config = env::var_os("XDG_CONFIG_HOME").map(PathBuf::from).unwrap_or_else(|| home().join(".config"));
+            Some(config.join("systemd/user").join(format!("{}.service", SERVICE_NAME)))
+        }
+        (Manager::Launchd, false) => Some(PathBuf::from(format!("/Library/LaunchDaemons/{}.plist", LAUNCHD_LABEL))),
+        (Manager::Launchd, true) => Some(home().join("Library/LaunchAgents").join(format!("{}.plist", LAUNCHD_LABEL))),
+        (Manager::TaskScheduler, _) => None,
+    }
+}
+
+/// Where the installed monitor keeps its `.netdiag` directory. A user service shares the
+/// installing user's; system-wide ones run as root or SYSTEM with a home of their own.
+fn service_data_dir(manager: Manager, user: bool) -> PathBuf {
+    match (manager, user) {
+        (Manager::TaskScheduler, _) => PathBuf::from(r"C:\Windows\System32\config\systemprofile\.netdiag"),
+        (_, true) => data_dir(),
+        (Manager::Systemd, false) => Path::new(SYSTEMD_STATE_DIR).join(".netdiag"),
+        (Manager::Launchd, false) => PathBuf::from("/var/root/.netdiag"),
+    }
+}
+
+/// The command line the service runs: this executable in monitor mode, with the options given
+/// here and an absolute `--config` so it does not depend on the working directory. Without
+/// `--config`, the installing user's own config is passed, as a system service has another home.
+fn monitor_command(matches: &ArgMatches) -> Vec<String> {
+    let exe = env::current_exe().map(|p| p.display().to_string()).unwrap_or_else(|_| "netdiag".to_string());
+    let mut command = vec![exe];
+    let config = matches.value_of("config").map(PathBuf::from).or_else(|| Some(config::default_path()).filter(|p| p.exists()));
+    if let Some(config) = config {
+        let config = fs::canonicalize(&config).unwrap_or(config);
+        command.extend(strings(&["--config", &config.display().to_string()]));
+    }
+    if let Some(server) = matches.value_of("resolve-via") {
+        command.extend(strings(&["--resolve-via", server]));
+    }
+    command.push("monitor".to_string());
+    if let Some(interval) = matches.value_of("interval") {
+        command.extend(strings(&["--interval", interval]));
+    }
+    if let Some(targets) = matches.values_of("targets") {
+        command.extend(strings(&["--targets", &targets.collect::<Vec<_>>().join(",")]));
+    }
+    command
+}
+
+/// A `Type=notify` unit with a watchdog: systemd restarts the monitor if it crashes or stops
+/// reporting progress. The system unit runs as root, so it gets `/var/lib/netdiag` as its home
+/// instead of writing root-owned files into the installing user's; a user unit keeps the
+/// user's own `HOME`.
+fn systemd_unit(command: &[String], user: bool) -> String {
+    let exec = command.iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ");
+    let home = if user {
+        String::new()
+    } else {
+        format!("StateDirectory=netdiag\nStateDirectoryMode=0700\nEnvironment=HOME={}\n", SYSTEMD_STATE_DIR)
+    };
+    format!(
+        "[Unit]\n\
+         Description=netdiag network monitor\n\
+         After=network-online.target\n\
+         Wants=network-online.target\n\
+         \n\
+         [Service]\n\
+         Type=notify\n\
+         NotifyAccess=main\n\
+         ExecStart={}\n\
+         {}\
+         WatchdogSec={}\n\
+         Restart=on-failure\n\
+         RestartSec=10\n\
+         \n\
+         [Install]\n\
+         WantedBy={}\n",
+        exec,
+        home,
+        WATCHDOG_SECS,
+        if user { "default.target" } else { "multi-user.target" }
+    )
+}
+
+/// Where launchd sends the monitor's output: the user's `~/.netdiag` for an agent, the system
+/// log directory for a daemon, which runs as root.
+fn launchd_log(user: bool) -> PathBuf {
+    if user { data_dir().join("monitor.out") } else { PathBuf::from(LAUNCHD_SYSTEM_LOG) }
+}
+
+/// A launchd job that starts at load and is restarted whenever it exits.
+fn launchd_plist(command: &[String], user: bool) -> String {
+    let arguments: String = command.iter().map(|a| format!("        <string>{}</string>\n", xml_escape(a))).collect();
+    let out = xml_escape(&launchd_log(user).display().to_string());
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+         <!DOCTYPE plist PUBLIC \"-//Apple//DTD PLIST 1.0//EN\" \"http://www.apple.com/DTDs/PropertyList-1.0.dtd\">\n\
+         <plist version=\"1.0\">\n\
+         <dict>\n\
+         \x20   <key>Label</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>ProgramArguments</key>\n\
+         \x20   <array>\n\
+         {}\
+         \x20   </array>\n\
+         \x20   <key>RunAtLoad</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>KeepAlive</key>\n\
+         \x20   <true/>\n\
+         \x20   <key>ThrottleInterval</key>\n\
+         \x20   <integer>10</integer>\n\
+         \x20   <key>StandardOutPath</key>\n\
+         \x20   <string>{}</string>\n\
+         \x20   <key>StandardErrorPath</key>\n\
+         \x20   <string>{}</string>\n\
+         </dict>\n\
+         </plist>\n",
+        LAUNCHD_LABEL, arguments, out, out
+    )
+}
+
+/// `schtasks` arguments registering the monitor to start at boot as SYSTEM.
+fn task_arguments(command: &[String]) -> Vec<String> {
+    let run = command.iter().map(|a| quote(a)).collect::<Vec<_>>().join(" ");
+    strings(&["schtasks", "/Create", "/TN", SERVICE_NAME, "/SC", "ONSTART", "/RU", "SYSTEM", "/RL", "HIGHEST", "/TR", &run, "/F"])
+}
+
+fn systemctl(user: bool, args: &[&str]) -> Vec<String> {
+    let mut command = strings(&["systemctl"]);
+    if user {
+        command.push("--user".to_string());
+    }
+    command.extend(strings(args));
+    command
+}
+
+/// Runs one installation step, printing it and any failure.
+fn execute(command: &[String]) -> bool {
+    println!("   $ {}", command.join(" "));
+    match Command::new(&command[0]).args(&command[1..]).output() {
+        Ok(output) if output.status.success() => true,
+        Ok(output) => {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            println!("❌ {} {} failed: {}", colorize("[ERROR]", "red"), command[0], stderr.trim());
+            false
+        }
+        Err(e) => {
+            println!("❌ {} Could not run {}: {}", colorize("[ERROR]", "red"), command[0], e);
+            false
+        }
+    }
+}
+
+fn strings(args: &[&str]) -> Vec<String> {
+    args.iter().map(|a| a.to_string()).collect()
+}
+
+fn path_text(path: &Option<PathBuf>) -> String {
+    path.as_ref().map(|p| p.display().to_string()).unwrap_or_default()
+}
+
+/// Double-quotes an argument containing whitespace or quotes, as systemd and cmd.exe expect.
+fn quote(arg: &str) -> String {
+    if arg.is_empty() || arg.contains(|c: char| c.is_whitespace() || c == '"') {
+        format!("\"{}\"", arg.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        arg.to_string()
+    }
+}
+
+fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;")
+}
+
+/// Sends a state change (`READY=1`, `STATUS=...`, `WATCHDOG=1`) to systemd. Does nothing when
+/// the process was not started by systemd with `Type=notify`.
+pub fn notify(state: &str) {
+    #[cfg(unix)]
+    {
+        use std::os::unix::net::UnixDatagram;
+
+        let socket = match env::var("NOTIFY_SOCKET") {
+            Ok(socket) if !socket.is_empty() => socket,
+            _ => return,
+        };
+        let datagram = match UnixDatagram::unbound() {
+            Ok(datagram) => datagram,
+            Err(_) => return,
+        };
+        // A leading '@' names a Linux abstract-namespace socket.
+        #[cfg(target_os = "linux")]
+        if let Some(name) = socket.strip_prefix('@') {
+            use std::os::linux::net::SocketAddrExt;
+            if let Ok(addr) = std::os::unix::net::SocketAddr::from_abstract_name(name.as_bytes()) {
+                let _ = datagram.send_to_addr(state.as_bytes(), &addr);
+            }
+            return;
+        }
+        let _ = datagram.send_to(state.as_bytes(), &socket);
+    }
+    #[cfg(not(unix))]
+    let _ = state;
+}
+
+/// How often systemd expects a watchdog ping, if it enabled the watchdog for this process.
+pub fn watchdog_interval() -> Option<Duration> {
+    if let Ok(pid) = env::var("WATCHDOG_PID") {
+        if pid.trim().parse::<u32>().ok() != Some(process::id()) {
+            return None;
+        }
+    }
+    let usec: u64 = env::var("WATCHDOG_USEC").ok()?.trim().parse().ok()?;
+    if usec == 0 {
+        return None;
+    }
+    Some(Duration::from_micros(usec))
+}
+
+/// Tells the watchdog the process is still making progress.
+pub fn watchdog() {
+    if watchdog_interval().is_some() {
+        notify("WATCHDOG=1");
+    }
+}
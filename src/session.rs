@@ -0,0 +1,173 @@
+use std::fs::{self, OpenOptions};
+use std::io;
+use std::path::PathBuf;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde_json;
+
+use crate::{colorize, data_dir, random_u64};
+use crate::clock;
+use crate::profile;
+
+/// Progress of one run of the default diagnostics suite, saved after every step so an
+/// interrupted run can pick up where it stopped.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Session {
+    pub id: String,
+    /// The `--profile` the run used; sessions saved before profiles ran the default one.
+    #[serde(default)]
+    pub profile: Option<String>,
+    pub started_at: u64,
+    pub updated_at: u64,
+    /// Names of the steps that finished, in order.
+    pub completed: Vec<String>,
+    pub finished: bool,
+}
+
+impl Session {
+    /// Starts a new session running `profile`, named after the current UTC time and a random
+    /// suffix, e.g. `20240501T120000-3f2a`. The session file is claimed with `create_new`, so
+    /// runs started in the same second never share one.
+    pub fn start(profile: &str) -> Session {
+        let now = clock::unix_now();
+        let mut session =
+            Session { id: String::new(), profile: Some(profile.to_string()), started_at: now, updated_at: now, completed: Vec::new(), finished: false };
+        loop {
+            session.id = format!("{}-{:04x}", clock::file_stamp(now), random_u64() & 0xffff);
+            let path = session_path(&session.id);
+            let claimed = fs::create_dir_all(sessions_dir()).and_then(|_| OpenOptions::new().write(true).create_new(true).open(&path));
+            match claimed {
+                Ok(_) => break,
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+                Err(e) => {
+                    println!("⚠️  {} Could not save session to {}: {}", colorize("[WARN]", "yellow"), path.display(), e);
+                    return session;
+                }
+            }
+        }
+        session.save();
+        session
+    }
+
+    /// Whether `step` already ran to completion in this session.
+    pub fn is_done(&self, step: &str) -> bool {
+        self.completed.iter().any(|s| s == step)
+    }
+
+    /// Records `step` as completed and saves the session.
+    pub fn complete(&mut self, step: &str) {
+        self.completed.push(step.to_string());
+        self.save();
+    }
+
+    /// Marks the whole suite as done and saves the session.
+    pub fn finish(&mut self) {
+        self.finished = true;
+        self.save();
+    }
+
+    /// Writes the session file; failures only cost the ability to resume, so they are just reported.
+    pub fn save(&mut self) {
+        self.updated_at = clock::unix_now();
+        let path = session_path(&self.id);
+        let result = fs::create_dir_all(sessions_dir())
+            .and_then(|_| fs::write(&path, serde_json::to_string_pretty(self).unwrap_or_default()));
+        if let Err(e) = result {
+            println!("⚠️  {} Could not save session to {}: {}", colorize("[WARN]", "yellow"), path.display(), e);
+        }
+    }
+}
+
+fn sessions_dir() -> PathBuf {
+    data_dir().join("sessions")
+}
+
+fn session_path(id: &str) -> PathBuf {
+    sessions_dir().join(format!("{}.json", id))
+}
+
+/// Loads a saved session by id.
+pub fn load(id: &str) -> Option<Session> {
+    let text = fs::read_to_string(session_path(id)).ok()?;
+    serde_json::from_str(&text).ok()
+}
+
+/// All saved sessions, oldest first.
+fn list() -> Vec<Session> {
+    let mut sessions: Vec<Session> = fs::read_dir(sessions_dir())
+        .map(|entries| {
+            entries
+                .filter_map(|e| e.ok())
+                .filter_map(|e| fs::read_to_string(e.path()).ok())
+                .filter_map(|text| serde_json::from_str(&text).ok())
+                .collect()
+        })
+        .unwrap_or_default();
+    sessions.sort_by_key(|s| s.started_at);
+    sessions
+}
+
+/// Returns the `resume` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("resume")
+        .about("Resumes an interrupted diagnostics run, skipping the checks it already completed")
+        .arg(Arg::with_name("session").help("Session id to resume (defaults to the latest unfinished one)"))
+        .arg(Arg::with_name("list").long("list").help("List saved sessions instead of resuming"))
+}
+
+/// Picks the session to resume for the `resume` subcommand, printing why when there is none.
+pub fn open(matches: &ArgMatches) -> Option<Session> {
+    if matches.is_present("list") {
+        print_list();
+        return None;
+    }
+    let session = match matches.value_of("session") {
+        Some(id) => match load(id) {
+            Some(session) => session,
+            None => {
+                println!("❌ {} No saved session {} in {}", colorize("[ERROR]", "red"), id, sessions_dir().display());
+                return None;
+            }
+        },
+        None => match list().into_iter().rev().find(|s| !s.finished) {
+            Some(session) => session,
+            None => {
+                println!("ℹ️  {} No unfinished sessions to resume", colorize("[INFO]", "blue"));
+                return None;
+            }
+        },
+    };
+    if session.finished {
+        println!("ℹ️  {} Session {} already completed every check", colorize("[INFO]", "blue"), session.id);
+        return None;
+    }
+    println!(
+        "\n🔁 {} Resuming session {} started {} ({} check(s) already done)",
+        colorize("[RESUME]", "blue"),
+        colorize(&session.id, "cyan"),
+        clock::format_timestamp(session.started_at),
+        session.completed.len()
+    );
+    Some(session)
+}
+
+/// Prints saved sessions with their progress.
+fn print_list() {
+    let sessions = list();
+    if sessions.is_empty() {
+        println!("ℹ️  {} No saved sessions", colorize("[INFO]", "blue"));
+        return;
+    }
+    println!("🔹 {}", colorize("Saved sessions", "blue"));
+    for session in sessions {
+        let state = if session.finished { colorize("finished", "green") } else { colorize("interrupted", "yellow") };
+        println!(
+            "   {} {} {} {} check(s) done, last update {}",
+            session.id,
+            session.profile.as_deref().unwrap_or(profile::DEFAULT),
+            state,
+            session.completed.len(),
+            clock::format_timestamp(session.updated_at)
+        );
+    }
+}
@@ -0,0 +1,181 @@
+use std::collections::HashMap;
+use std::net::IpAddr;
+
+use crate::colorize;
+use crate::netinfo::command_stdout;
+use crate::portscan;
+
+/// A local TCP or UDP socket and the process that owns it.
+#[derive(Debug, Clone)]
+pub struct Socket {
+    /// `tcp` or `udp`.
+    pub protocol: String,
+    /// Bound address; `None` for a wildcard bind.
+    pub local_ip: Option<IpAddr>,
+    pub local_port: u16,
+    pub pid: u32,
+    pub process: String,
+    /// Accepting connections: a listening TCP socket, or a UDP socket with no peer.
+    pub listening: bool,
+}
+
+impl Socket {
+    /// Whether `addr:port` over `protocol` is this socket's local end.
+    pub fn owns(&self, protocol: &str, addr: Option<IpAddr>, port: Option<u16>) -> bool {
+        self.protocol.eq_ignore_ascii_case(protocol)
+            && port == Some(self.local_port)
+            && (self.local_ip.is_none() || self.local_ip == addr)
+    }
+
+    /// Whether `selector` (a PID, or part of a process name) picks this socket's process.
+    pub fn matches(&self, selector: &str) -> bool {
+        match selector.parse::<u32>() {
+            Ok(pid) => self.pid == pid,
+            Err(_) => self.process.to_lowercase().contains(&selector.to_lowercase()),
+        }
+    }
+}
+
+/// Lists sockets with their owning processes via `ss` on Linux, `lsof` on macOS/BSD, or
+/// `netstat -ano` on Windows. Sockets of other users only show up when run as root.
+pub fn table() -> Vec<Socket> {
+    if cfg!(target_os = "windows") {
+        return parse_netstat(&command_stdout("netstat", &["-ano"]).unwrap_or_default(), &process_names());
+    }
+    if let Some(out) = command_stdout("ss", &["-tunapH"]) {
+        return parse_ss(&out);
+    }
+    parse_lsof(&command_stdout("lsof", &["-nP", "+c", "0", "-i"]).unwrap_or_default())
+}
+
+/// Prints the ports this host accepts connections on and who owns them, flagging the ones
+/// reachable from other hosts.
+pub fn print_listening() {
+    println!("🔹 {}", colorize("Listening ports", "blue"));
+    let mut listening: Vec<Socket> = table().into_iter().filter(|s| s.listening).collect();
+    listening.sort_by(|a, b| (a.local_port, &a.protocol, a.local_ip).cmp(&(b.local_port, &b.protocol, b.local_ip)));
+    listening.dedup_by(|a, b| a.protocol == b.protocol && a.local_ip == b.local_ip && a.local_port == b.local_port);
+    if listening.is_empty() {
+        println!("   (none found; sockets of other users only show up when run as root)\n");
+        return;
+    }
+    println!("   {:<5} {:<40} {:<12} {:<8} Process", "Proto", "Address", "Service", "PID");
+    let mut exposed = 0;
+    for socket in &listening {
+        let local = socket.local_ip.is_some_and(|ip| ip.is_loopback());
+        if !local {
+            exposed += 1;
+        }
+        let address = match socket.local_ip {
+            Some(ip) => format!("{}:{}", ip, socket.local_port),
+            None => format!("*:{}", socket.local_port),
+        };
+        println!(
+            "   {:<5} {:<40} {:<12} {:<8} {}",
+            socket.protocol,
+            if local { address } else { colorize(&format!("{:<40}", address), "yellow") },
+            portscan::service_name(socket.local_port),
+            socket.pid,
+            socket.process
+        );
+    }
+    println!("   {} of {} reachable from other hosts (not bound to loopback)\n", exposed, listening.len());
+}
+
+/// Returns the cgroup path of `pid` on Linux, preferring the unified (v2) hierarchy.
+pub fn cgroup(pid: u32) -> Option<String> {
+    let text = std::fs::read_to_string(format!("/proc/{}/cgroup", pid)).ok()?;
+    let line = text.lines().find(|l| l.starts_with("0::")).or_else(|| text.lines().next())?;
+    line.splitn(3, ':').nth(2).map(|path| path.to_string())
+}
+
+/// Parses `ss -tunapH`: `tcp ESTAB 0 0 10.0.0.2:50000 93.184.216.34:443 users:(("curl",pid=42,fd=3))`.
+fn parse_ss(out: &str) -> Vec<Socket> {
+    let mut sockets = Vec::new();
+    for line in out.lines() {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        let (local_ip, local_port) = match fields.get(4).and_then(|e| parse_endpoint(e)) {
+            Some(endpoint) => endpoint,
+            None => continue,
+        };
+        let users = match fields.iter().find(|f| f.starts_with("users:")) {
+            Some(users) => users,
+            None => continue,
+        };
+        // One socket may be shared by several processes, e.g. after fork.
+        for owner in users.split("),(") {
+            let process = owner.split('"').nth(1).unwrap_or("").to_string();
+            let pid = owner
+                .split("pid=")
+                .nth(1)
+                .and_then(|rest| rest.split(|c: char| !c.is_ascii_digit()).next())
+                .and_then(|pid| pid.parse().ok());
+            if let Some(pid) = pid {
+                let listening = matches!(fields.get(1), Some(&"LISTEN") | Some(&"UNCONN"));
+                sockets.push(Socket { protocol: fields[0].to_string(), local_ip, local_port, pid, process, listening });
+            }
+        }
+    }
+    sockets
+}
+
+/// Parses `lsof -nP -i`: `curl 42 me 3u IPv4 0x1 0t0 TCP 10.0.0.2:50000->93.184.216.34:443 (ESTABLISHED)`.
+fn parse_lsof(out: &str) -> Vec<Socket> {
+    out.lines()
+        .skip(1)
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let node = fields.iter().position(|f| *f == "TCP" || *f == "UDP")?;
+            let name = fields.get(node + 1)?;
+            let (local_ip, local_port) = parse_endpoint(name.split("->").next()?)?;
+            Some(Socket {
+                protocol: fields[node].to_lowercase(),
+                local_ip,
+                local_port,
+                pid: fields.get(1)?.parse().ok()?,
+                process: fields[0].replace("\\x20", " "),
+                listening: fields.last() == Some(&"(LISTEN)") || (fields[node] == "UDP" && !name.contains("->")),
+            })
+        })
+        .collect()
+}
+
+/// Parses `netstat -ano`: `TCP 10.0.0.2:50000 93.184.216.34:443 ESTABLISHED 42`.
+fn parse_netstat(out: &str, names: &HashMap<u32, String>) -> Vec<Socket> {
+    out.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split_whitespace().collect();
+            let protocol = fields.first()?.to_lowercase();
+            if protocol != "tcp" && protocol != "udp" {
+                return None;
+            }
+            let (local_ip, local_port) = parse_endpoint(fields.get(1)?)?;
+            let pid: u32 = fields.last()?.parse().ok()?;
+            let process = names.get(&pid).cloned().unwrap_or_default();
+            let listening = fields.get(3) == Some(&"LISTENING") || (protocol == "udp" && fields.get(2) == Some(&"*:*"));
+            Some(Socket { protocol, local_ip, local_port, pid, process, listening })
+        })
+        .collect()
+}
+
+/// Maps PIDs to image names using `tasklist /FO CSV /NH`.
+fn process_names() -> HashMap<u32, String> {
+    command_stdout("tasklist", &["/FO", "CSV", "/NH"])
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split("\",\"").map(|f| f.trim_matches('"')).collect();
+            Some((fields.get(1)?.parse().ok()?, fields.first()?.to_string()))
+        })
+        .collect()
+}
+
+/// Splits `addr:port`, `[v6]:port` or `*:port`; wildcard and unspecified addresses become `None`.
+fn parse_endpoint(endpoint: &str) -> Option<(Option<IpAddr>, u16)> {
+    let (host, port) = endpoint.rsplit_once(':')?;
+    let port = port.parse().ok()?;
+    let host = host.trim_start_matches('[').trim_end_matches(']');
+    let host = host.split('%').next().unwrap_or(host);
+    let ip = host.parse::<IpAddr>().ok().map(|ip| ip.to_canonical()).filter(|ip| !ip.is_unspecified());
+    Some((ip, port))
+}
@@ -0,0 +1,398 @@
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::mpsc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::interrupt;
+use crate::random_u64;
+use crate::scheduler;
+use crate::tcping;
+
+/// How long to wait for a keepalive answer before calling the connection dead.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Idle periods, in seconds, after which one untouched connection each is probed.
+const IDLE_STEPS: [u64; 8] = [30, 60, 120, 300, 600, 900, 1800, 3600];
+
+/// What the far end speaks, which decides what a keepalive looks like.
+#[derive(Debug, Clone)]
+enum Mode {
+    /// Any TCP service: no data is sent, only closes are noticed.
+    Plain,
+    /// A line echo service such as `netdiag stability --listen`.
+    Echo,
+    /// A WebSocket server, kept alive with ping frames.
+    WebSocket { host: String, path: String },
+}
+
+/// Something that happened to one of the test connections.
+#[derive(Debug)]
+enum Event {
+    Dropped { elapsed: u64, reason: String },
+    IdleOk { idle: u64 },
+    IdleDead { idle: u64, reason: String },
+}
+
+/// Returns the `stability` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("stability")
+        .about("Holds TCP/WebSocket connections open to find idle timeouts and NAT mapping lifetime")
+        .arg(Arg::with_name("target").required_unless("listen")
+            .help("host:port, or ws://host:port/path with --websocket"))
+        .arg(Arg::with_name("minutes").long("minutes").short("m").takes_value(true).default_value("10")
+            .help("How long to hold the connections"))
+        .arg(Arg::with_name("keepalive").long("keepalive").takes_value(true).default_value("30")
+            .help("Seconds between keepalives on the kept-alive connection"))
+        .arg(Arg::with_name("echo").long("echo")
+            .help("The target echoes lines back (e.g. another netdiag running --listen)"))
+        .arg(Arg::with_name("websocket").long("websocket").conflicts_with("echo")
+            .help("The target is a ws:// URL; keepalives are WebSocket pings"))
+        .arg(Arg::with_name("listen").long("listen").takes_value(true)
+            .help("Run a line echo server on this port for another netdiag to test against"))
+}
+
+/// Runs the `stability` subcommand.
+pub fn run(matches: &ArgMatches) {
+    if let Ok(port) = value_t!(matches, "listen", u16) {
+        serve_echo(port);
+        return;
+    }
+    let target = matches.value_of("target").unwrap_or_default();
+    let minutes = value_t!(matches, "minutes", u64).unwrap_or(10);
+    let keepalive = value_t!(matches, "keepalive", u64).unwrap_or(30).max(1);
+
+    let (addr, mode) = match parse_target(target, matches.is_present("echo"), matches.is_present("websocket")) {
+        Ok(parsed) => parsed,
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+    };
+    stability_test(addr, &mode, minutes * 60, keepalive);
+}
+
+fn parse_target(target: &str, echo: bool, websocket: bool) -> Result<(SocketAddr, Mode), String> {
+    if !websocket {
+        let addr = tcping::resolve(target)?;
+        return Ok((addr, if echo { Mode::Echo } else { Mode::Plain }));
+    }
+    if target.starts_with("wss://") {
+        return Err("wss:// needs TLS, which this test does not implement; use ws:// or --echo".to_string());
+    }
+    let rest = target.strip_prefix("ws://").ok_or_else(|| format!("{} is not a ws:// URL", target))?;
+    let (authority, path) = match rest.find('/') {
+        Some(i) => (&rest[..i], &rest[i..]),
+        None => (rest, "/"),
+    };
+    let with_port = if authority.contains(':') { authority.to_string() } else { format!("{}:80", authority) };
+    let addr = tcping::resolve(&with_port)?;
+    Ok((addr, Mode::WebSocket { host: authority.to_string(), path: path.to_string() }))
+}
+
+/// Opens one kept-alive connection and a set of idle ones, then reports what survived.
+fn stability_test(addr: SocketAddr, mode: &Mode, duration: u64, keepalive: u64) {
+    let steps: Vec<u64> = IDLE_STEPS.iter().cloned().filter(|s| *s <= duration).collect();
+    println!(
+        "\n⏱️  {} Holding connections to {} for {} min: 1 with keepalives every {}s, {} idle\n",
+        colorize("[INFO]", "blue"),
+        colorize(&addr.to_string(), "cyan"),
+        duration / 60,
+        keepalive,
+        steps.len()
+    );
+    if let Mode::Plain = mode {
+        println!("   Plain TCP: no keepalive data can be sent, so only server-side closes are visible.");
+        println!("   Use --echo against `netdiag stability --listen <port>` to measure NAT mapping lifetime.\n");
+    }
+
+    let (tx, rx) = mpsc::channel();
+    let start = Instant::now();
+    {
+        let (mode, tx) = (mode.clone(), tx.clone());
+        thread::spawn(move || kept_alive(addr, &mode, duration, keepalive, start, tx));
+    }
+    for idle in steps.iter().cloned() {
+        let (mode, tx) = (mode.clone(), tx.clone());
+        thread::spawn(move || idle_connection(addr, &mode, idle, tx));
+    }
+    drop(tx);
+
+    let mut events = Vec::new();
+    loop {
+        let event = match rx.recv_timeout(Duration::from_millis(200)) {
+            Ok(event) => event,
+            Err(mpsc::RecvTimeoutError::Timeout) if !interrupt::interrupted() => continue,
+            Err(mpsc::RecvTimeoutError::Timeout) => {
+                println!("\n🛑 {} Stopped after {}s; summarizing what was observed", colorize("[INTERRUPTED]", "yellow"), start.elapsed().as_secs());
+                break;
+            }
+            Err(mpsc::RecvTimeoutError::Disconnected) => break,
+        };
+        let now = start.elapsed().as_secs();
+        match &event {
+            Event::Dropped { elapsed, reason } => {
+                println!("{:>5}s ❌ {} kept-alive connection dropped after {}s: {}", now, colorize("[DROP]", "red"), elapsed, reason)
+            }
+            Event::IdleOk { idle } => println!("{:>5}s ✅ idle {}s connection still works", now, idle),
+            Event::IdleDead { idle, reason } => {
+                println!("{:>5}s ⚠️  {} idle {}s connection is dead: {}", now, colorize("[IDLE]", "yellow"), idle, reason)
+            }
+        }
+        events.push(event);
+    }
+    print_summary(&events, mode, keepalive);
+}
+
+/// Keeps one connection busy with keepalives, reconnecting after each drop.
+fn kept_alive(addr: SocketAddr, mode: &Mode, duration: u64, keepalive: u64, start: Instant, tx: mpsc::Sender<Event>) {
+    while start.elapsed().as_secs() < duration {
+        let opened = Instant::now();
+        let mut stream = match open(addr, mode) {
+            Ok(stream) => stream,
+            Err(e) => {
+                let _ = tx.send(Event::Dropped { elapsed: 0, reason: format!("could not connect: {}", e) });
+                thread::sleep(Duration::from_secs(keepalive));
+                continue;
+            }
+        };
+        loop {
+            let remaining = duration.saturating_sub(start.elapsed().as_secs());
+            if remaining == 0 {
+                return;
+            }
+            // Waiting in a read both paces the keepalives and notices a close from the far end.
+            if let Err(reason) = wait_for_close(&mut stream, Duration::from_secs(keepalive.min(remaining))) {
+                let _ = tx.send(Event::Dropped { elapsed: opened.elapsed().as_secs(), reason });
+                break;
+            }
+            if let Err(reason) = probe(&mut stream, mode) {
+                let _ = tx.send(Event::Dropped { elapsed: opened.elapsed().as_secs(), reason });
+                break;
+            }
+        }
+    }
+}
+
+/// Leaves a connection untouched for `idle` seconds, then checks whether it still works.
+fn idle_connection(addr: SocketAddr, mode: &Mode, idle: u64, tx: mpsc::Sender<Event>) {
+    let mut stream = match open(addr, mode) {
+        Ok(stream) => stream,
+        Err(e) => {
+            let _ = tx.send(Event::IdleDead { idle, reason: format!("could not connect: {}", e) });
+            return;
+        }
+    };
+    let opened = Instant::now();
+    let result = wait_for_close(&mut stream, Duration::from_secs(idle))
+        .map_err(|reason| format!("{} after {}s", reason, opened.elapsed().as_secs()))
+        .and_then(|_| probe(&mut stream, mode));
+    let _ = tx.send(match result {
+        Ok(()) => Event::IdleOk { idle },
+        Err(reason) => Event::IdleDead { idle, reason },
+    });
+}
+
+fn open(addr: SocketAddr, mode: &Mode) -> io::Result<TcpStream> {
+    let mut stream = scheduler::connect(addr, PROBE_TIMEOUT)?;
+    if let Mode::WebSocket { host, path } = mode {
+        websocket_handshake(&mut stream, host, path)?;
+    }
+    Ok(stream)
+}
+
+/// Blocks for `period` unless the far end closes or resets the connection first.
+fn wait_for_close(stream: &mut TcpStream, period: Duration) -> Result<(), String> {
+    let deadline = Instant::now() + period;
+    let mut buf = [0u8; 512];
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return Ok(());
+        }
+        stream.set_read_timeout(Some(remaining)).map_err(|e| e.to_string())?;
+        match stream.read(&mut buf) {
+            Ok(0) => return Err("closed by the far end (FIN)".to_string()),
+            // Unsolicited data (banners, server pings) is fine; keep waiting.
+            Ok(_) => continue,
+            Err(e) if e.kind() == ErrorKind::WouldBlock || e.kind() == ErrorKind::TimedOut => return Ok(()),
+            Err(e) if e.kind() == ErrorKind::ConnectionReset => return Err("reset (RST)".to_string()),
+            Err(e) => return Err(e.to_string()),
+        }
+    }
+}
+
+/// Sends one keepalive and waits for the answer the mode expects.
+fn probe(stream: &mut TcpStream, mode: &Mode) -> Result<(), String> {
+    stream.set_read_timeout(Some(PROBE_TIMEOUT)).map_err(|e| e.to_string())?;
+    let unanswered = |e: io::Error| match e.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => "keepalive unanswered (silently dropped, typical of an expired NAT mapping)".to_string(),
+        ErrorKind::ConnectionReset => "reset (RST) when the keepalive was sent".to_string(),
+        _ => e.to_string(),
+    };
+    match mode {
+        Mode::Plain => Ok(()),
+        Mode::Echo => {
+            stream.write_all(b"netdiag-keepalive\n").map_err(unanswered)?;
+            let mut line = String::new();
+            match BufReader::new(&*stream).read_line(&mut line) {
+                Ok(0) => Err("closed by the far end (FIN)".to_string()),
+                Ok(_) => Ok(()),
+                Err(e) => Err(unanswered(e)),
+            }
+        }
+        Mode::WebSocket { .. } => {
+            stream.write_all(&websocket_frame(0x9, b"netdiag")).map_err(unanswered)?;
+            // Skip any data frames until the pong arrives.
+            loop {
+                let mut header = [0u8; 2];
+                stream.read_exact(&mut header).map_err(unanswered)?;
+                let mut len = (header[1] & 0x7f) as u64;
+                if len == 126 {
+                    let mut ext = [0u8; 2];
+                    stream.read_exact(&mut ext).map_err(unanswered)?;
+                    len = u16::from_be_bytes(ext) as u64;
+                } else if len == 127 {
+                    let mut ext = [0u8; 8];
+                    stream.read_exact(&mut ext).map_err(unanswered)?;
+                    len = u64::from_be_bytes(ext);
+                }
+                io::copy(&mut (&*stream).take(len), &mut io::sink()).map_err(unanswered)?;
+                match header[0] & 0x0f {
+                    0xA => return Ok(()),
+                    0x8 => return Err("the server sent a WebSocket close frame".to_string()),
+                    _ => continue,
+                }
+            }
+        }
+    }
+}
+
+/// Performs the RFC 6455 opening handshake.
+fn websocket_handshake(stream: &mut TcpStream, host: &str, path: &str) -> io::Result<()> {
+    let key: Vec<u8> = random_u64().to_be_bytes().iter().chain(random_u64().to_be_bytes().iter()).cloned().collect();
+    let request = format!(
+        "GET {} HTTP/1.1\r\nHost: {}\r\nUpgrade: websocket\r\nConnection: Upgrade\r\nSec-WebSocket-Key: {}\r\nSec-WebSocket-Version: 13\r\n\r\n",
+        path,
+        host,
+        base64(&key)
+    );
+    stream.write_all(request.as_bytes())?;
+    stream.set_read_timeout(Some(PROBE_TIMEOUT))?;
+
+    // Read the response headers byte by byte so no frame data is consumed.
+    let mut response = Vec::new();
+    let mut byte = [0u8; 1];
+    while !response.ends_with(b"\r\n\r\n") && response.len() < 8192 {
+        stream.read_exact(&mut byte)?;
+        response.push(byte[0]);
+    }
+    let status = String::from_utf8_lossy(&response).lines().next().unwrap_or("").to_string();
+    if status.split_whitespace().nth(1) != Some("101") {
+        return Err(io::Error::other(format!("WebSocket upgrade refused: {}", status)));
+    }
+    Ok(())
+}
+
+/// Builds a masked client frame with the given opcode.
+fn websocket_frame(opcode: u8, payload: &[u8]) -> Vec<u8> {
+    let mask = (random_u64() as u32).to_be_bytes();
+    let mut frame = vec![0x80 | opcode, 0x80 | payload.len() as u8];
+    frame.extend_from_slice(&mask);
+    frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+    frame
+}
+
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut out = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = (chunk[0] as u32) << 16 | (*chunk.get(1).unwrap_or(&0) as u32) << 8 | *chunk.get(2).unwrap_or(&0) as u32;
+        for i in 0..4 {
+            if i <= chunk.len() {
+                out.push(ALPHABET[(n >> (18 - 6 * i) & 0x3f) as usize] as char);
+            } else {
+                out.push('=');
+            }
+        }
+    }
+    out
+}
+
+/// Serves line echo on `port` so a remote `stability --echo` can measure the path between them.
+fn serve_echo(port: u16) {
+    let listener = match TcpListener::bind(("0.0.0.0", port)) {
+        Ok(listener) => listener,
+        Err(e) => {
+            println!("❌ {} Could not listen on port {}: {}", colorize("[ERROR]", "red"), port, e);
+            return;
+        }
+    };
+    println!("\n👂 {} Echo server listening on port {} (Ctrl-C to stop)\n", colorize("[INFO]", "blue"), port);
+    for stream in listener.incoming().flatten() {
+        if let Ok(peer) = stream.peer_addr() {
+            println!("   connection from {}", peer);
+        }
+        thread::spawn(move || {
+            let mut writer = match stream.try_clone() {
+                Ok(writer) => writer,
+                Err(_) => return,
+            };
+            for line in BufReader::new(stream).lines().map_while(Result::ok) {
+                if writeln!(writer, "{}", line).is_err() {
+                    break;
+                }
+            }
+        });
+    }
+}
+
+/// Explains the pattern of drops and idle failures.
+fn print_summary(events: &[Event], mode: &Mode, keepalive: u64) {
+    let drops = events.iter().filter(|e| matches!(e, Event::Dropped { .. })).count();
+    let mut alive: Vec<u64> = Vec::new();
+    let mut dead: Vec<u64> = Vec::new();
+    for event in events {
+        match event {
+            Event::IdleOk { idle } => alive.push(*idle),
+            Event::IdleDead { idle, .. } => dead.push(*idle),
+            _ => {}
+        }
+    }
+    alive.sort();
+    dead.sort();
+
+    println!("\n📊 {} Kept-alive connection: {} drop(s)", colorize("[SUMMARY]", "blue"), drops);
+    match (dead.first(), alive.iter().rfind(|a| Some(*a) < dead.first())) {
+        (None, _) if !alive.is_empty() => println!(
+            "✅ {} Idle connections survived up to {}s; no idle timeout seen",
+            colorize("[SUCCESS]", "green"),
+            alive.last().cloned().unwrap_or(0)
+        ),
+        (Some(first_dead), longest_alive) => {
+            let lower = longest_alive.cloned().unwrap_or(0);
+            let what = if let Mode::Plain = mode { "Idle timeout" } else { "Idle timeout / NAT mapping lifetime" };
+            println!(
+                "⚠️  {} {} is between {}s and {}s",
+                colorize("[IDLE]", "yellow"),
+                what,
+                lower,
+                first_dead
+            );
+            if drops == 0 {
+                println!(
+                    "   Keepalives every {}s kept the other connection up: set SSH ServerAliveInterval (or TCP keepalive) below {}s.",
+                    keepalive, if lower > 0 { lower } else { first_dead / 2 }
+                );
+            }
+        }
+        _ => {}
+    }
+    if drops > 0 && dead.is_empty() {
+        println!("   Drops happened despite keepalives, so the cause is the path or the server, not an idle timeout.");
+    }
+    println!();
+}
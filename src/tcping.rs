@@ -0,0 +1,119 @@
+use std::io::ErrorKind;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::chart;
+use crate::colorize;
+use crate::interrupt;
+use crate::repeat;
+use crate::resolver;
+use crate::scheduler;
+
+/// Result of one connection attempt.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Attempt {
+    /// Handshake completed after this many milliseconds.
+    Connected(f64),
+    /// The host answered with a RST: reachable, but nothing listens on the port.
+    Refused(f64),
+    TimedOut,
+    Failed,
+}
+
+/// Returns the `tcping` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("tcping")
+        .about("Measures TCP handshake time to host:port, for networks that block ICMP")
+        .arg(Arg::with_name("target").required(true).help("host:port, e.g. example.com:443 or [2001:db8::1]:22"))
+        .arg(Arg::with_name("count").long("count").short("c").takes_value(true).default_value("10")
+            .help("Number of connection attempts"))
+        .arg(Arg::with_name("interval").long("interval").takes_value(true).default_value("1000")
+            .help("Milliseconds between attempts"))
+        .arg(Arg::with_name("timeout").long("timeout").takes_value(true).default_value("2000")
+            .help("Connect timeout per attempt in milliseconds"))
+}
+
+/// Runs the `tcping` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let target = matches.value_of("target").unwrap_or_default();
+    let count = value_t!(matches, "count", u32).unwrap_or(10);
+    let interval = Duration::from_millis(value_t!(matches, "interval", u64).unwrap_or(1000));
+    let timeout = Duration::from_millis(value_t!(matches, "timeout", u64).unwrap_or(2000));
+
+    let addr = match resolve(target) {
+        Ok(addr) => addr,
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+    };
+
+    println!("\n🔌 {} TCP connect to {} ({}), {} attempts\n", colorize("[INFO]", "blue"), colorize(target, "cyan"), addr, count);
+    let mut attempts = Vec::new();
+    for seq in 1..=count {
+        let attempt = connect(addr, timeout);
+        match attempt {
+            Attempt::Connected(ms) => println!("   seq={:<3} {} time={:.1} ms", seq, colorize("connected", "green"), ms),
+            Attempt::Refused(ms) => println!("   seq={:<3} {} time={:.1} ms", seq, colorize("refused", "yellow"), ms),
+            Attempt::TimedOut => println!("   seq={:<3} {}", seq, colorize("timed out", "red")),
+            Attempt::Failed => println!("   seq={:<3} {}", seq, colorize("failed", "red")),
+        }
+        attempts.push(attempt);
+        if seq < count && !interrupt::sleep(interval) {
+            break;
+        }
+    }
+    print_summary(&attempts);
+}
+
+/// Resolves `host:port`, accepting bracketed IPv6 literals.
+pub fn resolve(target: &str) -> Result<SocketAddr, String> {
+    let (host, port) = target.rsplit_once(':').ok_or_else(|| format!("{} is not host:port", target))?;
+    let port: u16 = port.parse().map_err(|_| format!("Invalid port in {}", target))?;
+    resolver::socket_addr(host, port).map_err(|e| e.to_string())
+}
+
+/// Times a single TCP handshake.
+pub fn connect(addr: SocketAddr, timeout: Duration) -> Attempt {
+    let start = Instant::now();
+    match scheduler::connect(addr, timeout) {
+        Ok(_) => Attempt::Connected(start.elapsed().as_secs_f64() * 1000.0),
+        Err(e) if e.kind() == ErrorKind::ConnectionRefused => Attempt::Refused(start.elapsed().as_secs_f64() * 1000.0),
+        Err(e) if e.kind() == ErrorKind::TimedOut || e.kind() == ErrorKind::WouldBlock => Attempt::TimedOut,
+        Err(_) => Attempt::Failed,
+    }
+}
+
+fn print_summary(attempts: &[Attempt]) {
+    let samples: Vec<f64> = attempts
+        .iter()
+        .filter_map(|a| match a {
+            Attempt::Connected(ms) => Some(*ms),
+            _ => None,
+        })
+        .collect();
+    let refused = attempts.iter().filter(|a| matches!(a, Attempt::Refused(_))).count();
+    let lost = attempts.len() - samples.len() - refused;
+
+    println!(
+        "\n📊 {} {} connected, {} refused, {} failed ({:.0}% loss)",
+        colorize("[SUMMARY]", "blue"),
+        samples.len(),
+        refused,
+        lost,
+        lost as f64 * 100.0 / attempts.len().max(1) as f64
+    );
+    chart::print_distribution("Handshake", &samples);
+    if !samples.is_empty() {
+        repeat::record("TCP handshake avg (ms)", chart::summarize(&samples).1);
+    }
+    repeat::record("TCP connect failures (%)", lost as f64 * 100.0 / attempts.len().max(1) as f64);
+    if refused > 0 && samples.is_empty() {
+        println!("⚠️  {} The host answers but nothing is listening on that port (or a firewall sends RSTs).", colorize("[WARN]", "yellow"));
+    } else if lost > 0 && samples.is_empty() && refused == 0 {
+        println!("❌ {} No handshakes completed: the port is filtered or the host is down.", colorize("[ERROR]", "red"));
+    }
+    println!();
+}
@@ -0,0 +1,397 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::Write;
+use std::process::Stdio;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde_json;
+
+use crate::config::TelemetryConfig;
+use crate::http;
+use crate::interrupt;
+use crate::netinfo;
+use crate::{colorize, random_u64};
+
+/// The run being traced; `None` when no OTLP endpoint is configured, which makes every call
+/// below a no-op.
+static TRACE: Mutex<Option<Trace>> = Mutex::new(None);
+
+/// A finished or still-open span.
+struct Span {
+    id: u64,
+    parent: Option<u64>,
+    name: String,
+    start_ns: u64,
+    end_ns: u64,
+    attributes: Vec<(String, Value)>,
+    /// Why the span failed; `None` once ended means it succeeded.
+    error: Option<String>,
+}
+
+/// Spans of this run and where to send them.
+struct Trace {
+    endpoint: String,
+    headers: Vec<(String, String)>,
+    service: String,
+    trace_id: String,
+    /// Span id from a `TRACEPARENT` the run was started under, e.g. by a CI job.
+    remote_parent: Option<String>,
+    spans: Vec<Span>,
+    /// Indexes into `spans` of the spans still open, innermost last.
+    open: Vec<usize>,
+}
+
+/// An attribute value, serialized as an OTLP `AnyValue`.
+#[derive(Debug, Clone, Serialize)]
+enum Value {
+    #[serde(rename = "stringValue")]
+    Text(String),
+    #[serde(rename = "doubleValue")]
+    Number(f64),
+}
+
+/// OTLP/JSON request body, trimmed to the fields netdiag fills in.
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ExportRequest {
+    resource_spans: Vec<ResourceSpans>,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ResourceSpans {
+    resource: Resource,
+    scope_spans: Vec<ScopeSpans>,
+}
+
+#[derive(Serialize)]
+struct Resource {
+    attributes: Vec<KeyValue>,
+}
+
+#[derive(Serialize)]
+struct ScopeSpans {
+    scope: Scope,
+    spans: Vec<OtlpSpan>,
+}
+
+#[derive(Serialize)]
+struct Scope {
+    name: String,
+    version: String,
+}
+
+#[derive(Serialize)]
+struct KeyValue {
+    key: String,
+    value: Value,
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct OtlpSpan {
+    trace_id: String,
+    span_id: String,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    parent_span_id: String,
+    name: String,
+    /// `SPAN_KIND_INTERNAL`.
+    kind: u8,
+    /// Nanoseconds since the epoch, as strings like every 64-bit integer in OTLP/JSON.
+    start_time_unix_nano: String,
+    end_time_unix_nano: String,
+    attributes: Vec<KeyValue>,
+    status: Status,
+}
+
+/// `code` 1 is OK and 2 is ERROR.
+#[derive(Serialize)]
+struct Status {
+    code: u8,
+    #[serde(skip_serializing_if = "String::is_empty")]
+    message: String,
+}
+
+fn now_ns() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |d| d.as_nanos() as u64)
+}
+
+/// Turns tracing on when an endpoint is given by `--otlp-endpoint`, the standard
+/// `OTEL_EXPORTER_OTLP_*` variables or the config, in that order. The trace joins the one in
+/// `TRACEPARENT` when the run was started from traced tooling.
+pub fn configure(settings: TelemetryConfig, endpoint: Option<&str>) {
+    let endpoint = endpoint
+        .map(traces_url)
+        .or_else(|| env::var("OTEL_EXPORTER_OTLP_TRACES_ENDPOINT").ok().filter(|e| !e.is_empty()))
+        .or_else(|| env::var("OTEL_EXPORTER_OTLP_ENDPOINT").ok().filter(|e| !e.is_empty()).map(|e| traces_url(&e)))
+        .or_else(|| settings.endpoint.as_deref().map(traces_url));
+    let endpoint = match endpoint {
+        Some(endpoint) => endpoint,
+        None => return,
+    };
+
+    let mut headers: Vec<(String, String)> = settings.headers.into_iter().collect();
+    // `key1=value1,key2=value2`, as the OpenTelemetry SDKs read it.
+    for pair in env::var("OTEL_EXPORTER_OTLP_HEADERS").unwrap_or_default().split(',') {
+        if let Some((key, value)) = pair.split_once('=') {
+            headers.push((key.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    let service = env::var("OTEL_SERVICE_NAME").ok().filter(|s| !s.is_empty()).unwrap_or(settings.service_name);
+    let (trace_id, remote_parent) = match env::var("TRACEPARENT").ok().as_deref().and_then(parse_traceparent) {
+        Some((trace_id, parent)) => (trace_id, Some(parent)),
+        None => (format!("{:016x}{:016x}", random_u64(), random_u64()), None),
+    };
+    // Status goes to stderr so it cannot corrupt machine-readable output on stdout.
+    eprintln!("ℹ️  {} Trace {} will be exported to {}", colorize("[OTEL]", "blue"), colorize(&trace_id, "cyan"), endpoint);
+    if let Ok(mut trace) = TRACE.lock() {
+        *trace = Some(Trace { endpoint, headers, service, trace_id, remote_parent, spans: Vec::new(), open: Vec::new() });
+    }
+}
+
+/// The OTLP/HTTP traces URL for a base endpoint such as `http://collector:4318`.
+fn traces_url(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+    if endpoint.ends_with("/v1/traces") {
+        endpoint.to_string()
+    } else {
+        format!("{}/v1/traces", endpoint)
+    }
+}
+
+/// Reads a W3C `traceparent`, e.g. `00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01`.
+fn parse_traceparent(value: &str) -> Option<(String, String)> {
+    let parts: Vec<&str> = value.trim().split('-').collect();
+    let hex = |s: &str, len: usize| s.len() == len && s.chars().all(|c| c.is_ascii_hexdigit()) && s.chars().any(|c| c != '0');
+    match parts[..] {
+        [_, trace_id, parent, _] if hex(trace_id, 32) && hex(parent, 16) => Some((trace_id.to_lowercase(), parent.to_lowercase())),
+        _ => None,
+    }
+}
+
+/// Runs `f` on the trace, when tracing is on.
+fn with_trace(f: impl FnOnce(&mut Trace)) {
+    if let Ok(mut trace) = TRACE.lock() {
+        if let Some(trace) = trace.as_mut() {
+            f(trace);
+        }
+    }
+}
+
+/// Opens a span named `name` inside the innermost open span.
+pub fn begin(name: &str) {
+    with_trace(|trace| {
+        let parent = trace.open.last().map(|&i| trace.spans[i].id);
+        trace.spans.push(Span {
+            id: random_u64(),
+            parent,
+            name: name.to_string(),
+            start_ns: now_ns(),
+            end_ns: 0,
+            attributes: Vec::new(),
+            error: None,
+        });
+        trace.open.push(trace.spans.len() - 1);
+    });
+}
+
+/// Closes the innermost open span.
+pub fn end() {
+    with_trace(|trace| {
+        if let Some(i) = trace.open.pop() {
+            trace.spans[i].end_ns = now_ns();
+        }
+    });
+}
+
+/// Sets a text attribute on the innermost open span.
+pub fn attribute(key: &str, value: &str) {
+    set(key, Value::Text(value.to_string()));
+}
+
+/// Records a check's headline number, e.g. `ping loss (%)`, on the innermost open span as
+/// `netdiag.ping_loss_pct`. The host or URL measured goes in `netdiag.target`, never in the key,
+/// so keys stay a small fixed set; a span that measures several targets gets one child span
+/// per extra target.
+pub fn metric(name: &str, target: Option<&str>, value: f64) {
+    let slug: String = name
+        .replace('%', "pct")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_lowercase() } else { '_' })
+        .collect();
+    let slug: Vec<&str> = slug.split('_').filter(|s| !s.is_empty()).collect();
+    let key = format!("netdiag.{}", slug.join("_"));
+    with_trace(|trace| {
+        let open = match trace.open.last() {
+            Some(&open) => open,
+            None => return,
+        };
+        let span = match target {
+            Some(target) => trace.span_for_target(open, target),
+            None => open,
+        };
+        set_on(&mut trace.spans[span], &key, Value::Number(value));
+    });
+}
+
+impl Trace {
+    /// The span holding `target`'s measurements under the open span `open`: `open` itself when
+    /// it has no target yet or this one, otherwise a child span for the target.
+    fn span_for_target(&mut self, open: usize, target: &str) -> usize {
+        let same = |span: &Span| span.attributes.iter().find(|(k, _)| k == "netdiag.target").map(|(_, v)| matches!(v, Value::Text(t) if t == target));
+        match same(&self.spans[open]) {
+            None => {
+                set_on(&mut self.spans[open], "netdiag.target", Value::Text(target.to_string()));
+                return open;
+            }
+            Some(true) => return open,
+            Some(false) => {}
+        }
+        let parent = self.spans[open].id;
+        if let Some(i) = self.spans.iter().position(|s| s.parent == Some(parent) && same(s) == Some(true)) {
+            return i;
+        }
+        let now = now_ns();
+        self.spans.push(Span {
+            id: random_u64(),
+            parent: Some(parent),
+            name: format!("{} {}", self.spans[open].name, target),
+            start_ns: now,
+            end_ns: now,
+            attributes: vec![("netdiag.target".to_string(), Value::Text(target.to_string()))],
+            error: None,
+        });
+        self.spans.len() - 1
+    }
+}
+
+fn set(key: &str, value: Value) {
+    with_trace(|trace| {
+        if let Some(&i) = trace.open.last() {
+            set_on(&mut trace.spans[i], key, value);
+        }
+    });
+}
+
+fn set_on(span: &mut Span, key: &str, value: Value) {
+    match span.attributes.iter_mut().find(|(k, _)| k == key) {
+        Some(existing) => existing.1 = value,
+        None => span.attributes.push((key.to_string(), value)),
+    }
+}
+
+/// Marks the innermost open span, and every span enclosing it, as failed.
+pub fn fail(reason: &str) {
+    with_trace(|trace| {
+        for &i in &trace.open {
+            trace.spans[i].error.get_or_insert_with(|| reason.to_string());
+        }
+    });
+}
+
+/// Closes any spans left open and sends the trace. Failures are reported, never fatal.
+pub fn export() {
+    let trace = match TRACE.lock().ok().and_then(|mut t| t.take()) {
+        Some(trace) => trace,
+        None => return,
+    };
+    // An interrupted run still sends what it recorded.
+    let _cleanup = interrupt::cleanup();
+    let finished = now_ns();
+    let spans: Vec<OtlpSpan> = trace
+        .spans
+        .iter()
+        .map(|span| OtlpSpan {
+            trace_id: trace.trace_id.clone(),
+            span_id: format!("{:016x}", span.id),
+            parent_span_id: span.parent.map(|p| format!("{:016x}", p)).or_else(|| trace.remote_parent.clone()).unwrap_or_default(),
+            name: span.name.clone(),
+            kind: 1,
+            start_time_unix_nano: span.start_ns.to_string(),
+            end_time_unix_nano: if span.end_ns == 0 { finished } else { span.end_ns }.to_string(),
+            attributes: span.attributes.iter().map(|(key, value)| KeyValue { key: key.clone(), value: value.clone() }).collect(),
+            status: match &span.error {
+                Some(reason) => Status { code: 2, message: reason.clone() },
+                None => Status { code: 1, message: String::new() },
+            },
+        })
+        .collect();
+    if spans.is_empty() {
+        return;
+    }
+    let count = spans.len();
+
+    let mut resource = vec![
+        KeyValue { key: "service.name".to_string(), value: Value::Text(trace.service.clone()) },
+        KeyValue { key: "service.version".to_string(), value: Value::Text(crate_version!().to_string()) },
+        KeyValue { key: "os.type".to_string(), value: Value::Text(env::consts::OS.to_string()) },
+    ];
+    if let Some(host) = netinfo::command_stdout("hostname", &[]).map(|h| h.trim().to_string()).filter(|h| !h.is_empty()) {
+        resource.push(KeyValue { key: "host.name".to_string(), value: Value::Text(host) });
+    }
+    let request = ExportRequest {
+        resource_spans: vec![ResourceSpans {
+            resource: Resource { attributes: resource },
+            scope_spans: vec![ScopeSpans { scope: Scope { name: "netdiag".to_string(), version: crate_version!().to_string() }, spans }],
+        }],
+    };
+    let body = match serde_json::to_vec(&request) {
+        Ok(body) => body,
+        Err(e) => {
+            eprintln!("⚠️  {} Could not encode the trace: {}", colorize("[OTEL]", "yellow"), e);
+            return;
+        }
+    };
+
+    match post(&trace.endpoint, &trace.headers, &body) {
+        Ok(()) => eprintln!("ℹ️  {} Exported trace {} ({} spans)", colorize("[OTEL]", "blue"), trace.trace_id, count),
+        Err(e) => eprintln!("⚠️  {} Could not export trace {} to {}: {}", colorize("[OTEL]", "yellow"), trace.trace_id, trace.endpoint, e),
+    }
+}
+
+/// POSTs an OTLP/JSON body with curl; a collector answers 2xx when it accepted the spans. The
+/// headers (often API keys) and body go to curl as a config on stdin, out of sight of `ps`.
+fn post(url: &str, headers: &[(String, String)], body: &[u8]) -> Result<(), String> {
+    let unique: HashMap<&str, &str> = headers.iter().map(|(k, v)| (k.as_str(), v.as_str())).collect();
+    let mut config = String::from("header = \"Content-Type: application/json\"\n");
+    for (key, value) in unique {
+        config.push_str(&format!("header = \"{}\"\n", quote(&format!("{}: {}", key, value))));
+    }
+    config.push_str(&format!("data-binary = \"{}\"\n", quote(&String::from_utf8_lossy(body))));
+
+    let mut command = http::curl(url);
+    // Bounds how long an interrupted run waits for the export.
+    command.args(["-sS", "--fail", "--max-time", "4", "-X", "POST", "-K", "-"]);
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| format!("could not run curl: {}", e))?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(config.as_bytes());
+    }
+    let output = child.wait_with_output().map_err(|e| e.to_string())?;
+    if output.status.success() {
+        Ok(())
+    } else {
+        Err(String::from_utf8_lossy(&output.stderr).trim().to_string())
+    }
+}
+
+/// Escapes `value` for a double-quoted curl config parameter.
+fn quote(value: &str) -> String {
+    let mut quoted = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => quoted.push_str("\\\\"),
+            '"' => quoted.push_str("\\\""),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c => quoted.push(c),
+        }
+    }
+    quoted
+}
@@ -0,0 +1,669 @@
+use serde_json::{Map, Value};
+
+/// A parsed template. The syntax is a small subset of Tera and Handlebars, not either engine,
+/// and both styles may be mixed. Exactly this is supported:
+///
+/// - `{{ path.to.value | filter }}` prints a value, `{{ map["key.with.dots"] }}` indexes by key,
+///   `{{ list.0 }}` by position, and `{{{ value }}}` never HTML-escapes it. Operands are paths
+///   or string, number and boolean literals.
+/// - `{% for item in list %}…{% else %}…{% endfor %}` and `{% for key, value in map %}`, with
+///   `loop.index`, `loop.index0`, `loop.first` and `loop.last` inside the body.
+/// - `{% if cond %}…{% elif cond %}…{% else %}…{% endif %}`, where a condition may use `not`,
+///   `and`, `or`, `==`, `!=`, `<`, `>`, `<=` and `>=`.
+/// - `{{#each list}}…{{else}}…{{/each}}` with `this`, `@index`, `@key`, `@first` and `@last`,
+///   `{{#if value}}…{{else}}…{{/if}}` and `{{#unless value}}…{{/unless}}`.
+/// - `{# comment #}` and `{{! comment }}`; a `-` inside a tag (`{%-`, `-%}`) trims whitespace.
+///
+/// Filters: `upper`, `lower`, `trim`, `length`, `first`, `last`, `join(sep=", ")`,
+/// `default(value="n/a")`, `round(precision=1)`, `json_encode` (or `json`) and `safe`; filter
+/// arguments must be literals.
+///
+/// Anything else is rejected by `parse` rather than rendered as empty text: other tags (`set`,
+/// `include`, `extends`, `block`, `macro`, `raw`, `#with`, partials), other filters or filter
+/// arguments, tests such as `is defined`, arithmetic, function calls and `../` paths.
+#[derive(Debug)]
+pub struct Template {
+    nodes: Vec<Node>,
+    /// HTML-escape printed values, as both engines do for HTML templates.
+    escape: bool,
+}
+
+#[derive(Debug)]
+enum Node {
+    Text(String),
+    Print { expr: Expr, raw: bool },
+    For { key: Option<String>, var: String, iter: Expr, body: Vec<Node>, empty: Vec<Node> },
+    If { branches: Vec<(Cond, Vec<Node>)>, otherwise: Vec<Node> },
+}
+
+#[derive(Debug, Clone)]
+enum Operand {
+    Path(Vec<String>),
+    Literal(Value),
+}
+
+#[derive(Debug, Clone)]
+struct Expr {
+    operand: Operand,
+    /// Filter names with their `name=value` arguments.
+    filters: Vec<(String, Vec<(String, Value)>)>,
+}
+
+#[derive(Debug)]
+enum Cond {
+    Truthy(Expr),
+    Not(Box<Cond>),
+    And(Box<Cond>, Box<Cond>),
+    Or(Box<Cond>, Box<Cond>),
+    Compare(Expr, String, Expr),
+}
+
+/// Filters `evaluate` knows, with the arguments each accepts.
+const FILTERS: &[(&str, &[&str])] = &[
+    ("upper", &[]),
+    ("lower", &[]),
+    ("trim", &[]),
+    ("length", &[]),
+    ("first", &[]),
+    ("last", &[]),
+    ("join", &["sep"]),
+    ("default", &["value"]),
+    ("round", &["precision"]),
+    ("json_encode", &[]),
+    ("json", &[]),
+    ("safe", &[]),
+];
+
+/// Tags that end a run of nodes; any other tag that does not open a block is unsupported.
+const CLOSERS: &[&str] = &["endfor", "endif", "elif", "else", "/each", "/if", "/unless"];
+
+/// One lexed piece of the template: literal text or the inside of a tag.
+#[derive(Debug)]
+enum Token {
+    Text(String),
+    /// `{{ … }}` or `{{{ … }}}` (raw).
+    Print { content: String, raw: bool, line: usize },
+    /// `{% … %}`, or a Handlebars block helper such as `#each list` / `/each` / `else`.
+    Block { content: String, line: usize },
+}
+
+impl Template {
+    /// Parses `source`; `escape` turns on HTML escaping of printed values.
+    pub fn parse(source: &str, escape: bool) -> Result<Template, String> {
+        let tokens = lex(source)?;
+        let mut position = 0;
+        let (nodes, end) = parse_nodes(&tokens, &mut position)?;
+        if let Some((tag, line)) = end {
+            return Err(format!("line {}: unexpected {{% {} %}}", line, tag));
+        }
+        Ok(Template { nodes, escape })
+    }
+
+    /// Renders the template against `context`, usually a serialized results model.
+    pub fn render(&self, context: &Value) -> Result<String, String> {
+        let mut out = String::new();
+        let mut scopes = vec![Scope { vars: Map::new(), this: Some(context.clone()) }];
+        self.render_nodes(&self.nodes, &mut scopes, &mut out)?;
+        Ok(out)
+    }
+
+    fn render_nodes(&self, nodes: &[Node], scopes: &mut Vec<Scope>, out: &mut String) -> Result<(), String> {
+        for node in nodes {
+            match node {
+                Node::Text(text) => out.push_str(text),
+                Node::Print { expr, raw } => {
+                    let value = evaluate(expr, scopes)?;
+                    let safe = *raw || expr.filters.iter().any(|(name, _)| name == "safe");
+                    let text = display(&value);
+                    out.push_str(&if self.escape && !safe { escape_html(&text) } else { text });
+                }
+                Node::For { key, var, iter, body, empty } => {
+                    let items: Vec<(Value, Value)> = match evaluate(iter, scopes)? {
+                        Value::Array(items) => items.into_iter().enumerate().map(|(i, v)| (Value::from(i), v)).collect(),
+                        Value::Object(map) => map.into_iter().map(|(k, v)| (Value::String(k), v)).collect(),
+                        _ => Vec::new(),
+                    };
+                    if items.is_empty() {
+                        self.render_nodes(empty, scopes, out)?;
+                        continue;
+                    }
+                    let count = items.len();
+                    for (index, (item_key, item)) in items.into_iter().enumerate() {
+                        let mut vars = Map::new();
+                        let mut lp = Map::new();
+                        lp.insert("index".to_string(), Value::from(index + 1));
+                        lp.insert("index0".to_string(), Value::from(index));
+                        lp.insert("first".to_string(), Value::from(index == 0));
+                        lp.insert("last".to_string(), Value::from(index + 1 == count));
+                        vars.insert("loop".to_string(), Value::Object(lp));
+                        vars.insert("@index".to_string(), Value::from(index));
+                        vars.insert("@first".to_string(), Value::from(index == 0));
+                        vars.insert("@last".to_string(), Value::from(index + 1 == count));
+                        vars.insert("@key".to_string(), item_key.clone());
+                        if let Some(key) = key {
+                            vars.insert(key.clone(), item_key);
+                        }
+                        vars.insert(var.clone(), item.clone());
+                        // Handlebars resolves bare names against the current item.
+                        let this = if var == "this" { Some(item) } else { None };
+                        scopes.push(Scope { vars, this });
+                        let result = self.render_nodes(body, scopes, out);
+                        scopes.pop();
+                        result?;
+                    }
+                }
+                Node::If { branches, otherwise } => {
+                    let mut taken = false;
+                    for (cond, body) in branches {
+                        if test(cond, scopes)? {
+                            self.render_nodes(body, scopes, out)?;
+                            taken = true;
+                            break;
+                        }
+                    }
+                    if !taken {
+                        self.render_nodes(otherwise, scopes, out)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Variables visible at one nesting level of the template.
+struct Scope {
+    vars: Map<String, Value>,
+    /// The object bare names are looked up in: the context, or the item of an `#each`.
+    this: Option<Value>,
+}
+
+/// Splits the source into text and tags, applying `-` whitespace trimming.
+fn lex(source: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+    let mut rest = source;
+    let mut line = 1;
+    let mut trim_next = false;
+    while !rest.is_empty() {
+        let start = match ["{{", "{%", "{#"].iter().filter_map(|open| rest.find(open)).min() {
+            Some(start) => start,
+            None => {
+                push_text(&mut tokens, rest, trim_next, false);
+                break;
+            }
+        };
+        let (open, close) = match &rest[start..start + 2] {
+            "{%" => ("{%", "%}"),
+            "{#" => ("{#", "#}"),
+            _ if rest[start..].starts_with("{{{") => ("{{{", "}}}"),
+            _ => ("{{", "}}"),
+        };
+        let inner_start = start + open.len();
+        let end = rest[inner_start..].find(close).map(|e| inner_start + e).ok_or_else(|| format!("line {}: unclosed {}", line, open))?;
+        let mut content = &rest[inner_start..end];
+        let trim_before = content.starts_with('-');
+        let trim_after = content.ends_with('-') && open != "{#";
+        content = content.trim_start_matches('-').trim_end_matches('-').trim();
+        push_text(&mut tokens, &rest[..start], trim_next, trim_before);
+        line += rest[..start].matches('\n').count();
+        let tag_line = line;
+        line += rest[start..end].matches('\n').count();
+        match open {
+            "{#" => {}
+            "{%" => tokens.push(Token::Block { content: content.to_string(), line: tag_line }),
+            "{{{" => tokens.push(Token::Print { content: content.to_string(), raw: true, line: tag_line }),
+            _ if content.starts_with('!') => {}
+            _ if content.starts_with('#') || content.starts_with('/') || content == "else" => {
+                tokens.push(Token::Block { content: content.to_string(), line: tag_line })
+            }
+            _ => tokens.push(Token::Print { content: content.to_string(), raw: false, line: tag_line }),
+        }
+        trim_next = trim_after;
+        rest = &rest[end + close.len()..];
+    }
+    Ok(tokens)
+}
+
+fn push_text(tokens: &mut Vec<Token>, text: &str, trim_start: bool, trim_end: bool) {
+    let text = if trim_start { text.trim_start() } else { text };
+    let text = if trim_end { text.trim_end() } else { text };
+    if !text.is_empty() {
+        tokens.push(Token::Text(text.to_string()));
+    }
+}
+
+/// A block tag that ended a run of nodes, such as `endfor` or `else`, with its line.
+type EndTag = Option<(String, usize)>;
+
+/// Parses nodes until a closing or intermediate block tag, which is returned with its line.
+fn parse_nodes(tokens: &[Token], position: &mut usize) -> Result<(Vec<Node>, EndTag), String> {
+    let mut nodes = Vec::new();
+    while let Some(token) = tokens.get(*position) {
+        *position += 1;
+        match token {
+            Token::Text(text) => nodes.push(Node::Text(text.clone())),
+            Token::Print { content, raw, line } => {
+                nodes.push(Node::Print { expr: parse_expr(content).map_err(|e| format!("line {}: {}", line, e))?, raw: *raw })
+            }
+            Token::Block { content, line } => {
+                // Errors from a nested block already name their own line.
+                let at = |e: String| if e.starts_with("line ") { e } else { format!("line {}: {}", line, e) };
+                let (keyword, args) = content.split_once(char::is_whitespace).unwrap_or((content.as_str(), ""));
+                match keyword {
+                    "for" => nodes.push(parse_for(args, tokens, position).map_err(at)?),
+                    "#each" => nodes.push(parse_each(args, tokens, position).map_err(at)?),
+                    "if" | "#if" | "#unless" => nodes.push(parse_if(keyword, args, tokens, position).map_err(at)?),
+                    _ if CLOSERS.contains(&keyword) => return Ok((nodes, Some((content.clone(), *line)))),
+                    _ => return Err(at(format!("unsupported tag `{}`", keyword))),
+                }
+            }
+        }
+    }
+    Ok((nodes, None))
+}
+
+/// Expects the block to end with one of `closers`; returns which one and its arguments.
+fn expect_end(end: EndTag, closers: &[&str], opened: &str) -> Result<(String, String), String> {
+    match end {
+        Some((tag, line)) => {
+            let (keyword, args) = tag.split_once(char::is_whitespace).unwrap_or((tag.as_str(), ""));
+            if closers.contains(&keyword) {
+                Ok((keyword.to_string(), args.trim().to_string()))
+            } else {
+                Err(format!("line {}: expected {} to close {}, found {}", line, closers.join(" or "), opened, tag))
+            }
+        }
+        None => Err(format!("{} is never closed", opened)),
+    }
+}
+
+fn parse_for(args: &str, tokens: &[Token], position: &mut usize) -> Result<Node, String> {
+    let (vars, iter) = args.split_once(" in ").ok_or("expected `for x in list`")?;
+    let (key, var) = match vars.split_once(',') {
+        Some((k, v)) => (Some(k.trim().to_string()), v.trim().to_string()),
+        None => (None, vars.trim().to_string()),
+    };
+    let iter = parse_expr(iter)?;
+    let (body, end) = parse_nodes(tokens, position)?;
+    let (closer, _) = expect_end(end, &["endfor", "else"], "for")?;
+    let empty = if closer == "else" {
+        let (empty, end) = parse_nodes(tokens, position)?;
+        expect_end(end, &["endfor"], "for")?;
+        empty
+    } else {
+        Vec::new()
+    };
+    Ok(Node::For { key, var, iter, body, empty })
+}
+
+fn parse_each(args: &str, tokens: &[Token], position: &mut usize) -> Result<Node, String> {
+    let iter = parse_expr(args)?;
+    let (body, end) = parse_nodes(tokens, position)?;
+    let (closer, _) = expect_end(end, &["/each", "else"], "#each")?;
+    let empty = if closer == "else" {
+        let (empty, end) = parse_nodes(tokens, position)?;
+        expect_end(end, &["/each"], "#each")?;
+        empty
+    } else {
+        Vec::new()
+    };
+    Ok(Node::For { key: None, var: "this".to_string(), iter, body, empty })
+}
+
+fn parse_if(keyword: &str, args: &str, tokens: &[Token], position: &mut usize) -> Result<Node, String> {
+    let handlebars = keyword.starts_with('#');
+    let closer = if handlebars { format!("/{}", &keyword[1..]) } else { "endif".to_string() };
+    let first = parse_cond(args)?;
+    let mut branches = vec![(if keyword == "#unless" { Cond::Not(Box::new(first)) } else { first }, Vec::new())];
+    let mut otherwise = Vec::new();
+    loop {
+        let (body, end) = parse_nodes(tokens, position)?;
+        let (tag, args) = expect_end(end, &[closer.as_str(), "elif", "else"], keyword)?;
+        match tag.as_str() {
+            "elif" => {
+                branches.last_mut().ok_or("empty if")?.1 = body;
+                branches.push((parse_cond(&args)?, Vec::new()));
+            }
+            "else" => {
+                branches.last_mut().ok_or("empty if")?.1 = body;
+                let (body, end) = parse_nodes(tokens, position)?;
+                expect_end(end, &[closer.as_str()], keyword)?;
+                otherwise = body;
+                break;
+            }
+            _ => {
+                branches.last_mut().ok_or("empty if")?.1 = body;
+                break;
+            }
+        }
+    }
+    Ok(Node::If { branches, otherwise })
+}
+
+/// Parses `a or b`, `a and b`, `not a` and comparisons, loosest first.
+fn parse_cond(text: &str) -> Result<Cond, String> {
+    let text = text.trim();
+    if let Some((left, right)) = split_word(text, " or ") {
+        return Ok(Cond::Or(Box::new(parse_cond(left)?), Box::new(parse_cond(right)?)));
+    }
+    if let Some((left, right)) = split_word(text, " and ") {
+        return Ok(Cond::And(Box::new(parse_cond(left)?), Box::new(parse_cond(right)?)));
+    }
+    if let Some(rest) = text.strip_prefix("not ") {
+        return Ok(Cond::Not(Box::new(parse_cond(rest)?)));
+    }
+    for op in ["==", "!=", "<=", ">=", "<", ">"] {
+        if let Some((left, right)) = split_word(text, op) {
+            return Ok(Cond::Compare(parse_expr(left)?, op.to_string(), parse_expr(right)?));
+        }
+    }
+    Ok(Cond::Truthy(parse_expr(text)?))
+}
+
+/// Splits at the first `separator` outside a string literal.
+fn split_word<'s>(text: &'s str, separator: &str) -> Option<(&'s str, &'s str)> {
+    let mut quote = None;
+    for (i, c) in text.char_indices() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => {}
+            None if c == '"' || c == '\'' => quote = Some(c),
+            None if text[i..].starts_with(separator) => return Some((&text[..i], &text[i + separator.len()..])),
+            None => {}
+        }
+    }
+    None
+}
+
+/// Parses `operand | filter | filter(name=value)`.
+fn parse_expr(text: &str) -> Result<Expr, String> {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some((head, tail)) = split_word(rest, "|") {
+        parts.push(head);
+        rest = tail;
+    }
+    parts.push(rest);
+    let operand = parse_operand(parts[0].trim())?;
+    let mut filters = Vec::new();
+    for filter in &parts[1..] {
+        let filter = filter.trim();
+        let (name, args) = match filter.split_once('(') {
+            Some((name, args)) => (name.trim(), args.trim_end_matches(')')),
+            None => (filter, ""),
+        };
+        let accepted = FILTERS.iter().find(|(known, _)| *known == name).map(|(_, accepted)| *accepted).ok_or_else(|| format!("unknown filter `{}`", name))?;
+        let mut parsed = Vec::new();
+        let mut rest = args;
+        while !rest.trim().is_empty() {
+            let (arg, tail) = split_word(rest, ",").unwrap_or((rest, ""));
+            let (key, value) = arg.split_once('=').ok_or_else(|| format!("filter argument `{}` must be name=value", arg.trim()))?;
+            if !accepted.contains(&key.trim()) {
+                return Err(format!("filter `{}` takes no argument `{}`", name, key.trim()));
+            }
+            match parse_operand(value.trim())? {
+                Operand::Literal(value) => parsed.push((key.trim().to_string(), value)),
+                Operand::Path(_) => return Err(format!("filter argument `{}` must be a literal", arg.trim())),
+            }
+            rest = tail;
+        }
+        filters.push((name.to_string(), parsed));
+    }
+    Ok(Expr { operand, filters })
+}
+
+/// A quoted string, number, boolean or dotted path.
+fn parse_operand(text: &str) -> Result<Operand, String> {
+    if text.is_empty() {
+        return Err("empty expression".to_string());
+    }
+    if text.len() >= 2 && ((text.starts_with('"') && text.ends_with('"')) || (text.starts_with('\'') && text.ends_with('\''))) {
+        return Ok(Operand::Literal(Value::String(text[1..text.len() - 1].to_string())));
+    }
+    match text {
+        "true" => return Ok(Operand::Literal(Value::Bool(true))),
+        "false" => return Ok(Operand::Literal(Value::Bool(false))),
+        _ => {}
+    }
+    if let Ok(number) = text.parse::<f64>() {
+        return Ok(Operand::Literal(serde_json::Number::from_f64(number).map(Value::Number).unwrap_or(Value::Null)));
+    }
+    if text.contains(char::is_whitespace) && !text.contains('[') {
+        return Err(format!("cannot parse `{}`", text));
+    }
+    parse_path(text.trim_start_matches("this.")).map(Operand::Path)
+}
+
+/// Splits `a.b.0` and `a["key.with.dots"]` into segments.
+fn parse_path(text: &str) -> Result<Vec<String>, String> {
+    let mut segments = Vec::new();
+    let mut rest = text;
+    while !rest.is_empty() {
+        if let Some(inner) = rest.strip_prefix('[') {
+            let end = inner.find(']').ok_or_else(|| format!("unclosed [ in `{}`", text))?;
+            segments.push(inner[..end].trim().trim_matches(|c| c == '"' || c == '\'').to_string());
+            rest = inner[end + 1..].trim_start_matches('.');
+            continue;
+        }
+        let end = rest.find(['.', '[']).unwrap_or(rest.len());
+        let segment = &rest[..end];
+        // Names, `@index`-style loop variables and positions; not `../parent`, `f()` or `a+b`.
+        if !segment.chars().all(|c| c.is_alphanumeric() || c == '_' || c == '@') {
+            return Err(format!("unsupported expression `{}`", text));
+        }
+        segments.push(segment.to_string());
+        rest = rest[end..].strip_prefix('.').unwrap_or(&rest[end..]);
+    }
+    if segments.is_empty() || segments[0].is_empty() {
+        return Err(format!("cannot parse `{}`", text));
+    }
+    Ok(segments)
+}
+
+/// Resolves a path: loop variables first, then fields of the current `#each` item, then the context.
+fn lookup(path: &[String], scopes: &[Scope]) -> Value {
+    let first = path[0].as_str();
+    let mut value = None;
+    for scope in scopes.iter().rev() {
+        if let Some(found) = scope.vars.get(first) {
+            value = Some(found.clone());
+            break;
+        }
+        if first == "this" {
+            if let Some(this) = &scope.this {
+                value = Some(this.clone());
+                break;
+            }
+        }
+        if let Some(found) = scope.this.as_ref().and_then(|t| t.get(first)) {
+            value = Some(found.clone());
+            break;
+        }
+    }
+    let mut value = value.unwrap_or(Value::Null);
+    for segment in &path[1..] {
+        value = match segment.parse::<usize>() {
+            Ok(index) => value.get(index).cloned(),
+            Err(_) => value.get(segment.as_str()).cloned(),
+        }
+        .unwrap_or(Value::Null);
+    }
+    value
+}
+
+fn evaluate(expr: &Expr, scopes: &[Scope]) -> Result<Value, String> {
+    let mut value = match &expr.operand {
+        Operand::Literal(value) => value.clone(),
+        Operand::Path(path) => lookup(path, scopes),
+    };
+    for (name, args) in &expr.filters {
+        let arg = |key: &str| args.iter().find(|(k, _)| k == key).map(|(_, v)| v.clone());
+        value = match name.as_str() {
+            "upper" => Value::String(display(&value).to_uppercase()),
+            "lower" => Value::String(display(&value).to_lowercase()),
+            "trim" => Value::String(display(&value).trim().to_string()),
+            "length" => Value::from(match &value {
+                Value::Array(a) => a.len(),
+                Value::Object(o) => o.len(),
+                Value::String(s) => s.chars().count(),
+                _ => 0,
+            }),
+            "first" => value.as_array().and_then(|a| a.first().cloned()).unwrap_or(Value::Null),
+            "last" => value.as_array().and_then(|a| a.last().cloned()).unwrap_or(Value::Null),
+            "join" => {
+                let separator = arg("sep").map(|s| display(&s)).unwrap_or_default();
+                Value::String(value.as_array().map(|a| a.iter().map(display).collect::<Vec<_>>().join(&separator)).unwrap_or_default())
+            }
+            "default" if value.is_null() => arg("value").unwrap_or(Value::Null),
+            "default" => value,
+            "round" => {
+                let precision = arg("precision").and_then(|p| p.as_f64()).unwrap_or(0.0) as i32;
+                let factor = 10f64.powi(precision);
+                match value.as_f64() {
+                    Some(n) => serde_json::Number::from_f64((n * factor).round() / factor).map(Value::Number).unwrap_or(Value::Null),
+                    None => value,
+                }
+            }
+            "json_encode" | "json" => Value::String(serde_json::to_string_pretty(&value).unwrap_or_default()),
+            "safe" => value,
+            other => return Err(format!("unknown filter `{}`", other)),
+        };
+    }
+    Ok(value)
+}
+
+fn test(cond: &Cond, scopes: &[Scope]) -> Result<bool, String> {
+    Ok(match cond {
+        Cond::Truthy(expr) => truthy(&evaluate(expr, scopes)?),
+        Cond::Not(inner) => !test(inner, scopes)?,
+        Cond::And(a, b) => test(a, scopes)? && test(b, scopes)?,
+        Cond::Or(a, b) => test(a, scopes)? || test(b, scopes)?,
+        Cond::Compare(left, op, right) => {
+            let (left, right) = (evaluate(left, scopes)?, evaluate(right, scopes)?);
+            match (left.as_f64(), right.as_f64()) {
+                (Some(l), Some(r)) => match op.as_str() {
+                    "==" => l == r,
+                    "!=" => l != r,
+                    "<" => l < r,
+                    ">" => l > r,
+                    "<=" => l <= r,
+                    _ => l >= r,
+                },
+                _ => match op.as_str() {
+                    "==" => display(&left) == display(&right),
+                    "!=" => display(&left) != display(&right),
+                    "<" => display(&left) < display(&right),
+                    ">" => display(&left) > display(&right),
+                    "<=" => display(&left) <= display(&right),
+                    _ => display(&left) >= display(&right),
+                },
+            }
+        }
+    })
+}
+
+/// Falsy: null, false, 0, "", [] and {}.
+fn truthy(value: &Value) -> bool {
+    match value {
+        Value::Null => false,
+        Value::Bool(b) => *b,
+        Value::Number(n) => n.as_f64() != Some(0.0),
+        Value::String(s) => !s.is_empty(),
+        Value::Array(a) => !a.is_empty(),
+        Value::Object(o) => !o.is_empty(),
+    }
+}
+
+/// Text for a printed value; whole numbers print without a fraction.
+fn display(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(s) => s.clone(),
+        Value::Number(n) => match n.as_f64() {
+            Some(f) if n.is_f64() && f.fract() == 0.0 && f.abs() < 1e15 => format!("{}", f as i64),
+            _ => n.to_string(),
+        },
+        other => serde_json::to_string(other).unwrap_or_default(),
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;").replace('\'', "&#x27;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(source: &str, context: &str) -> String {
+        let context: Value = serde_json::from_str(context).unwrap();
+        Template::parse(source, false).unwrap().render(&context).unwrap()
+    }
+
+    fn rejection(source: &str) -> String {
+        Template::parse(source, false).unwrap_err()
+    }
+
+    #[test]
+    fn looks_up_variables() {
+        let context = r#"{"host": {"name": "gw", "ports": [22, 443]}, "map": {"a.b": "dotted"}}"#;
+        assert_eq!(render("{{ host.name }}", context), "gw");
+        assert_eq!(render("{{ host.ports.1 }}", context), "443");
+        assert_eq!(render(r#"{{ map["a.b"] }}"#, context), "dotted");
+        assert_eq!(render("[{{ missing.field }}]", context), "[]");
+        assert_eq!(render("{{ 'literal' }} {{ 2 }} {{ true }}", context), "literal 2 true");
+    }
+
+    #[test]
+    fn nests_loops_and_conditions() {
+        let context = r#"{"checks": [{"name": "dns", "ok": true, "tags": ["a", "b"]}, {"name": "ping", "ok": false, "tags": []}]}"#;
+        let source = "{% for c in checks %}{{ loop.index }}:{{ c.name }}{% if c.ok %}+{% else %}-{% endif %}{% for t in c.tags %}{{ t }}{% else %}none{% endfor %};{% endfor %}";
+        assert_eq!(render(source, context), "1:dns+ab;2:ping-none;");
+        let source = "{{#each checks}}{{@index}}{{#if ok}}{{name}}{{else}}{{#unless ok}}!{{name}}{{/unless}}{{/if}} {{/each}}";
+        assert_eq!(render(source, context), "0dns 1!ping ");
+        assert_eq!(render("{% for k, v in m %}{{ k }}={{ v }},{% endfor %}", r#"{"m": {"x": 1}}"#), "x=1,");
+        assert_eq!(render("{% if n > 2 and not off %}big{% elif n == 1 %}one{% endif %}", r#"{"n": 1, "off": false}"#), "one");
+        assert_eq!(render("{%- if true -%}  x  {%- endif %}", "{}"), "x");
+    }
+
+    #[test]
+    fn applies_each_filter() {
+        let context = r#"{"s": " Mixed ", "list": ["a", "b", "c"], "n": 3.14159, "none": null, "obj": {"k": 1}}"#;
+        assert_eq!(render("{{ s | upper }}", context), " MIXED ");
+        assert_eq!(render("{{ s | lower }}", context), " mixed ");
+        assert_eq!(render("[{{ s | trim }}]", context), "[Mixed]");
+        assert_eq!(render("{{ list | length }} {{ obj | length }} {{ s | length }}", context), "3 1 7");
+        assert_eq!(render("{{ list | first }}{{ list | last }}", context), "ac");
+        assert_eq!(render(r#"{{ list | join(sep="-") }}"#, context), "a-b-c");
+        assert_eq!(render(r#"{{ none | default(value="n/a") }} {{ s | trim | default(value="x") }}"#, context), "n/a Mixed");
+        assert_eq!(render("{{ n | round(precision=2) }} {{ n | round }}", context), "3.14 3");
+        assert_eq!(render("{{ obj | json_encode }}", context), "{\n  \"k\": 1\n}");
+        assert_eq!(render("{{ list | json }}", context), serde_json::to_string_pretty(&["a", "b", "c"]).unwrap());
+        assert_eq!(render("{{ s | safe }}", context), " Mixed ");
+    }
+
+    #[test]
+    fn escapes_html_unless_raw_or_safe() {
+        let context: Value = serde_json::from_str(r#"{"v": "<a href='x'>&\"</a>"}"#).unwrap();
+        let escaped = Template::parse("{{ v }}", true).unwrap().render(&context).unwrap();
+        assert_eq!(escaped, "&lt;a href=&#x27;x&#x27;&gt;&amp;&quot;&lt;/a&gt;");
+        assert_eq!(Template::parse("{{{ v }}}", true).unwrap().render(&context).unwrap(), "<a href='x'>&\"</a>");
+        assert_eq!(Template::parse("{{ v | safe }}", true).unwrap().render(&context).unwrap(), "<a href='x'>&\"</a>");
+        assert_eq!(Template::parse("{{ v }}", false).unwrap().render(&context).unwrap(), "<a href='x'>&\"</a>");
+    }
+
+    #[test]
+    fn rejects_unknown_tags_and_filters() {
+        assert!(rejection("{% set x = 1 %}").contains("unsupported tag `set`"));
+        assert!(rejection("a\n{% include \"x\" %}").starts_with("line 2: unsupported tag `include`"));
+        assert!(rejection("{{#with host}}{{/with}}").contains("unsupported tag `#with`"));
+        assert!(rejection("{{ x | reverse }}").contains("unknown filter `reverse`"));
+        assert!(rejection("{{ x | join(glue=\",\") }}").contains("takes no argument `glue`"));
+        assert!(rejection("{{ x | default(value=y) }}").contains("must be a literal"));
+        assert!(rejection("{{ ../parent }}").contains("unsupported expression"));
+        assert!(rejection("{% for x in xs %}").contains("never closed"));
+        assert!(rejection("{% if x %}{% endfor %}").contains("expected endif"));
+        assert!(rejection("{% endif %}").contains("unexpected"));
+        assert!(rejection("{{ x ").contains("unclosed"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use crate::chart;
+use crate::colorize;
+use crate::packet::Packet;
+
+/// Data segments a transfer needs before it is worth explaining.
+const MIN_SEGMENTS: usize = 20;
+
+/// Share of ACK intervals in which the sender filled the receive window that makes a transfer window-limited.
+const WINDOW_LIMITED_SHARE: f64 = 0.3;
+
+/// Retransmitted share of data segments that makes a transfer loss-limited.
+const LOSS_LIMITED_SHARE: f64 = 0.01;
+
+type Endpoint = (IpAddr, u16);
+
+/// One direction of a TCP connection, seen from the sender.
+#[derive(Debug, Default)]
+struct Stream {
+    first: Option<f64>,
+    last_ack: f64,
+    /// Next sequence number the sender has not sent yet.
+    next_seq: Option<u32>,
+    /// Highest cumulative ACK from the receiver.
+    acked: Option<u32>,
+    delivered: u64,
+    /// Bytes acknowledged per second since the first data segment.
+    delivered_by_second: BTreeMap<u64, u64>,
+    /// Receiver's latest advertised window, scaled, in bytes; 0 when the scale is unknown.
+    window: u32,
+    max_window: u32,
+    mss: u32,
+    max_in_flight: u32,
+    in_flight_total: u64,
+    data_segments: usize,
+    retransmissions: usize,
+    /// ACKs that advanced the window, and how many of them followed a send that filled it.
+    acks: usize,
+    window_limited: usize,
+    window_full: bool,
+    zero_windows: usize,
+}
+
+/// Why a transfer did not go faster.
+#[derive(Debug, PartialEq)]
+enum Limit {
+    ReceiveWindow,
+    Loss,
+    Sender,
+}
+
+impl Stream {
+    fn goodput_mbps(&self) -> f64 {
+        let secs = self.first.map(|f| self.last_ack - f).unwrap_or(0.0);
+        if secs <= 0.0 {
+            return 0.0;
+        }
+        self.delivered as f64 * 8.0 / secs / 1e6
+    }
+
+    fn limit(&self) -> Limit {
+        let segments = self.data_segments.max(1) as f64;
+        if self.zero_windows > 0 || self.window_limited as f64 / self.acks.max(1) as f64 >= WINDOW_LIMITED_SHARE {
+            Limit::ReceiveWindow
+        } else if self.retransmissions as f64 / segments >= LOSS_LIMITED_SHARE {
+            Limit::Loss
+        } else {
+            Limit::Sender
+        }
+    }
+}
+
+/// Follows TCP transfers in a capture to explain what limited their throughput.
+#[derive(Debug, Default)]
+pub struct ThroughputWatch {
+    streams: HashMap<(Endpoint, Endpoint), Stream>,
+    /// Window scale each endpoint offered in its SYN; only applied when both sides offered one.
+    scales: HashMap<Endpoint, Option<u8>>,
+}
+
+impl ThroughputWatch {
+    pub fn new() -> ThroughputWatch {
+        ThroughputWatch::default()
+    }
+
+    /// Updates the sender's stream for data segments and the reverse stream for ACKs.
+    pub fn observe(&mut self, time: f64, packet: &Packet) {
+        let (tcp, src, dst) = match (packet.tcp, packet.src, packet.dst, packet.src_port, packet.dst_port) {
+            (Some(tcp), Some(s), Some(d), Some(sp), Some(dp)) => (tcp, (s, sp), (d, dp)),
+            _ => return,
+        };
+        if tcp.flags & 0x02 != 0 {
+            self.scales.insert(src, packet.handshake.as_ref().and_then(|h| h.window_scale));
+            return;
+        }
+        if tcp.flags & 0x04 != 0 {
+            return;
+        }
+        // Without both SYNs the scale is unknown and the raw window field means little.
+        let scale = match (self.scales.get(&src), self.scales.get(&dst)) {
+            (Some(Some(own)), Some(Some(_))) => Some(u32::from(*own).min(14)),
+            (Some(_), Some(_)) => Some(0),
+            _ => None,
+        };
+
+        if tcp.flags & 0x10 != 0 {
+            // This packet acknowledges data flowing the other way.
+            let reverse = self.streams.entry((dst, src)).or_default();
+            reverse.window = scale.map(|scale| u32::from(tcp.window) << scale).unwrap_or(0);
+            reverse.max_window = reverse.max_window.max(reverse.window);
+            if reverse.first.is_some() {
+                if tcp.window == 0 {
+                    reverse.zero_windows += 1;
+                }
+                let previous = reverse.acked.unwrap_or(tcp.ack);
+                let advance = tcp.ack.wrapping_sub(previous);
+                if (advance as i32) > 0 {
+                    reverse.delivered += u64::from(advance);
+                    let second = (time - reverse.first.unwrap_or(time)).max(0.0) as u64;
+                    *reverse.delivered_by_second.entry(second).or_insert(0) += u64::from(advance);
+                    reverse.last_ack = time;
+                    reverse.acks += 1;
+                    if reverse.window_full {
+                        reverse.window_limited += 1;
+                        reverse.window_full = false;
+                    }
+                }
+                if reverse.acked.is_none() || (advance as i32) > 0 {
+                    reverse.acked = Some(tcp.ack);
+                }
+            }
+        }
+
+        if tcp.payload_len == 0 {
+            return;
+        }
+        let stream = self.streams.entry((src, dst)).or_default();
+        stream.first.get_or_insert(time);
+        stream.data_segments += 1;
+        stream.mss = stream.mss.max(tcp.payload_len);
+        let end = tcp.seq.wrapping_add(tcp.payload_len);
+        let next = *stream.next_seq.get_or_insert(tcp.seq);
+        if (tcp.seq.wrapping_sub(next) as i32) < 0 {
+            stream.retransmissions += 1;
+        }
+        if (end.wrapping_sub(next) as i32) > 0 {
+            stream.next_seq = Some(end);
+        }
+        // Before the first ACK is seen, count from the first byte captured.
+        let acked = *stream.acked.get_or_insert(tcp.seq);
+        let in_flight = stream.next_seq.unwrap_or(end).wrapping_sub(acked);
+        if (in_flight as i32) > 0 {
+            stream.max_in_flight = stream.max_in_flight.max(in_flight);
+            stream.in_flight_total += u64::from(in_flight);
+            // Within one segment of the advertised window: the sender had to stop and wait.
+            if stream.window > 0 && in_flight + stream.mss >= stream.window {
+                stream.window_full = true;
+            }
+        }
+    }
+
+    /// Prints the largest transfers with their goodput over time and what limited them.
+    pub fn print_summary(&self) {
+        let mut transfers: Vec<(&(Endpoint, Endpoint), &Stream)> =
+            self.streams.iter().filter(|(_, s)| s.data_segments >= MIN_SEGMENTS && s.delivered > 0).collect();
+        if transfers.is_empty() {
+            return;
+        }
+        transfers.sort_by_key(|(_, s)| Reverse(s.delivered));
+        println!("\n🔹 {}", colorize("Why were transfers slow? (TCP throughput)", "blue"));
+        for ((sender, receiver), stream) in transfers.iter().take(5) {
+            let series: Vec<f64> = match (stream.delivered_by_second.keys().next(), stream.delivered_by_second.keys().last()) {
+                (Some(first), Some(last)) => (*first..=*last)
+                    .map(|s| stream.delivered_by_second.get(&s).cloned().unwrap_or(0) as f64 * 8.0 / 1e6)
+                    .collect(),
+                _ => Vec::new(),
+            };
+            let peak = series.iter().cloned().fold(0.0, f64::max);
+            println!(
+                "   {} → {}: {} KB at {:.2} Mbit/s (best second {:.2} Mbit/s)",
+                endpoint(sender),
+                endpoint(receiver),
+                stream.delivered / 1024,
+                stream.goodput_mbps(),
+                peak
+            );
+            if series.len() >= 3 {
+                println!("      Goodput over time: {}", chart::sparkline(&series));
+            }
+            let segments = stream.data_segments as f64;
+            let window = if stream.max_window > 0 { format!("{} KB", stream.max_window / 1024) } else { "unknown".to_string() };
+            println!(
+                "      In flight: max {} KB, mean {} KB; receive window: max {}; retransmitted {:.1}%; window full in {:.0}% of round trips",
+                stream.max_in_flight / 1024,
+                stream.in_flight_total / stream.data_segments as u64 / 1024,
+                window,
+                stream.retransmissions as f64 * 100.0 / segments,
+                stream.window_limited as f64 * 100.0 / stream.acks.max(1) as f64
+            );
+            match stream.limit() {
+                Limit::ReceiveWindow => {
+                    let zero = if stream.zero_windows > 0 { format!(", and advertised a zero window {} time(s)", stream.zero_windows) } else { String::new() };
+                    println!(
+                        "⚠️  {} Receive-window limited: the receiver's window was full{}. Raise its socket buffers (net.ipv4.tcp_rmem) or check that window scaling survives middleboxes; throughput cannot exceed window ÷ RTT.",
+                        colorize("[TCP]", "yellow"),
+                        zero
+                    );
+                }
+                Limit::Loss => println!(
+                    "⚠️  {} Loss limited: {} of {} segments were retransmitted, so congestion control kept cutting the rate. Look for packet loss on the path (netdiag loss, bufferbloat).",
+                    colorize("[TCP]", "yellow"),
+                    stream.retransmissions,
+                    stream.data_segments
+                ),
+                Limit::Sender => println!(
+                    "ℹ️  {} Sender limited: no loss and the window was rarely full, so the sending application or server produced data slower than the network could carry.",
+                    colorize("[TCP]", "blue")
+                ),
+            }
+            if stream.max_window == 0 {
+                println!("      The handshake was not captured, so window scaling and window-limited sends are unknown.");
+            }
+        }
+    }
+}
+
+fn endpoint((addr, port): &Endpoint) -> String {
+    match addr {
+        IpAddr::V6(a) => format!("[{}]:{}", a, port),
+        IpAddr::V4(a) => format!("{}:{}", a, port),
+    }
+}
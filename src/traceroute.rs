@@ -0,0 +1,325 @@
+use std::net::IpAddr;
+use std::process::Command;
+use std::thread;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::interrupt;
+use crate::packet::IcmpError;
+use crate::pathgraph::{self, Format};
+use crate::privileges;
+use crate::resolver;
+use crate::scheduler;
+use crate::telemetry;
+
+/// Probe packet type used by traceroute.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Proto {
+    Icmp,
+    Udp,
+    Tcp,
+}
+
+impl Proto {
+    pub fn from_name(name: &str) -> Option<Proto> {
+        match name.to_ascii_lowercase().as_str() {
+            "icmp" => Some(Proto::Icmp),
+            "udp" => Some(Proto::Udp),
+            "tcp" => Some(Proto::Tcp),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Proto::Icmp => "ICMP",
+            Proto::Udp => "UDP",
+            Proto::Tcp => "TCP",
+        }
+    }
+}
+
+/// One TTL step of a trace.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Hop {
+    pub ttl: u32,
+    pub addr: Option<String>,
+    pub rtts: Vec<f64>,
+    /// ICMP error flags traceroute printed for this hop, e.g. `!X` or `!F-1400`.
+    #[serde(default)]
+    pub annotations: Vec<String>,
+}
+
+/// A completed trace and the protocol that produced it.
+#[derive(Debug, Clone)]
+pub struct Trace {
+    pub requested: Proto,
+    pub proto: Proto,
+    pub port: Option<u16>,
+    pub hops: Vec<Hop>,
+    /// Set when the target did not resolve, so nothing was traced.
+    pub resolve_error: Option<String>,
+    /// Protocols asked for, directly or as a fallback, that ran as UDP because they need root.
+    pub downgraded: Vec<Proto>,
+}
+
+impl Trace {
+    /// True when at least one hop answered.
+    pub fn has_replies(&self) -> bool {
+        self.hops.iter().any(|h| h.addr.is_some())
+    }
+
+    /// Hop addresses in order, `*` for hops that did not answer.
+    pub fn path(&self) -> Vec<String> {
+        self.hops.iter().map(|h| h.addr.clone().unwrap_or_else(|| "*".to_string())).collect()
+    }
+}
+
+/// Returns the `traceroute` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("traceroute")
+        .about("Traces the path to one or more hosts with ICMP, UDP or TCP probes")
+        .arg(Arg::with_name("host").required(true).multiple(true).help("Destination host(s)"))
+        .arg(Arg::with_name("proto").long("proto").takes_value(true).default_value("udp")
+            .possible_values(&["icmp", "udp", "tcp"])
+            .help("Probe protocol"))
+        .arg(Arg::with_name("port").long("port").takes_value(true)
+            .help("Destination port for UDP/TCP probes (TCP defaults to 443)"))
+        .arg(Arg::with_name("no-fallback").long("no-fallback")
+            .help("Do not retry with other protocols when no hop answers"))
+        .arg(Arg::with_name("export-path").long("export-path").takes_value(true).value_name("FILE")
+            .help("Write the traced paths, merged at shared hops, as a graph annotated with RTT and ASN (.dot, or .mmd/.md for Mermaid)"))
+        .arg(Arg::with_name("format").long("format").takes_value(true).possible_values(&["dot", "mermaid"])
+            .help("Graph format for --export-path (default: from the file extension)"))
+}
+
+/// Runs the `traceroute` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let hosts: Vec<String> = matches.values_of("host").into_iter().flatten().map(|h| h.to_string()).collect();
+    let proto = matches.value_of("proto").and_then(Proto::from_name).unwrap_or(Proto::Udp);
+    let port = value_t!(matches, "port", u16).ok();
+    let fallback = !matches.is_present("no-fallback");
+
+    println!();
+    // Each trace waits on timeouts, so several targets are traced side by side.
+    let handles: Vec<_> = hosts
+        .iter()
+        .cloned()
+        .map(|host| thread::spawn(move || if fallback { trace_with_fallback(&host, proto, port) } else { trace(&host, proto, port) }))
+        .collect();
+    let traces: Vec<(String, Trace)> = hosts
+        .into_iter()
+        .zip(handles)
+        .map(|(host, handle)| {
+            let trace = handle.join().unwrap_or(Trace { requested: proto, proto, port, hops: Vec::new(), resolve_error: None, downgraded: Vec::new() });
+            (host, trace)
+        })
+        .collect();
+    for (host, trace) in &traces {
+        print_trace(host, trace);
+        println!();
+    }
+
+    if let Some(path) = matches.value_of("export-path") {
+        let format = match matches.value_of("format") {
+            Some("mermaid") => Format::Mermaid,
+            Some(_) => Format::Dot,
+            None => Format::from_path(path),
+        };
+        match pathgraph::export(path, format, &traces) {
+            Ok(nodes) => println!("✅ {} Wrote a {}-node path graph to {}\n", colorize("[SUCCESS]", "green"), nodes, path),
+            Err(e) => println!("❌ {} Could not write {}: {}\n", colorize("[ERROR]", "red"), path, e),
+        }
+    }
+}
+
+/// Traces `host` and prints the hops; used by the main diagnostics run.
+pub fn run_trace(host: &str) {
+    let trace = trace_with_fallback(host, Proto::Udp, None);
+    print_trace(host, &trace);
+}
+
+/// Hop addresses to `host`, falling back between protocols; used for path comparisons.
+pub fn path(host: &str) -> Vec<String> {
+    trace_with_fallback(host, Proto::Udp, None).path()
+}
+
+/// The probe type traceroute will really send for `proto`: Linux traceroute needs raw sockets
+/// for ICMP and TCP probes, while UDP works unprivileged.
+fn runnable(proto: Proto) -> Proto {
+    if cfg!(target_os = "linux") && proto != Proto::Udp && !privileges::is_root() { Proto::Udp } else { proto }
+}
+
+/// Runs the system traceroute once with the given probe type.
+pub fn trace(host: &str, proto: Proto, port: Option<u16>) -> Trace {
+    let requested = proto;
+    let proto = runnable(proto);
+    let downgraded = if proto != requested { vec![requested] } else { Vec::new() };
+    let port = port.or(if proto == Proto::Tcp { Some(443) } else { None });
+    let port_text = port.map(|p| p.to_string()).unwrap_or_default();
+    let ip = match resolver::resolve_one(host) {
+        Ok(ip) => ip,
+        Err(e) => return Trace { requested, proto, port, hops: Vec::new(), resolve_error: Some(e.to_string()), downgraded },
+    };
+    let target = ip.to_string();
+
+    let (command, mut args): (&str, Vec<&str>) = if cfg!(windows) {
+        // tracert only speaks ICMP.
+        ("tracert", vec!["-d", "-h", "30", "-w", "2000"])
+    } else {
+        ("traceroute", vec!["-n", "-q", "3", "-w", "2", "-m", "30"])
+    };
+    if !cfg!(windows) {
+        match (proto, cfg!(target_os = "macos")) {
+            (Proto::Icmp, true) => args.extend(["-P", "icmp"]),
+            (Proto::Tcp, true) => args.extend(["-P", "tcp"]),
+            (Proto::Icmp, false) => args.push("-I"),
+            (Proto::Tcp, false) => args.push("-T"),
+            (Proto::Udp, _) => {}
+        }
+        if port.is_some() {
+            args.extend(["-p", &port_text]);
+        }
+    }
+    args.push(&target);
+
+    let _permit = scheduler::acquire(&format!("traceroute-{}", proto.name().to_ascii_lowercase()), ip, port);
+    let hops = interrupt::output(Command::new(command).args(&args))
+        .map(|out| parse_hops(&String::from_utf8_lossy(&out.stdout)))
+        .unwrap_or_default();
+    Trace { requested, proto, port, hops, resolve_error: None, downgraded }
+}
+
+/// Traces with `proto` first and, if no hop answered, retries with the other protocols. A
+/// fallback that would send the same probes as an earlier attempt (an ICMP or TCP trace run as
+/// UDP without root) is skipped rather than repeated.
+pub fn trace_with_fallback(host: &str, proto: Proto, port: Option<u16>) -> Trace {
+    let mut order = vec![proto];
+    for other in &[Proto::Tcp, Proto::Icmp, Proto::Udp] {
+        if !order.contains(other) {
+            order.push(*other);
+        }
+    }
+
+    let mut tried: Vec<Proto> = Vec::new();
+    let mut downgraded: Vec<Proto> = Vec::new();
+    let mut last = None;
+    for (i, &p) in order.iter().enumerate() {
+        if runnable(p) != p {
+            downgraded.push(p);
+        }
+        if tried.contains(&runnable(p)) {
+            continue;
+        }
+        tried.push(runnable(p));
+        // A port chosen for the first protocol is kept; fallbacks use their defaults.
+        let mut trace = trace(host, p, if i == 0 { port } else { None });
+        trace.requested = proto;
+        trace.downgraded = downgraded.clone();
+        if trace.has_replies() || trace.resolve_error.is_some() {
+            return trace;
+        }
+        last = Some(trace);
+    }
+    let mut trace = last.unwrap_or(Trace { requested: proto, proto, port, hops: Vec::new(), resolve_error: None, downgraded: Vec::new() });
+    trace.downgraded = downgraded;
+    trace
+}
+
+/// Parses traceroute/tracert output into hops.
+fn parse_hops(output: &str) -> Vec<Hop> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let tokens: Vec<&str> = line.split_whitespace().collect();
+            let ttl: u32 = tokens.first()?.parse().ok()?;
+            let mut hop = Hop { ttl, addr: None, rtts: Vec::new(), annotations: Vec::new() };
+            for (i, token) in tokens.iter().enumerate().skip(1) {
+                let token = token.trim_matches(|c| c == '(' || c == ')');
+                if token.starts_with('!') {
+                    if !hop.annotations.iter().any(|a| a == token) {
+                        hop.annotations.push(token.to_string());
+                    }
+                } else if token.parse::<IpAddr>().is_ok() {
+                    if hop.addr.is_none() {
+                        hop.addr = Some(token.to_string());
+                    }
+                } else if tokens.get(i + 1) == Some(&"ms") {
+                    if let Ok(rtt) = token.trim_start_matches('<').parse() {
+                        hop.rtts.push(rtt);
+                    }
+                }
+            }
+            Some(hop)
+        })
+        .collect()
+}
+
+/// Prints a trace as a hop table.
+pub fn print_trace(host: &str, trace: &Trace) {
+    let proto = match trace.port {
+        Some(port) => format!("{}/{}", trace.proto.name(), port),
+        None => trace.proto.name().to_string(),
+    };
+    println!("🔹 {}", colorize(&format!("Traceroute to {} ({})", host, proto), "blue"));
+    if let Some(e) = &trace.resolve_error {
+        println!("❌ {} {}", colorize("[DNS]", "red"), e);
+        telemetry::fail(e);
+        return;
+    }
+    if !trace.downgraded.is_empty() {
+        let names: Vec<&str> = trace.downgraded.iter().map(|p| p.name()).collect();
+        println!("{}", privileges::hint(&format!("{} traceroute", names.join(" and ")), "needs root; only UDP probes were sent (rerun with sudo)"));
+    }
+    if trace.requested != trace.proto && !trace.downgraded.contains(&trace.requested) {
+        println!(
+            "   ⚠️  {} {} probes got no replies; fell back to {}",
+            colorize("[WARN]", "yellow"),
+            trace.requested.name(),
+            trace.proto.name()
+        );
+    }
+    if trace.hops.is_empty() {
+        println!("❌ {} traceroute produced no output", colorize("[ERROR]", "red"));
+        telemetry::fail(&format!("traceroute to {} produced no output", host));
+        return;
+    }
+    for hop in &trace.hops {
+        let rtts: Vec<String> = hop.rtts.iter().map(|r| format!("{:.1} ms", r)).collect();
+        println!(
+            "   {:>2}  {:<40} {} {}",
+            hop.ttl,
+            colorize(hop.addr.as_deref().unwrap_or("*"), if hop.addr.is_some() { "cyan" } else { "yellow" }),
+            rtts.join("  "),
+            colorize(&hop.annotations.join(" "), "red")
+        );
+    }
+
+    let target = match (trace.proto, trace.port) {
+        (Proto::Tcp, Some(port)) => format!("tcp/{} to {}", port, host),
+        (Proto::Udp, Some(port)) => format!("udp/{} to {}", port, host),
+        _ => host.to_string(),
+    };
+    for hop in &trace.hops {
+        for error in hop.annotations.iter().filter_map(|a| annotation_error(a)) {
+            let reporter = hop.addr.as_deref().unwrap_or("*");
+            println!("   ⚠️  {} hop {}: {}", colorize("[ICMP]", "yellow"), hop.ttl, error.explain(reporter, &target));
+        }
+    }
+}
+
+/// Maps traceroute's `!` annotations to the ICMP error they stand for.
+fn annotation_error(annotation: &str) -> Option<IcmpError> {
+    let flag = annotation.trim_start_matches('!');
+    let error = match flag.chars().next()? {
+        'N' => IcmpError::NetUnreachable,
+        'H' => IcmpError::HostUnreachable,
+        'P' => IcmpError::ProtocolUnreachable,
+        'F' => IcmpError::TooBig(flag.trim_start_matches("F-").trim_start_matches('F').parse().ok()),
+        'X' | 'A' | 'C' | 'Z' | 'Q' => IcmpError::AdminProhibited,
+        c if c.is_ascii_digit() => IcmpError::OtherUnreachable(flag.parse().ok()?),
+        _ => return None,
+    };
+    Some(error)
+}
@@ -0,0 +1,143 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, HashMap};
+use std::net::IpAddr;
+
+use crate::analyze;
+use crate::chart;
+use crate::colorize;
+use crate::packet::Packet;
+
+/// Rows shown in the protocol and host tables.
+const TOP: usize = 10;
+
+/// Packet and byte counters.
+#[derive(Debug, Default, Clone, Copy)]
+struct Count {
+    packets: u64,
+    bytes: u64,
+}
+
+impl Count {
+    fn add(&mut self, bytes: u64) {
+        self.packets += 1;
+        self.bytes += bytes;
+    }
+}
+
+/// Counts traffic per second, per protocol and per host instead of listing packets, for links
+/// too busy to read packet by packet. With sampling every count is scaled back up.
+#[derive(Debug)]
+pub struct TrafficStats {
+    /// One packet in this many was kept.
+    sample: u64,
+    total: Count,
+    per_second: BTreeMap<u64, Count>,
+    protocols: HashMap<String, Count>,
+    /// Traffic each address sent or received.
+    hosts: HashMap<IpAddr, Count>,
+}
+
+impl TrafficStats {
+    pub fn new(sample: u32) -> TrafficStats {
+        TrafficStats {
+            sample: u64::from(sample.max(1)),
+            total: Count::default(),
+            per_second: BTreeMap::new(),
+            protocols: HashMap::new(),
+            hosts: HashMap::new(),
+        }
+    }
+
+    /// Counts one packet of `length` bytes on the wire seen at Unix time `second`. Returns the
+    /// previous second's totals (packets and bytes, scaled) once a later second starts.
+    pub fn observe(&mut self, second: u64, length: u32, packet: &Packet) -> Option<(u64, u64, u64)> {
+        let finished = match self.per_second.keys().next_back() {
+            Some(&last) if last < second => self.per_second.get(&last).map(|c| (last, c.packets * self.sample, c.bytes * self.sample)),
+            _ => None,
+        };
+        let length = u64::from(length);
+        self.total.add(length);
+        self.per_second.entry(second).or_default().add(length);
+        self.protocols.entry(analyze::service(packet)).or_default().add(length);
+        for addr in [packet.src, packet.dst].iter().flatten() {
+            self.hosts.entry(*addr).or_default().add(length);
+        }
+        finished
+    }
+
+    /// Prints rates over time and the busiest protocols and hosts.
+    pub fn print_summary(&self) {
+        let (first, last) = match (self.per_second.keys().next(), self.per_second.keys().next_back()) {
+            (Some(first), Some(last)) => (*first, *last),
+            _ => return,
+        };
+        let scale = self.sample;
+        let seconds = (last - first + 1) as f64;
+        let pps: Vec<f64> = (first..=last).map(|s| self.per_second.get(&s).map_or(0, |c| c.packets * scale) as f64).collect();
+        let mbps: Vec<f64> = (first..=last).map(|s| self.per_second.get(&s).map_or(0, |c| c.bytes * scale) as f64 * 8.0 / 1e6).collect();
+
+        println!("\n🔹 {}", colorize("Traffic statistics", "blue"));
+        if scale > 1 {
+            println!("   Estimated from a 1-in-{} sample: {} packets were counted", scale, self.total.packets);
+        }
+        println!(
+            "   {} packets, {} over {:.0}s: mean {:.0} pkt/s and {:.2} Mbit/s, peak {:.0} pkt/s and {:.2} Mbit/s",
+            self.total.packets * scale,
+            bytes(self.total.bytes * scale),
+            seconds,
+            (self.total.packets * scale) as f64 / seconds,
+            (self.total.bytes * scale) as f64 * 8.0 / 1e6 / seconds,
+            pps.iter().cloned().fold(0.0, f64::max),
+            mbps.iter().cloned().fold(0.0, f64::max)
+        );
+        if mbps.len() >= 3 {
+            println!("   Mbit/s over time: {}", chart::sparkline(&mbps));
+        }
+
+        println!("\n   {:<16} {:>10} {:>10} {:>7}", "Protocol", "Packets", "Bytes", "Share");
+        for (label, count) in top(&self.protocols) {
+            println!(
+                "   {:<16} {:>10} {:>10} {:>6.1}%",
+                label,
+                count.packets * scale,
+                bytes(count.bytes * scale),
+                count.bytes as f64 * 100.0 / self.total.bytes.max(1) as f64
+            );
+        }
+        if self.protocols.len() > TOP {
+            println!("   … and {} more", self.protocols.len() - TOP);
+        }
+
+        println!("\n   {:<40} {:>10} {:>10} {:>7}", "Host", "Packets", "Bytes", "Share");
+        for (addr, count) in top(&self.hosts) {
+            println!(
+                "   {:<40} {:>10} {:>10} {:>6.1}%",
+                addr.to_string(),
+                count.packets * scale,
+                bytes(count.bytes * scale),
+                count.bytes as f64 * 100.0 / self.total.bytes.max(1) as f64
+            );
+        }
+        if self.hosts.len() > TOP {
+            println!("   … and {} more", self.hosts.len() - TOP);
+        }
+    }
+}
+
+/// The busiest entries by bytes.
+fn top<K: Ord>(counts: &HashMap<K, Count>) -> Vec<(&K, &Count)> {
+    let mut entries: Vec<(&K, &Count)> = counts.iter().collect();
+    entries.sort_by_key(|(key, count)| (Reverse(count.bytes), *key));
+    entries.truncate(TOP);
+    entries
+}
+
+/// A byte count with a binary unit.
+fn bytes(n: u64) -> String {
+    match n {
+        n if n >= 1 << 30 => format!("{:.1} GiB", n as f64 / (1u64 << 30) as f64),
+        n if n >= 1 << 20 => format!("{:.1} MiB", n as f64 / (1u64 << 20) as f64),
+        n if n >= 1 << 10 => format!("{:.1} KiB", n as f64 / 1024.0),
+        n => format!("{} B", n),
+    }
+}
@@ -0,0 +1,302 @@
+use std::net::IpAddr;
+use std::path::Path;
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::baseline::Snapshot;
+use crate::colorize;
+use crate::dns;
+use crate::netinfo;
+use crate::pathgraph::{self, Asn};
+use crate::privileges;
+use crate::routes;
+use crate::telemetry;
+
+/// External hosts whose reachability decides whether the internet works.
+const EXTERNAL_HOSTS: &[&str] = &["8.8.8.8", "1.1.1.1"];
+
+/// Name resolved through the system resolver to decide whether DNS works.
+const DNS_TEST_NAME: &str = "google.com";
+
+/// Pings sent to the gateway and to each hop when looking for loss.
+const HOP_PINGS: u32 = 10;
+
+/// Loss at or above this share counts as a problem.
+const LOSS_THRESHOLD: f64 = 0.05;
+
+/// Where the decision tree places the fault.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Fault {
+    None,
+    /// Not connected: no default route or no address.
+    Link,
+    /// The Wi-Fi, LAN or router.
+    Local,
+    /// The router's uplink or the ISP's network.
+    Isp,
+    /// A network beyond the ISP.
+    BeyondIsp,
+    Dns,
+}
+
+/// One input to the decision tree: whether it passed and what was seen.
+#[derive(Debug, Clone, Serialize)]
+pub struct Check {
+    pub name: String,
+    /// `None` when the check could not be run.
+    pub passed: Option<bool>,
+    pub detail: String,
+}
+
+/// Plain-English conclusion of the decision tree and the checks it was based on.
+#[derive(Debug, Clone, Serialize)]
+pub struct Triage {
+    pub conclusion: String,
+    pub fault: Fault,
+    pub checks: Vec<Check>,
+}
+
+/// Loss measured to one hop of the path to the first external host.
+#[derive(Debug, Clone)]
+struct HopLoss {
+    ttl: usize,
+    addr: String,
+    loss: f64,
+    asn: Option<Asn>,
+}
+
+/// Returns the `auto-triage` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("auto-triage")
+        .about("Checks link, gateway, DNS, internet and per-hop loss, and says in plain English where the fault is")
+}
+
+/// Runs the `auto-triage` subcommand.
+pub fn run(_matches: &ArgMatches) {
+    diagnose();
+}
+
+/// Triages the connection and prints the verdict.
+pub fn diagnose() {
+    println!("\n🔹 {}", colorize("Triaging the connection (about 15 seconds)", "blue"));
+    let triage = Triage::run(&Snapshot::take());
+    triage.print();
+    if triage.fault != Fault::None {
+        if let Ok(serde_json::Value::String(fault)) = serde_json::to_value(triage.fault) {
+            telemetry::attribute("netdiag.triage.fault", &fault);
+        }
+        telemetry::fail(&triage.conclusion);
+    }
+    println!();
+}
+
+impl Triage {
+    /// Probes the link, gateway, DNS and each hop towards the internet, reusing the latencies
+    /// and paths already in `snapshot`, and walks the decision tree.
+    pub fn run(snapshot: &Snapshot) -> Triage {
+        let mut checks = Vec::new();
+        let default_route = routes::table().into_iter().find(|r| r.is_default());
+        let interface = default_route.as_ref().map(|r| r.interface.clone());
+        let wireless = interface.as_deref().is_some_and(is_wireless);
+        let local = if wireless { "Wi-Fi" } else { "local network" };
+
+        // 1. Link: a default route over an interface with an address.
+        let addrs: Vec<String> = match &interface {
+            Some(name) => netinfo::interfaces().into_iter().filter(|i| &i.name == name).flat_map(|i| i.addrs).collect(),
+            None => Vec::new(),
+        };
+        let link_up = interface.is_some() && !addrs.is_empty();
+        checks.push(Check {
+            name: "Link".to_string(),
+            passed: Some(link_up),
+            detail: match &interface {
+                Some(name) if link_up => format!("{} ({}) with {}", name, local, addrs.join(", ")),
+                Some(name) => format!("{} carries the default route but has no address", name),
+                None => "no default route".to_string(),
+            },
+        });
+        if !link_up {
+            return Triage::conclude(Fault::Link, "You are not connected: there is no default route with an address. Check that Wi-Fi is joined or the cable is plugged in, and that DHCP gave you an address.".to_string(), checks);
+        }
+
+        // Gateway, DNS and hop loss are probed together; they are independent.
+        let gateway = snapshot.gateway.clone();
+        let gateway_probe = gateway.clone().map(|g| thread::spawn(move || netinfo::ping(&g, HOP_PINGS)));
+        let dns_probe = thread::spawn(resolve_test_name);
+        let path = EXTERNAL_HOSTS.iter().find_map(|h| snapshot.paths.get(*h)).cloned().unwrap_or_default();
+        let hops_probe = thread::spawn(move || hop_losses(&path));
+
+        // 2. Gateway.
+        let gateway_loss = gateway_probe.and_then(|p| p.join().ok().flatten()).map(|s| loss_of(s.transmitted, s.received));
+        checks.push(Check {
+            name: "Gateway".to_string(),
+            passed: gateway_loss.map(|l| l < LOSS_THRESHOLD),
+            detail: match (&gateway, gateway_loss) {
+                (Some(g), Some(loss)) if loss >= 1.0 => format!("{} does not answer ping", g),
+                (Some(g), Some(loss)) => format!("{} answers, {:.0}% loss", g, loss * 100.0),
+                (Some(g), None) => format!("{} could not be pinged", g),
+                (None, _) => "no IPv4 gateway found".to_string(),
+            },
+        });
+
+        // 3. Internet.
+        let external: Vec<(&str, f64)> = EXTERNAL_HOSTS.iter().filter_map(|h| snapshot.latencies.get(*h).map(|ms| (*h, *ms))).collect();
+        checks.push(Check {
+            name: "Internet".to_string(),
+            passed: Some(!external.is_empty()),
+            detail: if external.is_empty() {
+                format!("{} do not answer", EXTERNAL_HOSTS.join(" and "))
+            } else {
+                external.iter().map(|(h, ms)| format!("{} in {:.0} ms", h, ms)).collect::<Vec<_>>().join(", ")
+            },
+        });
+
+        // 4. DNS.
+        let dns = dns_probe.join().unwrap_or_else(|_| Err("lookup failed".to_string()));
+        checks.push(Check {
+            name: "DNS".to_string(),
+            passed: Some(dns.is_ok()),
+            detail: match &dns {
+                Ok((server, ms)) => format!("{} resolved by {} in {:.0} ms", DNS_TEST_NAME, server, ms),
+                Err(e) => e.clone(),
+            },
+        });
+
+        // 5. Loss along the path.
+        let hops = hops_probe.join().unwrap_or(None);
+        let isp = snapshot.public_ip.as_ref().and_then(|ip| ip.parse::<IpAddr>().ok()).and_then(|ip| {
+            let server = netinfo::dns_servers().iter().find_map(|s| s.parse::<IpAddr>().ok())?;
+            pathgraph::lookup_asn(server, ip)
+        });
+        let loss_start = hops.as_ref().and_then(|hops| first_persistent_loss(hops));
+        checks.push(Check {
+            name: "Path loss".to_string(),
+            passed: hops.as_ref().map(|_| loss_start.is_none()),
+            detail: match (&hops, &loss_start) {
+                (None, _) => "not measured (no path, or ICMP is not permitted)".to_string(),
+                (Some(hops), None) => format!("no persistent loss over {} hop(s)", hops.len()),
+                (Some(_), Some(hop)) => format!("{:.0}% loss from hop {} ({}) onwards", hop.loss * 100.0, hop.ttl, hop.addr),
+            },
+        });
+
+        let gateway_answers = gateway_loss.is_some_and(|l| l < 1.0);
+        if external.is_empty() {
+            if gateway.is_some() && !gateway_answers {
+                let message = format!("Your {} or router is the problem: the router at {} does not answer and nothing beyond it does either. Restart the router, or move closer / check the cable.", local, gateway.unwrap_or_default());
+                return Triage::conclude(Fault::Local, message, checks);
+            }
+            let message = format!("Your {} is fine, but nothing beyond the router answers. The router's internet connection or your ISP is down; check the modem lights or the ISP's status page.", local);
+            return Triage::conclude(Fault::Isp, message, checks);
+        }
+        if let Err(e) = &dns {
+            let message = format!("The internet is reachable but DNS is not ({}). Point DNS at a public resolver such as 1.1.1.1, or fix the router's DNS settings.", e);
+            return Triage::conclude(Fault::Dns, message, checks);
+        }
+        if let Some(loss) = gateway_loss.filter(|l| *l >= LOSS_THRESHOLD && *l < 1.0) {
+            let message = format!("Your {} is dropping packets: {:.0}% loss to the router. Move closer to the access point, change channel, or try a cable.", local, loss * 100.0);
+            return Triage::conclude(Fault::Local, message, checks);
+        }
+        if let Some(hop) = loss_start {
+            let (fault, place) = locate(&hop, isp.as_ref());
+            let message = format!("Your {} is fine; loss begins at hop {} ({}) {}.", local, hop.ttl, hop.addr, place);
+            return Triage::conclude(fault, message, checks);
+        }
+        let fastest = external.iter().map(|(_, ms)| *ms).fold(f64::MAX, f64::min);
+        let message = format!("Everything looks healthy: your {}, router, DNS and internet all work ({:.0} ms to the internet).", local, fastest);
+        Triage::conclude(Fault::None, message, checks)
+    }
+
+    fn conclude(fault: Fault, conclusion: String, checks: Vec<Check>) -> Triage {
+        Triage { conclusion, fault, checks }
+    }
+
+    /// Prints the conclusion first, then the checks that led to it.
+    pub fn print(&self) {
+        match self.fault {
+            Fault::None => println!("✅ {} {}", colorize("[TRIAGE]", "green"), self.conclusion),
+            _ => println!("❌ {} {}", colorize("[TRIAGE]", "red"), self.conclusion),
+        }
+        for check in &self.checks {
+            let icon = match check.passed {
+                Some(true) => "✅",
+                Some(false) => "❌",
+                None => "⚠️ ",
+            };
+            println!("   {} {:<10} {}", icon, check.name, check.detail);
+        }
+    }
+}
+
+/// True for Wi-Fi interfaces: sysfs marks them on Linux; elsewhere the name is the best hint.
+fn is_wireless(interface: &str) -> bool {
+    Path::new(&format!("/sys/class/net/{}/wireless", interface)).exists() || interface.starts_with("wl") || interface.starts_with("wifi")
+}
+
+fn loss_of(transmitted: u32, received: u32) -> f64 {
+    if transmitted == 0 { 1.0 } else { 1.0 - received as f64 / transmitted as f64 }
+}
+
+/// Resolves the test name through the system resolver, returning the server and time taken.
+fn resolve_test_name() -> Result<(String, f64), String> {
+    let server: IpAddr = netinfo::dns_servers().iter().find_map(|s| s.parse().ok()).ok_or("no system resolver configured")?;
+    match dns::query(server, DNS_TEST_NAME, dns::TYPE_A, Duration::from_secs(3)) {
+        Ok(r) if r.rcode == dns::RCODE_NOERROR && !r.values(dns::TYPE_A).is_empty() => Ok((server.to_string(), r.elapsed.as_secs_f64() * 1000.0)),
+        Ok(r) => Err(format!("resolver {} answered {} for {}", server, dns::rcode_name(r.rcode), DNS_TEST_NAME)),
+        Err(e) => Err(format!("resolver {} did not answer: {}", server, e)),
+    }
+}
+
+/// Pings every answering hop of `path` concurrently. `None` when there is no path or routers
+/// cannot be pinged (the TCP fallback would make every router look lossy).
+fn hop_losses(path: &[String]) -> Option<Vec<HopLoss>> {
+    if path.is_empty() || !privileges::icmp_allowed() {
+        return None;
+    }
+    let server = netinfo::dns_servers().iter().find_map(|s| s.parse::<IpAddr>().ok());
+    let probes: Vec<_> = path
+        .iter()
+        .enumerate()
+        .filter(|(_, addr)| *addr != "*")
+        .map(|(i, addr)| {
+            let addr = addr.clone();
+            thread::spawn(move || {
+                let loss = netinfo::ping(&addr, HOP_PINGS).map_or(1.0, |s| loss_of(s.transmitted, s.received));
+                let asn = match (server, addr.parse::<IpAddr>()) {
+                    (Some(server), Ok(ip)) if pathgraph::is_public(&ip) => pathgraph::lookup_asn(server, ip),
+                    _ => None,
+                };
+                HopLoss { ttl: i + 1, addr, loss, asn }
+            })
+        })
+        .collect();
+    Some(probes.into_iter().filter_map(|p| p.join().ok()).collect())
+}
+
+/// The first hop whose loss carries on to every later hop. Loss at a router that later hops
+/// do not share is just that router rate-limiting its ICMP replies.
+fn first_persistent_loss(hops: &[HopLoss]) -> Option<HopLoss> {
+    // Routers that never answer ping say nothing either way.
+    let answering: Vec<&HopLoss> = hops.iter().filter(|h| h.loss < 1.0).collect();
+    (0..answering.len()).find(|&i| answering[i..].iter().all(|h| h.loss >= LOSS_THRESHOLD)).map(|i| answering[i].clone())
+}
+
+/// Says whether a hop is inside the ISP, using its AS when known and its address otherwise.
+fn locate(hop: &HopLoss, isp: Option<&Asn>) -> (Fault, String) {
+    let describe = |asn: &Asn| match &asn.name {
+        Some(name) => format!("AS{} {}", asn.number, name),
+        None => format!("AS{}", asn.number),
+    };
+    match (&hop.asn, isp) {
+        (Some(hop_asn), Some(isp)) if hop_asn.number == isp.number => (Fault::Isp, format!("inside your ISP ({})", describe(isp))),
+        (Some(hop_asn), Some(_)) => (Fault::BeyondIsp, format!("beyond your ISP, in {}", describe(hop_asn))),
+        // Private and carrier-grade NAT hops past the router belong to the ISP's access network.
+        (None, _) if hop.addr.parse::<IpAddr>().is_ok_and(|ip| !pathgraph::is_public(&ip)) && hop.ttl > 1 => {
+            (Fault::Isp, "inside your ISP's access network".to_string())
+        }
+        (None, _) if hop.ttl == 1 => (Fault::Local, "at your router".to_string()),
+        _ => (Fault::Isp, "between your router and the wider internet".to_string()),
+    }
+}
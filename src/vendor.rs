@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::http;
+use crate::netinfo;
+use crate::packet::MacAddr;
+use crate::{colorize, data_dir};
+
+/// IEEE MA-L (24-bit OUI) registry; downloaded once with `netdiag oui --update`.
+const REGISTRY_URL: &str = "https://standards-oui.ieee.org/oui/oui.csv";
+
+/// Vendors common on home and office networks, used until the full registry is downloaded.
+const BUILTIN: &[(u32, &str)] = &[
+    (0x00000C, "Cisco"),
+    (0x000393, "Apple"),
+    (0x00040E, "AVM"),
+    (0x000569, "VMware"),
+    (0x000585, "Juniper Networks"),
+    (0x00089B, "QNAP"),
+    (0x00090F, "Fortinet"),
+    (0x000A95, "Apple"),
+    (0x000B86, "Aruba Networks"),
+    (0x000C29, "VMware"),
+    (0x000C42, "MikroTik"),
+    (0x000D93, "Apple"),
+    (0x000DB9, "PC Engines"),
+    (0x001124, "Apple"),
+    (0x001132, "Synology"),
+    (0x00146C, "Netgear"),
+    (0x001451, "Apple"),
+    (0x00155D, "Microsoft (Hyper-V)"),
+    (0x00163E, "Xen"),
+    (0x0016CB, "Apple"),
+    (0x0017F2, "Apple"),
+    (0x001788, "Philips Lighting"),
+    (0x00180A, "Cisco Meraki"),
+    (0x0019E3, "Apple"),
+    (0x001B17, "Palo Alto Networks"),
+    (0x001B21, "Intel"),
+    (0x001B2F, "Netgear"),
+    (0x001B63, "Apple"),
+    (0x001C14, "VMware"),
+    (0x001C42, "Parallels"),
+    (0x001E67, "Intel"),
+    (0x001EC2, "Apple"),
+    (0x001FF3, "Apple"),
+    (0x002312, "Apple"),
+    (0x002500, "Apple"),
+    (0x002590, "Supermicro"),
+    (0x0026BB, "Apple"),
+    (0x002722, "Ubiquiti"),
+    (0x005056, "VMware"),
+    (0x0090A9, "Western Digital"),
+    (0x00E04C, "Realtek"),
+    (0x0418D6, "Ubiquiti"),
+    (0x080027, "VirtualBox"),
+    (0x0CC47A, "Supermicro"),
+    (0x14CC20, "TP-Link"),
+    (0x18FE34, "Espressif"),
+    (0x240AC4, "Espressif"),
+    (0x246F28, "Espressif"),
+    (0x24A43C, "Ubiquiti"),
+    (0x28CDC1, "Raspberry Pi"),
+    (0x28CFE9, "Apple"),
+    (0x30AEA4, "Espressif"),
+    (0x3C0754, "Apple"),
+    (0x3C22FB, "Apple"),
+    (0x3C5AB4, "Google"),
+    (0x3CFDFE, "Intel"),
+    (0x44650D, "Amazon"),
+    (0x44D9E7, "Ubiquiti"),
+    (0x48B02D, "NVIDIA"),
+    (0x4C5E0C, "MikroTik"),
+    (0x50C7BF, "TP-Link"),
+    (0x525400, "QEMU/KVM"),
+    (0x546009, "Google"),
+    (0x5CCF7F, "Espressif"),
+    (0x64D154, "MikroTik"),
+    (0x6854FD, "Amazon"),
+    (0x6C3B6B, "MikroTik"),
+    (0x7483C2, "Ubiquiti"),
+    (0x788A20, "Ubiquiti"),
+    (0x802AA8, "Ubiquiti"),
+    (0x84F3EB, "Espressif"),
+    (0xA0369F, "Intel"),
+    (0xA040A0, "Netgear"),
+    (0xA483E7, "Apple"),
+    (0xA4CF12, "Espressif"),
+    (0xAC1F6B, "Supermicro"),
+    (0xACBC32, "Apple"),
+    (0xB827EB, "Raspberry Pi"),
+    (0xB869F4, "MikroTik"),
+    (0xB8AC6F, "Dell"),
+    (0xD4CA6D, "MikroTik"),
+    (0xD83ADD, "Raspberry Pi"),
+    (0xDCA632, "Raspberry Pi"),
+    (0xE45F01, "Raspberry Pi"),
+    (0xE48D8C, "MikroTik"),
+    (0xEC086B, "TP-Link"),
+    (0xF01898, "Apple"),
+    (0xF09FC2, "Ubiquiti"),
+    (0xF4F26D, "TP-Link"),
+    (0xF4F5D8, "Google"),
+    (0xF88FCA, "Google"),
+    (0xF8BC12, "Dell"),
+    (0xFCECDA, "Ubiquiti"),
+];
+
+static VENDORS: OnceLock<HashMap<u32, String>> = OnceLock::new();
+
+/// Returns the `oui` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("oui")
+        .about("Looks up the vendor of MAC addresses, or lists the ARP/neighbor table with vendors")
+        .arg(Arg::with_name("mac").multiple(true)
+            .help("MAC addresses or OUI prefixes, e.g. b8:27:eb:12:34:56, B8-27-EB or b827.eb12.3456 (default: the neighbor table)"))
+        .arg(Arg::with_name("update").long("update")
+            .help("Download the full IEEE registry to ~/.netdiag/oui.csv for offline lookups"))
+}
+
+/// Runs the `oui` subcommand.
+pub fn run(matches: &ArgMatches) {
+    println!();
+    if matches.is_present("update") {
+        if let Err(e) = update() {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+        }
+        println!();
+        if !matches.is_present("mac") {
+            return;
+        }
+    }
+
+    match matches.values_of("mac") {
+        Some(macs) => {
+            println!("🔹 {}", colorize("MAC vendor lookup", "blue"));
+            for mac in macs {
+                match parse_prefix(mac) {
+                    Some(oui) => println!("   {:<20} {}", mac, describe_oui(oui)),
+                    None => println!("   {:<20} {}", mac, colorize("not a MAC address or OUI", "red")),
+                }
+            }
+        }
+        None => print_neighbors(),
+    }
+    if !registry_path().exists() {
+        println!("\nℹ️  {} Using the built-in list of common vendors; run `netdiag oui --update` for the full IEEE registry.", colorize("[INFO]", "blue"));
+    }
+    println!();
+}
+
+/// The system's neighbor (ARP) table with each MAC's vendor.
+fn print_neighbors() {
+    println!("🔹 {}", colorize("Neighbor table", "blue"));
+    let neighbors = netinfo::neighbors();
+    if neighbors.is_empty() {
+        println!("   (empty)");
+        return;
+    }
+    println!("   {:<40} {:<20} Vendor", "Address", "MAC");
+    for (addr, mac) in &neighbors {
+        let vendor = MacAddr::parse(mac).map(|m| label(&m)).unwrap_or_else(|| "-".to_string());
+        println!("   {:<40} {:<20} {}", addr, mac, vendor);
+    }
+}
+
+fn registry_path() -> PathBuf {
+    data_dir().join("oui.csv")
+}
+
+/// Downloads the IEEE registry and replaces the cached copy once it parses.
+fn update() -> Result<(), String> {
+    println!("🔹 {}", colorize("Downloading the IEEE OUI registry", "blue"));
+    let partial = data_dir().join("oui.csv.part");
+    fs::create_dir_all(data_dir()).map_err(|e| format!("Could not create {}: {}", data_dir().display(), e))?;
+    let output = http::curl(REGISTRY_URL)
+        .args(["-sSfL", "--max-time", "120", "-A", "netdiag", "-o"])
+        .arg(&partial)
+        .output()
+        .map_err(|e| format!("Could not run curl: {}", e))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&partial);
+        return Err(format!("Download failed: {}", String::from_utf8_lossy(&output.stderr).trim()));
+    }
+    let entries = fs::read_to_string(&partial).map(|text| parse_registry(&text).len()).unwrap_or(0);
+    if entries == 0 {
+        let _ = fs::remove_file(&partial);
+        return Err(format!("{} did not return the registry CSV", REGISTRY_URL));
+    }
+    fs::rename(&partial, registry_path()).map_err(|e| format!("Could not save {}: {}", registry_path().display(), e))?;
+    println!("✅ {} Saved {} vendor prefixes to {}", colorize("[SUCCESS]", "green"), entries, registry_path().display());
+    Ok(())
+}
+
+/// Reads `MA-L,<6 hex digits>,<organization>,...` rows; the organization may be quoted.
+fn parse_registry(text: &str) -> HashMap<u32, String> {
+    text.lines()
+        .filter_map(|line| {
+            let rest = line.strip_prefix("MA-L,")?;
+            let (assignment, organization) = rest.split_once(',')?;
+            let oui = u32::from_str_radix(assignment.trim(), 16).ok().filter(|_| assignment.trim().len() == 6)?;
+            let name = match organization.strip_prefix('"') {
+                Some(quoted) => quoted.split('"').next().unwrap_or(""),
+                None => organization.split(',').next().unwrap_or(""),
+            };
+            Some((oui, name.trim().to_string())).filter(|(_, n)| !n.is_empty())
+        })
+        .collect()
+}
+
+/// The built-in list, overridden by the downloaded registry when there is one.
+fn vendors() -> &'static HashMap<u32, String> {
+    VENDORS.get_or_init(|| {
+        let mut vendors: HashMap<u32, String> = BUILTIN.iter().map(|&(oui, name)| (oui, name.to_string())).collect();
+        if let Ok(text) = fs::read_to_string(registry_path()) {
+            vendors.extend(parse_registry(&text));
+        }
+        vendors
+    })
+}
+
+/// Reads the OUI from a full MAC or a bare prefix in any common notation.
+fn parse_prefix(text: &str) -> Option<u32> {
+    let hex: String = text.chars().filter(|c| !matches!(c, ':' | '-' | '.')).collect();
+    if hex.len() < 6 || hex.len() > 12 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return None;
+    }
+    u32::from_str_radix(&hex[..6], 16).ok()
+}
+
+fn oui(mac: &MacAddr) -> u32 {
+    u32::from_be_bytes([0, mac.0[0], mac.0[1], mac.0[2]])
+}
+
+/// The vendor of `mac`, or what kind of address it is when no vendor owns it.
+pub fn label(mac: &MacAddr) -> String {
+    describe_oui(oui(mac))
+}
+
+/// Describes a prefix. Locally administered addresses are usually the randomized MACs phones
+/// and laptops use for privacy, so they carry no vendor.
+fn describe_oui(oui: u32) -> String {
+    let first = (oui >> 16) as u8;
+    match vendors().get(&oui) {
+        Some(vendor) => vendor.clone(),
+        None if oui == 0xFFFFFF => "broadcast".to_string(),
+        None if first & 0x01 != 0 => "multicast".to_string(),
+        None if first & 0x02 != 0 => "private (randomized) address".to_string(),
+        None => "unknown vendor".to_string(),
+    }
+}
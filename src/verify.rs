@@ -0,0 +1,206 @@
+use std::net::IpAddr;
+use std::path::PathBuf;
+use std::process;
+
+use clap::{App, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::config::{self, PolicyConfig, PublicIpPolicy, ResolvePolicy, RoutePolicy};
+use crate::dns_config;
+use crate::netinfo;
+use crate::pathgraph;
+use crate::resolver;
+use crate::route_lookup;
+use crate::routes::RouteEntry;
+use crate::telemetry;
+
+/// Internet address whose route stands for "the default route", so split routes such as the
+/// 0.0.0.0/1 pair VPN clients install count as the default.
+const INTERNET_PROBE: &str = "1.1.1.1";
+
+/// The outcome of one assertion: what was found, and whether it complies.
+struct Assertion {
+    name: String,
+    passed: bool,
+    detail: String,
+}
+
+impl Assertion {
+    fn new(name: String, result: Result<String, String>) -> Assertion {
+        match result {
+            Ok(detail) => Assertion { name, passed: true, detail },
+            Err(detail) => Assertion { name, passed: false, detail },
+        }
+    }
+}
+
+/// Returns the `verify` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("verify")
+        .about("Checks the live network configuration against the policy assertions in the config; exits 1 if any fail, 2 if there is no valid policy")
+}
+
+/// Runs the `verify` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let path = matches.value_of("config").map(PathBuf::from).unwrap_or_else(config::default_path);
+    println!();
+    println!("🔹 {}", colorize("Verifying network policy", "blue"));
+    // A missing or broken policy must not pass a compliance gate.
+    let policy = match config::load_strict(&path) {
+        Ok(config) => config.policy,
+        Err(e) => exit(2, &format!("Cannot verify: {}", e)),
+    };
+    let assertions = verify(&policy);
+    if assertions.is_empty() {
+        exit(2, &format!("Cannot verify: {} has no policy assertions; add a `policy:` section", path.display()));
+    }
+
+    for a in &assertions {
+        if a.passed {
+            println!("   ✅ {}: {}", a.name, a.detail);
+        } else {
+            println!("   ❌ {}: {}", a.name, colorize(&a.detail, "red"));
+        }
+    }
+    let failed = assertions.iter().filter(|a| !a.passed).count();
+    if failed == 0 {
+        println!("✅ {} All {} policy assertions hold.\n", colorize("[SUCCESS]", "green"), assertions.len());
+    } else {
+        exit(1, &format!("{} of {} policy assertions failed.", failed, assertions.len()));
+    }
+}
+
+/// Reports `message` as an error and exits with `code`, exporting the trace first since
+/// exiting skips the export at the end of main.
+fn exit(code: i32, message: &str) -> ! {
+    println!("❌ {} {}\n", colorize("[ERROR]", "red"), message);
+    telemetry::fail(message);
+    telemetry::export();
+    process::exit(code);
+}
+
+/// Checks every assertion in `policy`, in config order.
+fn verify(policy: &PolicyConfig) -> Vec<Assertion> {
+    let mut assertions = Vec::new();
+    if !policy.dns_servers.is_empty() {
+        assertions.push(Assertion::new("DNS servers".to_string(), check_dns_servers(&policy.dns_servers)));
+    }
+    for route in &policy.routes {
+        let name = if route.destination == "default" { "Default route".to_string() } else { format!("Route to {}", route.destination) };
+        assertions.push(Assertion::new(name, check_route(route)));
+    }
+    if let Some(public_ip) = &policy.public_ip {
+        assertions.push(Assertion::new("Public IP".to_string(), check_public_ip(public_ip)));
+    }
+    for resolve in &policy.resolves {
+        assertions.push(Assertion::new(format!("DNS answer for {}", resolve.name), check_resolve(resolve)));
+    }
+    assertions
+}
+
+/// Every resolver the system may query must be on the allowed list.
+fn check_dns_servers(allowed: &[String]) -> Result<String, String> {
+    let servers = dns_config::nameservers();
+    if servers.is_empty() {
+        return Err(format!("no DNS servers are configured (expected {})", allowed.join(", ")));
+    }
+    let unexpected: Vec<&String> = servers.iter().filter(|s| !allowed.iter().any(|a| same_address(a, s))).collect();
+    if unexpected.is_empty() {
+        Ok(format!("uses {}", servers.join(", ")))
+    } else {
+        Err(format!(
+            "uses {}; {} not allowed (expected only {})",
+            servers.join(", "),
+            unexpected.iter().map(|s| s.as_str()).collect::<Vec<_>>().join(", "),
+            allowed.join(", ")
+        ))
+    }
+}
+
+/// The path the OS selects for the destination must use the expected interface and gateway.
+fn check_route(policy: &RoutePolicy) -> Result<String, String> {
+    let destination = if policy.destination == "default" { INTERNET_PROBE } else { policy.destination.as_str() };
+    let addr = resolver::resolve_one(destination).map_err(|e| e.to_string())?;
+    let (interface, gateway) = route_lookup::selected_path(addr);
+    let found = format!(
+        "dev {}{}",
+        interface.as_deref().unwrap_or("unknown"),
+        gateway.map(|g| format!(" via {}", g)).unwrap_or_default()
+    );
+
+    let mut problems = Vec::new();
+    if let Some(expected) = &policy.interface {
+        if interface.as_deref() != Some(expected.as_str()) {
+            problems.push(format!("expected dev {}", expected));
+        }
+    }
+    if let Some(expected) = &policy.gateway {
+        if !gateway.is_some_and(|g| same_address(expected, &g.to_string())) {
+            problems.push(format!("expected via {}", expected));
+        }
+    }
+    if problems.is_empty() {
+        Ok(found)
+    } else {
+        Err(format!("{} leaves {}; {}", addr, found, problems.join(", ")))
+    }
+}
+
+/// The public address must fall within one of the prefixes and belong to the expected AS.
+fn check_public_ip(policy: &PublicIpPolicy) -> Result<String, String> {
+    let ip: IpAddr = netinfo::public_ip()
+        .and_then(|ip| ip.parse().ok())
+        .ok_or_else(|| "could not determine the public IP address".to_string())?;
+
+    if !policy.prefixes.is_empty() {
+        let mut inside = false;
+        for prefix in &policy.prefixes {
+            inside |= in_prefix(ip, prefix)?;
+        }
+        if !inside {
+            return Err(format!("{} is outside {}", ip, policy.prefixes.join(", ")));
+        }
+    }
+    let mut detail = ip.to_string();
+    if let Some(expected) = policy.asn {
+        let server = netinfo::dns_servers().iter().find_map(|s| s.parse::<IpAddr>().ok());
+        let asn = server
+            .and_then(|server| pathgraph::lookup_asn(server, ip))
+            .ok_or_else(|| format!("could not look up the AS of {}", ip))?;
+        let name = asn.name.map(|n| format!(" ({})", n)).unwrap_or_default();
+        if asn.number != expected {
+            return Err(format!("{} is in AS{}{}, expected AS{}", ip, asn.number, name, expected));
+        }
+        detail = format!("{} in AS{}{}", ip, asn.number, name);
+    }
+    Ok(detail)
+}
+
+/// The name must resolve, and only to the expected addresses.
+fn check_resolve(policy: &ResolvePolicy) -> Result<String, String> {
+    let addrs: Vec<String> = resolver::resolve(&policy.name).map_err(|e| e.to_string())?.iter().map(|a| a.to_string()).collect();
+    let unexpected: Vec<&String> = addrs.iter().filter(|a| !policy.addresses.iter().any(|e| same_address(e, a))).collect();
+    if unexpected.is_empty() {
+        Ok(format!("resolves to {}", addrs.join(", ")))
+    } else {
+        Err(format!("resolves to {}, expected {}", addrs.join(", "), policy.addresses.join(", ")))
+    }
+}
+
+/// Compares addresses by value, so `::1` and `0:0::1` match; anything else compares as text.
+fn same_address(a: &str, b: &str) -> bool {
+    match (a.parse::<IpAddr>(), b.parse::<IpAddr>()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a.eq_ignore_ascii_case(b),
+    }
+}
+
+/// Whether `addr` falls within `cidr`, e.g. `203.0.113.0/24`.
+fn in_prefix(addr: IpAddr, cidr: &str) -> Result<bool, String> {
+    let invalid = || format!("invalid prefix {} in policy.public_ip.prefixes", cidr);
+    let (network, length) = cidr.split_once('/').ok_or_else(invalid)?;
+    let destination: IpAddr = network.parse().map_err(|_| invalid())?;
+    let prefix: u8 = length.parse().map_err(|_| invalid())?;
+    let route = RouteEntry { destination, prefix, gateway: None, interface: String::new(), metric: None };
+    Ok(route.contains(addr))
+}
@@ -0,0 +1,365 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::chart;
+use crate::colorize;
+use crate::interrupt;
+use crate::random_u64;
+use crate::repeat;
+use crate::scheduler;
+use crate::tcping;
+
+/// Marks a packet as part of a netdiag VoIP test.
+const MAGIC: &[u8; 4] = b"NDVP";
+
+/// RTP header, marker and the timestamps and counters the server fills in.
+const MEASUREMENT_LEN: usize = 44;
+
+/// Audio carried in each packet.
+const FRAME: Duration = Duration::from_millis(20);
+
+/// How long to wait for replies after the last packet is sent.
+const DRAIN: Duration = Duration::from_secs(1);
+
+/// A voice codec's packet size and its E-model impairment factors (ITU-T G.113).
+#[derive(Debug, Clone, Copy)]
+struct Codec {
+    name: &'static str,
+    payload_type: u8,
+    /// Payload bytes per 20 ms frame.
+    payload: usize,
+    /// Equipment impairment with no loss.
+    ie: f64,
+    /// Packet-loss robustness with concealment.
+    bpl: f64,
+    /// Samples per frame at the RTP clock rate.
+    samples: u32,
+}
+
+const CODECS: &[Codec] = &[
+    Codec { name: "g711", payload_type: 0, payload: 160, ie: 0.0, bpl: 25.1, samples: 160 },
+    Codec { name: "g729", payload_type: 18, payload: 20, ie: 11.0, bpl: 19.0, samples: 160 },
+];
+
+/// Fields of a test packet after the RTP header.
+#[derive(Debug, Clone, Copy, Default)]
+struct Probe {
+    index: u32,
+    ssrc: u32,
+    reflected: bool,
+    /// Client clock when sent, in microseconds since the test started.
+    sent_us: u64,
+    /// Server clock when received, in microseconds since the server started.
+    server_us: u64,
+    /// Packets of this stream the server had received, and how many of them out of order.
+    server_received: u32,
+    server_reordered: u32,
+}
+
+/// Loss, jitter and reordering of one direction.
+#[derive(Debug, Default)]
+struct Direction {
+    expected: u32,
+    received: u32,
+    reordered: u32,
+    jitter_ms: f64,
+}
+
+impl Direction {
+    fn loss(&self) -> f64 {
+        if self.expected == 0 { 0.0 } else { 1.0 - (self.received.min(self.expected) as f64 / self.expected as f64) }
+    }
+}
+
+/// RFC 3550 interarrival jitter over a sequence of transit times.
+#[derive(Debug, Default)]
+struct Jitter {
+    last_transit: Option<f64>,
+    value: f64,
+}
+
+impl Jitter {
+    fn add(&mut self, transit_ms: f64) {
+        if let Some(last) = self.last_transit {
+            self.value += ((transit_ms - last).abs() - self.value) / 16.0;
+        }
+        self.last_transit = Some(transit_ms);
+    }
+}
+
+/// Returns the `voip` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("voip")
+        .about("Streams RTP-like UDP to a reflector and estimates call quality (jitter, loss, reordering, MOS)")
+        .arg(Arg::with_name("reflector").required_unless("server")
+            .help("host or host:port of a UDP echo service or another netdiag running `voip --server`"))
+        .arg(Arg::with_name("server").long("server")
+            .help("Reflect test streams back to their sender, stamping one-way measurements"))
+        .arg(Arg::with_name("port").long("port").short("p").takes_value(true).default_value("5004")
+            .help("UDP port to send to, or to listen on with --server"))
+        .arg(Arg::with_name("duration").long("duration").short("d").takes_value(true).default_value("10")
+            .help("Seconds of audio to simulate"))
+        .arg(Arg::with_name("codec").long("codec").takes_value(true).default_value("g711")
+            .possible_values(&["g711", "g729"])
+            .help("Codec whose packet size and loss sensitivity are simulated"))
+}
+
+/// Runs the `voip` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let port = value_t!(matches, "port", u16).unwrap_or(5004);
+    if matches.is_present("server") {
+        serve(port);
+        return;
+    }
+    let reflector = matches.value_of("reflector").unwrap_or_default();
+    let target = match reflector.parse::<IpAddr>() {
+        Ok(IpAddr::V6(v6)) => format!("[{}]:{}", v6, port),
+        Ok(ip) => format!("{}:{}", ip, port),
+        // Already host:port or [v6]:port.
+        Err(_) if reflector.contains(':') => reflector.to_string(),
+        Err(_) => format!("{}:{}", reflector, port),
+    };
+    let addr = match tcping::resolve(&target) {
+        Ok(addr) => addr,
+        Err(e) => {
+            println!("❌ {} {}", colorize("[ERROR]", "red"), e);
+            return;
+        }
+    };
+    let codec = CODECS.iter().find(|c| Some(c.name) == matches.value_of("codec")).copied().unwrap_or(CODECS[0]);
+    let duration = Duration::from_secs(value_t!(matches, "duration", u64).unwrap_or(10).max(1));
+    voip_test(addr, codec, duration);
+}
+
+/// Sends one frame every 20 ms for `duration`, collects the reflected packets and prints the
+/// per-direction results and the estimated MOS.
+fn voip_test(addr: SocketAddr, codec: Codec, duration: Duration) {
+    let total = (duration.as_millis() / FRAME.as_millis()) as u32;
+    println!(
+        "\n📞 {} Streaming {} s of {} to {} ({} packets, {} bytes each)\n",
+        colorize("[INFO]", "blue"),
+        duration.as_secs(),
+        codec.name.to_uppercase(),
+        colorize(&addr.to_string(), "cyan"),
+        total,
+        packet_len(codec)
+    );
+    let bind = if addr.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+    let socket = match UdpSocket::bind(bind).and_then(|s| s.connect(addr).map(|_| s)) {
+        Ok(socket) => socket,
+        Err(e) => {
+            println!("❌ {} Could not open a UDP socket to {}: {}", colorize("[ERROR]", "red"), addr, e);
+            return;
+        }
+    };
+
+    // The stream is one scheduled probe; its packets are paced by the codec, not the rate limit.
+    let _permit = scheduler::acquire(&format!("voip-stream({})", total), addr.ip(), Some(addr.port()));
+    let ssrc = random_u64() as u32;
+    let start = Instant::now();
+    let mut replies: Vec<(Probe, f64)> = Vec::new();
+    let mut seen = vec![false; total as usize];
+    let mut buf = [0u8; 2048];
+    let mut sent = 0u32;
+    let end = start + FRAME * total + DRAIN;
+    while Instant::now() < end && !interrupt::interrupted() {
+        let next_send = start + FRAME * sent;
+        if sent < total && Instant::now() >= next_send {
+            let probe = Probe { index: sent, ssrc, sent_us: start.elapsed().as_micros() as u64, ..Probe::default() };
+            // A send error (e.g. ICMP port unreachable from the last packet) is not fatal.
+            let _ = socket.send(&encode(&probe, codec));
+            sent += 1;
+            continue;
+        }
+        let wait = if sent < total { (start + FRAME * sent).saturating_duration_since(Instant::now()) } else { end.saturating_duration_since(Instant::now()) };
+        let _ = socket.set_read_timeout(Some(wait.max(Duration::from_millis(1))));
+        // Errors are timeouts, or port-unreachable errors left over from earlier sends.
+        if let Ok(n) = socket.recv(&mut buf) {
+            let received_ms = start.elapsed().as_secs_f64() * 1000.0;
+            if let Some(probe) = decode(&buf[..n]) {
+                if probe.ssrc == ssrc && probe.index < total && !seen[probe.index as usize] {
+                    seen[probe.index as usize] = true;
+                    replies.push((probe, received_ms));
+                }
+            }
+        }
+    }
+    report(addr, codec, sent, &replies);
+}
+
+/// Computes and prints loss, jitter, reordering, RTT and MOS from the reflected packets.
+fn report(addr: SocketAddr, codec: Codec, sent: u32, replies: &[(Probe, f64)]) {
+    if replies.is_empty() {
+        println!("❌ {} No packets came back from {}.", colorize("[ERROR]", "red"), addr);
+        println!("   Is `netdiag voip --server` (or a UDP echo service) running there, and is the UDP port open?");
+        return;
+    }
+    let rtts: Vec<f64> = replies.iter().map(|(p, received)| received - p.sent_us as f64 / 1000.0).collect();
+    let reflected = replies.iter().all(|(p, _)| p.reflected);
+
+    // Packets are in client arrival order; reordering shows as an index below the highest seen.
+    let mut directions: Vec<(&str, Direction)> = Vec::new();
+    if reflected {
+        let server_received = replies.iter().map(|(p, _)| p.server_received).max().unwrap_or(0);
+        let server_reordered = replies.iter().map(|(p, _)| p.server_reordered).max().unwrap_or(0);
+        let mut by_server: Vec<&(Probe, f64)> = replies.iter().collect();
+        by_server.sort_by_key(|(p, _)| p.server_received);
+        let mut up = Jitter::default();
+        for (p, _) in &by_server {
+            up.add((p.server_us as f64 - p.sent_us as f64) / 1000.0);
+        }
+        let mut down = Jitter::default();
+        for (p, received) in replies {
+            down.add(received - p.server_us as f64 / 1000.0);
+        }
+        directions.push(("Upstream", Direction { expected: sent, received: server_received, reordered: server_reordered, jitter_ms: up.value }));
+        directions.push(("Downstream", Direction { expected: server_received, received: replies.len() as u32, reordered: reordered(replies), jitter_ms: down.value }));
+    } else {
+        let mut round_trip = Jitter::default();
+        for rtt in &rtts {
+            round_trip.add(*rtt);
+        }
+        directions.push(("Round trip", Direction { expected: sent, received: replies.len() as u32, reordered: reordered(replies), jitter_ms: round_trip.value }));
+    }
+
+    println!("   {:<11} {:>7} {:>10} {:>10}", "Direction", "Loss", "Jitter", "Reordered");
+    for (name, d) in &directions {
+        repeat::record_target("VoIP jitter (ms)", &name.to_lowercase(), d.jitter_ms);
+        println!("   {:<11} {:>6.1}% {:>7.1} ms {:>10}", name, d.loss() * 100.0, d.jitter_ms, d.reordered);
+    }
+    let (_, mean_rtt, _, _) = chart::summarize(&rtts);
+    println!("   RTT: mean {:.1} ms, p95 {:.1} ms", mean_rtt, chart::percentile(&rtts, 95.0));
+    if !reflected {
+        println!("   The reflector is a plain echo, so loss and jitter are for both directions combined.");
+        println!("   Run `netdiag voip --server` on the far end for separate upstream and downstream figures.");
+    }
+
+    // One-way delay is taken as half the RTT; each direction is scored and the worse one counts.
+    let scores: Vec<(&str, f64)> = directions.iter().map(|(name, d)| (*name, mos(codec, mean_rtt / 2.0, d.jitter_ms, d.loss()))).collect();
+    let (worst, mos) = scores.iter().cloned().fold(("", f64::MAX), |worst, (name, score)| if score < worst.1 { (name, score) } else { worst });
+    let best = scores.iter().map(|(_, score)| *score).fold(0.0, f64::max);
+    let (label, color, icon) = match mos {
+        m if m >= 4.3 => ("excellent", "green", "✅"),
+        m if m >= 4.0 => ("good", "green", "✅"),
+        m if m >= 3.6 => ("fair: noticeable but acceptable", "yellow", "⚠️ "),
+        m if m >= 3.1 => ("poor: many users dissatisfied", "red", "❌"),
+        _ => ("bad: calls are hard to hold", "red", "❌"),
+    };
+    repeat::record("VoIP MOS", mos);
+    println!("\n{} {} Estimated MOS {:.2} ({}){}", icon, colorize("[MOS]", color), mos, label, if best - mos >= 0.1 { format!(", limited by {}", worst.to_lowercase()) } else { String::new() });
+}
+
+/// Replies that arrived after one with a higher index.
+fn reordered(replies: &[(Probe, f64)]) -> u32 {
+    let mut highest = None;
+    let mut count = 0;
+    for (p, _) in replies {
+        match highest {
+            Some(h) if p.index < h => count += 1,
+            _ => highest = Some(p.index),
+        }
+    }
+    count
+}
+
+/// Estimates MOS with the simplified ITU-T G.107 E-model. The jitter buffer is assumed to
+/// add twice the jitter to the delay; codec lookahead adds 10 ms.
+fn mos(codec: Codec, one_way_ms: f64, jitter_ms: f64, loss: f64) -> f64 {
+    let delay = one_way_ms + 2.0 * jitter_ms + 10.0;
+    let id = if delay < 160.0 { delay / 40.0 } else { (delay - 120.0) / 10.0 };
+    let loss_pct = loss * 100.0;
+    let ie_eff = codec.ie + (95.0 - codec.ie) * loss_pct / (loss_pct + codec.bpl);
+    let r = (93.2 - id - ie_eff).clamp(0.0, 100.0);
+    (1.0 + 0.035 * r + 7e-6 * r * (r - 60.0) * (100.0 - r)).clamp(1.0, 4.5)
+}
+
+/// Reflects test packets to their sender, stamping arrival time and per-stream counters.
+/// Anything else is echoed unchanged.
+fn serve(port: u16) {
+    let socket = match UdpSocket::bind(("::", port)).or_else(|_| UdpSocket::bind(("0.0.0.0", port))) {
+        Ok(socket) => socket,
+        Err(e) => {
+            println!("❌ {} Could not listen on UDP port {}: {}", colorize("[ERROR]", "red"), port, e);
+            return;
+        }
+    };
+    println!("\n📞 {} Reflecting VoIP test streams on UDP port {} (Ctrl-C to stop)\n", colorize("[INFO]", "blue"), port);
+    let _ = socket.set_read_timeout(Some(Duration::from_millis(500)));
+    let start = Instant::now();
+    // Received and reordered counts, and the highest index, per sender stream.
+    let mut streams: HashMap<(SocketAddr, u32), (u32, u32, u32)> = HashMap::new();
+    let mut buf = [0u8; 2048];
+    while !interrupt::interrupted() {
+        let (n, from) = match socket.recv_from(&mut buf) {
+            Ok(received) => received,
+            Err(_) => continue,
+        };
+        let packet = &mut buf[..n];
+        if let Some(mut probe) = decode(packet) {
+            if !streams.contains_key(&(from, probe.ssrc)) {
+                println!("   {} New stream from {}", colorize("[VOIP]", "cyan"), from);
+            }
+            let (received, reordered, highest) = streams.entry((from, probe.ssrc)).or_insert((0, 0, 0));
+            *received += 1;
+            if *received > 1 && probe.index < *highest {
+                *reordered += 1;
+            } else {
+                *highest = probe.index;
+            }
+            probe.reflected = true;
+            probe.server_us = start.elapsed().as_micros() as u64;
+            probe.server_received = *received;
+            probe.server_reordered = *reordered;
+            write_fields(packet, &probe);
+        }
+        let _ = socket.send_to(packet, from);
+    }
+    println!("\n📊 {} Reflected {} stream(s)", colorize("[SUMMARY]", "blue"), streams.len());
+}
+
+fn packet_len(codec: Codec) -> usize {
+    (12 + codec.payload).max(MEASUREMENT_LEN)
+}
+
+/// Builds an RTP packet (version 2, the codec's payload type) carrying the probe fields.
+fn encode(probe: &Probe, codec: Codec) -> Vec<u8> {
+    let mut packet = vec![0u8; packet_len(codec)];
+    packet[0] = 0x80;
+    packet[1] = codec.payload_type;
+    packet[2..4].copy_from_slice(&(probe.index as u16).to_be_bytes());
+    packet[4..8].copy_from_slice(&probe.index.wrapping_mul(codec.samples).to_be_bytes());
+    packet[8..12].copy_from_slice(&probe.ssrc.to_be_bytes());
+    packet[12..16].copy_from_slice(MAGIC);
+    packet[17..21].copy_from_slice(&probe.index.to_be_bytes());
+    write_fields(&mut packet, probe);
+    packet
+}
+
+/// Writes the fields that change between sending and reflecting.
+fn write_fields(packet: &mut [u8], probe: &Probe) {
+    packet[16] = probe.reflected as u8;
+    packet[21..29].copy_from_slice(&probe.sent_us.to_be_bytes());
+    packet[29..37].copy_from_slice(&probe.server_us.to_be_bytes());
+    packet[37..41].copy_from_slice(&probe.server_received.to_be_bytes());
+    packet[41..44].copy_from_slice(&probe.server_reordered.min(0xff_ffff).to_be_bytes()[1..]);
+}
+
+fn decode(packet: &[u8]) -> Option<Probe> {
+    if packet.len() < MEASUREMENT_LEN || packet[0] >> 6 != 2 || &packet[12..16] != MAGIC {
+        return None;
+    }
+    let u32_at = |i: usize| u32::from_be_bytes([packet[i], packet[i + 1], packet[i + 2], packet[i + 3]]);
+    let u64_at = |i: usize| u64::from(u32_at(i)) << 32 | u64::from(u32_at(i + 4));
+    Some(Probe {
+        index: u32_at(17),
+        ssrc: u32_at(8),
+        reflected: packet[16] != 0,
+        sent_us: u64_at(21),
+        server_us: u64_at(29),
+        server_received: u32_at(37),
+        server_reordered: u32::from_be_bytes([0, packet[41], packet[42], packet[43]]),
+    })
+}
@@ -0,0 +1,299 @@
+use std::collections::BTreeMap;
+use std::io::{BufRead, BufReader};
+use std::net::{IpAddr, ToSocketAddrs};
+use std::process::{Command, Stdio};
+use std::thread;
+use std::time::Duration;
+
+use clap::{App, Arg, ArgMatches, SubCommand};
+
+use crate::colorize;
+use crate::dns;
+use crate::http;
+use crate::interrupt;
+use crate::netinfo::{self, Interface};
+use crate::routes;
+use crate::telemetry;
+
+/// Interface name prefixes used by common VPN clients.
+const VPN_PREFIXES: &[&str] = &["tun", "tap", "wg", "utun", "ppp", "ipsec", "tailscale", "zt", "nordlynx", "proton"];
+
+/// Domains looked up while capturing, to generate DNS traffic that could leak.
+const LEAK_TEST_DOMAINS: &[&str] = &["example.com", "wikipedia.org", "github.com", "cloudflare.com"];
+
+/// Returns the `vpn-check` subcommand definition.
+pub fn subcommand<'a, 'b>() -> App<'a, 'b> {
+    SubCommand::with_name("vpn-check")
+        .about("Checks that traffic and DNS go through the VPN and nothing leaks out the physical interface")
+        .arg(Arg::with_name("seconds").long("seconds").takes_value(true).default_value("8")
+            .help("How long to watch the physical interface for leaks"))
+}
+
+/// Runs the `vpn-check` subcommand.
+pub fn run(matches: &ArgMatches) {
+    let seconds = value_t!(matches, "seconds", u64).unwrap_or(8);
+    vpn_check(seconds);
+}
+
+/// True for interface names that belong to a VPN client.
+pub fn is_vpn_interface(name: &str) -> bool {
+    VPN_PREFIXES.iter().any(|p| name.starts_with(p))
+}
+
+/// True for loopback, private and link-local addresses that never leave the LAN. Anything that
+/// is not an address counts as public, so it gets reported rather than ignored.
+pub fn is_local(addr: &str) -> bool {
+    // Drop an IPv6 zone (`fe80::1%eth0`), which only link-local addresses carry.
+    match addr.split('%').next().unwrap_or(addr).parse::<IpAddr>() {
+        Ok(IpAddr::V4(v4)) => v4.is_private() || v4.is_loopback() || v4.is_link_local() || v4.is_multicast(),
+        Ok(IpAddr::V6(v6)) => {
+            v6.is_loopback() || v6.is_multicast() || (v6.segments()[0] & 0xffc0) == 0xfe80 || (v6.segments()[0] & 0xfe00) == 0xfc00
+        }
+        Err(_) => false,
+    }
+}
+
+/// VPN interfaces that carry a routable address (macOS keeps idle `utun`s with only link-local ones).
+fn active_vpn_interfaces(interfaces: &[Interface]) -> Vec<&Interface> {
+    interfaces
+        .iter()
+        .filter(|i| is_vpn_interface(&i.name))
+        .filter(|i| i.addrs.iter().any(|a| !a.starts_with("fe80")))
+        .collect()
+}
+
+/// Finds the non-VPN interface that holds a default route.
+fn physical_interface(interfaces: &[Interface]) -> Option<String> {
+    routes::table()
+        .into_iter()
+        .filter(|r| r.is_default() && !is_vpn_interface(&r.interface))
+        .map(|r| r.interface)
+        .find(|name| interfaces.iter().any(|i| &i.name == name))
+}
+
+/// Verifies egress IP, DNS path and physical-interface traffic while a VPN is up.
+pub fn vpn_check(seconds: u64) {
+    println!("\n🛡️  {} Checking for VPN leaks\n", colorize("[INFO]", "blue"));
+
+    let interfaces = netinfo::interfaces();
+    let vpns = active_vpn_interfaces(&interfaces);
+    if vpns.is_empty() {
+        println!("ℹ️  {} No VPN interface is up; nothing to check.\n", colorize("[INFO]", "blue"));
+        return;
+    }
+    let vpn_names: Vec<&str> = vpns.iter().map(|i| i.name.as_str()).collect();
+    println!("   VPN interface(s): {}", colorize(&vpn_names.join(", "), "cyan"));
+
+    let physical = physical_interface(&interfaces);
+    println!("   Physical interface: {}\n", colorize(physical.as_deref().unwrap_or("unknown"), "cyan"));
+    let mut leaks = 0;
+
+    // 1. Internet-bound traffic must route via the tunnel.
+    println!("🔹 {}", colorize("Checking the route to the internet", "blue"));
+    match netinfo::route_interface("1.1.1.1") {
+        Some(ref iface) if is_vpn_interface(iface) => println!("   ✅ Internet traffic routes via {}", iface),
+        Some(iface) => {
+            leaks += 1;
+            println!("   ❌ {} Internet traffic routes via {}, not the VPN", colorize("[LEAK]", "red"), iface);
+        }
+        None => println!("   ⚠️  {} Could not determine the route to 1.1.1.1", colorize("[WARN]", "yellow")),
+    }
+
+    // 2. The public IP seen through the default path must differ from the ISP's.
+    println!("🔹 {}", colorize("Comparing public IP addresses", "blue"));
+    let vpn_ip = netinfo::public_ip();
+    let direct = physical.as_ref().map(|p| public_ip_via(p));
+    let isp_ip = direct.as_ref().and_then(|d| d.as_ref().ok().cloned());
+    let shown = match &direct {
+        Some(Ok(ip)) => ip.clone(),
+        Some(Err(Direct::Blocked)) => "blocked".to_string(),
+        Some(Err(Direct::Failed(e))) => format!("unknown ({})", e),
+        None => "unknown (no physical interface)".to_string(),
+    };
+    println!("   Public IP (default path): {}", vpn_ip.as_deref().unwrap_or("unknown"));
+    println!("   Public IP (physical):     {}", shown);
+    match (&vpn_ip, &direct) {
+        (Some(a), Some(Ok(b))) if a == b => {
+            leaks += 1;
+            println!("   ❌ {} Traffic egresses with the ISP address {}", colorize("[LEAK]", "red"), a);
+        }
+        (Some(_), Some(Ok(_))) => println!("   ✅ Public IP belongs to the VPN"),
+        (Some(_), Some(Err(Direct::Blocked))) => {
+            println!("   ✅ Physical interface cannot reach the internet directly (kill switch active)")
+        }
+        (Some(_), _) => println!("   ⚠️  {} Could not check whether a kill switch is active", colorize("[WARN]", "yellow")),
+        (None, _) => println!("   ⚠️  {} Could not determine the public IP", colorize("[WARN]", "yellow")),
+    }
+
+    // 3. Resolvers must be reached through the tunnel, not the ISP link.
+    println!("🔹 {}", colorize("Checking DNS resolvers", "blue"));
+    for server in netinfo::dns_servers() {
+        let iface = netinfo::route_interface(&server);
+        match iface {
+            Some(ref i) if is_vpn_interface(i) => println!("   ✅ {} is reached via {}", server, i),
+            _ if is_local(&server) => println!("   ℹ️  {} is a local resolver; check what it forwards to", server),
+            Some(i) => {
+                leaks += 1;
+                println!("   ❌ {} {} is reached via {} (outside the VPN)", colorize("[LEAK]", "red"), server, i);
+            }
+            None => println!("   ⚠️  {} Could not route to {}", colorize("[WARN]", "yellow"), server),
+        }
+    }
+    if let Some(egress) = resolver_egress() {
+        println!("   Recursive resolver egress address: {}", colorize(&egress, "cyan"));
+        if Some(&egress) == isp_ip.as_ref() {
+            leaks += 1;
+            println!("   ❌ {} DNS recursion leaves via your ISP address", colorize("[LEAK]", "red"));
+        }
+    }
+
+    // 4. Watch the physical interface for anything besides the tunnel itself.
+    if let Some(ref phys) = physical {
+        println!("🔹 {}", colorize(&format!("Watching {} for {} seconds", phys, seconds), "blue"));
+        leaks += watch_physical(phys, seconds);
+    }
+
+    if leaks == 0 {
+        println!("\n✅ {} No VPN leaks detected.\n", colorize("[SUCCESS]", "green"));
+    } else {
+        println!("\n❌ {} {} leak(s) detected.\n", colorize("[ERROR]", "red"), leaks);
+        telemetry::fail(&format!("{} VPN leak(s)", leaks));
+    }
+}
+
+/// Why the public IP could not be fetched through the physical interface.
+enum Direct {
+    /// The connection timed out or the network was unreachable, as with a kill switch.
+    Blocked,
+    /// Anything else: DNS, TLS, a refused connection or curl itself.
+    Failed(String),
+}
+
+/// Fetches the public IP while forcing curl out of `iface`.
+fn public_ip_via(iface: &str) -> Result<String, Direct> {
+    let output = http::curl("https://ifconfig.me")
+        .args(["-sS", "--max-time", "5", "--interface", iface])
+        .output()
+        .map_err(|e| Direct::Failed(e.to_string()))?;
+    let ip = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    if output.status.success() && !ip.is_empty() {
+        return Ok(ip);
+    }
+    let error = String::from_utf8_lossy(&output.stderr).trim().trim_start_matches("curl: ").to_string();
+    let unreachable = ["Network is unreachable", "No route to host"].iter().any(|m| error.contains(m));
+    // curl exits 28 on a timeout and 7 on a failed connect, which also covers a refused one.
+    match output.status.code() {
+        Some(28) => Err(Direct::Blocked),
+        Some(7) if unreachable => Err(Direct::Blocked),
+        _ if error.is_empty() => Err(Direct::Failed("no address returned".to_string())),
+        _ => Err(Direct::Failed(error)),
+    }
+}
+
+/// Asks Akamai which address the recursive resolver used; that is where DNS really exits.
+fn resolver_egress() -> Option<String> {
+    let server: IpAddr = netinfo::dns_servers().first()?.parse().ok()?;
+    let response = dns::query(server, "whoami.akamai.net", dns::TYPE_A, Duration::from_secs(3)).ok()?;
+    response.values(dns::TYPE_A).into_iter().next()
+}
+
+/// Captures on the physical interface while generating traffic and reports public peers
+/// other than the VPN endpoint. Returns the number of leaks found.
+fn watch_physical(iface: &str, seconds: u64) -> usize {
+    let mut child = match Command::new("tcpdump")
+        .args(["-i", iface, "-nn", "-q", "-l", "ip or ip6"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::null())
+        .spawn()
+    {
+        Ok(child) => {
+            interrupt::track(&child);
+            child
+        }
+        Err(e) => {
+            println!("   ⚠️  {} Could not start tcpdump ({}); skipping capture", colorize("[WARN]", "yellow"), e);
+            return 0;
+        }
+    };
+    let stdout = child.stdout.take().expect("tcpdump stdout is piped");
+
+    let null = if cfg!(windows) { "NUL" } else { "/dev/null" };
+    let generator = thread::spawn(move || {
+        for domain in LEAK_TEST_DOMAINS {
+            let _ = (*domain, 443).to_socket_addrs();
+        }
+        let _ = http::curl("https://example.com/").args(["-s", "-o", null, "--max-time", "5"]).status();
+    });
+
+    // Tally traffic per public peer: (packets, saw port 53).
+    let mut peers: BTreeMap<String, (usize, bool)> = BTreeMap::new();
+    let reader_thread = thread::spawn(move || {
+        let mut lines = Vec::new();
+        for line in BufReader::new(stdout).lines() {
+            match line {
+                Ok(line) => lines.push(line),
+                Err(_) => break,
+            }
+        }
+        lines
+    });
+
+    interrupt::sleep(Duration::from_secs(seconds));
+    let _ = child.kill();
+    let _ = child.wait();
+    interrupt::untrack(&child);
+    let _ = generator.join();
+
+    for line in reader_thread.join().unwrap_or_default() {
+        if let Some((src, dst)) = parse_endpoints(&line) {
+            for (addr, port) in &[src, dst] {
+                if !is_local(addr) {
+                    let entry = peers.entry(addr.clone()).or_insert((0, false));
+                    entry.0 += 1;
+                    entry.1 |= port == "53";
+                }
+            }
+        }
+    }
+
+    // The busiest public peer is the tunnel endpoint; everything else went around the VPN.
+    let endpoint = peers.iter().max_by_key(|(_, &(n, _))| n).map(|(a, _)| a.clone());
+    if let Some(ref ep) = endpoint {
+        println!("   VPN endpoint: {} ({} packets)", ep, peers[ep].0);
+    }
+
+    let mut leaks = 0;
+    for (addr, &(packets, dns)) in &peers {
+        if Some(addr) == endpoint.as_ref() {
+            continue;
+        }
+        leaks += 1;
+        let kind = if dns { "DNS leak" } else { "traffic leak" };
+        println!("   ❌ {} {} packets to {} bypassed the VPN", colorize(&format!("[{}]", kind.to_uppercase()), "red"), packets, addr);
+    }
+    if leaks == 0 {
+        println!("   ✅ Only tunnel traffic seen on {}", iface);
+    }
+    leaks
+}
+
+/// Extracts `(addr, port)` pairs from a `tcpdump -nn -q` line like
+/// `12:00:00.000 IP 10.0.0.5.51820 > 1.2.3.4.51820: UDP, length 148`.
+fn parse_endpoints(line: &str) -> Option<((String, String), (String, String))> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let arrow = words.iter().position(|w| *w == ">")?;
+    let split = |endpoint: &str| -> (String, String) {
+        let endpoint = endpoint.trim_end_matches(':');
+        // Port-less endpoints (ICMP) are bare addresses; `10.0.0.5` must not become `10.0.0` port 5.
+        if endpoint.parse::<IpAddr>().is_ok() {
+            return (endpoint.to_string(), String::new());
+        }
+        match endpoint.rsplit_once('.') {
+            Some((addr, port)) if port.chars().all(|c| c.is_ascii_digit()) && addr.parse::<IpAddr>().is_ok() => {
+                (addr.to_string(), port.to_string())
+            }
+            _ => (endpoint.to_string(), String::new()),
+        }
+    };
+    Some((split(words.get(arrow - 1)?), split(words.get(arrow + 1)?)))
+}
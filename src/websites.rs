@@ -0,0 +1,119 @@
+use std::thread;
+use std::time::Duration;
+
+use crate::colorize;
+use crate::config::SiteConfig;
+use crate::http;
+use crate::interrupt;
+use crate::resolver;
+
+/// Time allowed for each site, redirects included.
+const SITE_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Sites visited when the config lists none.
+const DEFAULT_SITES: &[(&str, &str)] = &[
+    ("https://www.google.com/search?q=network+diagnostics", "Google"),
+    ("http://www.microsoft.com", "Microsoft"),
+    ("http://www.amazon.com.au", "Amazon"),
+    ("http://www.facebook.com", "Facebook"),
+    ("https://www.youtube.com", "YouTube"),
+    ("http://www.apple.com", "Apple"),
+    ("http://www.github.com", "GitHub"),
+    ("http://www.linkedin.com", "LinkedIn"),
+    ("http://www.reddit.com", "Reddit"),
+    ("http://www.twitter.com", "Twitter"),
+    ("http://www.wikipedia.org", "Wikipedia"),
+    ("http://www.instagram.com", "Instagram"),
+    ("http://www.netflix.com", "Netflix"),
+    ("http://www.spotify.com", "Spotify"),
+    ("http://www.stackoverflow.com", "StackOverflow"),
+    ("http://www.medium.com", "Medium"),
+    ("http://www.quora.com", "Quora"),
+    ("http://www.udemy.com", "Udemy"),
+    ("http://www.coursera.org", "Coursera"),
+    ("http://www.khanacademy.org", "Khan Academy"),
+];
+
+/// Visits `sites` (or the built-in list) concurrently, one blocking thread per site, and, when
+/// `report` is set, prints a table of status, time, protocol, content check and redirect chain
+/// for each. `http://` sites are fetched in-process; `https://` ones still run curl (see
+/// `http::probe`), so HTTPS sites need curl installed.
+pub fn visit(sites: &[SiteConfig], report: bool) {
+    let sites: Vec<SiteConfig> = if sites.is_empty() {
+        DEFAULT_SITES
+            .iter()
+            .map(|(url, name)| SiteConfig { url: url.to_string(), name: Some(name.to_string()), expect_status: Vec::new(), expect_content: None })
+            .collect()
+    } else {
+        sites.to_vec()
+    };
+    // Resolve every host up front, concurrently; failures are reported as DNS problems.
+    let hosts: Vec<String> = sites.iter().map(|s| http::host_of(&s.url)).collect();
+    let hosts: Vec<&str> = hosts.iter().map(|h| h.as_str()).collect();
+    if report {
+        resolver::preflight(&hosts);
+    }
+    let checks: Vec<_> = sites
+        .iter()
+        .cloned()
+        .map(|site| thread::spawn(move || http::probe(&site.url, site.expect_content.as_deref(), SITE_TIMEOUT)))
+        .collect();
+    let results: Vec<_> = checks.into_iter().map(|check| check.join().unwrap_or_else(|_| Err("check panicked".to_string()))).collect();
+    if !report || interrupt::interrupted() {
+        return;
+    }
+
+    println!(
+        "   {:<15} {:<6} {:>8} {:<9} {:<12} {:<4} {:<8} Redirects",
+        "Site", "Status", "Time", "Protocol", "Compression", "H3", "Content"
+    );
+    let mut healthy = 0;
+    let mut slowest: Option<(String, f64)> = None;
+    for (site, result) in sites.iter().zip(&results) {
+        let name = site.name.clone().unwrap_or_else(|| http::host_of(&site.url));
+        let report = match result {
+            Ok(report) => report,
+            Err(e) => {
+                println!("❌ {} Failed to visit {}: {}", colorize("[ERROR]", "red"), name, e);
+                continue;
+            }
+        };
+        let status = report.final_hop().map_or(0, |h| h.status);
+        let status_ok = if site.expect_status.is_empty() { (200..300).contains(&status) } else { site.expect_status.contains(&status) };
+        let ok = status_ok && report.content_match != Some(false);
+        if ok {
+            healthy += 1;
+        }
+        if slowest.as_ref().is_none_or(|(_, secs)| report.time_total > *secs) {
+            slowest = Some((name.clone(), report.time_total));
+        }
+        let content = match report.content_match {
+            Some(true) => "found",
+            Some(false) => "missing",
+            None => "-",
+        };
+        println!(
+            "{} {:<15} {:<6} {:>5.0} ms {:<9} {:<12} {:<4} {:<8} {}",
+            if ok { "✅" } else { "❌" },
+            colorize(&format!("{:<15}", name), "cyan"),
+            status,
+            report.time_total * 1000.0,
+            report.protocol(),
+            report.encoding.as_deref().unwrap_or("none"),
+            if report.h3_advertised { "yes" } else { "no" },
+            content,
+            report.redirect_chain()
+        );
+        if !status_ok && !site.expect_status.is_empty() {
+            let expected: Vec<String> = site.expect_status.iter().map(|s| s.to_string()).collect();
+            println!("   Expected status {}, got {}", expected.join(" or "), status);
+        }
+        if let (Some(false), Some(text)) = (report.content_match, &site.expect_content) {
+            println!("   Expected content {:?} not found in the final response", text);
+        }
+    }
+    println!("\n📊 {} {} of {} sites healthy", colorize("[SUMMARY]", "blue"), healthy, sites.len());
+    if let Some((name, secs)) = slowest {
+        println!("   • Slowest: {} ({:.0} ms)", name, secs * 1000.0);
+    }
+}
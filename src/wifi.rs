@@ -0,0 +1,216 @@
+use std::fs;
+use std::path::Path;
+
+use crate::colorize;
+use crate::netinfo::command_stdout;
+use crate::repeat;
+
+/// The macOS `airport` utility; removed in recent releases, where the report falls back to
+/// `system_profiler`.
+const AIRPORT: &str = "/System/Library/PrivateFrameworks/Apple80211.framework/Versions/Current/Resources/airport";
+
+/// What the OS reports about the current Wi-Fi association.
+#[derive(Debug, Default)]
+struct Link {
+    interface: String,
+    ssid: Option<String>,
+    signal_dbm: Option<i32>,
+    noise_dbm: Option<i32>,
+    /// Transmit rate in Mbit/s.
+    rate_mbps: Option<f64>,
+    channel: Option<String>,
+}
+
+/// Prints the signal strength and link rate of every associated Wi-Fi interface.
+pub fn report() {
+    println!("🔹 {}", colorize("Checking Wi-Fi signal", "blue"));
+    let links = links();
+    if links.is_empty() {
+        println!("⚠️  {} No associated Wi-Fi interface found\n", colorize("[WARN]", "yellow"));
+        return;
+    }
+    for link in &links {
+        println!(
+            "   {} {}",
+            colorize(&link.interface, "cyan"),
+            link.ssid.as_ref().map(|s| format!("on \"{}\"", s)).unwrap_or_default()
+        );
+        if let Some(channel) = &link.channel {
+            println!("   Channel:     {}", channel);
+        }
+        if let Some(rate) = link.rate_mbps {
+            repeat::record_target("Wi-Fi link rate (Mbit/s)", &link.interface, rate);
+            println!("   Link rate:   {:.0} Mbit/s", rate);
+        }
+        if let Some(noise) = link.noise_dbm {
+            println!("   Noise:       {} dBm", noise);
+        }
+        match link.signal_dbm {
+            Some(signal) => {
+                repeat::record_target("Wi-Fi signal (dBm)", &link.interface, f64::from(signal));
+                let (grade, color) = grade(signal);
+                println!("   Signal:      {} dBm ({})", signal, colorize(grade, color));
+                if let Some(noise) = link.noise_dbm {
+                    println!("   SNR:         {} dB", signal - noise);
+                }
+                if signal < -67 {
+                    println!(
+                        "⚠️  {} Weak signal: expect retransmissions and lower rates; move closer to the access point or reduce obstructions",
+                        colorize("[WARN]", "yellow")
+                    );
+                }
+            }
+            None => println!("   Signal:      unknown"),
+        }
+    }
+    println!();
+}
+
+/// Grades a received signal level the way Wi-Fi site surveys do.
+fn grade(dbm: i32) -> (&'static str, &'static str) {
+    match dbm {
+        d if d >= -50 => ("excellent", "green"),
+        d if d >= -60 => ("good", "green"),
+        d if d >= -67 => ("fair", "yellow"),
+        d if d >= -75 => ("weak", "red"),
+        _ => ("unusable", "red"),
+    }
+}
+
+/// Associated Wi-Fi links, using the platform's own tools.
+fn links() -> Vec<Link> {
+    if cfg!(target_os = "windows") {
+        return command_stdout("netsh", &["wlan", "show", "interfaces"]).map(|out| parse_netsh(&out)).unwrap_or_default();
+    }
+    if cfg!(target_os = "macos") {
+        return command_stdout(AIRPORT, &["-I"])
+            .and_then(|out| parse_airport(&out))
+            .or_else(|| command_stdout("system_profiler", &["SPAirPortDataType"]).and_then(|out| parse_system_profiler(&out)))
+            .into_iter()
+            .collect();
+    }
+    let mut names: Vec<String> = fs::read_dir("/sys/class/net")
+        .map(|entries| entries.filter_map(|e| e.ok()).map(|e| e.file_name().to_string_lossy().into_owned()).collect())
+        .unwrap_or_default();
+    names.sort();
+    names
+        .iter()
+        .filter(|name| Path::new(&format!("/sys/class/net/{}/wireless", name)).exists())
+        .filter_map(|name| linux_link(name))
+        .collect()
+}
+
+/// Reads `iw dev <if> link`, falling back to `/proc/net/wireless` for the signal level.
+fn linux_link(interface: &str) -> Option<Link> {
+    let mut link = Link { interface: interface.to_string(), ..Link::default() };
+    if let Some(out) = command_stdout("iw", &["dev", interface, "link"]) {
+        if out.starts_with("Not connected") {
+            return None;
+        }
+        for line in out.lines().map(str::trim) {
+            if let Some(ssid) = line.strip_prefix("SSID:") {
+                link.ssid = Some(ssid.trim().to_string());
+            } else if let Some(signal) = line.strip_prefix("signal:") {
+                link.signal_dbm = first_number(signal).map(|n| n as i32);
+            } else if let Some(rate) = line.strip_prefix("tx bitrate:") {
+                link.rate_mbps = first_number(rate);
+            } else if let Some(freq) = line.strip_prefix("freq:") {
+                link.channel = first_number(freq).map(|mhz| format!("{:.0} MHz", mhz));
+            }
+        }
+    }
+    if link.signal_dbm.is_none() {
+        // `wlan0: 0000   54.  -56.  -256 ...`: status, quality, level, noise.
+        let text = fs::read_to_string("/proc/net/wireless").ok()?;
+        let line = text.lines().find(|l| l.trim_start().starts_with(&format!("{}:", interface)))?;
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        link.signal_dbm = fields.get(3).and_then(|f| first_number(f)).map(|n| n as i32);
+        link.noise_dbm = fields.get(4).and_then(|f| first_number(f)).map(|n| n as i32).filter(|n| *n > -256);
+    }
+    Some(link)
+}
+
+/// Parses `airport -I`: `agrCtlRSSI: -52`, `agrCtlNoise: -90`, `lastTxRate: 866`, `SSID: home`.
+fn parse_airport(out: &str) -> Option<Link> {
+    let mut link = Link { interface: "en0".to_string(), ..Link::default() };
+    for line in out.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "agrCtlRSSI" => link.signal_dbm = value.parse().ok(),
+            "agrCtlNoise" => link.noise_dbm = value.parse().ok(),
+            "lastTxRate" => link.rate_mbps = value.parse().ok(),
+            "SSID" => link.ssid = Some(value.to_string()),
+            "channel" => link.channel = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    link.signal_dbm.map(|_| link)
+}
+
+/// Parses the "Current Network Information" block of `system_profiler SPAirPortDataType`, where
+/// the signal reads `Signal / Noise: -52 dBm / -90 dBm`.
+fn parse_system_profiler(out: &str) -> Option<Link> {
+    let mut lines = out.lines().skip_while(|l| !l.contains("Current Network Information:"));
+    lines.next()?;
+    let mut link = Link { interface: "en0".to_string(), ..Link::default() };
+    link.ssid = lines.next().map(|l| l.trim().trim_end_matches(':').to_string());
+    for line in lines.take(12) {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "Signal / Noise" => {
+                let mut levels = value.split('/').filter_map(first_number);
+                link.signal_dbm = levels.next().map(|n| n as i32);
+                link.noise_dbm = levels.next().map(|n| n as i32);
+            }
+            "Transmit Rate" => link.rate_mbps = first_number(value),
+            "Channel" => link.channel = Some(value.to_string()),
+            _ => {}
+        }
+    }
+    link.signal_dbm.map(|_| link)
+}
+
+/// Parses `netsh wlan show interfaces`, which gives the signal as a percentage; Windows maps
+/// -100..-50 dBm linearly onto 0..100%.
+fn parse_netsh(out: &str) -> Vec<Link> {
+    let mut links = Vec::new();
+    let mut current: Option<Link> = None;
+    for line in out.lines() {
+        let (key, value) = match line.split_once(':') {
+            Some((key, value)) => (key.trim(), value.trim()),
+            None => continue,
+        };
+        match key {
+            "Name" => {
+                links.extend(current.take());
+                current = Some(Link { interface: value.to_string(), ..Link::default() });
+            }
+            "SSID" => current.iter_mut().for_each(|l| l.ssid = Some(value.to_string())),
+            "Channel" => current.iter_mut().for_each(|l| l.channel = Some(value.to_string())),
+            "Transmit rate (Mbps)" => current.iter_mut().for_each(|l| l.rate_mbps = first_number(value)),
+            "Signal" => current.iter_mut().for_each(|l| l.signal_dbm = first_number(value).map(|pct| pct as i32 / 2 - 100)),
+            _ => {}
+        }
+    }
+    links.extend(current);
+    links.retain(|l| l.ssid.is_some());
+    links
+}
+
+/// The first (possibly negative, possibly fractional) number in `text`.
+fn first_number(text: &str) -> Option<f64> {
+    let start = text.find(|c: char| c.is_ascii_digit() || c == '-')?;
+    let number: String = text[start..]
+        .chars()
+        .enumerate()
+        .take_while(|(i, c)| c.is_ascii_digit() || *c == '.' || (*i == 0 && *c == '-'))
+        .map(|(_, c)| c)
+        .collect();
+    number.trim_end_matches('.').parse().ok()
+}